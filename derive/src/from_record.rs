@@ -1,11 +1,15 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display, Formatter},
+};
 
 use proc_macro::{Span, TokenStream};
 use quote::{ToTokens, format_ident, quote};
 use syn::{
-    Data, DeriveInput, Error, ExprClosure, Field, Fields, GenericArgument, Ident, LitInt, Pat,
-    PathArguments, Result, Token, Type,
+    Data, DeriveInput, Error, ExprClosure, Field, Fields, GenericArgument, Ident, LitInt, LitStr,
+    Pat, PathArguments, Result, Token, Type,
     parse::{Parse, ParseStream},
+    parse_quote,
     spanned::Spanned,
 };
 
@@ -28,13 +32,15 @@ pub(crate) fn expand_from_record(input: &DeriveInput) -> Result<TokenStream> {
         .named
         .iter()
         .map(FieldMetadata::parse)
-        .map(Result::transpose)
-        .flatten() // Skip fields without an attribute.
+        .filter_map(Result::transpose) // Skip fields without an attribute.
         .collect::<Result<Vec<_>>>()?;
 
     type Case = (Ident, Option<(Type, ExprClosure)>);
     let mut field_methods: HashMap<Type, HashMap<LitInt, Case>> = HashMap::new();
+    let mut developer_methods: HashMap<Type, Vec<(DeveloperIdentifier, Case)>> = HashMap::new();
+    let mut developer_identifiers = HashSet::new();
     let mut time_method: Option<Case> = None;
+    let mut unknown_method: Option<Case> = None;
 
     for field in fields {
         let assignment = (field.name, field.handler);
@@ -46,17 +52,40 @@ pub(crate) fn expand_from_record(input: &DeriveInput) -> Result<TokenStream> {
                     .or_default()
                     .insert(number.clone(), assignment);
 
-                if !existing.is_none() {
+                if existing.is_some() {
                     Err(Error::new(
                         number.span(),
                         "Field identifiers must be unique.",
                     ))?
                 }
             }
+            FieldIdentifier::Developer(identifier) => {
+                if !developer_identifiers.insert(identifier.to_string()) {
+                    Err(Error::new(
+                        field.span.into(),
+                        "Developer field identifiers must be unique.",
+                    ))?
+                }
+
+                developer_methods
+                    .entry(field.primitive)
+                    .or_default()
+                    .push((identifier, assignment));
+            }
             FieldIdentifier::Time => {
                 let existing = time_method.replace(assignment);
 
-                if !existing.is_none() {
+                if existing.is_some() {
+                    Err(Error::new(
+                        field.span.into(),
+                        "Field identifiers must be unique.",
+                    ))?;
+                }
+            }
+            FieldIdentifier::Unknown => {
+                let existing = unknown_method.replace(assignment);
+
+                if existing.is_some() {
                     Err(Error::new(
                         field.span.into(),
                         "Field identifiers must be unique.",
@@ -68,20 +97,7 @@ pub(crate) fn expand_from_record(input: &DeriveInput) -> Result<TokenStream> {
 
     let field_methods = field_methods.into_iter().map(|(primitive, fields)| {
         let cases = fields.into_iter().map(|(number, (name, handler))| {
-            let assignment = if let Some((field_type, handler)) = handler {
-                let body = handler.body;
-                let acc = handler.inputs.iter().nth(0).unwrap();
-                let val = handler.inputs.iter().nth(1).unwrap();
-
-                quote! {
-                    (|#acc: &mut #field_type, #val| {#body})(&mut self.#name, value)
-                }
-            } else {
-                quote! {
-                    self.#name = Some(value)
-                }
-            };
-
+            let assignment = assignment(&name, handler, format_ident!("value"));
             quote! { #number => { #assignment } }
         });
 
@@ -98,21 +114,53 @@ pub(crate) fn expand_from_record(input: &DeriveInput) -> Result<TokenStream> {
         }
     });
 
+    let developer_methods = developer_methods.into_iter().map(|(primitive, fields)| {
+        let cases = fields.into_iter().map(|(identifier, (name, handler))| {
+            let condition = match identifier {
+                DeveloperIdentifier::Number { developer, number } => quote! {
+                    field.developer() == #developer && field.number() == #number
+                },
+                DeveloperIdentifier::Name(field_name) => quote! {
+                    field.name() == Some(#field_name)
+                },
+            };
+
+            let assignment = assignment(&name, handler, format_ident!("value"));
+            quote! { if #condition { #assignment; } }
+        });
+
+        let primitive = format_ident!("{}", primitive.to_token_stream().to_string());
+        let method = format_ident!("add_developer_{}", primitive);
+
+        quote! {
+            fn #method(&mut self, field: &FieldDescription, value: #primitive) {
+                #(#cases)*
+            }
+        }
+    });
+
     let time_method = time_method.map(|(name, handler)| {
-        let assignment = if let Some((field_type, handler)) = handler {
-            let body = handler.body;
-            let acc = handler.inputs.iter().nth(0).unwrap();
-            let val = handler.inputs.iter().nth(1).unwrap();
+        let assignment = assignment(&name, handler, format_ident!("offset"));
 
-            quote! {
-                (|#acc: &mut #field_type, #val| #body)(&mut self.#name, offset)
+        quote! {
+            fn add_time_offset(&mut self, offset: u8) {
+                #assignment;
             }
-        } else {
-            quote! { self.#name = Some(offset) }
+        }
+    });
+
+    let unknown_method = unknown_method.map(|(name, handler)| {
+        // Without a handler, collect owned copies of the fields.
+        let assignment = match handler {
+            Some(handler) => {
+                assignment(&name, Some(handler), format_ident!("unknown")).to_token_stream()
+            }
+            None => quote! { self.#name.push((field, base_type, bytes.to_vec())) },
         };
 
         quote! {
-            fn add_time_offset(&mut self, offset: u8) {
+            fn add_unknown(&mut self, field: u8, base_type: u8, bytes: &[u8]) {
+                let unknown = (field, base_type, bytes);
                 #assignment;
             }
         }
@@ -123,13 +171,36 @@ pub(crate) fn expand_from_record(input: &DeriveInput) -> Result<TokenStream> {
     let expanded = quote! {
         impl FromRecord for #name {
             #(#field_methods)*
+            #(#developer_methods)*
             #time_method
+            #unknown_method
         }
     };
 
     Ok(expanded.into())
 }
 
+/// Generate an expression storing a value in a struct field, either directly
+/// or through a handler closure.
+fn assignment(name: &Ident, handler: Option<(Type, ExprClosure)>, value: Ident) -> impl ToTokens {
+    if let Some((field_type, handler)) = handler {
+        let body = handler.body;
+        let acc = handler.inputs.iter().next().unwrap();
+        let val = handler.inputs.iter().nth(1).unwrap();
+
+        quote! {
+            (|#acc: &mut #field_type, #val| {#body})(&mut self.#name, #value)
+        }
+    } else {
+        quote! { self.#name = Some(#value) }
+    }
+}
+
+/// Primitive types with a corresponding `FromRecord` method.
+const PRIMITIVES: [&str; 10] = [
+    "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64",
+];
+
 #[derive(Debug)]
 struct FieldMetadata {
     name: Ident,
@@ -142,23 +213,64 @@ struct FieldMetadata {
 #[derive(Debug)]
 enum FieldIdentifier {
     Number(LitInt),
+    Developer(DeveloperIdentifier),
     Time,
+    Unknown,
+}
+
+#[derive(Debug)]
+enum DeveloperIdentifier {
+    Number { developer: LitInt, number: LitInt },
+    Name(LitStr),
+}
+
+impl Display for DeveloperIdentifier {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Number { developer, number } => {
+                let (developer, number) = (developer.base10_digits(), number.base10_digits());
+                write!(f, "{developer}:{number}")
+            }
+            Self::Name(name) => write!(f, "{}", name.value()),
+        }
+    }
 }
 
 impl FieldMetadata {
     fn parse(field: &Field) -> Result<Option<Self>> {
         let name = field.ident.clone().unwrap();
 
-        let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("field")) else {
+        let Some(attr) = field
+            .attrs
+            .iter()
+            .find(|a| a.path().is_ident("field") || a.path().is_ident("developer_field"))
+        else {
             return Ok(None);
         };
 
         let span = attr.span().unwrap();
 
+        let list = attr.meta.require_list()?;
+
         let FieldAttribute {
             identifier,
             handler,
-        } = attr.meta.require_list()?.parse_args()?;
+        } = if attr.path().is_ident("field") {
+            list.parse_args()?
+        } else {
+            list.parse_args_with(FieldAttribute::parse_developer)?
+        };
+
+        // Unknown fields are collected whole, rather than as a primitive.
+        if let FieldIdentifier::Unknown = identifier {
+            return Ok(Some(Self {
+                name,
+                primitive: field.ty.clone(),
+                identifier,
+                handler: handler.map(|h| (field.ty.clone(), h)),
+                span,
+            }));
+        }
 
         let primitive = if let Some(handler) = &handler {
             let Some(parameter) = handler.inputs.iter().nth(1) else {
@@ -184,7 +296,7 @@ impl FieldMetadata {
                 ))?
             };
 
-            let Some(segment) = path.path.segments.first() else {
+            let Some(segment) = path.path.segments.last() else {
                 Err(Error::new_spanned(
                     &path.path.segments,
                     "Field must have a type annotation.",
@@ -222,8 +334,41 @@ impl FieldMetadata {
             inner_type.clone()
         };
 
+        // Paths to primitives, such as `core::primitive::u8`, are matched by
+        // their last segment. Anything else named like a primitive is left
+        // to type checking.
+        let ident = match &primitive {
+            Type::Path(path) if path.qself.is_none() => path
+                .path
+                .segments
+                .last()
+                .filter(|segment| segment.arguments.is_none())
+                .map(|segment| segment.ident.clone()),
+            _ => None,
+        };
+
+        let supported = ident
+            .as_ref()
+            .is_some_and(|ident| PRIMITIVES.iter().any(|p| ident == p));
+
+        if !matches!(identifier, FieldIdentifier::Time) && !supported {
+            Err(Error::new_spanned(
+                &primitive,
+                format!(
+                    "Field primitive type must be one of {}.",
+                    PRIMITIVES.map(|p| format!("`{p}`")).join(", "),
+                ),
+            ))?
+        }
+
         let handler = handler.map(|h| (field.ty.clone(), h));
 
+        // Methods are named after the primitive, so refer to it plainly.
+        let primitive = match ident {
+            Some(ident) if supported => parse_quote!(#ident),
+            _ => primitive,
+        };
+
         Ok(Some(Self {
             name,
             primitive,
@@ -245,10 +390,12 @@ impl Parse for FieldAttribute {
         let identifier = if let Ok(ident) = input.parse::<Ident>() {
             if ident == "time" {
                 FieldIdentifier::Time
+            } else if ident == "unknown" {
+                FieldIdentifier::Unknown
             } else {
                 Err(Error::new_spanned(
                     ident,
-                    "Field identifier must be an integer literal or `time`.",
+                    "Field identifier must be an integer literal, `time`, or `unknown`.",
                 ))?
             }
         } else {
@@ -268,3 +415,55 @@ impl Parse for FieldAttribute {
         })
     }
 }
+
+impl FieldAttribute {
+    /// Parse the arguments of a `developer_field` attribute.
+    fn parse_developer(input: ParseStream) -> Result<Self> {
+        let span = input.span();
+
+        let (mut developer, mut number, mut name) = (None, None, None);
+        let mut handler = None;
+
+        while !input.is_empty() {
+            if input.peek(Ident) && input.peek2(Token![=]) {
+                let key = input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+
+                if key == "developer" {
+                    developer = Some(input.parse::<LitInt>()?);
+                } else if key == "number" {
+                    number = Some(input.parse::<LitInt>()?);
+                } else if key == "name" {
+                    name = Some(input.parse::<LitStr>()?);
+                } else {
+                    Err(Error::new_spanned(
+                        key,
+                        "Expected `developer`, `number`, or `name`.",
+                    ))?
+                }
+            } else {
+                handler = Some(input.parse::<ExprClosure>()?);
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let identifier = match (developer, number, name) {
+            (Some(developer), Some(number), None) => {
+                DeveloperIdentifier::Number { developer, number }
+            }
+            (None, None, Some(name)) => DeveloperIdentifier::Name(name),
+            _ => Err(Error::new(
+                span,
+                "Developer field must be identified by either `developer` and `number`, or `name`.",
+            ))?,
+        };
+
+        Ok(Self {
+            identifier: FieldIdentifier::Developer(identifier),
+            handler,
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    Data, DeriveInput, Error, Field, Fields, Ident, LitInt, Result, Type,
+    Data, DeriveInput, Error, Expr, Field, Fields, Ident, LitInt, Result, Token, Type,
     parse::{Parse, ParseStream},
 };
 
@@ -24,8 +24,7 @@ pub(crate) fn expand_from_records(input: &DeriveInput) -> Result<TokenStream> {
         .named
         .iter()
         .map(FieldMetadata::parse)
-        .map(Result::transpose)
-        .flatten() // Skip fields without an attribute.
+        .filter_map(Result::transpose) // Skip fields without an attribute.
         .collect::<Result<Vec<_>>>()?;
 
     let cases = fields.iter().map(|field| {
@@ -33,15 +32,51 @@ pub(crate) fn expand_from_records(input: &DeriveInput) -> Result<TokenStream> {
             name,
             number,
             is_vec,
+            keep,
+            duplicate,
+            limit,
+            overflow,
         } = field;
 
+        let duplicate = duplicate.as_ref().map(|hook| {
+            quote! {
+                if self.#name.is_some() {
+                    (#hook)(self, id);
+                }
+            }
+        });
+
         let assignment = if *is_vec {
+            let limit = limit.as_ref().map(|limit| {
+                let exceeded = match overflow {
+                    Overflow::Skip => quote! { Ok(None) },
+                    Overflow::Error => quote! { Err(LimitExceeded { id, limit: #limit }) },
+                };
+
+                quote! {
+                    if self.#name.len() >= #limit {
+                        return #exceeded;
+                    }
+                }
+            });
+
             quote! {
+                #limit
                 self.#name.push(Default::default());
                 self.#name.last_mut().map(|r| r as _)
             }
+        } else if let Keep::First = keep {
+            quote! {
+                #duplicate
+                if self.#name.is_some() {
+                    None
+                } else {
+                    Some(self.#name.insert(Default::default()))
+                }
+            }
         } else {
             quote! {
+                #duplicate
                 Some(self.#name.insert(Default::default()))
             }
         };
@@ -51,13 +86,22 @@ pub(crate) fn expand_from_records(input: &DeriveInput) -> Result<TokenStream> {
 
     let name = &input.ident;
 
+    // Receivers fail only if a limit is to be an error.
+    let error = match fields.iter().any(|f| matches!(f.overflow, Overflow::Error)) {
+        true => quote! { LimitExceeded },
+        false => quote! { ::core::convert::Infallible },
+    };
+
     let expanded = quote! {
-        impl FromRecords for #name {
-            fn add_record(&mut self, id: u16) -> Option<&mut dyn FromRecord> {
-                match id {
+        impl FromRecords<#error> for #name {
+            fn add_record(
+                &mut self,
+                id: u16,
+            ) -> ::core::result::Result<Option<&mut dyn FromRecord>, #error> {
+                Ok(match id {
                     #(#cases)*
                     _ => None,
-                }
+                })
             }
         }
     };
@@ -70,6 +114,24 @@ struct FieldMetadata {
     name: Ident,
     number: LitInt,
     is_vec: bool,
+    keep: Keep,
+    duplicate: Option<Expr>,
+    limit: Option<LitInt>,
+    overflow: Overflow,
+}
+
+#[derive(Debug, Default)]
+enum Keep {
+    First,
+    #[default]
+    Last,
+}
+
+#[derive(Debug, Default)]
+enum Overflow {
+    #[default]
+    Skip,
+    Error,
 }
 
 impl FieldMetadata {
@@ -80,7 +142,13 @@ impl FieldMetadata {
             return Ok(None);
         };
 
-        let RecordAttribute { number } = attr.meta.require_list()?.parse_args()?;
+        let RecordAttribute {
+            number,
+            keep,
+            duplicate,
+            limit,
+            overflow,
+        } = attr.meta.require_list()?.parse_args()?;
 
         let Type::Path(path) = &field.ty else {
             Err(Error::new_spanned(
@@ -107,10 +175,42 @@ impl FieldMetadata {
             ))?
         };
 
+        if is_vec {
+            if let Some((ident, _)) = &keep {
+                Err(Error::new_spanned(
+                    ident,
+                    "Only a field of type `Option<T>` may specify `keep`.",
+                ))?
+            }
+
+            if let Some(duplicate) = &duplicate {
+                Err(Error::new_spanned(
+                    duplicate,
+                    "Only a field of type `Option<T>` may specify `duplicate`.",
+                ))?
+            }
+        } else if let Some(limit) = &limit {
+            Err(Error::new_spanned(
+                limit,
+                "Only a field of type `Vec<T>` may specify `limit`.",
+            ))?
+        }
+
+        if let (Some((ident, _)), None) = (&overflow, &limit) {
+            Err(Error::new_spanned(
+                ident,
+                "Only a field specifying `limit` may specify `overflow`.",
+            ))?
+        }
+
         Ok(Some(Self {
             name,
             number,
             is_vec,
+            keep: keep.map(|(_, keep)| keep).unwrap_or_default(),
+            duplicate,
+            limit,
+            overflow: overflow.map(|(_, overflow)| overflow).unwrap_or_default(),
         }))
     }
 }
@@ -118,11 +218,75 @@ impl FieldMetadata {
 #[derive(Debug)]
 struct RecordAttribute {
     number: LitInt,
+    keep: Option<(Ident, Keep)>,
+    duplicate: Option<Expr>,
+    limit: Option<LitInt>,
+    overflow: Option<(Ident, Overflow)>,
 }
 
 impl Parse for RecordAttribute {
     fn parse(input: ParseStream) -> Result<Self> {
         let number = input.parse::<LitInt>()?;
-        Ok(Self { number })
+
+        let mut keep = None;
+        let mut duplicate = None;
+        let mut limit = None;
+        let mut overflow = None;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            let key = input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+
+            if key == "keep" {
+                let value = input.parse::<Ident>()?;
+
+                let policy = if value == "first" {
+                    Keep::First
+                } else if value == "last" {
+                    Keep::Last
+                } else {
+                    Err(Error::new_spanned(
+                        &value,
+                        "Record policy must be `first` or `last`.",
+                    ))?
+                };
+
+                keep = Some((key, policy));
+            } else if key == "duplicate" {
+                duplicate = Some(input.parse::<Expr>()?);
+            } else if key == "limit" {
+                limit = Some(input.parse::<LitInt>()?);
+            } else if key == "overflow" {
+                let value = input.parse::<Ident>()?;
+
+                let policy = if value == "skip" {
+                    Overflow::Skip
+                } else if value == "error" {
+                    Overflow::Error
+                } else {
+                    Err(Error::new_spanned(
+                        &value,
+                        "Overflow policy must be `skip` or `error`.",
+                    ))?
+                };
+
+                overflow = Some((key, policy));
+            } else {
+                Err(Error::new_spanned(
+                    &key,
+                    "Record option must be `keep`, `duplicate`, `limit`, or `overflow`.",
+                ))?
+            }
+        }
+
+        Ok(Self {
+            number,
+            keep,
+            duplicate,
+            limit,
+            overflow,
+        })
     }
 }
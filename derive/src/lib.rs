@@ -4,7 +4,7 @@ use syn::{DeriveInput, parse_macro_input};
 mod from_record;
 mod from_records;
 
-#[proc_macro_derive(FromRecord, attributes(field))]
+#[proc_macro_derive(FromRecord, attributes(field, developer_field))]
 pub fn derive_from_record(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     match from_record::expand_from_record(&input) {
@@ -2,24 +2,383 @@
 //!
 //! The functions in this module are suited to decoding records from files and
 //! data slices, publishing to the [`FromRecords`] and [`FromRecord`] traits.
+//! Symmetrically, documents can be encoded from the [`IntoRecords`] and
+//! [`IntoRecord`] traits.
 //!
 //! In many cases (when records are of a known shape), these traits can be
 //! derived. See the [`FromRecords`](macro@FromRecords) and
 //! [`FromRecord`](macro@FromRecord) macros for details.
 
+use core::{convert::Infallible, fmt, ops::ControlFlow};
+
+pub mod adapters;
+mod context;
+mod developer;
+#[cfg(feature = "embedded-io-async")]
+pub mod embedded;
+#[cfg(feature = "std")]
+pub mod histogram;
+pub mod iter;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod push;
+#[cfg(feature = "std")]
+pub mod raw;
 #[cfg(feature = "std")]
 pub mod reader;
+#[cfg(feature = "async")]
+pub mod reader_async;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "std")]
+pub mod seek;
+#[cfg(feature = "async")]
+pub mod seek_async;
+mod sink;
 pub mod slice;
+mod source;
+#[cfg(feature = "std")]
+pub mod tape;
+mod timestamp;
+#[cfg(feature = "std")]
+pub mod transcode;
+#[cfg(feature = "std")]
+mod view;
+#[cfg(feature = "std")]
+pub mod writer;
 
+pub use context::{FieldContext, FromFields, WithContext};
+pub use developer::FieldDescription;
+#[cfg(feature = "std")]
+pub use histogram::MessageHistogram;
+pub use iter::ByteSource;
+#[cfg(feature = "std")]
+pub use push::{DecoderState, PushDecoder};
+#[cfg(feature = "std")]
+pub use raw::{DynRecord, MessageSender, RecordRouter};
+#[cfg(feature = "std")]
+pub use reader::{
+    decode as decode_reader, decode_path, decode_static as decode_reader_static,
+    decode_with as decode_reader_with,
+};
+#[cfg(feature = "std")]
+pub use repair::repair;
 #[cfg(feature = "std")]
-pub use reader::decode as decode_reader;
-pub use slice::decode as decode_slice;
+pub use seek::SeekableDecoder;
+pub use sink::RecordError;
+pub use slice::{
+    decode as decode_slice, decode_at as decode_slice_at, decode_ref as decode_slice_ref,
+    decode_static as decode_slice_static, decode_with as decode_slice_with, encode as encode_slice,
+    peek_header,
+};
+#[cfg(feature = "std")]
+pub use tape::Tape;
+pub use timestamp::TimestampTracker;
+#[cfg(feature = "std")]
+pub use transcode::transcode;
+#[cfg(feature = "std")]
+pub use view::DefinitionView;
+#[cfg(feature = "std")]
+pub use writer::{DecodeWriter, encode as encode_writer};
+
+/// Options controlling the behaviour of the decoders.
+///
+/// Options can be set as fields, or chained from the defaults:
+///
+/// ```
+/// let options = DecodeOptions::new().lenient(true).max_records(Some(100));
+/// derailleur::avec::decode_slice_with(&data, &mut records, &options)?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions {
+    /// Deliver the absolute timestamp of records with compressed timestamp
+    /// headers as a `u32` for field 253 (`timestamp`).
+    ///
+    /// The decoder tracks the most recent timestamp, and expands each time
+    /// offset relative to it, accounting for roll-over. Records preceding the
+    /// first full timestamp receive only the time offset.
+    pub synthesize_timestamps: bool,
+
+    /// Deliver the running total of accumulated developer fields as a `u32`,
+    /// rather than their raw values.
+    ///
+    /// Accumulated fields (see [`FieldDescription::is_accumulated`]) hold a
+    /// counter which rolls over at the width of its base type. The decoder
+    /// tracks the total for each unsigned integer field, adding the
+    /// difference from the previous value. Other base types are delivered
+    /// unchanged.
+    pub accumulate_developer_fields: bool,
+
+    /// Accept documents ending exactly at the end of their record section,
+    /// without the trailing CRC.
+    ///
+    /// Some older devices and exporters omit the CRC. When it is missing, the
+    /// slice decoders report the bytes consumed as excluding it.
+    pub allow_missing_crc: bool,
+
+    /// Treat documents with a size of zero in their header as of unknown
+    /// length, decoding records until the end of the input, or a trailing CRC
+    /// ending it.
+    ///
+    /// Devices recording a live stream may write a header before the size of
+    /// the document is known. Since a CRC is only recognised when it directly
+    /// precedes the end of the input, this applies only to the reader- and
+    /// slice-based decoders.
+    pub unknown_length: bool,
+
+    /// Skip malformed records, rather than failing, continuing from the next
+    /// plausible definition record.
+    ///
+    /// Data records with the developer data flag in their header, or without a
+    /// preceding definition, are skipped along with the bytes following them,
+    /// up to a definition record header (with a reserved byte of zero, and a
+    /// valid architecture) or the end of the record section. Each skipped span
+    /// is reported to [`FromRecords::add_skipped`]. This applies only to the
+    /// reader- and slice-based decoders.
+    pub lenient: bool,
+
+    /// Check the trailing CRC of each document against the bytes decoded.
+    ///
+    /// Enabled by default. Otherwise, the CRC is still taken, but a mismatch
+    /// is ignored.
+    pub verify_crc: bool,
+
+    /// Decode developer fields described by the document.
+    ///
+    /// Enabled by default. Otherwise, developer fields are skipped as if
+    /// undescribed, while `field_description` records are still delivered.
+    pub allow_developer: bool,
+
+    /// Continue decoding documents chained after the first, publishing their
+    /// records to the same receiver.
+    ///
+    /// Each document is delivered to [`FromRecords::add_header`] before its
+    /// records. Decoding ends at the end of the input, and the slice decoders
+    /// report the bytes consumed by all documents. This applies only to the
+    /// reader- and slice-based decoders.
+    pub allow_chained: bool,
+
+    /// Stop decoding once this many data records have been decoded, across
+    /// chained documents.
+    ///
+    /// Decoding ends successfully at the start of the next record, without
+    /// reaching the trailing CRC (which the slice decoders still check before
+    /// decoding). This applies only to the reader- and slice-based decoders.
+    pub max_records: Option<usize>,
+
+    /// Stop decoding at the first data record of a global message number,
+    /// without publishing it.
+    ///
+    /// Decoding ends as for [`DecodeOptions::max_records`]. This applies only to
+    /// the reader- and slice-based decoders.
+    pub stop_at: Option<u16>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            synthesize_timestamps: false,
+            accumulate_developer_fields: false,
+            allow_missing_crc: false,
+            unknown_length: false,
+            lenient: false,
+            verify_crc: true,
+            allow_developer: true,
+            allow_chained: false,
+            max_records: None,
+            stop_at: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set [`DecodeOptions::synthesize_timestamps`].
+    pub fn synthesize_timestamps(mut self, value: bool) -> Self {
+        self.synthesize_timestamps = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::accumulate_developer_fields`].
+    pub fn accumulate_developer_fields(mut self, value: bool) -> Self {
+        self.accumulate_developer_fields = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::allow_missing_crc`].
+    pub fn allow_missing_crc(mut self, value: bool) -> Self {
+        self.allow_missing_crc = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::unknown_length`].
+    pub fn unknown_length(mut self, value: bool) -> Self {
+        self.unknown_length = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::lenient`].
+    pub fn lenient(mut self, value: bool) -> Self {
+        self.lenient = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::verify_crc`].
+    pub fn verify_crc(mut self, value: bool) -> Self {
+        self.verify_crc = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::allow_developer`].
+    pub fn allow_developer(mut self, value: bool) -> Self {
+        self.allow_developer = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::allow_chained`].
+    pub fn allow_chained(mut self, value: bool) -> Self {
+        self.allow_chained = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::max_records`].
+    pub fn max_records(mut self, value: Option<usize>) -> Self {
+        self.max_records = value;
+        self
+    }
+
+    /// Set [`DecodeOptions::stop_at`].
+    pub fn stop_at(mut self, value: Option<u16>) -> Self {
+        self.stop_at = value;
+        self
+    }
+}
+
+/// Metadata from the header of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderInfo {
+    /// Length of the header in bytes.
+    pub header_size: u8,
+    /// Protocol version, with the major version in the upper four bits.
+    pub protocol_version: u8,
+    /// Profile version, encoding the major and minor versions (for example,
+    /// 2056 for version 20.56).
+    pub profile_version: u16,
+    /// Number of record bytes in the document.
+    pub data_size: u32,
+    /// Whether the header holds a (non-zero) CRC of its first 12 bytes.
+    pub has_header_crc: bool,
+}
+
+impl HeaderInfo {
+    /// Read metadata from the bytes of a document header, and any CRC of an
+    /// extended header.
+    pub(crate) fn new(r: [u8; 12], crc: Option<[u8; 2]>) -> Self {
+        Self {
+            header_size: r[0],
+            protocol_version: r[1],
+            profile_version: u16::from_le_bytes([r[2], r[3]]),
+            data_size: u32::from_le_bytes([r[4], r[5], r[6], r[7]]),
+            has_header_crc: crc.is_some_and(|crc| crc != [0; 2]),
+        }
+    }
+}
+
+/// A view of the definition record describing a data record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefinitionInfo<'d> {
+    r: &'d [u8], // A stored definition record, beginning with its record header.
+}
+
+/// A field of a [`DefinitionInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    /// Field number, within the message.
+    pub number: u8,
+    /// Size of the field in bytes, which is a multiple of the size of its base
+    /// type for arrays.
+    pub size: u8,
+    /// Base type of the field, as a raw byte.
+    pub base_type: u8,
+}
+
+/// A developer field of a [`DefinitionInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeveloperFieldInfo {
+    /// Field number, as described by the developer.
+    pub number: u8,
+    /// Size of the field in bytes.
+    pub size: u8,
+    /// Index of the developer describing the field.
+    pub developer: u8,
+}
+
+impl<'d> DefinitionInfo<'d> {
+    pub(crate) fn new(r: &'d [u8]) -> Self {
+        Self { r }
+    }
+
+    /// Global message number of the record.
+    pub fn global(&self) -> u16 {
+        let global = [self.r[3], self.r[4]];
+
+        match self.is_little_endian() {
+            true => u16::from_le_bytes(global),
+            false => u16::from_be_bytes(global),
+        }
+    }
+
+    /// Local message number the definition is stored under.
+    pub fn local(&self) -> u8 {
+        self.r[0] & 0x0F
+    }
+
+    /// Whether multi-byte values of the record are little-endian.
+    pub fn is_little_endian(&self) -> bool {
+        self.r[2] == 0
+    }
+
+    /// Fields of the record, in the order their values are received.
+    pub fn fields(&self) -> impl Iterator<Item = FieldInfo> + use<'d> {
+        let count = self.r[5] as usize;
+
+        self.r[6..6 + count * 3].chunks_exact(3).map(|f| FieldInfo {
+            number: f[0],
+            size: f[1],
+            base_type: f[2],
+        })
+    }
+
+    /// Developer fields of the record, following its fields.
+    pub fn developer_fields(&self) -> impl Iterator<Item = DeveloperFieldInfo> + use<'d> {
+        let start = 6 + self.r[5] as usize * 3;
+
+        // Developer fields are present with the flag on the record header.
+        let fields = match self.r.get(start) {
+            Some(&count) if self.r[0] & 0x20 != 0 => {
+                &self.r[start + 1..start + 1 + count as usize * 3]
+            }
+            _ => &[],
+        };
+
+        fields.chunks_exact(3).map(|f| DeveloperFieldInfo {
+            number: f[0],
+            size: f[1],
+            developer: f[2],
+        })
+    }
+}
 
 /// Derive [`FromRecords`] for a struct holding a collection of records.
 ///
 /// _Requires Cargo feature `derive`._
 ///
-/// # Example
+/// # Examples
 ///
 /// To collect a single record, add the `record(N)` attribute to an `Option<T>`
 /// struct field, where `N` is the global message number and `T` is a type
@@ -36,6 +395,50 @@ pub use slice::decode as decode_slice;
 ///     records: Vec<Record>,
 /// }
 /// ```
+///
+/// To keep the first record received for a message number instead, supply
+/// `keep = first` (or `keep = last` for the default). To be notified of
+/// additional records, which do occur in the wild, supply a function taking the
+/// struct and the message number as `duplicate`.
+///
+/// ```
+/// #[derive(Debug, Default, FromRecords)]
+/// struct ActivityRecordSet {
+///     #[record(0, keep = first, duplicate = Self::warn)]
+///     file_id: Option<FileId>,
+/// }
+///
+/// impl ActivityRecordSet {
+///     fn warn(&mut self, id: u16) {
+///         eprintln!("Found a duplicate record ({id}).");
+///     }
+/// }
+/// ```
+///
+/// To bound the number of records collected into a `Vec<T>`, protecting against
+/// pathological documents, supply `limit = N`. Records received once the limit
+/// is reached are passed over, as if the struct had no receiver for them, and
+/// reported to [`FromRecords::unknown_record`].
+///
+/// ```
+/// #[derive(Debug, Default, FromRecords)]
+/// struct ActivityRecordSet {
+///     #[record(20, limit = 10_000)]
+///     records: Vec<Record>,
+/// }
+/// ```
+///
+/// To end decoding instead, supply `overflow = error` (or `overflow = skip` for
+/// the default). The struct then implements `FromRecords<LimitExceeded>`,
+/// failing with [`LimitExceeded`].
+///
+/// ```
+/// #[derive(Debug, Default, FromRecords)]
+/// struct ActivityRecordSet {
+///     #[record(20, limit = 10_000, overflow = error)]
+///     records: Vec<Record>,
+/// }
+/// ```
 #[cfg(feature = "derive")]
 pub use derailleur_derive::FromRecords;
 
@@ -43,9 +446,102 @@ pub use derailleur_derive::FromRecords;
 ///
 /// See the [`FromRecords`](macro@FromRecords) derive macro for an automatic
 /// implementation of this trait.
-pub trait FromRecords {
+///
+/// Receivers which can fail, such as while validating records, implement the
+/// trait for their own error type `E`, returning it from
+/// [`add_record`](Self::add_record) or [`finish`](Self::finish) to end
+/// decoding. The default, [`Infallible`], suits receivers which cannot.
+///
+/// The methods of [`FromRecord`] themselves cannot fail. A record receiver
+/// keeps a failure for the receiver producing it, which returns it when the
+/// next record or the end of the document is reached.
+pub trait FromRecords<E = Infallible> {
     /// Retrieve a receiver for a record, if one exists.
-    fn add_record(&mut self, id: u16) -> Option<&mut dyn FromRecord>;
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, E>;
+
+    /// Retrieve a receiver for a record, if one exists, given the definition
+    /// describing it.
+    ///
+    /// Decoders call this method in place of [`FromRecords::add_record`],
+    /// which it calls by default. Replaying a [`Tape`] does not, as
+    /// definitions are not recorded.
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, E> {
+        _ = definition;
+        self.add_record(id)
+    }
+
+    /// Receive metadata from the document header, before any records.
+    fn add_header(&mut self, info: HeaderInfo) {
+        _ = info;
+    }
+
+    /// Receive a span of the document skipped while decoding leniently (see
+    /// [`DecodeOptions::lenient`]), between records.
+    fn add_skipped(&mut self, skipped: Skipped) {
+        _ = skipped;
+    }
+
+    /// Receive the global message number of a record for which no receiver
+    /// exists, once it is passed over.
+    ///
+    /// This suits logging or counting messages the receiver doesn't collect,
+    /// such as those of devices new to it. Replaying a [`Tape`] does not call
+    /// this method.
+    fn unknown_record(&mut self, id: u16) {
+        _ = id;
+    }
+
+    /// Decide whether to continue decoding, before each record.
+    ///
+    /// Breaking ends decoding successfully, as for
+    /// [`DecodeOptions::max_records`], once the receiver has what it wants.
+    /// This applies only to the reader- and slice-based decoders, and to
+    /// replaying a [`Tape`].
+    fn control(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Conclude the document, once decoding reaches the end of its record
+    /// section (before the trailing CRC is checked), or stops early as the
+    /// options direct.
+    ///
+    /// An error, as from [`FromRecords::add_record`], is returned as
+    /// `Error::Receiver` from the decoder.
+    fn finish(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+impl<E, O: FromRecords<E> + ?Sized> FromRecords<E> for &mut O {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, E> {
+        (**self).add_record(id)
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, E> {
+        (**self).add_record_with(id, definition)
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        (**self).add_header(info);
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        (**self).add_skipped(skipped);
+    }
+    fn unknown_record(&mut self, id: u16) {
+        (**self).unknown_record(id);
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        (**self).control()
+    }
+    fn finish(&mut self) -> Result<(), E> {
+        (**self).finish()
+    }
 }
 
 /// Derive [`FromRecord`] for a struct representing a single record.
@@ -123,15 +619,47 @@ pub trait FromRecords {
 ///     }
 /// }
 /// ```
+///
+/// To receive a developer field, add the `developer_field` attribute, naming
+/// either the developer data index and field number, or the field name given
+/// in its description. Accumulator closures are supported as above. The
+/// generated code refers to [`FieldDescription`], which must be in scope.
+///
+/// ```
+/// use derailleur::avec::{FieldDescription, FromRecord};
+///
+/// #[derive(Debug, Default, FromRecord)]
+/// struct Record {
+///     #[developer_field(developer = 0, number = 5)]
+///     stryd_power: Option<u16>,
+///     #[developer_field(name = "Running Power")]
+///     power: Option<u16>,
+/// }
+/// ```
+///
+/// To receive the raw bytes of fields without a typed representation (see
+/// [`FromRecord::add_unknown`]), supply `unknown` in place of a field number.
+/// Without a closure, the field number, base type, and an owned copy of the
+/// bytes are pushed to a collection. A closure receives these as a tuple.
+///
+/// ```
+/// #[derive(Debug, Default, FromRecord)]
+/// struct Record {
+///     #[field(unknown)]
+///     unknown: Vec<(u8, u8, Vec<u8>)>,
+/// }
+/// ```
 #[cfg(feature = "derive")]
 pub use derailleur_derive::FromRecord;
 
 /// Receive field values for a record.
 ///
 /// Before publishing, fields are converted to their corresponding Rust
-/// primitive, and those holding the 'invalid' marker value are skipped. Array
-/// types (including strings) are published item-by-item, calling the receiver
-/// repeatedly.
+/// primitive, and those holding the 'invalid' marker value are published apart
+/// (see [`FromRecord::add_invalid`]). Array types (including strings) are
+/// published item-by-item, calling the receiver repeatedly. Strings are also
+/// published whole, once the field is complete (see
+/// [`FromRecord::add_string`]).
 ///
 /// The default implementation of each method ignores received values.
 ///
@@ -139,8 +667,25 @@ pub use derailleur_derive::FromRecord;
 /// implementation of this trait.
 #[allow(unused_variables)]
 pub trait FromRecord {
+    /// Begin receiving the record, before any of its values.
+    fn begin(&mut self) {}
+    /// End receiving the record, once all of its values have been received.
+    ///
+    /// This is not called if decoding fails within the record.
+    fn end(&mut self) {}
+
     /// Add the compressed time offset to the record.
     fn add_time_offset(&mut self, _: u8) {}
+    /// Receive a warning about a field of the record, which was skipped.
+    fn add_warning(&mut self, _: Warning) {}
+    /// Receive the position of the next value of an array field within the
+    /// array, before the value is added.
+    ///
+    /// This method is called for the values of arrays received one at a time,
+    /// such as when some of their values are invalid, and by the default
+    /// implementations of the methods receiving arrays at once. Positions
+    /// within `string` fields are those within each string.
+    fn add_element(&mut self, field: u8, _: Element) {}
     /// Add a `u8` for a field to the record.
     ///
     /// This method receives values for fields represented by a, or an array of,
@@ -167,4 +712,422 @@ pub trait FromRecord {
     fn add_f32(&mut self, field: u8, _: f32) {}
     /// Add a `f64` for a field to the record.
     fn add_f64(&mut self, field: u8, _: f64) {}
+
+    /// Add an array of `u8`s for a field to the record.
+    ///
+    /// This method receives the values of an array field at once, such as the
+    /// bytes of a `string` or `byte` field, if all are valid. Single values,
+    /// and arrays with invalid values, are received by [`FromRecord::add_u8`].
+    /// The default implementation adds each value in turn with
+    /// [`FromRecord::add_u8`], following its position with
+    /// [`FromRecord::add_element`].
+    fn add_u8_slice(&mut self, field: u8, values: &[u8]) {
+        add_each(self, field, values, Self::add_u8);
+    }
+    /// Add an array of `u16`s for a field to the record.
+    fn add_u16_slice(&mut self, field: u8, values: &[u16]) {
+        add_each(self, field, values, Self::add_u16);
+    }
+    /// Add an array of `u32`s for a field to the record.
+    fn add_u32_slice(&mut self, field: u8, values: &[u32]) {
+        add_each(self, field, values, Self::add_u32);
+    }
+    /// Add an array of `u64`s for a field to the record.
+    fn add_u64_slice(&mut self, field: u8, values: &[u64]) {
+        add_each(self, field, values, Self::add_u64);
+    }
+
+    /// Add an array of `i8`s for a field to the record.
+    fn add_i8_slice(&mut self, field: u8, values: &[i8]) {
+        add_each(self, field, values, Self::add_i8);
+    }
+    /// Add an array of `i16`s for a field to the record.
+    fn add_i16_slice(&mut self, field: u8, values: &[i16]) {
+        add_each(self, field, values, Self::add_i16);
+    }
+    /// Add an array of `i32`s for a field to the record.
+    fn add_i32_slice(&mut self, field: u8, values: &[i32]) {
+        add_each(self, field, values, Self::add_i32);
+    }
+    /// Add an array of `i64`s for a field to the record.
+    fn add_i64_slice(&mut self, field: u8, values: &[i64]) {
+        add_each(self, field, values, Self::add_i64);
+    }
+
+    /// Add an array of `f32`s for a field to the record.
+    fn add_f32_slice(&mut self, field: u8, values: &[f32]) {
+        add_each(self, field, values, Self::add_f32);
+    }
+    /// Add an array of `f64`s for a field to the record.
+    fn add_f64_slice(&mut self, field: u8, values: &[f64]) {
+        add_each(self, field, values, Self::add_f64);
+    }
+
+    /// Add a string for a field to the record.
+    ///
+    /// This method receives each non-empty, NUL-terminated string of a
+    /// `string` field as bytes, without the terminator, after its bytes have
+    /// been published with [`FromRecord::add_u8`]. A field may hold multiple
+    /// strings. The bytes are usually (but not necessarily) UTF-8.
+    fn add_string(&mut self, field: u8, _: &[u8]) {}
+
+    /// Add the raw bytes of a field without a typed representation to the
+    /// record, with its base type.
+    ///
+    /// This method receives fields of base types unknown to this library, and
+    /// those whose size is not a multiple of the size of their base type
+    /// (following [`Warning::MisalignedField`]). Their values are not
+    /// published otherwise.
+    fn add_unknown(&mut self, field: u8, base_type: u8, _: &[u8]) {}
+    /// Add the 'invalid' marker value of a field to the record, with its base
+    /// type.
+    ///
+    /// Values holding the marker are passed to this method in place of the
+    /// method for their primitive, such as to keep them when encoding the
+    /// record again. Invalid values of arrays follow their position (see
+    /// [`FromRecord::add_element`]), and `string` fields without any strings
+    /// are received as a single invalid value.
+    fn add_invalid(&mut self, field: u8, base_type: u8) {}
+
+    /// Add a `u8` for a developer field to the record.
+    ///
+    /// Developer fields are decoded according to the `field_description`
+    /// message describing them, and skipped if no such message precedes them.
+    /// Identify a field by its developer and number, or by its name:
+    ///
+    /// ```
+    /// fn add_developer_u16(&mut self, field: &FieldDescription, value: u16) {
+    ///     if field.name() == Some("Running Power") {
+    ///         self.power = Some(value);
+    ///     }
+    /// }
+    /// ```
+    fn add_developer_u8(&mut self, field: &FieldDescription, _: u8) {}
+    /// Add a `u16` for a developer field to the record.
+    fn add_developer_u16(&mut self, field: &FieldDescription, _: u16) {}
+    /// Add a `u32` for a developer field to the record.
+    fn add_developer_u32(&mut self, field: &FieldDescription, _: u32) {}
+    /// Add a `u64` for a developer field to the record.
+    fn add_developer_u64(&mut self, field: &FieldDescription, _: u64) {}
+
+    /// Add a `i8` for a developer field to the record.
+    fn add_developer_i8(&mut self, field: &FieldDescription, _: i8) {}
+    /// Add a `i16` for a developer field to the record.
+    fn add_developer_i16(&mut self, field: &FieldDescription, _: i16) {}
+    /// Add a `i32` for a developer field to the record.
+    fn add_developer_i32(&mut self, field: &FieldDescription, _: i32) {}
+    /// Add a `i64` for a developer field to the record.
+    fn add_developer_i64(&mut self, field: &FieldDescription, _: i64) {}
+
+    /// Add a `f32` for a developer field to the record.
+    fn add_developer_f32(&mut self, field: &FieldDescription, _: f32) {}
+    /// Add a `f64` for a developer field to the record.
+    fn add_developer_f64(&mut self, field: &FieldDescription, _: f64) {}
+    /// Add the 'invalid' marker value of a developer field to the record, of
+    /// the base type given by its description.
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {}
+}
+
+/// Produce record receivers for a document in a slice, borrowing from it.
+///
+/// This is the counterpart of [`FromRecords`] for [`decode_slice_ref`], whose
+/// record receivers take strings and bytes borrowed from the slice for its
+/// whole lifetime, rather than copying them.
+pub trait FromRecordsRef<'a> {
+    /// Retrieve a receiver for a record, if one exists.
+    fn add_record(&mut self, id: u16) -> Option<&mut dyn FromRecordRef<'a>>;
+
+    /// Receive metadata from the document header, before any records.
+    fn add_header(&mut self, info: HeaderInfo) {
+        _ = info;
+    }
+
+    /// Decide whether to continue decoding, before each record, as for
+    /// [`FromRecords::control`].
+    fn control(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Conclude the document, as for [`FromRecords::finish`].
+    fn finish(&mut self) {}
+}
+
+/// Produce record receivers of a single type for a document, without dynamic
+/// dispatch.
+///
+/// This is the counterpart of [`FromRecords`] for [`decode_slice_static`] and
+/// [`decode_reader_static`], whose decoding is specialized to the receiver
+/// type. Where a record set holds several record types, its receiver can be an
+/// enum of references to them, implementing [`FromRecord`] by forwarding to
+/// each.
+///
+/// ```
+/// struct Records { file_id: FileId, records: Vec<Record> }
+///
+/// enum Receiver<'a> { FileId(&'a mut FileId), Record(&'a mut Record) }
+///
+/// impl FromRecordsStatic for Records {
+///     type Receiver<'s> = Receiver<'s>;
+///
+///     fn add_record(&mut self, id: u16) -> Option<Receiver<'_>> {
+///         match id {
+///             0 => Some(Receiver::FileId(&mut self.file_id)),
+///             20 => {
+///                 self.records.push(Record::default());
+///                 self.records.last_mut().map(Receiver::Record)
+///             }
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait FromRecordsStatic {
+    /// Receiver for a single record, which may borrow from the record set.
+    type Receiver<'s>: FromRecord
+    where
+        Self: 's;
+
+    /// Retrieve a receiver for a record, if one exists.
+    fn add_record(&mut self, id: u16) -> Option<Self::Receiver<'_>>;
+
+    /// Receive metadata from the document header, before any records.
+    fn add_header(&mut self, info: HeaderInfo) {
+        _ = info;
+    }
+
+    /// Decide whether to continue decoding, before each record, as for
+    /// [`FromRecords::control`].
+    fn control(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Conclude the document, as for [`FromRecords::finish`].
+    fn finish(&mut self) {}
+}
+
+/// Receive values for a record, borrowing strings and bytes from the document.
+///
+/// Values other than strings and bytes are received through [`FromRecord`].
+/// The default implementation of each method forwards to its counterpart
+/// there, copying the borrow away.
+pub trait FromRecordRef<'a>: FromRecord {
+    /// Add a string for a field to the record, borrowed from the document.
+    ///
+    /// This method receives strings in place of [`FromRecord::add_string`].
+    fn add_string_ref(&mut self, field: u8, value: &'a [u8]) {
+        self.add_string(field, value);
+    }
+
+    /// Add the raw bytes of a field without a typed representation to the
+    /// record, borrowed from the document, with its base type.
+    ///
+    /// This method receives bytes in place of [`FromRecord::add_unknown`].
+    fn add_unknown_ref(&mut self, field: u8, base_type: u8, bytes: &'a [u8]) {
+        self.add_unknown(field, base_type, bytes);
+    }
+}
+
+/// Add each value of an array to a record, following its position.
+fn add_each<O: FromRecord + ?Sized, T: Copy>(
+    o: &mut O,
+    field: u8,
+    values: &[T],
+    add: fn(&mut O, u8, T),
+) {
+    let count = values.len() as u8;
+
+    for (index, &value) in values.iter().enumerate() {
+        let index = index as u8;
+        o.add_element(field, Element { index, count });
+        add(o, field, value);
+    }
+}
+
+/// Position of a value within an array field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Element {
+    /// Index of the value, counting invalid values.
+    pub index: u8,
+    /// Number of values in the array, including invalid values.
+    pub count: u8,
+}
+
+/// A recoverable problem with a field, found while decoding a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// A field whose size is not a multiple of the size of its base type.
+    MisalignedField { field: u8, size: u8 },
+    /// A field with a size of zero.
+    EmptyField { field: u8 },
+}
+
+/// A document held more records of a message than a receiver collects, as
+/// directed by `overflow = error` (see [`FromRecords`](macro@FromRecords)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Found more than {limit} records of message {id}.")]
+pub struct LimitExceeded {
+    /// Global message number of the records.
+    pub id: u16,
+    /// Number of records collected.
+    pub limit: usize,
+}
+
+/// A malformed span of a document, skipped while decoding leniently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skipped {
+    /// Offset to the start of the malformed record, from the start of the
+    /// document.
+    pub offset: usize,
+    /// Number of bytes skipped, including the malformed record header.
+    pub length: usize,
+    /// Problem with the malformed record.
+    pub error: Malformed,
+}
+
+/// Position in a document at which a decoding error occurred.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Offset from the start of the document reached when the error occurred.
+    pub offset: usize,
+    /// Local message number of the record being decoded, if any.
+    pub local: Option<u8>,
+    /// Global message number of the data record being decoded, if known.
+    pub global: Option<u16>,
+    /// Field number of the field being decoded, if any (other than a developer
+    /// field).
+    pub field: Option<u8>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at offset {}", self.offset)?;
+
+        if let Some(local) = self.local {
+            write!(f, ", local message {local}")?;
+        }
+        if let Some(global) = self.global {
+            write!(f, ", global message {global}")?;
+        }
+        if let Some(field) = self.field {
+            write!(f, ", field {field}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem with a record, causing it to be skipped while decoding leniently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Malformed {
+    /// Found the developer data flag on a data record header.
+    DeveloperData,
+    /// Found a data record for a local message number without a definition.
+    MissingDefinition { local: u8 },
+}
+
+/// Produce the records of a document, for encoding.
+///
+/// Each record is encoded with a definition derived from its fields. Up to 16
+/// definitions are kept at once, each under a local message number, and reused
+/// by later records with identical fields. Once all are in use, the least
+/// recently used definition is replaced.
+pub trait IntoRecords {
+    /// Publish each record to an encoder, with its global message number.
+    ///
+    /// Records are visited more than once while encoding, and must be
+    /// published identically each time.
+    fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord));
+}
+
+/// Produce the field values of a record, for encoding.
+///
+/// ```
+/// impl IntoRecord for Record {
+///     fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+///         add(253, Value::U32(self.timestamp));
+///
+///         if let Some(heart_rate) = self.heart_rate {
+///             add(3, Value::U8(heart_rate));
+///         }
+///     }
+/// }
+/// ```
+pub trait IntoRecord {
+    /// Publish each field value to an encoder, with its field number.
+    ///
+    /// Records are visited more than once while encoding, and must publish the
+    /// same fields, in the same order, each time. Consecutive values of a
+    /// field are encoded as an array, and each field may only be published
+    /// once.
+    fn fields(&self, add: &mut dyn FnMut(u8, Value));
+
+    /// Publish each developer field value to an encoder, with its field number
+    /// and developer data index.
+    ///
+    /// Developer fields follow the standard fields of the record, and must be
+    /// described by `developer_data_id` and `field_description` records
+    /// earlier in the document. As with [`IntoRecord::fields`], the same
+    /// fields must be published each time.
+    fn developer_fields(&self, _: &mut dyn FnMut(u8, u8, Value)) {}
+
+    /// Declare the size and base type of a field, in place of those of its
+    /// values.
+    ///
+    /// This suits keeping the definition a record was decoded with, such as
+    /// an `enum` or `uint32z` field, or a `string` padded to a fixed size.
+    /// The values of the field must fit the declaration: each must be of the
+    /// Rust primitive of the base type (or the bytes of a string), and they
+    /// must fill the size exactly, although strings are padded with the
+    /// 'invalid' marker value.
+    fn field_info(&self, field: u8) -> Option<FieldInfo> {
+        _ = field;
+        None
+    }
+
+    /// Declare the size and base type of a developer field, as for
+    /// [`IntoRecord::field_info`], given its developer data index.
+    ///
+    /// The base type must be that given by the `field_description` record
+    /// describing the field.
+    fn developer_field_info(&self, field: u8, developer: u8) -> Option<FieldInfo> {
+        _ = (field, developer);
+        None
+    }
+}
+
+/// A field value, for encoding.
+///
+/// Each value is encoded with the base type corresponding to its Rust
+/// primitive, unless another is declared for its field (see
+/// [`IntoRecord::field_info`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    /// A `uint8`.
+    U8(u8),
+    /// A `uint16`.
+    U16(u16),
+    /// A `uint32`.
+    U32(u32),
+    /// A `uint64`.
+    U64(u64),
+    /// A `sint8`.
+    I8(i8),
+    /// A `sint16`.
+    I16(i16),
+    /// A `sint32`.
+    I32(i32),
+    /// A `sint64`.
+    I64(i64),
+    /// A `float32`.
+    F32(f32),
+    /// A `float64`.
+    F64(f64),
+    /// A `string`, of at most 254 bytes.
+    String(&'a str),
+    /// An array of `byte`, of 1 to 255 bytes.
+    Bytes(&'a [u8]),
+    /// The 'invalid' marker value of a base type, given as its byte.
+    Invalid(u8),
 }
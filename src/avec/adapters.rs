@@ -0,0 +1,742 @@
+//! Adapters wrapping receivers, to reuse them in new ways.
+
+use core::{convert::Infallible, ops::ControlFlow};
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use super::{DefinitionInfo, FromRecord, FromRecords, HeaderInfo, Skipped};
+#[cfg(feature = "std")]
+use super::{Element, FieldDescription, Tape, Value, Warning};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// A receiver forwarding only the records of selected global message numbers
+/// to another.
+///
+/// Records not selected are passed over, as if the wrapped receiver had no
+/// receiver for them.
+///
+/// ```
+/// // Collect only `session` and `lap` records.
+/// let mut records = Filter::allow(ActivityRecordSet::default(), [18, 19]);
+/// derailleur::avec::decode_slice(&data, &mut records)?;
+/// let records = records.into_inner();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Filter<O, P> {
+    inner: O,
+    predicate: P,
+}
+
+impl<O, P: FnMut(u16) -> bool> Filter<O, P> {
+    /// Wrap a receiver, forwarding records for which a predicate of their
+    /// global message number holds.
+    pub fn new(inner: O, predicate: P) -> Self {
+        Self { inner, predicate }
+    }
+}
+
+impl<O> Filter<O, ()> {
+    /// Wrap a receiver, forwarding records of the global message numbers of an
+    /// allow-list.
+    pub fn allow(inner: O, ids: impl AsRef<[u16]>) -> Filter<O, impl FnMut(u16) -> bool> {
+        Filter::new(inner, move |id| ids.as_ref().contains(&id))
+    }
+}
+
+impl<O, P> Filter<O, P> {
+    /// Borrow the wrapped receiver.
+    pub fn get_ref(&self) -> &O {
+        &self.inner
+    }
+
+    /// Mutably borrow the wrapped receiver.
+    pub fn get_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    /// Unwrap the receiver.
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+}
+
+impl<E, O: FromRecords<E>, P: FnMut(u16) -> bool> FromRecords<E> for Filter<O, P> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, E> {
+        match (self.predicate)(id) {
+            true => self.inner.add_record(id),
+            false => Ok(None),
+        }
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, E> {
+        match (self.predicate)(id) {
+            true => self.inner.add_record_with(id, definition),
+            false => Ok(None),
+        }
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.inner.add_header(info);
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        self.inner.add_skipped(skipped);
+    }
+    fn unknown_record(&mut self, id: u16) {
+        self.inner.unknown_record(id);
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        self.inner.control()
+    }
+    fn finish(&mut self) -> Result<(), E> {
+        self.inner.finish()
+    }
+}
+
+/// A receiver collecting the records of a global message number into any
+/// collection implementing [`Extend`].
+///
+/// ```
+/// let mut laps = Collect::new(19, VecDeque::<Lap>::new());
+/// derailleur::avec::decode_slice(&data, &mut laps)?;
+/// let laps = laps.into_inner();
+/// ```
+///
+/// Each record is added to the collection once the next record begins or the
+/// document is finished, or when the collection is unwrapped.
+#[derive(Debug, Clone)]
+pub struct Collect<C, T> {
+    id: u16,
+    collection: C,
+    record: Option<T>, // The record being received.
+}
+
+impl<C: Extend<T>, T> Collect<C, T> {
+    /// Wrap a collection, collecting the records of a global message number.
+    pub fn new(id: u16, collection: C) -> Self {
+        Self {
+            id,
+            collection,
+            record: None,
+        }
+    }
+
+    /// Borrow the collection, without any record being received.
+    pub fn get_ref(&self) -> &C {
+        &self.collection
+    }
+
+    /// Unwrap the collection, adding any record being received.
+    pub fn into_inner(mut self) -> C {
+        self.collection.extend(self.record.take());
+        self.collection
+    }
+}
+
+impl<C: Extend<T>, T: FromRecord + Default> FromRecords for Collect<C, T> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.collection.extend(self.record.take());
+
+        match id == self.id {
+            true => Ok(Some(self.record.insert(T::default()))),
+            false => Ok(None),
+        }
+    }
+    fn finish(&mut self) -> Result<(), Infallible> {
+        self.collection.extend(self.record.take());
+        Ok(())
+    }
+}
+
+/// A receiver forwarding each record to two others.
+///
+/// This allows a single pass over a document to feed several receivers, such
+/// as a statistics collector alongside a typed record set. Nest tees to feed
+/// more.
+///
+/// ```
+/// let mut records = Tee::new(Statistics::default(), ActivityRecordSet::default());
+/// derailleur::avec::decode_slice(&data, &mut records)?;
+/// let (statistics, activity) = records.into_inner();
+/// ```
+///
+/// Each record is buffered until it ends, then published to each receiver in
+/// turn before the next call to the tee. Records in which decoding fails are
+/// not published. Once a receiver breaks from [`FromRecords::control`], it
+/// receives no further records, and decoding stops once both have. A failure
+/// of either receiver, of error type `E`, ends decoding by the next record.
+///
+/// _Requires Cargo feature `std`._
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct Tee<A, B, E = Infallible> {
+    a: A,
+    b: B,
+    done: (bool, bool),
+    buffer: Buffer,
+    failure: Option<E>, // A failure while publishing, not yet returned.
+}
+
+#[cfg(feature = "std")]
+impl<A, B, E> Tee<A, B, E> {
+    /// Wrap two receivers.
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a,
+            b,
+            done: (false, false),
+            buffer: Buffer::default(),
+            failure: None,
+        }
+    }
+
+    /// Borrow the wrapped receivers.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.a, &self.b)
+    }
+
+    /// Mutably borrow the wrapped receivers.
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a, &mut self.b)
+    }
+
+    /// Unwrap the receivers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+
+    /// Publish the buffered record to each receiver, if it has ended.
+    fn publish(&mut self) -> Result<(), E>
+    where
+        A: FromRecords<E>,
+        B: FromRecords<E>,
+    {
+        let Some(id) = self.buffer.take() else {
+            return Ok(());
+        };
+
+        if !self.done.0 {
+            self.buffer.publish_to(id, &mut self.a)?;
+        }
+        if !self.done.1 {
+            self.buffer.publish_to(id, &mut self.b)?;
+        }
+
+        Ok(())
+    }
+
+    /// Publish the buffered record where a failure can't be returned, keeping
+    /// it for the next call which can.
+    fn publish_or_keep(&mut self)
+    where
+        A: FromRecords<E>,
+        B: FromRecords<E>,
+    {
+        if let Err(err) = self.publish() {
+            self.failure.get_or_insert(err);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E, A: FromRecords<E>, B: FromRecords<E>> FromRecords<E> for Tee<A, B, E> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        Ok(Some(self.buffer.start(id, None)))
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        Ok(Some(self.buffer.start(id, Some(definition))))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.publish_or_keep();
+        self.a.add_header(info);
+        self.b.add_header(info);
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        self.publish_or_keep();
+        self.a.add_skipped(skipped);
+        self.b.add_skipped(skipped);
+    }
+    fn unknown_record(&mut self, id: u16) {
+        self.publish_or_keep();
+        self.a.unknown_record(id);
+        self.b.unknown_record(id);
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        self.publish_or_keep();
+        self.done.0 = self.done.0 || self.a.control().is_break();
+        self.done.1 = self.done.1 || self.b.control().is_break();
+
+        match self.done {
+            (true, true) => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+    fn finish(&mut self) -> Result<(), E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        self.a.finish()?;
+        self.b.finish()
+    }
+}
+
+/// A call made to a receiver, as reported by [`Inspect`].
+///
+/// _Requires Cargo feature `std`._
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Call<'c> {
+    /// Metadata from the document header.
+    Header(HeaderInfo),
+    /// A span of the document skipped while decoding leniently.
+    Skipped(Skipped),
+    /// A record, and whether the receiver produced a receiver for it.
+    Record { id: u16, received: bool },
+    /// The compressed time offset of the record.
+    TimeOffset(u8),
+    /// A warning about a field of the record.
+    Warning(Warning),
+    /// The position of the next value of an array field.
+    Element(u8, Element),
+    /// A value for a field of the record, other than a string.
+    Value(u8, Value<'c>),
+    /// A whole string for a field of the record.
+    String(u8, &'c [u8]),
+    /// The raw bytes of a field of an unknown base type.
+    Unknown {
+        field: u8,
+        base_type: u8,
+        bytes: &'c [u8],
+    },
+    /// A value for a developer field of the record.
+    Developer(&'c FieldDescription, Value<'c>),
+    /// The end of the document.
+    Finish,
+}
+
+/// A receiver reporting each call made to another to a closure, such as to
+/// log them.
+///
+/// This shows which records a receiver declines, and the values it is given
+/// for those it receives, which helps to find why a record is missing values.
+///
+/// ```
+/// let mut records = Inspect::new(ActivityRecordSet::default(), |call| {
+///     eprintln!("{call:?}");
+/// });
+/// derailleur::avec::decode_slice(&data, &mut records)?;
+/// ```
+///
+/// Each record is buffered until it ends, then reported and published to the
+/// receiver before the next call to the inspector. Values are reported for
+/// records the receiver declines too. Arrays are reported value by value. A
+/// failure of the receiver, of error type `E`, ends decoding by the next
+/// record.
+///
+/// _Requires Cargo feature `std`._
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct Inspect<O, F, E = Infallible> {
+    inner: O,
+    f: F,
+    buffer: Buffer,
+    failure: Option<E>, // A failure while publishing, not yet returned.
+}
+
+#[cfg(feature = "std")]
+impl<O, F: FnMut(Call<'_>), E> Inspect<O, F, E> {
+    /// Wrap a receiver, reporting its calls to a closure.
+    pub fn new(inner: O, f: F) -> Self {
+        Self {
+            inner,
+            f,
+            buffer: Buffer::default(),
+            failure: None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<O, F, E> Inspect<O, F, E> {
+    /// Borrow the wrapped receiver.
+    pub fn get_ref(&self) -> &O {
+        &self.inner
+    }
+
+    /// Mutably borrow the wrapped receiver.
+    pub fn get_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    /// Unwrap the receiver.
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    /// Report and publish the buffered record, if it has ended.
+    fn publish(&mut self) -> Result<(), E>
+    where
+        O: FromRecords<E>,
+        F: FnMut(Call<'_>),
+    {
+        let Some(id) = self.buffer.take() else {
+            return Ok(());
+        };
+
+        let f = &mut self.f;
+
+        let inner = match self.buffer.add_record_to(id, &mut self.inner)? {
+            Some(r) => {
+                f(Call::Record { id, received: true });
+                Some(r)
+            }
+            None => {
+                f(Call::Record {
+                    id,
+                    received: false,
+                });
+                self.inner.unknown_record(id);
+                None
+            }
+        };
+
+        self.buffer.tape.replay_record(&mut Reported { inner, f });
+
+        Ok(())
+    }
+
+    /// Report and publish the buffered record where a failure can't be
+    /// returned, keeping it for the next call which can.
+    fn publish_or_keep(&mut self)
+    where
+        O: FromRecords<E>,
+        F: FnMut(Call<'_>),
+    {
+        if let Err(err) = self.publish() {
+            self.failure.get_or_insert(err);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E, O: FromRecords<E>, F: FnMut(Call<'_>)> FromRecords<E> for Inspect<O, F, E> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        Ok(Some(self.buffer.start(id, None)))
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        Ok(Some(self.buffer.start(id, Some(definition))))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.publish_or_keep();
+        (self.f)(Call::Header(info));
+        self.inner.add_header(info);
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        self.publish_or_keep();
+        (self.f)(Call::Skipped(skipped));
+        self.inner.add_skipped(skipped);
+    }
+    fn unknown_record(&mut self, id: u16) {
+        self.publish_or_keep();
+        self.inner.unknown_record(id);
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        self.publish_or_keep();
+        self.inner.control()
+    }
+    fn finish(&mut self) -> Result<(), E> {
+        self.failure.take().map_or(Ok(()), Err)?;
+        self.publish()?;
+        (self.f)(Call::Finish);
+        self.inner.finish()
+    }
+}
+
+/// A record receiver reporting each call to a closure, before forwarding it
+/// to another, if one exists.
+#[cfg(feature = "std")]
+struct Reported<'r, F> {
+    inner: Option<&'r mut dyn FromRecord>,
+    f: &'r mut F,
+}
+
+/// Report and forward values of a type.
+#[cfg(feature = "std")]
+macro_rules! report {
+    ($($add:ident: $variant:ident),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: u8, value: report!(@type $variant)) {
+                (self.f)(Call::Value(field, Value::$variant(value)));
+
+                if let Some(o) = &mut self.inner {
+                    o.$add(field, value);
+                }
+            }
+        )*
+    };
+    (developer $($add:ident: $variant:ident),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: &FieldDescription, value: report!(@type $variant)) {
+                (self.f)(Call::Developer(field, Value::$variant(value)));
+
+                if let Some(o) = &mut self.inner {
+                    o.$add(field, value);
+                }
+            }
+        )*
+    };
+    (@type U8) => { u8 };
+    (@type U16) => { u16 };
+    (@type U32) => { u32 };
+    (@type U64) => { u64 };
+    (@type I8) => { i8 };
+    (@type I16) => { i16 };
+    (@type I32) => { i32 };
+    (@type I64) => { i64 };
+    (@type F32) => { f32 };
+    (@type F64) => { f64 };
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(Call<'_>)> FromRecord for Reported<'_, F> {
+    fn begin(&mut self) {
+        if let Some(o) = &mut self.inner {
+            o.begin();
+        }
+    }
+    fn end(&mut self) {
+        if let Some(o) = &mut self.inner {
+            o.end();
+        }
+    }
+
+    fn add_time_offset(&mut self, offset: u8) {
+        (self.f)(Call::TimeOffset(offset));
+
+        if let Some(o) = &mut self.inner {
+            o.add_time_offset(offset);
+        }
+    }
+    fn add_warning(&mut self, warning: Warning) {
+        (self.f)(Call::Warning(warning));
+
+        if let Some(o) = &mut self.inner {
+            o.add_warning(warning);
+        }
+    }
+    fn add_element(&mut self, field: u8, element: Element) {
+        (self.f)(Call::Element(field, element));
+
+        if let Some(o) = &mut self.inner {
+            o.add_element(field, element);
+        }
+    }
+
+    report! {
+        add_u8: U8,
+        add_u16: U16,
+        add_u32: U32,
+        add_u64: U64,
+        add_i8: I8,
+        add_i16: I16,
+        add_i32: I32,
+        add_i64: I64,
+        add_f32: F32,
+        add_f64: F64,
+    }
+
+    fn add_string(&mut self, field: u8, value: &[u8]) {
+        (self.f)(Call::String(field, value));
+
+        if let Some(o) = &mut self.inner {
+            o.add_string(field, value);
+        }
+    }
+    fn add_unknown(&mut self, field: u8, base_type: u8, bytes: &[u8]) {
+        (self.f)(Call::Unknown {
+            field,
+            base_type,
+            bytes,
+        });
+
+        if let Some(o) = &mut self.inner {
+            o.add_unknown(field, base_type, bytes);
+        }
+    }
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        (self.f)(Call::Value(field, Value::Invalid(base_type)));
+
+        if let Some(o) = &mut self.inner {
+            o.add_invalid(field, base_type);
+        }
+    }
+
+    report! {
+        developer
+        add_developer_u8: U8,
+        add_developer_u16: U16,
+        add_developer_u32: U32,
+        add_developer_u64: U64,
+        add_developer_i8: I8,
+        add_developer_i16: I16,
+        add_developer_i32: I32,
+        add_developer_i64: I64,
+        add_developer_f32: F32,
+        add_developer_f64: F64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        (self.f)(Call::Developer(field, Value::Invalid(field.base_type())));
+
+        if let Some(o) = &mut self.inner {
+            o.add_developer_invalid(field);
+        }
+    }
+}
+
+/// A single record, buffered to be published later.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+struct Buffer {
+    record: Option<(u16, bool)>, // The record and whether it has ended.
+    definition: Option<Vec<u8>>, // A copy of the definition of the record.
+    tape: Tape,                  // The values of the record.
+}
+
+#[cfg(feature = "std")]
+impl Buffer {
+    /// Begin buffering a record, discarding any record not yet taken.
+    fn start(&mut self, id: u16, definition: Option<DefinitionInfo<'_>>) -> &mut Self {
+        self.record = Some((id, false));
+        self.definition = definition.map(|d| Vec::from(d.r));
+        self.tape.clear();
+        self
+    }
+
+    /// Take the global message number of the buffered record, if it has
+    /// ended.
+    fn take(&mut self) -> Option<u16> {
+        match self.record.take() {
+            Some((id, true)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Retrieve a receiver for the buffered record from a receiver.
+    fn add_record_to<'o, E>(
+        &self,
+        id: u16,
+        o: &'o mut impl FromRecords<E>,
+    ) -> Result<Option<&'o mut dyn FromRecord>, E> {
+        match self.definition.as_deref() {
+            Some(d) => o.add_record_with(id, DefinitionInfo::new(d)),
+            None => o.add_record(id),
+        }
+    }
+
+    /// Publish the buffered record to a receiver.
+    fn publish_to<E>(&self, id: u16, o: &mut impl FromRecords<E>) -> Result<(), E> {
+        match self.add_record_to(id, o)? {
+            Some(r) => self.tape.replay_record(r),
+            None => o.unknown_record(id),
+        }
+
+        Ok(())
+    }
+}
+
+/// Buffer values of a type.
+#[cfg(feature = "std")]
+macro_rules! buffer {
+    ($($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: u8, value: $t) {
+                self.tape.$add(field, value);
+            }
+        )*
+    };
+    (developer $($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: &FieldDescription, value: $t) {
+                self.tape.$add(field, value);
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "std")]
+impl FromRecord for Buffer {
+    fn end(&mut self) {
+        if let Some((_, ended)) = &mut self.record {
+            *ended = true;
+        }
+    }
+
+    fn add_time_offset(&mut self, offset: u8) {
+        self.tape.add_time_offset(offset);
+    }
+    fn add_warning(&mut self, warning: Warning) {
+        self.tape.add_warning(warning);
+    }
+    fn add_element(&mut self, field: u8, element: Element) {
+        self.tape.add_element(field, element);
+    }
+
+    buffer! {
+        add_u8: u8,
+        add_u16: u16,
+        add_u32: u32,
+        add_u64: u64,
+        add_i8: i8,
+        add_i16: i16,
+        add_i32: i32,
+        add_i64: i64,
+        add_f32: f32,
+        add_f64: f64,
+        add_string: &[u8],
+    }
+
+    fn add_unknown(&mut self, field: u8, base_type: u8, bytes: &[u8]) {
+        self.tape.add_unknown(field, base_type, bytes);
+    }
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.tape.add_invalid(field, base_type);
+    }
+
+    buffer! {
+        developer
+        add_developer_u8: u8,
+        add_developer_u16: u16,
+        add_developer_u32: u32,
+        add_developer_u64: u64,
+        add_developer_i8: i8,
+        add_developer_i16: i16,
+        add_developer_i32: i32,
+        add_developer_i64: i64,
+        add_developer_f32: f32,
+        add_developer_f64: f64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        self.tape.add_developer_invalid(field);
+    }
+}
@@ -0,0 +1,203 @@
+//! Receiving values with the context of their records.
+
+use core::{convert::Infallible, mem};
+
+use super::{DefinitionInfo, Element, FromRecord, FromRecords, Warning};
+
+/// The context of a value, locating it within a document.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FieldContext {
+    /// Global message number of the record.
+    pub global: u16,
+    /// Local message number of the record, or zero when replayed from a
+    /// [`Tape`](super::Tape).
+    pub local: u8,
+    /// Field number, within the message.
+    pub field: u8,
+    /// Index of the value within an array field (counting invalid values), or
+    /// zero for other fields.
+    pub index: u8,
+}
+
+/// Receive the values of records, with their context.
+///
+/// Unlike [`FromRecord`], which receives the values of a single record, this
+/// trait suits a single receiver serving records of any message. Wrap it in a
+/// [`WithContext`] to decode documents to it.
+///
+/// ```
+/// impl FromFields for Counter {
+///     fn add_u16(&mut self, context: FieldContext, _: u16) {
+///         *self.0.entry((context.global, context.field)).or_default() += 1;
+///     }
+/// }
+///
+/// let mut counter = WithContext::new(Counter::default());
+/// derailleur::avec::decode_slice(&data, &mut counter)?;
+/// ```
+///
+/// Developer fields are not received.
+#[allow(unused_variables)]
+pub trait FromFields {
+    /// Begin receiving a record, before any of its values.
+    fn begin(&mut self, global: u16) {}
+    /// End receiving a record, once all of its values have been received.
+    ///
+    /// This is not called if decoding fails within the record.
+    fn end(&mut self, global: u16) {}
+
+    /// Add the compressed time offset of a record.
+    fn add_time_offset(&mut self, global: u16, _: u8) {}
+    /// Receive a warning about a field of a record, which was skipped.
+    fn add_warning(&mut self, global: u16, _: Warning) {}
+
+    /// Add a `u8` for a field.
+    fn add_u8(&mut self, context: FieldContext, _: u8) {}
+    /// Add a `u16` for a field.
+    fn add_u16(&mut self, context: FieldContext, _: u16) {}
+    /// Add a `u32` for a field.
+    fn add_u32(&mut self, context: FieldContext, _: u32) {}
+    /// Add a `u64` for a field.
+    fn add_u64(&mut self, context: FieldContext, _: u64) {}
+
+    /// Add an `i8` for a field.
+    fn add_i8(&mut self, context: FieldContext, _: i8) {}
+    /// Add an `i16` for a field.
+    fn add_i16(&mut self, context: FieldContext, _: i16) {}
+    /// Add an `i32` for a field.
+    fn add_i32(&mut self, context: FieldContext, _: i32) {}
+    /// Add an `i64` for a field.
+    fn add_i64(&mut self, context: FieldContext, _: i64) {}
+
+    /// Add an `f32` for a field.
+    fn add_f32(&mut self, context: FieldContext, _: f32) {}
+    /// Add an `f64` for a field.
+    fn add_f64(&mut self, context: FieldContext, _: f64) {}
+
+    /// Add a whole string for a `string` field, as for
+    /// [`FromRecord::add_string`].
+    fn add_string(&mut self, context: FieldContext, _: &[u8]) {}
+    /// Add the raw bytes of a field of an unknown base type, as for
+    /// [`FromRecord::add_unknown`].
+    fn add_unknown(&mut self, context: FieldContext, base_type: u8, _: &[u8]) {}
+}
+
+/// A receiver of documents, publishing the values of every record to a
+/// [`FromFields`] receiver with their context.
+#[derive(Debug, Default, Clone)]
+pub struct WithContext<O> {
+    inner: O,
+    context: FieldContext,
+}
+
+impl<O> WithContext<O> {
+    /// Wrap a receiver.
+    pub fn new(inner: O) -> Self {
+        Self {
+            inner,
+            context: FieldContext::default(),
+        }
+    }
+
+    /// Borrow the wrapped receiver.
+    pub fn get_ref(&self) -> &O {
+        &self.inner
+    }
+
+    /// Mutably borrow the wrapped receiver.
+    pub fn get_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    /// Unwrap the receiver.
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    /// Take the context of the next value of a field.
+    fn context(&mut self, field: u8) -> FieldContext {
+        FieldContext {
+            field,
+            index: mem::take(&mut self.context.index),
+            ..self.context
+        }
+    }
+}
+
+impl<O: FromFields> FromRecords for WithContext<O> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.context = FieldContext {
+            global: id,
+            ..FieldContext::default()
+        };
+
+        Ok(Some(self))
+    }
+
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.context = FieldContext {
+            global: id,
+            local: definition.local(),
+            ..FieldContext::default()
+        };
+
+        Ok(Some(self))
+    }
+}
+
+/// Forward values of a primitive type to the wrapped receiver.
+macro_rules! forward {
+    ($($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: u8, value: $t) {
+                let context = self.context(field);
+                self.inner.$add(context, value);
+            }
+        )*
+    };
+}
+
+impl<O: FromFields> FromRecord for WithContext<O> {
+    fn begin(&mut self) {
+        self.inner.begin(self.context.global);
+    }
+    fn end(&mut self) {
+        self.inner.end(self.context.global);
+    }
+
+    fn add_time_offset(&mut self, offset: u8) {
+        self.inner.add_time_offset(self.context.global, offset);
+    }
+    fn add_warning(&mut self, warning: Warning) {
+        self.inner.add_warning(self.context.global, warning);
+    }
+    fn add_element(&mut self, _: u8, element: Element) {
+        self.context.index = element.index;
+    }
+
+    forward! {
+        add_u8: u8,
+        add_u16: u16,
+        add_u32: u32,
+        add_u64: u64,
+        add_i8: i8,
+        add_i16: i16,
+        add_i32: i32,
+        add_i64: i64,
+        add_f32: f32,
+        add_f64: f64,
+    }
+
+    fn add_string(&mut self, field: u8, value: &[u8]) {
+        let context = self.context(field);
+        self.inner.add_string(context, value);
+    }
+    fn add_unknown(&mut self, field: u8, base_type: u8, bytes: &[u8]) {
+        let context = self.context(field);
+        self.inner.add_unknown(context, base_type, bytes);
+    }
+}
@@ -0,0 +1,283 @@
+//! Registry of developer field descriptions.
+
+use core::str;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// Global message number of `field_description` messages.
+pub(crate) const FIELD_DESCRIPTION: u16 = 206;
+/// Global message number of `developer_data_id` messages.
+pub(crate) const DEVELOPER_DATA_ID: u16 = 207;
+
+/// Maximum number of developer fields described at once.
+const CAPACITY: usize = 64;
+/// Maximum length of a developer field name, in bytes.
+const NAME: usize = 32;
+
+/// Description of a developer field, from a `field_description` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldDescription {
+    developer: u8,
+    number: u8,
+    base_type: u8,
+    name: Name,
+    accumulated: bool,
+}
+
+/// A field name, collected byte-by-byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Name {
+    bytes: [u8; NAME],
+    len: u8, // Exceeds the capacity if the name was too long.
+}
+
+impl FieldDescription {
+    /// Index of the developer declaring the field, matching the
+    /// `developer_data_index` of a `developer_data_id` message.
+    pub fn developer(&self) -> u8 {
+        self.developer
+    }
+
+    /// Number of the field, unique for its developer.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    /// FIT base type of the field.
+    pub fn base_type(&self) -> u8 {
+        self.base_type
+    }
+
+    /// Name of the field, such as "Running Power".
+    ///
+    /// Returns `None` if the description has no name, or a name that is not
+    /// valid UTF-8 or exceeds 32 bytes.
+    pub fn name(&self) -> Option<&str> {
+        let bytes = self.name.bytes.get(..self.name.len as usize)?;
+        (!bytes.is_empty()).then(|| str::from_utf8(bytes).ok())?
+    }
+
+    /// Whether the field is accumulated, holding a counter that rolls over.
+    ///
+    /// See [`DecodeOptions::accumulate_developer_fields`] to receive the
+    /// expanded totals instead.
+    ///
+    /// [`DecodeOptions::accumulate_developer_fields`]:
+    ///     super::DecodeOptions::accumulate_developer_fields
+    pub fn is_accumulated(&self) -> bool {
+        self.accumulated
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self {
+            bytes: [0; NAME],
+            len: 0,
+        }
+    }
+}
+
+impl Name {
+    fn push(&mut self, b: u8) {
+        if let Some(slot) = self.bytes.get_mut(self.len as usize) {
+            *slot = b;
+        }
+
+        self.len = (self.len + 1).min(NAME as u8 + 1);
+    }
+}
+
+/// Descriptions of the developer fields declared so far in a document.
+///
+/// Descriptions are collected from `field_description` messages as they are
+/// decoded, and discarded when their developer is declared again by a
+/// `developer_data_id` message. Once full, further descriptions are ignored,
+/// and their fields are skipped.
+#[derive(Debug, Clone)]
+pub(crate) struct Registry {
+    descriptions: [Option<FieldDescription>; CAPACITY],
+    totals: [Total; CAPACITY], // Running totals of accumulated fields.
+    pending: Pending,          // Fields of the message being decoded.
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Pending {
+    developer: Option<u8>,
+    number: Option<u8>,
+    base_type: Option<u8>,
+    name: Name,
+    accumulated: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Total {
+    last: u32,
+    value: u32,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            descriptions: [None; CAPACITY],
+            totals: [Total::default(); CAPACITY],
+            pending: Pending::default(),
+        }
+    }
+}
+
+impl Registry {
+    /// Find the description of a developer field.
+    pub(crate) fn get(&self, developer: u8, number: u8) -> Option<&FieldDescription> {
+        self.descriptions[self.position(developer, number)?].as_ref()
+    }
+
+    /// Add a value of an accumulated field to its running total, allowing the
+    /// value to roll over at the given bit width.
+    ///
+    /// Returns the updated total.
+    pub(crate) fn accumulate(&mut self, field: &FieldDescription, value: u32, bits: u32) -> u32 {
+        let Some(i) = self.position(field.developer, field.number) else {
+            return value;
+        };
+
+        let total = &mut self.totals[i];
+        let mask = u32::MAX >> (u32::BITS - bits);
+
+        total.value = total
+            .value
+            .wrapping_add(value.wrapping_sub(total.last) & mask);
+        total.last = value;
+
+        total.value
+    }
+
+    fn position(&self, developer: u8, number: u8) -> Option<usize> {
+        self.descriptions
+            .iter()
+            .position(|d| d.is_some_and(|d| (d.developer, d.number) == (developer, number)))
+    }
+
+    /// Note a `u8` field of a message, if it concerns developer fields.
+    pub(crate) fn capture(&mut self, global: u16, field: u8, value: u8) {
+        let slot = match (global, field) {
+            (FIELD_DESCRIPTION, 3) => return self.pending.name.push(value),
+            (FIELD_DESCRIPTION, 10) => return self.pending.accumulated = true,
+            (FIELD_DESCRIPTION, 0) => &mut self.pending.developer,
+            (FIELD_DESCRIPTION, 1) => &mut self.pending.number,
+            (FIELD_DESCRIPTION, 2) => &mut self.pending.base_type,
+            (DEVELOPER_DATA_ID, 3) => &mut self.pending.developer,
+            _ => return,
+        };
+
+        *slot = Some(value);
+    }
+
+    /// Conclude a message, applying any developer field declarations.
+    pub(crate) fn commit(&mut self, global: u16) {
+        let pending = core::mem::take(&mut self.pending);
+
+        match (global, pending) {
+            (
+                FIELD_DESCRIPTION,
+                Pending {
+                    developer: Some(developer),
+                    number: Some(number),
+                    base_type: Some(base_type),
+                    name,
+                    accumulated,
+                },
+            ) => {
+                let description = FieldDescription {
+                    developer,
+                    number,
+                    base_type,
+                    name,
+                    accumulated,
+                };
+
+                // Replace an existing description, or take a free slot.
+                let slot = self
+                    .position(developer, number)
+                    .or_else(|| self.descriptions.iter().position(Option::is_none));
+
+                if let Some(slot) = slot {
+                    self.descriptions[slot] = Some(description);
+                    self.totals[slot] = Total::default();
+                }
+            }
+            (
+                DEVELOPER_DATA_ID,
+                Pending {
+                    developer: Some(developer),
+                    ..
+                },
+            ) => {
+                for d in &mut self.descriptions {
+                    if d.is_some_and(|d| d.developer == developer) {
+                        *d = None;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Registry {
+    /// Append the descriptions and running totals to a buffer, to be restored
+    /// with [`Registry::read`].
+    ///
+    /// Fields of a message being decoded are not kept, since decoders are only
+    /// suspended between records.
+    pub(crate) fn write(&self, w: &mut Vec<u8>) {
+        let count = self.descriptions.iter().flatten().count();
+        w.push(count as u8);
+
+        for (i, (d, total)) in self.descriptions.iter().zip(&self.totals).enumerate() {
+            let Some(d) = d else { continue };
+
+            w.extend([i as u8, d.developer, d.number, d.base_type, d.name.len]);
+            w.extend(d.name.bytes);
+            w.push(d.accumulated as u8);
+            w.extend(total.last.to_le_bytes());
+            w.extend(total.value.to_le_bytes());
+        }
+    }
+
+    /// Restore descriptions and running totals written by [`Registry::write`],
+    /// advancing past them.
+    pub(crate) fn read(r: &mut &[u8]) -> Option<Self> {
+        fn take<const N: usize>(r: &mut &[u8]) -> Option<[u8; N]> {
+            let (bytes, rest) = r.split_first_chunk()?;
+            *r = rest;
+            Some(*bytes)
+        }
+
+        let mut registry = Self::default();
+        let [count] = take(r)?;
+
+        for _ in 0..count {
+            let [i, developer, number, base_type, len] = take(r)?;
+            let bytes = take(r)?;
+            let [accumulated] = take(r)?;
+            let last = u32::from_le_bytes(take(r)?);
+            let value = u32::from_le_bytes(take(r)?);
+
+            *registry.descriptions.get_mut(i as usize)? = Some(FieldDescription {
+                developer,
+                number,
+                base_type,
+                name: Name { bytes, len },
+                accumulated: accumulated != 0,
+            });
+            registry.totals[i as usize] = Total { last, value };
+        }
+
+        Some(registry)
+    }
+}
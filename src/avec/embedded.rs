@@ -0,0 +1,83 @@
+//! Decoder over [`embedded_io_async`] readers, without the standard library.
+//!
+//! _Requires Cargo feature `embedded-io-async`._
+
+use core::convert::Infallible;
+
+use embedded_io_async::{Read, ReadExactError};
+use thiserror::Error;
+
+use crate::sans::Decoder;
+
+use super::{DecodeOptions, FromRecords, slice};
+
+/// Errors occurring while decoding from an [`embedded_io_async`] reader.
+#[derive(Debug, Error)]
+pub enum Error<R, E = Infallible> {
+    /// An error from the reader.
+    #[error("Reader failed: {0:?}.")]
+    Read(R),
+    /// Unexpectedly reached the end of the reader.
+    #[error("Unexpectedly reached the end of the reader.")]
+    EndOfInput,
+    /// The document is larger than the buffer.
+    #[error("The document ({size} bytes) is larger than the buffer.")]
+    BufferTooSmall { size: usize },
+    /// Decoding the buffered document failed.
+    #[error(transparent)]
+    Decode(#[from] slice::Error<E>),
+}
+
+impl<R, E> From<ReadExactError<R>> for Error<R, E> {
+    fn from(err: ReadExactError<R>) -> Self {
+        match err {
+            ReadExactError::UnexpectedEof => Self::EndOfInput,
+            ReadExactError::Other(err) => Self::Read(err),
+        }
+    }
+}
+
+/// Decode records from an [`embedded_io_async`] reader of a document, into a
+/// buffer, publishing to a receiver.
+///
+/// ```
+/// let mut buf = [0; 4096];
+/// let mut records = Records::default();
+/// derailleur::avec::embedded::decode(&mut uart, &mut buf, &mut records).await?;
+/// ```
+///
+/// The whole document is read into the buffer before its records are decoded
+/// (as for [`decode_slice`](super::decode_slice)), so the buffer must hold it.
+/// Bytes are never read past the end of the document.
+pub async fn decode<R: Read, E>(
+    r: &mut R,
+    buf: &mut [u8],
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<R::Error, E>> {
+    decode_with(r, buf, o, &DecodeOptions::default()).await
+}
+
+/// Decode records from an [`embedded_io_async`] reader of a document, into a
+/// buffer, publishing to a receiver, with options.
+pub async fn decode_with<R: Read, E>(
+    r: &mut R,
+    buf: &mut [u8],
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<R::Error, E>> {
+    let header = buf
+        .first_chunk_mut()
+        .ok_or(Error::BufferTooSmall { size: 12 })?;
+    r.read_exact(header).await?;
+
+    let (data_size, _) = Decoder::advance(*header).map_err(slice::Error::from)?;
+
+    // The header, records and trailing CRC.
+    let size = header[0] as usize + data_size as usize + 2;
+    let document = buf.get_mut(..size).ok_or(Error::BufferTooSmall { size })?;
+    r.read_exact(&mut document[12..]).await?;
+
+    slice::decode_with(document, o, options)?;
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Counting the records of a document.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::convert::Infallible;
+use std::collections::BTreeMap;
+
+use super::{DefinitionInfo, FromRecord, FromRecords};
+
+extern crate std;
+
+/// A receiver counting the records of each global message number, to see what
+/// a document contains in a single pass.
+///
+/// ```
+/// let mut histogram = MessageHistogram::new();
+/// derailleur::avec::decode_slice(&data, &mut histogram)?;
+///
+/// for (id, count) in histogram.iter() {
+///     println!("{id}: {} records, {} bytes", count.records, count.bytes);
+/// }
+/// ```
+///
+/// The values of records are not received, unless created with
+/// [`MessageHistogram::counting_invalid`] to also count the values of each
+/// field holding the 'invalid' marker value.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageHistogram {
+    counts: BTreeMap<u16, MessageCount>,
+    invalid: Option<BTreeMap<(u16, u8), usize>>, // Invalid values of each field, if counted.
+    id: u16, // Global message number of the record being received.
+}
+
+/// The records of a global message number, counted by a [`MessageHistogram`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageCount {
+    /// Number of records.
+    pub records: usize,
+    /// Size of the records in bytes, including their record headers, or zero
+    /// when replayed from a [`Tape`](super::Tape).
+    pub bytes: usize,
+}
+
+impl MessageHistogram {
+    /// Create a histogram, before any records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a histogram, before any records, also counting the invalid
+    /// values of each field.
+    ///
+    /// ```
+    /// let mut histogram = MessageHistogram::counting_invalid();
+    /// derailleur::avec::decode_slice(&data, &mut histogram)?;
+    ///
+    /// for ((id, field), count) in histogram.invalid_values() {
+    ///     println!("{id}: field {field} invalid {count} times");
+    /// }
+    /// ```
+    ///
+    /// Values of standard fields are counted, including each element of an
+    /// array. Values of developer fields are not.
+    pub fn counting_invalid() -> Self {
+        Self {
+            invalid: Some(BTreeMap::new()),
+            ..Self::default()
+        }
+    }
+
+    /// The records counted for a global message number, if any.
+    pub fn get(&self, id: u16) -> Option<MessageCount> {
+        self.counts.get(&id).copied()
+    }
+
+    /// Global message numbers and their records, in order of global message
+    /// number.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, MessageCount)> + '_ {
+        self.counts.iter().map(|(&id, &count)| (id, count))
+    }
+
+    /// Total number of records counted.
+    pub fn records(&self) -> usize {
+        self.counts.values().map(|c| c.records).sum()
+    }
+
+    /// Number of invalid values counted for a field of a global message
+    /// number, or zero if not counting them.
+    pub fn invalid(&self, id: u16, field: u8) -> usize {
+        let invalid = self.invalid.as_ref();
+        invalid
+            .and_then(|i| i.get(&(id, field)))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Global message numbers and field numbers with invalid values, and the
+    /// number of those values, in order of global message number and field
+    /// number.
+    pub fn invalid_values(&self) -> impl Iterator<Item = ((u16, u8), usize)> + '_ {
+        self.invalid
+            .iter()
+            .flatten()
+            .map(|(&key, &count)| (key, count))
+    }
+
+    /// Receive the values of a record, if counting invalid values.
+    fn receive(&mut self, id: u16) -> Option<&mut dyn FromRecord> {
+        self.id = id;
+        self.invalid.is_some().then_some(self)
+    }
+}
+
+impl FromRecords for MessageHistogram {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.counts.entry(id).or_default().records += 1;
+        Ok(self.receive(id))
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        let fields = definition.fields().map(|f| f.size as usize);
+        let developer_fields = definition.developer_fields().map(|f| f.size as usize);
+        let size = 1 + fields.chain(developer_fields).sum::<usize>();
+
+        let count = self.counts.entry(id).or_default();
+        count.records += 1;
+        count.bytes += size;
+
+        Ok(self.receive(id))
+    }
+}
+
+impl FromRecord for MessageHistogram {
+    fn add_invalid(&mut self, field: u8, _: u8) {
+        if let Some(invalid) = &mut self.invalid {
+            *invalid.entry((self.id, field)).or_default() += 1;
+        }
+    }
+}
@@ -0,0 +1,716 @@
+//! Decoder implementation over iterators of bytes, and other sources of bytes
+//! implementing [`ByteSource`].
+
+use core::convert::Infallible;
+
+use thiserror::Error;
+
+use crate::sans::{
+    check::compute_crc,
+    header::{DocumentHeaderError, RecordHeaderError},
+};
+
+use super::{
+    DecodeOptions, FromRecords, Location,
+    source::{self, Exhausted, Locate, MissingDefinition, Next, Receiver, Retain, Source, Stored},
+};
+
+/// Errors occurring while decoding from an iterator of bytes.
+#[derive(Debug, Error)]
+pub enum Error<S = Infallible, E = Infallible> {
+    /// Unexpectedly reached the end of the bytes.
+    #[error("Unexpectedly reached the end of the bytes, {at}.")]
+    EndOfInput { at: Location },
+    /// An error from the supplied iterator or source.
+    #[error("{source}, {at}.")]
+    Source { source: S, at: Location },
+    /// Calculated and found CRC values do not match.
+    #[error("Calculated ({calculated}) and found ({found}) CRC values do not match, {at}.")]
+    CyclicRedundancyCheck {
+        found: u16,
+        calculated: u16,
+        at: Location,
+    },
+    /// Incorrect file header.
+    #[error("Incorrect file header: {source}, {at}.")]
+    Header {
+        source: DocumentHeaderError,
+        at: Location,
+    },
+    /// Found the developer data flag on a data record header.
+    #[error("Found the developer data flag on a data record header, {at}.")]
+    Developer { at: Location },
+    /// Found a data record for a local message number without a preceding
+    /// definition record.
+    #[error("Found a data record for local message {local} without a definition, {at}.")]
+    MissingDefinition { local: u8, at: Location },
+    /// Found a definition record exceeding the storage for its local message
+    /// number, as given to [`decode_source_in`].
+    #[error("Found a definition record exceeding its storage, {at}.")]
+    DefinitionStorage { at: Location },
+    /// The receiver failed, as returned from [`FromRecords::add_record`] or
+    /// [`FromRecords::finish`].
+    #[error("Receiver failed: {0}.")]
+    Receiver(E),
+}
+
+impl<S, E> Error<S, E> {
+    /// Position in the document at which the error occurred, or the default
+    /// for a failure of the receiver.
+    pub fn location(&self) -> Location {
+        match self {
+            Self::EndOfInput { at }
+            | Self::Source { at, .. }
+            | Self::CyclicRedundancyCheck { at, .. }
+            | Self::Header { at, .. }
+            | Self::Developer { at }
+            | Self::MissingDefinition { at, .. }
+            | Self::DefinitionStorage { at } => *at,
+            Self::Receiver(_) => Location::default(),
+        }
+    }
+}
+
+impl<S> Error<S> {
+    /// Carry an error of the decoder into that of a fallible receiver.
+    fn widen<E>(self) -> Error<S, E> {
+        match self {
+            Self::EndOfInput { at } => Error::EndOfInput { at },
+            Self::Source { source, at } => Error::Source { source, at },
+            Self::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            } => Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            },
+            Self::Header { source, at } => Error::Header { source, at },
+            Self::Developer { at } => Error::Developer { at },
+            Self::MissingDefinition { local, at } => Error::MissingDefinition { local, at },
+            Self::DefinitionStorage { at } => Error::DefinitionStorage { at },
+            Self::Receiver(never) => match never {},
+        }
+    }
+}
+
+impl<S, E> Locate for Error<S, E> {
+    fn locate(mut self, location: Location) -> Self {
+        let (Self::EndOfInput { at }
+        | Self::Source { at, .. }
+        | Self::CyclicRedundancyCheck { at, .. }
+        | Self::Header { at, .. }
+        | Self::Developer { at }
+        | Self::MissingDefinition { at, .. }
+        | Self::DefinitionStorage { at }) = &mut self
+        else {
+            return self;
+        };
+
+        // Retain a location given where the error was found.
+        if *at == Location::default() {
+            *at = location;
+        }
+
+        self
+    }
+}
+
+impl<S, E> From<DocumentHeaderError> for Error<S, E> {
+    fn from(source: DocumentHeaderError) -> Self {
+        Self::Header {
+            source,
+            at: Location::default(),
+        }
+    }
+}
+
+impl<S, E> From<RecordHeaderError> for Error<S, E> {
+    fn from(err: RecordHeaderError) -> Self {
+        match err {
+            RecordHeaderError::DeveloperData => Self::Developer {
+                at: Location::default(),
+            },
+        }
+    }
+}
+
+impl<S, E> From<MissingDefinition> for Error<S, E> {
+    fn from(MissingDefinition(local): MissingDefinition) -> Self {
+        Self::MissingDefinition {
+            local,
+            at: Location::default(),
+        }
+    }
+}
+
+impl<S, E> From<Exhausted> for Error<S, E> {
+    fn from(_: Exhausted) -> Self {
+        Self::EndOfInput {
+            at: Location::default(),
+        }
+    }
+}
+
+/// Decode records from an iterator over the bytes of a document, publishing to
+/// a receiver.
+///
+/// ```
+/// let bytes = core::iter::from_fn(|| ring_buffer.dequeue());
+/// derailleur::avec::iter::decode(bytes, &mut records)?;
+/// ```
+///
+/// Bytes are never taken past the end of the document. Definition records are
+/// stored in a fixed buffer of about 25 KB, held on the stack while decoding.
+/// To store them elsewhere, or in less space, use [`decode_source_in`].
+pub fn decode<E>(
+    r: impl IntoIterator<Item = u8>,
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<Infallible, E>> {
+    decode_with(r, o, &DecodeOptions::default())
+}
+
+/// Decode records from an iterator over the bytes of a document, publishing to
+/// a receiver, with options.
+pub fn decode_with<E>(
+    r: impl IntoIterator<Item = u8>,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<Infallible, E>> {
+    try_decode_with(r.into_iter().map(Ok), o, options)
+}
+
+/// Decode records from a document split across several slices, publishing to a
+/// receiver.
+///
+/// This suits ring buffers and the two slices of [`VecDeque::as_slices`],
+/// which don't need to be made contiguous first.
+///
+/// ```
+/// let (front, back) = received.as_slices();
+/// derailleur::avec::iter::decode_slices([front, back], &mut records)?;
+/// ```
+///
+/// Bytes are taken a run of each slice at a time. Arrays lying within a single
+/// slice are viewed in place, and only values crossing from one slice to the
+/// next are copied to be decoded. Otherwise, bytes are decoded as for
+/// [`decode`].
+///
+/// [`VecDeque::as_slices`]:
+///     https://doc.rust-lang.org/std/collections/struct.VecDeque.html#method.as_slices
+pub fn decode_slices<'a, E>(
+    r: impl IntoIterator<Item = &'a [u8]>,
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<Infallible, E>> {
+    let r = SlicesSource {
+        r: r.into_iter(),
+        s: &[],
+    };
+
+    decode_source(r, o)
+}
+
+/// Decode records from a [`bytes::Buf`] holding a document, publishing to a
+/// receiver.
+///
+/// ```
+/// let body: bytes::Bytes = request.into_body().collect().await?.to_bytes();
+/// derailleur::avec::iter::decode_buf(body, &mut records)?;
+/// ```
+///
+/// Bytes are taken from the buffer as they are decoded, without first copying
+/// it to be contiguous, and are never taken past the end of the document.
+///
+/// _Requires Cargo feature `bytes`._
+#[cfg(feature = "bytes")]
+pub fn decode_buf<E>(
+    r: impl bytes::Buf,
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<Infallible, E>> {
+    decode_source(BufSource(r), o)
+}
+
+/// Decode records from an iterator over the bytes of a document, any of which
+/// may fail, publishing to a receiver.
+///
+/// Decoding stops at the first failure, which is returned as
+/// [`Error::Source`].
+pub fn try_decode<S, E>(
+    r: impl IntoIterator<Item = Result<u8, S>>,
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<S, E>> {
+    try_decode_with(r, o, &DecodeOptions::default())
+}
+
+/// Decode records from an iterator over the bytes of a document, any of which
+/// may fail, publishing to a receiver, with options.
+pub fn try_decode_with<S, E>(
+    r: impl IntoIterator<Item = Result<u8, S>>,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<S, E>> {
+    decode_source_with(IterSource(r.into_iter()), o, options)
+}
+
+/// Decode records from a source of the bytes of a document, publishing to a
+/// receiver.
+///
+/// ```
+/// struct Ring<'a>(&'a mut RingBuffer);
+///
+/// impl ByteSource for Ring<'_> {
+///     type Error = Infallible;
+///
+///     fn take(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+///         Ok(self.0.dequeue_into(buf))
+///     }
+/// }
+///
+/// derailleur::avec::iter::decode_source(Ring(&mut ring_buffer), &mut records)?;
+/// ```
+///
+/// Bytes are decoded as for [`decode`]. Decoding stops at the first failure of
+/// the source, which is returned as [`Error::Source`].
+pub fn decode_source<S: ByteSource, E>(
+    r: S,
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<S::Error, E>> {
+    decode_source_with(r, o, &DecodeOptions::default())
+}
+
+/// Decode records from a source of the bytes of a document, publishing to a
+/// receiver, with options.
+pub fn decode_source_with<S: ByteSource, E>(
+    r: S,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<S::Error, E>> {
+    decode_source_in(r, o, options, &mut [0; 16 * DEFINITION_SIZE])
+}
+
+/// Decode records from a source of the bytes of a document, publishing to a
+/// receiver, with options, storing definition records in the given storage.
+///
+/// ```
+/// let mut storage = [0; 2048];
+/// derailleur::avec::iter::decode_source_in(source, &mut records, &options, &mut storage)?;
+/// ```
+///
+/// The storage is divided evenly among the 16 local message numbers, and a
+/// definition record exceeding its share fails with
+/// [`Error::DefinitionStorage`]. Storage of 16 times [`DEFINITION_SIZE`]
+/// bytes, as used by the other functions, holds any definition record, though
+/// most take well under 100 bytes. Otherwise, bytes are decoded as for
+/// [`decode`].
+pub fn decode_source_in<S: ByteSource, E>(
+    r: S,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+    storage: &mut [u8],
+) -> Result<(), Error<S::Error, E>> {
+    let s = &mut Counted {
+        r,
+        i: 0,
+        c: 0,
+        a: [0; 3],
+        n: 0,
+    };
+
+    decode_documents(s, o, options, storage)
+}
+
+/// Decode a document, followed by any chained documents the options allow.
+fn decode_documents<S: ByteSource, E>(
+    s: &mut Counted<S>,
+    o: &mut impl for<'a> Receiver<'a, E>,
+    options: &DecodeOptions,
+    storage: &mut [u8],
+) -> Result<(), Error<S::Error, E>> {
+    let mut options = *options;
+
+    loop {
+        let definitions = &mut DefinitionBuffer::slots(storage);
+        let decoded = source::decode_in(s, o, &options, definitions)
+            .map_err(Error::widen)?
+            .map_err(Error::Receiver)?;
+
+        if !options.allow_chained || decoded.stopped {
+            return Ok(());
+        }
+
+        // End at the end of the input, between documents.
+        s.read_ahead().map_err(Error::widen)?;
+        if s.n == 0 {
+            return Ok(());
+        }
+
+        options.max_records = options.max_records.map(|n| n - decoded.records);
+        (s.i, s.c) = (0, 0);
+    }
+}
+
+/// A source of the bytes of a document, taken in order.
+///
+/// Every decoder takes its bytes through this trait, with the slice and reader
+/// decoders implementing it over their own inputs. For a source supplied to
+/// [`decode_source`], the decoder counts the bytes taken and computes their CRC
+/// itself, so the source only has to supply them. Implement this for sources
+/// the other decoders don't cover, such as ring buffers or memory-mapped
+/// regions.
+pub trait ByteSource {
+    /// Errors occurring while taking bytes.
+    type Error;
+
+    /// Take the next bytes into a buffer, returning the number taken.
+    ///
+    /// Fewer bytes than the length of the buffer may only be taken at the end
+    /// of the input.
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Take an exact number of the next bytes at once, without a copy, if the
+    /// source holds them contiguously.
+    ///
+    /// Returns `None`, taking nothing, where it doesn't, so that the bytes are
+    /// taken with [`ByteSource::take`] instead.
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, Self::Error> {
+        _ = n;
+        Ok(None)
+    }
+}
+
+impl ByteSource for &[u8] {
+    type Error = Infallible;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let n = buf.len().min(self.len());
+        let (bytes, rest) = self.split_at(n);
+        buf[..n].copy_from_slice(bytes);
+        *self = rest;
+
+        Ok(n)
+    }
+
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, Infallible> {
+        let Some((bytes, rest)) = self.split_at_checked(n) else {
+            return Ok(None);
+        };
+        *self = rest;
+
+        Ok(Some(bytes))
+    }
+}
+
+impl<S: ByteSource + ?Sized> ByteSource for &mut S {
+    type Error = S::Error;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, S::Error> {
+        (**self).take(buf)
+    }
+
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, S::Error> {
+        (**self).take_slice(n)
+    }
+}
+
+/// A source of bytes from a document split across slices, taken from one slice
+/// at a time.
+struct SlicesSource<'a, I> {
+    r: I,        // Slices not yet reached.
+    s: &'a [u8], // Bytes of the current slice, not yet taken.
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> SlicesSource<'a, I> {
+    /// Move to the next slice holding any bytes, if the current one is spent.
+    ///
+    /// Returns whether any bytes remain.
+    fn advance(&mut self) -> bool {
+        while self.s.is_empty() {
+            match self.r.next() {
+                Some(s) => self.s = s,
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> ByteSource for SlicesSource<'a, I> {
+    type Error = Infallible;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let mut k = 0;
+
+        // Copy whole runs of each slice, crossing to the next as needed.
+        while k < buf.len() && self.advance() {
+            let n = (buf.len() - k).min(self.s.len());
+            let (bytes, rest) = self.s.split_at(n);
+            buf[k..k + n].copy_from_slice(bytes);
+            self.s = rest;
+            k += n;
+        }
+
+        Ok(k)
+    }
+
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, Infallible> {
+        // Only bytes crossing from one slice to the next need a copy.
+        if !self.advance() {
+            return Ok(None);
+        }
+
+        let Some((bytes, rest)) = self.s.split_at_checked(n) else {
+            return Ok(None);
+        };
+        self.s = rest;
+
+        Ok(Some(bytes))
+    }
+}
+
+/// A source of bytes from an iterator, any of which may fail.
+struct IterSource<I>(I);
+
+impl<I: Iterator<Item = Result<u8, S>>, S> ByteSource for IterSource<I> {
+    type Error = S;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, S> {
+        for (n, b) in buf.iter_mut().enumerate() {
+            match self.0.next() {
+                Some(v) => *b = v?,
+                None => return Ok(n),
+            }
+        }
+
+        Ok(buf.len())
+    }
+}
+
+/// A source of bytes from a [`bytes::Buf`], copied a chunk at a time.
+#[cfg(feature = "bytes")]
+struct BufSource<B>(B);
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::Buf> ByteSource for BufSource<B> {
+    type Error = Infallible;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let n = buf.len().min(self.0.remaining());
+        self.0.copy_to_slice(&mut buf[..n]);
+
+        Ok(n)
+    }
+}
+
+/// Greatest size of a definition record, including its record header.
+///
+/// Storage of 16 times this size holds the definition records for every local
+/// message number, whatever their size.
+pub const DEFINITION_SIZE: usize = 1 + 5 + 3 * 255 + 1 + 3 * 255;
+
+/// Storage for a definition record, within a share of caller-provided storage.
+#[derive(Default)]
+struct DefinitionBuffer<'s> {
+    r: &'s mut [u8],
+    len: usize,
+}
+
+impl AsRef<[u8]> for DefinitionBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.r[..self.len]
+    }
+}
+
+impl Stored for DefinitionBuffer<'_> {}
+
+impl DefinitionBuffer<'_> {
+    /// Divide storage evenly among the 16 local message numbers.
+    fn slots(storage: &mut [u8]) -> [DefinitionBuffer<'_>; 16] {
+        let mut shares = storage.chunks_exact_mut((storage.len() / 16).max(1));
+
+        core::array::from_fn(|_| DefinitionBuffer {
+            r: shares.next().unwrap_or_default(),
+            len: 0,
+        })
+    }
+
+    fn extend<S>(&mut self, bytes: &[u8]) -> Result<(), Error<S>> {
+        let Some(r) = self.r.get_mut(self.len..self.len + bytes.len()) else {
+            Err(Error::DefinitionStorage {
+                at: Location::default(),
+            })?
+        };
+
+        r.copy_from_slice(bytes);
+        self.len += bytes.len();
+
+        Ok(())
+    }
+}
+
+/// A source of bytes, counting and checking those taken.
+struct Counted<S> {
+    r: S,
+    i: usize,   // Counter of bytes taken, used to end decoding.
+    c: u16,     // Cyclic redundancy check accumulator value.
+    a: [u8; 3], // Bytes read ahead, not yet taken.
+    n: usize,   // Number of bytes read ahead.
+}
+
+impl<S: ByteSource> Counted<S> {
+    /// Take the next byte, from those read ahead first.
+    fn next(&mut self) -> Result<Option<u8>, Error<S::Error>> {
+        if self.n == 0 {
+            return self.pull();
+        }
+
+        let b = self.a[0];
+        self.a.copy_within(1.., 0);
+        self.n -= 1;
+
+        Ok(Some(b))
+    }
+
+    /// Read ahead up to three bytes, fewer at the end of the input.
+    fn read_ahead(&mut self) -> Result<(), Error<S::Error>> {
+        while self.n < 3 {
+            let Some(b) = self.pull()? else { break };
+
+            self.a[self.n] = b;
+            self.n += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Pull the next byte from the source.
+    fn pull(&mut self) -> Result<Option<u8>, Error<S::Error>> {
+        let mut b = [0; 1];
+        Ok((self.read(&mut b)? == 1).then_some(b[0]))
+    }
+
+    /// Read from the source into a buffer, returning the number of bytes
+    /// taken.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error<S::Error>> {
+        self.r.take(buf).map_err(|source| Error::Source {
+            source,
+            at: Location::default(),
+        })
+    }
+}
+
+impl<S: ByteSource> Retain<'_> for Counted<S> {}
+
+impl<S: ByteSource> ByteSource for Counted<S> {
+    type Error = Error<S::Error>;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Error<S::Error>> {
+        // Take any bytes read ahead first.
+        let k = self.n.min(buf.len());
+        buf[..k].copy_from_slice(&self.a[..k]);
+        self.a.copy_within(k.., 0);
+        self.n -= k;
+
+        let n = k + self.read(&mut buf[k..])?;
+
+        self.i += n;
+        self.c = compute_crc(self.c, &buf[..n]);
+
+        Ok(n)
+    }
+
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, Error<S::Error>> {
+        // Bytes read ahead must be taken first.
+        if self.n > 0 {
+            return Ok(None);
+        }
+
+        let bytes = self.r.take_slice(n).map_err(|source| Error::Source {
+            source,
+            at: Location::default(),
+        })?;
+
+        if let Some(bytes) = bytes {
+            self.i += n;
+            self.c = compute_crc(self.c, bytes);
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl<S: ByteSource> Source for Counted<S> {
+    type Definition<'s> = DefinitionBuffer<'s>;
+
+    fn position(&self) -> usize {
+        self.i
+    }
+
+    fn begin(&mut self, _: usize, _: &DecodeOptions) -> Result<(), Error<S::Error>> {
+        Ok(())
+    }
+
+    fn finish(&mut self, options: &DecodeOptions) -> Result<bool, Error<S::Error>> {
+        let calculated = self.c;
+
+        // Take the first byte alone, to distinguish a missing CRC from a
+        // partial one.
+        let first = match self.next()? {
+            None if options.allow_missing_crc => return Ok(false),
+            first => first.ok_or(Exhausted)?,
+        };
+        let second = self.next()?.ok_or(Exhausted)?;
+        let found = u16::from_le_bytes([first, second]);
+
+        if options.verify_crc && found != calculated {
+            Err(Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at: Location::default(),
+            })?;
+        }
+
+        Ok(true)
+    }
+
+    fn more(&mut self) -> Result<Next, Error<S::Error>> {
+        // Read ahead enough to recognise a trailing CRC followed by the end of
+        // the input.
+        self.read_ahead()?;
+
+        Ok(match self.a[..self.n] {
+            [] => Next::End { crc: false },
+            [a, b] if u16::from_le_bytes([a, b]) == self.c => {
+                self.n = 0;
+                Next::End { crc: true }
+            }
+            _ => Next::Record,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<[u8; 3]>, Error<S::Error>> {
+        self.read_ahead()?;
+        Ok((self.n == 3).then_some(self.a))
+    }
+
+    fn begin_definition(
+        &mut self,
+        d: &mut DefinitionBuffer<'_>,
+        header: [u8; 1],
+    ) -> Result<(), Error<S::Error>> {
+        d.len = 0;
+        d.extend(&header)
+    }
+
+    fn fill_definition(
+        &mut self,
+        d: &mut DefinitionBuffer<'_>,
+        buf: &mut [u8],
+    ) -> Result<(), Error<S::Error>> {
+        self.fill(buf)?;
+        d.extend(buf)
+    }
+
+    fn end_definition(&mut self, _: &mut DefinitionBuffer<'_>) {}
+}
@@ -0,0 +1,301 @@
+//! Prebuilt record sets for common documents.
+//!
+//! These types collect the messages most applications need from activity,
+//! course, and monitoring documents, storing the received primitives as-is.
+//! They are a quick way to begin decoding, and a reference for writing record
+//! sets with the [`FromRecords`](macro@FromRecords) and
+//! [`FromRecord`](macro@FromRecord) derive macros.
+//!
+//! ```
+//! let data = std::fs::read("activity.fit")?;
+//! let mut activity = ActivityRecordSet::default();
+//! derailleur::avec::decode_slice(&data, &mut activity)?;
+//! ```
+//!
+//! Units follow the FIT profile: positions are in semicircles, times are in
+//! seconds since the FIT epoch (1989-12-31 00:00:00 UTC), distances are in
+//! centimetres, speeds are in millimetres per second, and durations are in
+//! milliseconds.
+//!
+//! _Requires Cargo feature `profile`._
+
+use std::{str, vec::Vec};
+
+use super::{FromRecord, FromRecords};
+
+extern crate std;
+
+/// Records of an activity document.
+#[derive(Debug, Default, FromRecords)]
+pub struct ActivityRecordSet {
+    #[record(0, keep = first)]
+    pub file_id: Option<FileId>,
+    #[record(34)]
+    pub activity: Option<Activity>,
+    #[record(18)]
+    pub sessions: Vec<Session>,
+    #[record(19)]
+    pub laps: Vec<Lap>,
+    #[record(20)]
+    pub records: Vec<Record>,
+    #[record(21)]
+    pub events: Vec<Event>,
+}
+
+/// Records of a course document.
+#[derive(Debug, Default, FromRecords)]
+pub struct CourseRecordSet {
+    #[record(0, keep = first)]
+    pub file_id: Option<FileId>,
+    #[record(31)]
+    pub course: Option<Course>,
+    #[record(19)]
+    pub laps: Vec<Lap>,
+    #[record(20)]
+    pub records: Vec<Record>,
+    #[record(21)]
+    pub events: Vec<Event>,
+    #[record(32)]
+    pub course_points: Vec<CoursePoint>,
+}
+
+/// Records of a monitoring document.
+#[derive(Debug, Default, FromRecords)]
+pub struct MonitoringRecordSet {
+    #[record(0, keep = first)]
+    pub file_id: Option<FileId>,
+    #[record(103)]
+    pub monitoring_info: Option<MonitoringInfo>,
+    #[record(55)]
+    pub monitoring: Vec<Monitoring>,
+}
+
+/// A `file_id` message (0), identifying the document.
+#[derive(Debug, Default, FromRecord)]
+pub struct FileId {
+    /// The `file` type, such as 4 for an activity.
+    #[field(0)]
+    pub type_: Option<u8>,
+    #[field(1)]
+    pub manufacturer: Option<u16>,
+    #[field(2)]
+    pub product: Option<u16>,
+    #[field(3)]
+    pub serial_number: Option<u32>,
+    #[field(4)]
+    pub time_created: Option<u32>,
+}
+
+/// An `activity` message (34), summarising an activity.
+#[derive(Debug, Default, FromRecord)]
+pub struct Activity {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(0)]
+    pub total_timer_time: Option<u32>,
+    #[field(1)]
+    pub num_sessions: Option<u16>,
+    #[field(5)]
+    pub local_timestamp: Option<u32>,
+}
+
+/// A `session` message (18), summarising a session of an activity.
+#[derive(Debug, Default, FromRecord)]
+pub struct Session {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(2)]
+    pub start_time: Option<u32>,
+    #[field(3)]
+    pub start_position_lat: Option<i32>,
+    #[field(4)]
+    pub start_position_long: Option<i32>,
+    #[field(5)]
+    pub sport: Option<u8>,
+    #[field(6)]
+    pub sub_sport: Option<u8>,
+    #[field(7)]
+    pub total_elapsed_time: Option<u32>,
+    #[field(8)]
+    pub total_timer_time: Option<u32>,
+    #[field(9)]
+    pub total_distance: Option<u32>,
+    #[field(11)]
+    pub total_calories: Option<u16>,
+    #[field(14)]
+    pub avg_speed: Option<u16>,
+    #[field(15)]
+    pub max_speed: Option<u16>,
+    #[field(16)]
+    pub avg_heart_rate: Option<u8>,
+    #[field(17)]
+    pub max_heart_rate: Option<u8>,
+    #[field(22)]
+    pub total_ascent: Option<u16>,
+    #[field(23)]
+    pub total_descent: Option<u16>,
+}
+
+/// A `lap` message (19), summarising a lap of an activity or course.
+#[derive(Debug, Default, FromRecord)]
+pub struct Lap {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(2)]
+    pub start_time: Option<u32>,
+    #[field(3)]
+    pub start_position_lat: Option<i32>,
+    #[field(4)]
+    pub start_position_long: Option<i32>,
+    #[field(5)]
+    pub end_position_lat: Option<i32>,
+    #[field(6)]
+    pub end_position_long: Option<i32>,
+    #[field(7)]
+    pub total_elapsed_time: Option<u32>,
+    #[field(8)]
+    pub total_timer_time: Option<u32>,
+    #[field(9)]
+    pub total_distance: Option<u32>,
+    #[field(11)]
+    pub total_calories: Option<u16>,
+    #[field(13)]
+    pub avg_speed: Option<u16>,
+    #[field(14)]
+    pub max_speed: Option<u16>,
+    #[field(15)]
+    pub avg_heart_rate: Option<u8>,
+    #[field(16)]
+    pub max_heart_rate: Option<u8>,
+    #[field(21)]
+    pub total_ascent: Option<u16>,
+    #[field(22)]
+    pub total_descent: Option<u16>,
+}
+
+/// A `record` message (20), sampling an activity or course at a point in
+/// time.
+#[derive(Debug, Default, FromRecord)]
+pub struct Record {
+    #[field(time)]
+    pub time_offset: Option<u8>,
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(0)]
+    pub position_lat: Option<i32>,
+    #[field(1)]
+    pub position_long: Option<i32>,
+    /// Altitude, in fifths of a metre offset by 500 metres.
+    #[field(2)]
+    pub altitude: Option<u16>,
+    #[field(3)]
+    pub heart_rate: Option<u8>,
+    #[field(4)]
+    pub cadence: Option<u8>,
+    #[field(5)]
+    pub distance: Option<u32>,
+    #[field(6)]
+    pub speed: Option<u16>,
+    #[field(7)]
+    pub power: Option<u16>,
+    #[field(13)]
+    pub temperature: Option<i8>,
+    #[field(73)]
+    pub enhanced_speed: Option<u32>,
+    /// Altitude, in fifths of a metre offset by 500 metres.
+    #[field(78)]
+    pub enhanced_altitude: Option<u32>,
+}
+
+/// An `event` message (21), marking an event such as a timer start or stop.
+#[derive(Debug, Default, FromRecord)]
+pub struct Event {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(0)]
+    pub event: Option<u8>,
+    #[field(1)]
+    pub event_type: Option<u8>,
+    #[field(3)]
+    pub data: Option<u32>,
+}
+
+/// A `course` message (31), describing a course.
+#[derive(Debug, Default, FromRecord)]
+pub struct Course {
+    #[field(4)]
+    pub sport: Option<u8>,
+    #[field(5, |v, c: u8| v.push(c))]
+    pub name: Vec<u8>,
+}
+
+impl Course {
+    /// The name of the course, if present and valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        (!self.name.is_empty())
+            .then(|| str::from_utf8(&self.name).ok())
+            .flatten()
+    }
+}
+
+/// A `course_point` message (32), marking a point of interest on a course.
+#[derive(Debug, Default, FromRecord)]
+pub struct CoursePoint {
+    #[field(1)]
+    pub timestamp: Option<u32>,
+    #[field(2)]
+    pub position_lat: Option<i32>,
+    #[field(3)]
+    pub position_long: Option<i32>,
+    #[field(4)]
+    pub distance: Option<u32>,
+    #[field(5)]
+    pub type_: Option<u8>,
+    #[field(6, |v, c: u8| v.push(c))]
+    pub name: Vec<u8>,
+}
+
+impl CoursePoint {
+    /// The name of the course point, if present and valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        (!self.name.is_empty())
+            .then(|| str::from_utf8(&self.name).ok())
+            .flatten()
+    }
+}
+
+/// A `monitoring_info` message (103), describing a monitoring document.
+#[derive(Debug, Default, FromRecord)]
+pub struct MonitoringInfo {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    #[field(0)]
+    pub local_timestamp: Option<u32>,
+}
+
+/// A `monitoring` message (55), sampling all-day activity.
+#[derive(Debug, Default, FromRecord)]
+pub struct Monitoring {
+    #[field(253)]
+    pub timestamp: Option<u32>,
+    /// Lower 16 bits of the timestamp, used in place of a full timestamp.
+    #[field(26)]
+    pub timestamp_16: Option<u16>,
+    #[field(0)]
+    pub device_index: Option<u8>,
+    #[field(1)]
+    pub calories: Option<u16>,
+    #[field(2)]
+    pub distance: Option<u32>,
+    /// Steps, strokes, or other cycles, in halves.
+    #[field(3)]
+    pub cycles: Option<u32>,
+    #[field(4)]
+    pub active_time: Option<u32>,
+    #[field(5)]
+    pub activity_type: Option<u8>,
+    #[field(27)]
+    pub heart_rate: Option<u8>,
+    #[field(28)]
+    pub intensity: Option<u8>,
+}
@@ -0,0 +1,622 @@
+//! Resumable decoder accepting bytes in arbitrary-size chunks.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::mem;
+use std::{vec, vec::Vec};
+
+use crate::sans::{
+    Decoder,
+    definition::{
+        Definition, DefinitionAlt, DefinitionField, DeveloperDefinition, DeveloperDefinitionField,
+    },
+    header::{
+        DocumentHeaderSuccessor, ExtendedDocumentHeader, ExtendedHeaderSuccessor, RecordHeader,
+        RecordHeaderSuccessor, UnknownDocumentHeader,
+    },
+    step::Step,
+};
+
+use super::{
+    DecodeOptions, DefinitionView, FromRecords, HeaderInfo, Location,
+    developer::Registry,
+    reader::{Error, ReaderSource},
+    source::{self, Context, MissingDefinition, Source},
+};
+
+extern crate std;
+
+/// Decoder retaining partial progress between chunks of bytes.
+///
+/// ```
+/// let mut decoder = PushDecoder::new();
+/// let mut records = Records::default();
+///
+/// while !decoder.is_done() {
+///     let chunk = uart.receive()?;
+///     decoder.push(chunk, &mut records)?;
+/// }
+/// ```
+///
+/// Bytes are buffered until a whole unit (a header or record) is available,
+/// which is then decoded at once. Receivers are only called while decoding a
+/// whole unit.
+///
+/// _Requires Cargo feature `std`._
+pub struct PushDecoder {
+    state: State,
+    unit: Vec<u8>, // Bytes of the unit being buffered.
+    definitions: [DefinitionView; 16],
+    x: Context,
+    i: usize,   // Counter of bytes decoded, used to end decoding.
+    c: u16,     // Cyclic redundancy check accumulator value.
+    end: usize, // Offset to the end of the record section.
+}
+
+enum State {
+    DocumentHeader,
+    ExtendedDocumentHeader(ExtendedDocumentHeader, [u8; 12]),
+    UnknownDocumentHeader(UnknownDocumentHeader, [u8; 12], u8), // Header, bytes skipped.
+    RecordHeader(RecordHeader),
+    Definition(Definition, u8, [u8; 1]),
+    Data(DefinitionAlt, Option<u8>, u8, [u8; 1]),
+    Crc,
+    Done,
+}
+
+impl PushDecoder {
+    /// Create a decoder at the start of a document.
+    pub fn new() -> Self {
+        Self::with_options(DecodeOptions::default())
+    }
+
+    /// Create a decoder at the start of a document, with options.
+    pub fn with_options(options: DecodeOptions) -> Self {
+        Self {
+            state: State::DocumentHeader,
+            unit: Vec::new(),
+            definitions: Default::default(),
+            x: Context::new(options),
+            i: 0,
+            c: 0,
+            end: 0,
+        }
+    }
+
+    /// Whether the whole document has been decoded.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Conclude decoding at the end of the input, if only the trailing CRC
+    /// remains and the options allow it to be missing.
+    ///
+    /// Returns whether the whole document has been decoded.
+    pub fn end_of_input(&mut self) -> bool {
+        if matches!(self.state, State::Crc)
+            && self.unit.is_empty()
+            && self.x.options.allow_missing_crc
+        {
+            self.state = State::Done;
+        }
+
+        self.is_done()
+    }
+
+    /// Number of bytes needed to complete the unit being buffered.
+    ///
+    /// Pushing at most this many bytes at a time never passes the end of the
+    /// document.
+    pub fn wanted(&self) -> usize {
+        let needed = needed(&self.state, &self.unit, &self.definitions);
+        needed.saturating_sub(self.unit.len())
+    }
+
+    /// Decode records from a chunk of bytes, publishing to a receiver.
+    ///
+    /// Returns the number of bytes consumed, which is less than the length of
+    /// the chunk only if the end of the document was reached.
+    pub fn push<E>(
+        &mut self,
+        mut r: &[u8],
+        o: &mut impl FromRecords<E>,
+    ) -> Result<usize, Error<E>> {
+        let len = r.len();
+
+        while !self.is_done() {
+            let (bytes, rest) = r.split_at(self.wanted().min(r.len()));
+            self.unit.extend_from_slice(bytes);
+            r = rest;
+
+            if self.wanted() == 0 {
+                self.step(o)
+                    .map_err(self.x.locate(self.i))
+                    .map_err(Error::widen)?
+                    .map_err(Error::Receiver)?;
+            } else if r.is_empty() {
+                break;
+            }
+        }
+
+        Ok(len - r.len())
+    }
+
+    /// Take a snapshot of the progress of decoding, from which decoding can be
+    /// resumed with [`PushDecoder::resume`].
+    ///
+    /// ```
+    /// let bytes = decoder.snapshot().to_bytes();
+    /// flash.write(STATE_OFFSET, &bytes)?;
+    ///
+    /// // After a reset.
+    /// let state = DecoderState::from_bytes(&bytes, options).unwrap();
+    /// let mut decoder = PushDecoder::resume(state);
+    /// ```
+    pub fn snapshot(&self) -> DecoderState {
+        let position = match &self.state {
+            State::DocumentHeader => Position::DocumentHeader,
+            State::ExtendedDocumentHeader(_, header) => Position::ExtendedDocumentHeader(*header),
+            State::UnknownDocumentHeader(_, header, skipped) => {
+                Position::UnknownDocumentHeader(*header, *skipped)
+            }
+            State::RecordHeader(_) => Position::RecordHeader,
+            State::Definition(_, _, [header]) => Position::Definition(*header),
+            State::Data(_, _, _, [header]) => Position::Data(*header),
+            State::Crc => Position::Crc,
+            State::Done => Position::Done,
+        };
+
+        DecoderState {
+            position,
+            unit: self.unit.clone(),
+            definitions: self.definitions.clone(),
+            x: self.x.clone(),
+            i: self.i,
+            c: self.c,
+            end: self.end,
+        }
+    }
+
+    /// Resume decoding from a snapshot.
+    ///
+    /// Receivers are not called again for records decoded before the snapshot
+    /// was taken.
+    pub fn resume(state: DecoderState) -> Self {
+        let DecoderState {
+            position,
+            unit,
+            definitions,
+            x,
+            i,
+            c,
+            end,
+        } = state;
+
+        Self {
+            // Positions are checked to be reachable as they are restored.
+            state: position.state().unwrap(),
+            unit,
+            definitions,
+            x,
+            i,
+            c,
+            end,
+        }
+    }
+
+    /// The local message number of the record whose body is being buffered,
+    /// and whether the record is a definition.
+    #[cfg(feature = "async")]
+    pub(super) fn record(&self) -> Option<(u8, bool)> {
+        match self.state {
+            State::Definition(_, local, _) => Some((local, true)),
+            State::Data(_, _, local, _) => Some((local, false)),
+            _ => None,
+        }
+    }
+
+    /// Offset of the next byte to be pushed, from the start of the document.
+    #[cfg(feature = "async")]
+    pub(super) fn offset(&self) -> usize {
+        self.i + self.unit.len()
+    }
+
+    /// The definition of a local message number.
+    #[cfg(feature = "async")]
+    pub(super) fn definition(&self, local: u8) -> &DefinitionView {
+        &self.definitions[local as usize]
+    }
+
+    /// Developer fields described so far.
+    #[cfg(feature = "async")]
+    pub(super) fn registry(&self) -> &Registry {
+        &self.x.registry
+    }
+
+    /// Decode a whole buffered unit.
+    ///
+    /// Failures of the receiver are returned within a successful result.
+    fn step<E>(&mut self, o: &mut impl FromRecords<E>) -> Result<Result<(), E>, Error> {
+        let mut unit = mem::take(&mut self.unit);
+
+        let r = &mut unit.as_slice();
+        let s = &mut ReaderSource {
+            i: self.i,
+            c: self.c,
+            ..ReaderSource::new(r, Default::default())
+        };
+
+        self.state = match mem::replace(&mut self.state, State::Done) {
+            State::DocumentHeader => {
+                let header = s.take_array()?;
+                let (size, successor) = Decoder::advance(header)?;
+
+                match successor {
+                    DocumentHeaderSuccessor::Extended(state) => {
+                        State::ExtendedDocumentHeader(state, header)
+                    }
+                    DocumentHeaderSuccessor::NextRecord(state) => {
+                        o.add_header(HeaderInfo::new(header, None));
+                        self.end = s.i + size as usize;
+                        self.next(s.i, state)
+                    }
+                }
+            }
+            State::ExtendedDocumentHeader(state, header) => {
+                let crc = s.take_array()?;
+
+                let info = HeaderInfo::new(header, Some(crc));
+                o.add_header(info);
+
+                let size = info.data_size;
+
+                match state.advance(crc) {
+                    ExtendedHeaderSuccessor::MoreBytes(state) => {
+                        State::UnknownDocumentHeader(state, header, 0)
+                    }
+                    ExtendedHeaderSuccessor::NextRecord(state) => {
+                        self.end = s.i + size as usize;
+                        self.next(s.i, state)
+                    }
+                }
+            }
+            State::UnknownDocumentHeader(state, header, skipped) => match s.step(state)? {
+                ExtendedHeaderSuccessor::MoreBytes(state) => {
+                    State::UnknownDocumentHeader(state, header, skipped + 1)
+                }
+                ExtendedHeaderSuccessor::NextRecord(state) => {
+                    let size = HeaderInfo::new(header, None).data_size;
+                    self.end = s.i + size as usize;
+                    self.next(s.i, state)
+                }
+            },
+            State::RecordHeader(state) => {
+                self.x.location = Location::default();
+
+                let header = s.take_array()?;
+                let (local, successor) = state.advance(header)?;
+                self.x.location.local = Some(local);
+
+                match successor {
+                    RecordHeaderSuccessor::Definition(state) => {
+                        State::Definition(state, local, header)
+                    }
+                    RecordHeaderSuccessor::Data(..)
+                        if self.definitions[local as usize].as_bytes().is_empty() =>
+                    {
+                        Err(MissingDefinition(local))?
+                    }
+                    RecordHeaderSuccessor::Data(time, state) => {
+                        State::Data(state, time, local, header)
+                    }
+                }
+            }
+            State::Definition(state, local, header) => {
+                let d = &mut self.definitions[local as usize];
+                let state = source::decode_definition(state, header, s, d)?;
+                self.next(s.i, state)
+            }
+            State::Data(state, time, local, _) => {
+                let d = &self.definitions[local as usize];
+                match source::decode_data(state, time, s, d, &mut self.x, o)? {
+                    Ok(state) => self.next(s.i, state),
+                    Err(err) => return Ok(Err(err)),
+                }
+            }
+            State::Crc => {
+                self.x.location = Location::default();
+
+                let calculated = s.c;
+                let found = u16::from_le_bytes(unit[..2].try_into().unwrap());
+
+                if self.x.options.verify_crc && found != calculated {
+                    Err(Error::CyclicRedundancyCheck {
+                        found,
+                        calculated,
+                        at: Location::default(),
+                    })?;
+                }
+
+                State::Done
+            }
+            State::Done => State::Done,
+        };
+
+        (self.i, self.c) = (s.i, s.c);
+
+        // Only the trailing CRC remains after the record section.
+        if let State::Crc = self.state
+            && let Err(err) = o.finish()
+        {
+            return Ok(Err(err));
+        }
+
+        // Retain the allocation for the next unit.
+        unit.clear();
+        self.unit = unit;
+
+        Ok(Ok(()))
+    }
+
+    /// Select the state following a record, given the offset reached.
+    fn next(&self, i: usize, state: RecordHeader) -> State {
+        if i < self.end {
+            State::RecordHeader(state)
+        } else {
+            State::Crc
+        }
+    }
+}
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Number of bytes in the unit to be buffered in a state, given the bytes
+/// buffered so far.
+fn needed(state: &State, unit: &[u8], definitions: &[DefinitionView; 16]) -> usize {
+    match state {
+        State::DocumentHeader => Decoder::LEN,
+        State::ExtendedDocumentHeader(..) => ExtendedDocumentHeader::LEN,
+        State::UnknownDocumentHeader(..) => UnknownDocumentHeader::LEN,
+        State::RecordHeader(_) => RecordHeader::LEN,
+        State::Definition(state, ..) => {
+            let fields = match unit.get(4) {
+                Some(&fields) => Definition::LEN + DefinitionField::LEN * fields as usize,
+                None => Definition::LEN,
+            };
+
+            if state.has_developer_data() && unit.len() >= fields {
+                let developer = fields + DeveloperDefinition::LEN;
+                match unit.get(fields) {
+                    Some(&developer_fields) => {
+                        developer + DeveloperDefinitionField::LEN * developer_fields as usize
+                    }
+                    None => developer,
+                }
+            } else {
+                fields
+            }
+        }
+        State::Data(_, _, local, _) => definitions[*local as usize].data_size(),
+        State::Crc => 2,
+        State::Done => 0,
+    }
+}
+
+/// A snapshot of the progress of a [`PushDecoder`], including the definitions
+/// and developer fields declared so far.
+///
+/// Snapshots can be stored as bytes with [`DecoderState::to_bytes`], so
+/// decoding can continue after a reset or on another task.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone)]
+pub struct DecoderState {
+    position: Position,
+    unit: Vec<u8>,
+    definitions: [DefinitionView; 16],
+    x: Context,
+    i: usize,
+    c: u16,
+    end: usize,
+}
+
+/// A state of the finite-state machine, with the bytes needed to reach it
+/// again.
+#[derive(Debug, Clone, Copy)]
+enum Position {
+    DocumentHeader,
+    ExtendedDocumentHeader([u8; 12]),
+    UnknownDocumentHeader([u8; 12], u8),
+    RecordHeader,
+    Definition(u8),
+    Data(u8),
+    Crc,
+    Done,
+}
+
+impl Position {
+    /// Reach the state again, returning `None` if it can't be reached.
+    fn state(self) -> Option<State> {
+        let extended = |header| match Decoder::advance(header).ok()?.1 {
+            DocumentHeaderSuccessor::Extended(state) => Some(state),
+            DocumentHeaderSuccessor::NextRecord(_) => None,
+        };
+        let unknown = |successor| match successor {
+            ExtendedHeaderSuccessor::MoreBytes(state) => Some(state),
+            ExtendedHeaderSuccessor::NextRecord(_) => None,
+        };
+
+        Some(match self {
+            Position::DocumentHeader => State::DocumentHeader,
+            Position::ExtendedDocumentHeader(header) => {
+                State::ExtendedDocumentHeader(extended(header)?, header)
+            }
+            Position::UnknownDocumentHeader(header, skipped) => {
+                let mut state = unknown(extended(header)?.advance([0; 2]))?;
+                for _ in 0..skipped {
+                    state = unknown(state.advance([0]))?;
+                }
+
+                State::UnknownDocumentHeader(state, header, skipped)
+            }
+            Position::RecordHeader => State::RecordHeader(RecordHeader::random_access()),
+            Position::Definition(header) => {
+                match RecordHeader::random_access().advance([header]).ok()? {
+                    (local, RecordHeaderSuccessor::Definition(state)) => {
+                        State::Definition(state, local, [header])
+                    }
+                    _ => None?,
+                }
+            }
+            Position::Data(header) => match RecordHeader::random_access().advance([header]).ok()? {
+                (local, RecordHeaderSuccessor::Data(time, state)) => {
+                    State::Data(state, time, local, [header])
+                }
+                _ => None?,
+            },
+            Position::Crc => State::Crc,
+            Position::Done => State::Done,
+        })
+    }
+}
+
+/// Version of the byte representation of a [`DecoderState`].
+const VERSION: u8 = 1;
+
+impl DecoderState {
+    /// Represent the snapshot as bytes, to be restored with
+    /// [`DecoderState::from_bytes`].
+    ///
+    /// Decoding options are not included, and must be given again when
+    /// restoring.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = vec![VERSION];
+
+        match self.position {
+            Position::DocumentHeader => w.push(0),
+            Position::ExtendedDocumentHeader(header) => {
+                w.push(1);
+                w.extend(header);
+            }
+            Position::UnknownDocumentHeader(header, skipped) => {
+                w.push(2);
+                w.extend(header);
+                w.push(skipped);
+            }
+            Position::RecordHeader => w.push(3),
+            Position::Definition(header) => w.extend([4, header]),
+            Position::Data(header) => w.extend([5, header]),
+            Position::Crc => w.push(6),
+            Position::Done => w.push(7),
+        }
+
+        w.extend((self.i as u64).to_le_bytes());
+        w.extend(self.c.to_le_bytes());
+        w.extend((self.end as u64).to_le_bytes());
+
+        match self.x.timestamp.timestamp() {
+            Some(timestamp) => {
+                w.push(1);
+                w.extend(timestamp.to_le_bytes());
+            }
+            None => w.push(0),
+        }
+
+        let definitions = self.definitions.iter().map(DefinitionView::as_bytes);
+        for bytes in [self.unit.as_slice()].into_iter().chain(definitions) {
+            w.extend((bytes.len() as u32).to_le_bytes());
+            w.extend(bytes);
+        }
+
+        self.x.registry.write(&mut w);
+
+        w
+    }
+
+    /// Restore a snapshot from bytes written by [`DecoderState::to_bytes`],
+    /// with options.
+    ///
+    /// Returns `None` if the bytes are not a snapshot of this version, or
+    /// don't describe a reachable state.
+    pub fn from_bytes(mut r: &[u8], options: DecodeOptions) -> Option<Self> {
+        fn take<const N: usize>(r: &mut &[u8]) -> Option<[u8; N]> {
+            let (bytes, rest) = r.split_first_chunk()?;
+            *r = rest;
+            Some(*bytes)
+        }
+
+        fn take_vec(r: &mut &[u8]) -> Option<Vec<u8>> {
+            let len = u32::from_le_bytes(take(r)?) as usize;
+            let (bytes, rest) = r.split_at_checked(len)?;
+            *r = rest;
+            Some(bytes.to_vec())
+        }
+
+        let r = &mut r;
+
+        if take(r)? != [VERSION] {
+            return None;
+        }
+
+        let position = match take(r)? {
+            [0] => Position::DocumentHeader,
+            [1] => Position::ExtendedDocumentHeader(take(r)?),
+            [2] => Position::UnknownDocumentHeader(take(r)?, take::<1>(r)?[0]),
+            [3] => Position::RecordHeader,
+            [4] => Position::Definition(take::<1>(r)?[0]),
+            [5] => Position::Data(take::<1>(r)?[0]),
+            [6] => Position::Crc,
+            [7] => Position::Done,
+            _ => return None,
+        };
+        let state = position.state()?;
+
+        let i = u64::from_le_bytes(take(r)?).try_into().ok()?;
+        let c = u16::from_le_bytes(take(r)?);
+        let end = u64::from_le_bytes(take(r)?).try_into().ok()?;
+
+        let mut x = Context::new(options);
+        match take(r)? {
+            [0] => {}
+            [1] => x.timestamp.update(u32::from_le_bytes(take(r)?)),
+            _ => return None,
+        }
+
+        let unit = take_vec(r)?;
+        let mut definitions: [DefinitionView; 16] = Default::default();
+        for d in &mut definitions {
+            let bytes = take_vec(r)?;
+            if !bytes.is_empty() {
+                *d = DefinitionView::parse(&bytes)?;
+            }
+        }
+
+        x.registry = Registry::read(r)?;
+
+        // Data records are only reached once their definition is declared.
+        if let State::Data(_, _, local, _) = state
+            && definitions[local as usize].as_bytes().is_empty()
+        {
+            return None;
+        }
+
+        // A whole unit would have been decoded before the snapshot was taken.
+        if unit.len() > needed(&state, &unit, &definitions) {
+            return None;
+        }
+
+        r.is_empty().then_some(Self {
+            position,
+            unit,
+            definitions,
+            x,
+            i,
+            c,
+            end,
+        })
+    }
+}
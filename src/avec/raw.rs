@@ -0,0 +1,772 @@
+//! Records decoded without a predefined shape, for exploring documents.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::{convert::Infallible, fmt, mem, ops::ControlFlow};
+use std::{boxed::Box, collections::BTreeMap, string::String, sync::mpsc, vec::Vec};
+
+use super::{
+    DecodeOptions, DefinitionInfo, DeveloperFieldInfo, FieldDescription, FieldInfo, FromRecord,
+    FromRecords, HeaderInfo, IntoRecord, IntoRecords, Value, sink, slice,
+};
+
+extern crate std;
+
+/// Decode every record of a document from a slice, without first describing
+/// them.
+///
+/// This suits exploring documents, before designing receivers for their
+/// records. Compressed timestamps are expanded into full timestamps.
+///
+/// ```
+/// let document = derailleur::avec::raw::decode(&data)?;
+///
+/// for message in document.messages() {
+///     println!("{}: {:?}", message.global(), message.fields());
+/// }
+/// ```
+///
+/// _Requires Cargo feature `std`._
+pub fn decode(data: &[u8]) -> Result<RawDocument, slice::Error> {
+    let options = DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    };
+
+    let mut document = RawDocument::new();
+    slice::decode_with(data, &mut document, &options)?;
+
+    Ok(document)
+}
+
+/// An owned document of records of any message.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawDocument {
+    header: Option<HeaderInfo>,
+    messages: Vec<Message>,
+}
+
+impl RawDocument {
+    /// Create a document without records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metadata from the document header, if decoded from a document.
+    pub fn header(&self) -> Option<HeaderInfo> {
+        self.header
+    }
+
+    /// Records of the document, in order.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Unwrap the records of the document.
+    pub fn into_messages(self) -> Vec<Message> {
+        self.messages
+    }
+}
+
+impl From<Vec<Message>> for RawDocument {
+    fn from(messages: Vec<Message>) -> Self {
+        Self {
+            header: None,
+            messages,
+        }
+    }
+}
+
+impl FromRecords for RawDocument {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.messages.push(Message::new(id));
+        Ok(self
+            .messages
+            .last_mut()
+            .map(|m| &mut m.record as &mut dyn FromRecord))
+    }
+    fn add_record_with(
+        &mut self,
+        _: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.messages.push(Message::with_definition(definition));
+        Ok(self.messages.last_mut().map(|m| m as &mut dyn FromRecord))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.header = Some(info);
+    }
+}
+
+impl IntoRecords for RawDocument {
+    fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+        for message in &self.messages {
+            add(message.global, message);
+        }
+    }
+}
+
+/// An owned record of any message, with its global message number.
+///
+/// Records decoded with their definition keep the size and base type of each
+/// field, which are declared when encoding them again (see
+/// [`IntoRecord::field_info`]).
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    global: u16,
+    record: DynRecord,
+    declared: Declared,
+}
+
+/// Fields of a record, as defined when decoding it.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Declared {
+    fields: Vec<FieldInfo>,
+    // Base types of developer fields are given by their description, once
+    // their values are received.
+    developer: Vec<(DeveloperFieldInfo, Option<u8>)>,
+}
+
+impl Message {
+    /// Create a record for a global message number, without fields.
+    pub fn new(global: u16) -> Self {
+        Self {
+            global,
+            record: DynRecord::new(),
+            declared: Declared::default(),
+        }
+    }
+
+    /// Create a record, without fields, declaring those of its definition.
+    pub(crate) fn with_definition(definition: DefinitionInfo<'_>) -> Self {
+        let declared = Declared {
+            fields: definition.fields().collect(),
+            developer: definition.developer_fields().map(|f| (f, None)).collect(),
+        };
+
+        Self {
+            global: definition.global(),
+            record: DynRecord::new(),
+            declared,
+        }
+    }
+
+    /// Global message number of the record.
+    pub fn global(&self) -> u16 {
+        self.global
+    }
+
+    /// Fields of the record, in order.
+    ///
+    /// Array fields other than bytes hold an entry for each value.
+    pub fn fields(&self) -> &[(u8, FieldValue)] {
+        self.record.fields()
+    }
+
+    /// Developer fields of the record, in order, each with its field number
+    /// and developer data index.
+    pub fn developer_fields(&self) -> &[(u8, u8, FieldValue)] {
+        self.record.developer_fields()
+    }
+
+    /// Find the first value of a field.
+    pub fn get(&self, field: u8) -> Option<&FieldValue> {
+        self.record.get(field)
+    }
+
+    /// Set the value of a field, replacing any existing values in place.
+    ///
+    /// The size and base type the field was decoded with are kept if the
+    /// value fills them, and otherwise follow from the value.
+    pub fn set(&mut self, field: u8, value: FieldValue) {
+        let fills = self
+            .field_info(field)
+            .is_some_and(|info| sink::fills(info, value.as_value()));
+
+        if !fills {
+            self.declared.fields.retain(|info| info.number != field);
+        }
+
+        self.record.set(field, value);
+    }
+
+    /// Remove all values of a field.
+    ///
+    /// Returns whether the field was present.
+    pub fn remove(&mut self, field: u8) -> bool {
+        self.record.remove(field)
+    }
+
+    /// Keep only the fields for which a predicate holds.
+    pub(crate) fn retain(&mut self, f: impl FnMut(u8) -> bool) {
+        self.record.retain(f);
+    }
+
+    /// Keep the base type of a developer field, given by its description.
+    fn describe(&mut self, field: &FieldDescription) {
+        let (number, developer) = (field.number(), field.developer());
+
+        for (info, base_type) in &mut self.declared.developer {
+            if (info.number, info.developer) == (number, developer) {
+                *base_type = Some(field.base_type());
+            }
+        }
+    }
+}
+
+impl IntoRecord for Message {
+    fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+        IntoRecord::fields(&self.record, add);
+    }
+    fn developer_fields(&self, add: &mut dyn FnMut(u8, u8, Value)) {
+        IntoRecord::developer_fields(&self.record, add);
+    }
+    fn field_info(&self, field: u8) -> Option<FieldInfo> {
+        let mut fields = self.declared.fields.iter();
+        fields.find(|info| info.number == field).copied()
+    }
+    fn developer_field_info(&self, field: u8, developer: u8) -> Option<FieldInfo> {
+        let mut fields = self.declared.developer.iter();
+        let (info, base_type) =
+            fields.find(|(info, _)| (info.number, info.developer) == (field, developer))?;
+
+        Some(FieldInfo {
+            number: field,
+            size: info.size,
+            base_type: (*base_type)?,
+        })
+    }
+}
+
+/// Forward values of a primitive type to the record.
+macro_rules! forward {
+    ($($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: u8, value: $t) {
+                self.record.$add(field, value);
+            }
+        )*
+    };
+    (developer $($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: &FieldDescription, value: $t) {
+                self.record.$add(field, value);
+            }
+        )*
+    };
+    (described $($add:ident: $t:ty),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: &FieldDescription, value: $t) {
+                self.describe(field);
+                self.record.$add(field, value);
+            }
+        )*
+    };
+}
+
+impl FromRecord for Message {
+    forward! {
+        add_u8: u8,
+        add_u16: u16,
+        add_u32: u32,
+        add_u64: u64,
+        add_i8: i8,
+        add_i16: i16,
+        add_i32: i32,
+        add_i64: i64,
+        add_f32: f32,
+        add_f64: f64,
+        add_string: &[u8],
+    }
+
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.record.add_invalid(field, base_type);
+    }
+
+    forward! {
+        described
+        add_developer_u8: u8,
+        add_developer_u16: u16,
+        add_developer_u32: u32,
+        add_developer_u64: u64,
+        add_developer_i8: i8,
+        add_developer_i16: i16,
+        add_developer_i32: i32,
+        add_developer_i64: i64,
+        add_developer_f32: f32,
+        add_developer_f64: f64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        self.describe(field);
+        self.record.add_developer_invalid(field);
+    }
+}
+
+/// Collect records of every message, by global message number.
+impl FromRecords for BTreeMap<u16, Vec<DynRecord>> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        let records = self.entry(id).or_default();
+        records.push(DynRecord::new());
+        Ok(records.last_mut().map(|r| r as &mut dyn FromRecord))
+    }
+}
+
+/// A receiver passing the records of each global message number to a closure
+/// registered for it, for quick use without writing a receiver.
+///
+/// ```
+/// let mut heart_rates = Vec::new();
+///
+/// let mut router = RecordRouter::new().on(20, |record| {
+///     if let Some(FieldValue::U8(heart_rate)) = record.get(3) {
+///         heart_rates.push(*heart_rate);
+///     }
+/// });
+/// derailleur::avec::decode_slice(&data, &mut router)?;
+/// ```
+///
+/// Each record is passed to its closure once it ends, and records of other
+/// messages are passed over.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Default)]
+pub struct RecordRouter<'a> {
+    handlers: Vec<(u16, Handler<'a>)>,
+    current: Option<usize>, // The handler of the record being received.
+    record: DynRecord,
+}
+
+/// A closure registered with a [`RecordRouter`].
+type Handler<'a> = Box<dyn FnMut(&DynRecord) + 'a>;
+
+impl<'a> RecordRouter<'a> {
+    /// Create a router, without closures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a closure for the records of a global message number,
+    /// replacing any registered before.
+    pub fn on(mut self, id: u16, f: impl FnMut(&DynRecord) + 'a) -> Self {
+        self.handlers.retain(|(i, _)| *i != id);
+        self.handlers.push((id, Box::new(f)));
+        self
+    }
+}
+
+impl fmt::Debug for RecordRouter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<_> = self.handlers.iter().map(|(id, _)| id).collect();
+        f.debug_struct("RecordRouter").field("ids", &ids).finish()
+    }
+}
+
+impl FromRecords for RecordRouter<'_> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        let Some(i) = self.handlers.iter().position(|(i, _)| *i == id) else {
+            return Ok(None);
+        };
+
+        self.current = Some(i);
+        self.record = DynRecord::new();
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for RecordRouter<'_> {
+    fn end(&mut self) {
+        if let Some(i) = self.current.take() {
+            (self.handlers[i].1)(&self.record);
+        }
+    }
+
+    forward! {
+        add_u8: u8,
+        add_u16: u16,
+        add_u32: u32,
+        add_u64: u64,
+        add_i8: i8,
+        add_i16: i16,
+        add_i32: i32,
+        add_i64: i64,
+        add_f32: f32,
+        add_f64: f64,
+        add_string: &[u8],
+    }
+
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.record.add_invalid(field, base_type);
+    }
+
+    forward! {
+        developer
+        add_developer_u8: u8,
+        add_developer_u16: u16,
+        add_developer_u32: u32,
+        add_developer_u64: u64,
+        add_developer_i8: i8,
+        add_developer_i16: i16,
+        add_developer_i32: i32,
+        add_developer_i64: i64,
+        add_developer_f32: f32,
+        add_developer_f64: f64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        self.record.add_developer_invalid(field);
+    }
+}
+
+/// A receiver sending each record over a channel once it ends, so records
+/// can be processed on another thread while decoding continues.
+///
+/// ```
+/// let (tx, rx) = std::sync::mpsc::channel();
+///
+/// let decoder = std::thread::spawn(move || {
+///     derailleur::avec::decode_slice(&data, &mut MessageSender::new(tx))
+/// });
+///
+/// for message in rx {
+///     println!("{}: {:?}", message.global(), message.fields());
+/// }
+/// decoder.join().unwrap()?;
+/// ```
+///
+/// Decoding stops once the receiving end of the channel is dropped.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug)]
+pub struct MessageSender {
+    tx: mpsc::Sender<Message>,
+    disconnected: bool,
+    global: u16,
+    record: DynRecord,
+}
+
+impl MessageSender {
+    /// Create a receiver sending records over a channel.
+    pub fn new(tx: mpsc::Sender<Message>) -> Self {
+        Self {
+            tx,
+            disconnected: false,
+            global: 0,
+            record: DynRecord::new(),
+        }
+    }
+}
+
+impl FromRecords for MessageSender {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.global = id;
+        self.record = DynRecord::new();
+        Ok(Some(self))
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        match self.disconnected {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    }
+}
+
+impl FromRecord for MessageSender {
+    fn end(&mut self) {
+        let message = Message {
+            global: self.global,
+            record: mem::take(&mut self.record),
+            declared: Declared::default(),
+        };
+
+        self.disconnected |= self.tx.send(message).is_err();
+    }
+
+    forward! {
+        add_u8: u8,
+        add_u16: u16,
+        add_u32: u32,
+        add_u64: u64,
+        add_i8: i8,
+        add_i16: i16,
+        add_i32: i32,
+        add_i64: i64,
+        add_f32: f32,
+        add_f64: f64,
+        add_string: &[u8],
+    }
+
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.record.add_invalid(field, base_type);
+    }
+
+    forward! {
+        developer
+        add_developer_u8: u8,
+        add_developer_u16: u16,
+        add_developer_u32: u32,
+        add_developer_u64: u64,
+        add_developer_i8: i8,
+        add_developer_i16: i16,
+        add_developer_i32: i32,
+        add_developer_i64: i64,
+        add_developer_f32: f32,
+        add_developer_f64: f64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        self.record.add_developer_invalid(field);
+    }
+}
+
+/// An owned field value of a [`DynRecord`].
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// A `string`, encoded with a terminator.
+    String(String),
+    /// An array of bytes, such as a decoded `byte` field.
+    Bytes(Vec<u8>),
+    /// The 'invalid' marker value of a base type, given as its byte.
+    Invalid(u8),
+}
+
+/// A record receiver storing every value it receives, for decoding documents
+/// without first describing their records.
+///
+/// ```
+/// #[derive(Debug, Default, FromRecords)]
+/// struct Exploration {
+///     #[record(20)]
+///     records: Vec<DynRecord>,
+/// }
+/// ```
+///
+/// A `BTreeMap<u16, Vec<DynRecord>>` receives the records of every message,
+/// by global message number.
+///
+/// Array fields other than bytes hold an entry for each value, including
+/// those holding the 'invalid' marker value (as [`FieldValue::Invalid`]).
+/// Developer fields are held apart, with their developer data index.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DynRecord {
+    fields: Vec<(u8, FieldValue)>,
+    developer: Vec<(u8, u8, FieldValue)>,
+}
+
+impl DynRecord {
+    /// Create a record without fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fields of the record, in order.
+    pub fn fields(&self) -> &[(u8, FieldValue)] {
+        &self.fields
+    }
+
+    /// Developer fields of the record, in order, each with its field number
+    /// and developer data index.
+    pub fn developer_fields(&self) -> &[(u8, u8, FieldValue)] {
+        &self.developer
+    }
+
+    /// Find the first value of a field.
+    pub fn get(&self, field: u8) -> Option<&FieldValue> {
+        self.fields
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, v)| v)
+    }
+
+    /// Set the value of a field, replacing any existing values in place.
+    pub fn set(&mut self, field: u8, value: FieldValue) {
+        let mut found = false;
+        self.fields
+            .retain(|(f, _)| *f != field || !mem::replace(&mut found, true));
+
+        match self.fields.iter_mut().find(|(f, _)| *f == field) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((field, value)),
+        }
+    }
+
+    /// Remove all values of a field.
+    ///
+    /// Returns whether the field was present.
+    pub fn remove(&mut self, field: u8) -> bool {
+        let len = self.fields.len();
+        self.fields.retain(|(f, _)| *f != field);
+        self.fields.len() != len
+    }
+
+    /// Keep only the fields for which a predicate holds.
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(u8) -> bool) {
+        self.fields.retain(|(field, _)| f(*field));
+    }
+}
+
+impl FieldValue {
+    /// Borrow the value, for encoding.
+    fn as_value(&self) -> Value<'_> {
+        match self {
+            FieldValue::U8(v) => Value::U8(*v),
+            FieldValue::U16(v) => Value::U16(*v),
+            FieldValue::U32(v) => Value::U32(*v),
+            FieldValue::U64(v) => Value::U64(*v),
+            FieldValue::I8(v) => Value::I8(*v),
+            FieldValue::I16(v) => Value::I16(*v),
+            FieldValue::I32(v) => Value::I32(*v),
+            FieldValue::I64(v) => Value::I64(*v),
+            FieldValue::F32(v) => Value::F32(*v),
+            FieldValue::F64(v) => Value::F64(*v),
+            FieldValue::String(v) => Value::String(v),
+            FieldValue::Bytes(v) => Value::Bytes(v),
+            FieldValue::Invalid(b) => Value::Invalid(*b),
+        }
+    }
+
+    /// Add a byte to the value, if it holds bytes, collecting them into an
+    /// array.
+    ///
+    /// Returns the byte if the value holds anything else.
+    fn push(&mut self, value: u8) -> Option<u8> {
+        match self {
+            FieldValue::U8(b) => *self = FieldValue::Bytes(Vec::from([*b, value])),
+            FieldValue::Bytes(b) => b.push(value),
+            _ => return Some(value),
+        }
+
+        None
+    }
+}
+
+impl IntoRecord for DynRecord {
+    fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+        for (field, value) in &self.fields {
+            add(*field, value.as_value());
+        }
+    }
+    fn developer_fields(&self, add: &mut dyn FnMut(u8, u8, Value)) {
+        for (field, developer, value) in &self.developer {
+            add(*field, *developer, value.as_value());
+        }
+    }
+}
+
+/// Store values of a primitive type for a developer field.
+macro_rules! developer {
+    ($($add:ident: $t:ty => $variant:ident),* $(,)?) => {
+        $(
+            fn $add(&mut self, field: &FieldDescription, value: $t) {
+                let (f, developer) = (field.number(), field.developer());
+                self.developer.push((f, developer, FieldValue::$variant(value)));
+            }
+        )*
+    };
+}
+
+impl FromRecord for DynRecord {
+    fn add_u8(&mut self, field: u8, value: u8) {
+        // Collect consecutive bytes for a field into an array.
+        let value = match self.fields.last_mut() {
+            Some((f, v)) if *f == field => v.push(value),
+            _ => Some(value),
+        };
+
+        if let Some(value) = value {
+            self.fields.push((field, FieldValue::U8(value)));
+        }
+    }
+    fn add_u16(&mut self, field: u8, value: u16) {
+        self.fields.push((field, FieldValue::U16(value)));
+    }
+    fn add_u32(&mut self, field: u8, value: u32) {
+        self.fields.push((field, FieldValue::U32(value)));
+    }
+    fn add_u64(&mut self, field: u8, value: u64) {
+        self.fields.push((field, FieldValue::U64(value)));
+    }
+    fn add_i8(&mut self, field: u8, value: i8) {
+        self.fields.push((field, FieldValue::I8(value)));
+    }
+    fn add_i16(&mut self, field: u8, value: i16) {
+        self.fields.push((field, FieldValue::I16(value)));
+    }
+    fn add_i32(&mut self, field: u8, value: i32) {
+        self.fields.push((field, FieldValue::I32(value)));
+    }
+    fn add_i64(&mut self, field: u8, value: i64) {
+        self.fields.push((field, FieldValue::I64(value)));
+    }
+    fn add_f32(&mut self, field: u8, value: f32) {
+        self.fields.push((field, FieldValue::F32(value)));
+    }
+    fn add_f64(&mut self, field: u8, value: f64) {
+        self.fields.push((field, FieldValue::F64(value)));
+    }
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.fields.push((field, FieldValue::Invalid(base_type)));
+    }
+    fn add_string(&mut self, field: u8, value: &[u8]) {
+        // Replace the bytes of the strings, published before the first.
+        if matches!(
+            self.fields.last(),
+            Some((f, FieldValue::U8(_) | FieldValue::Bytes(_))) if *f == field
+        ) {
+            self.fields.pop();
+        }
+
+        let value = String::from_utf8_lossy(value).into_owned();
+        self.fields.push((field, FieldValue::String(value)));
+    }
+
+    fn add_developer_u8(&mut self, field: &FieldDescription, value: u8) {
+        let (f, developer) = (field.number(), field.developer());
+
+        // Collect consecutive bytes for a field into an array.
+        let value = match self.developer.last_mut() {
+            Some((i, j, v)) if (*i, *j) == (f, developer) => v.push(value),
+            _ => Some(value),
+        };
+
+        if let Some(value) = value {
+            self.developer.push((f, developer, FieldValue::U8(value)));
+        }
+    }
+
+    developer! {
+        add_developer_u16: u16 => U16,
+        add_developer_u32: u32 => U32,
+        add_developer_u64: u64 => U64,
+        add_developer_i8: i8 => I8,
+        add_developer_i16: i16 => I16,
+        add_developer_i32: i32 => I32,
+        add_developer_i64: i64 => I64,
+        add_developer_f32: f32 => F32,
+        add_developer_f64: f64 => F64,
+    }
+
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        let (f, developer) = (field.number(), field.developer());
+        let invalid = FieldValue::Invalid(field.base_type());
+        self.developer.push((f, developer, invalid));
+    }
+}
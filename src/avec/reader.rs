@@ -2,217 +2,810 @@
 //!
 //! _Requires Cargo feature `std`._
 
-use std::{io::Read, vec::Vec};
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    fs::File,
+    io::{BufReader, ErrorKind, Read, Seek},
+    path::Path,
+    vec,
+    vec::Vec,
+};
 
-use either::Either::{self, Left, Right};
+#[cfg(feature = "flate2")]
+use flate2::read::GzDecoder;
 use thiserror::Error;
 
 use crate::sans::{
-    Decoder,
     check::compute_crc,
-    data::{AnyField, Field, FieldInner},
-    definition::{Definition, DefinitionAlt, DefinitionFieldAlt},
-    header::{DocumentHeaderError, RecordHeader, RecordHeaderError},
+    header::{DocumentHeaderError, RecordHeaderError},
 };
 
-use super::{FromRecord, FromRecords};
+use super::{
+    ByteSource, DecodeOptions, DefinitionInfo, DefinitionView, FromRecord, FromRecords,
+    FromRecordsStatic, Location,
+    push::PushDecoder,
+    raw::Message,
+    source::{self, Exhausted, Locate, MissingDefinition, Next, Receiver, Retain, Source, Static},
+};
 
 extern crate std;
 
 /// Errors occurring while decoding from a reader.
 #[derive(Debug, Error)]
-pub enum Error {
+pub enum Error<E = Infallible> {
     /// An error from the supplied reader.
-    #[error(transparent)]
-    Io(#[from] std::io::Error),
+    #[error("{source}, {at}.")]
+    Io {
+        source: std::io::Error,
+        at: Location,
+    },
     /// Calculated and found CRC values do not match.
-    #[error("Calculated ({calculated}) and found ({found}) CRC values do not match.")]
-    CyclicRedundancyCheck { found: u16, calculated: u16 },
+    #[error("Calculated ({calculated}) and found ({found}) CRC values do not match, {at}.")]
+    CyclicRedundancyCheck {
+        found: u16,
+        calculated: u16,
+        at: Location,
+    },
     /// Incorrect file header.
-    #[error("Incorrect file header: {0}.")]
-    Header(#[from] DocumentHeaderError),
-    /// Found unsupported developer data.
-    #[error("Found unsupported developer data.")]
-    Developer,
+    #[error("Incorrect file header: {source}, {at}.")]
+    Header {
+        source: DocumentHeaderError,
+        at: Location,
+    },
+    /// Found the developer data flag on a data record header.
+    #[error("Found the developer data flag on a data record header, {at}.")]
+    Developer { at: Location },
+    /// Found a data record for a local message number without a preceding
+    /// definition record.
+    #[error("Found a data record for local message {local} without a definition, {at}.")]
+    MissingDefinition { local: u8, at: Location },
+    /// The receiver failed, as returned from [`FromRecords::add_record`] or
+    /// [`FromRecords::finish`].
+    #[error("Receiver failed: {0}.")]
+    Receiver(E),
+}
+
+impl<E> Error<E> {
+    /// Position in the document at which the error occurred, or the default
+    /// for a failure of the receiver.
+    pub fn location(&self) -> Location {
+        match self {
+            Self::Io { at, .. }
+            | Self::CyclicRedundancyCheck { at, .. }
+            | Self::Header { at, .. }
+            | Self::Developer { at }
+            | Self::MissingDefinition { at, .. } => *at,
+            Self::Receiver(_) => Location::default(),
+        }
+    }
+}
+
+impl Error {
+    /// Carry an error of the decoder into that of a fallible receiver.
+    pub(super) fn widen<E>(self) -> Error<E> {
+        match self {
+            Self::Io { source, at } => Error::Io { source, at },
+            Self::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            } => Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            },
+            Self::Header { source, at } => Error::Header { source, at },
+            Self::Developer { at } => Error::Developer { at },
+            Self::MissingDefinition { local, at } => Error::MissingDefinition { local, at },
+            Self::Receiver(never) => match never {},
+        }
+    }
+}
+
+impl<E> Locate for Error<E> {
+    fn locate(mut self, location: Location) -> Self {
+        let (Self::Io { at, .. }
+        | Self::CyclicRedundancyCheck { at, .. }
+        | Self::Header { at, .. }
+        | Self::Developer { at }
+        | Self::MissingDefinition { at, .. }) = &mut self
+        else {
+            return self;
+        };
+
+        // Retain a location given where the error was found.
+        if *at == Location::default() {
+            *at = location;
+        }
+
+        self
+    }
 }
 
-impl From<RecordHeaderError> for Error {
+impl<E> From<DocumentHeaderError> for Error<E> {
+    fn from(source: DocumentHeaderError) -> Self {
+        Self::Header {
+            source,
+            at: Location::default(),
+        }
+    }
+}
+
+impl<E> From<RecordHeaderError> for Error<E> {
     fn from(err: RecordHeaderError) -> Self {
         match err {
-            RecordHeaderError::DeveloperData => Self::Developer,
+            RecordHeaderError::DeveloperData => Self::Developer {
+                at: Location::default(),
+            },
+        }
+    }
+}
+
+impl<E> From<MissingDefinition> for Error<E> {
+    fn from(MissingDefinition(local): MissingDefinition) -> Self {
+        Self::MissingDefinition {
+            local,
+            at: Location::default(),
+        }
+    }
+}
+
+impl<E> From<Exhausted> for Error<E> {
+    fn from(_: Exhausted) -> Self {
+        std::io::Error::from(ErrorKind::UnexpectedEof).into()
+    }
+}
+
+impl<E> From<std::io::Error> for Error<E> {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io {
+            source,
+            at: Location::default(),
         }
     }
 }
 
 /// Decode records from a reader of a document, publishing to a receiver.
 ///
+/// With Cargo feature `flate2`, gzip-compressed documents are detected and
+/// decompressed transparently.
+///
+/// Records are read from the reader in chunks, so unbuffered readers need not
+/// be wrapped in a buffer. Bytes following the records of a document are never
+/// read ahead.
+///
 /// This method is also re-exported as `derailleur::avec::decode_reader`.
 ///
 /// _Requires Cargo feature `std`._
-pub fn decode(r: &mut impl Read, o: &mut impl FromRecords) -> Result<(), Error> {
-    let i = &mut 0; // Counter of bytes read, used to end decoding.
-    let c = &mut 0; // Cyclic redundancy check accumulator value.
+pub fn decode<E>(r: &mut impl Read, o: &mut impl FromRecords<E>) -> Result<(), Error<E>> {
+    decode_with(r, o, &DecodeOptions::default())
+}
+
+/// Decode records from a document file at a path, publishing to a receiver.
+///
+/// The file is opened and read through a buffer, as for [`decode`]. With Cargo
+/// feature `flate2`, gzip-compressed files (such as the `.fit.gz` files of bulk
+/// exports) are decompressed transparently.
+///
+/// This method is also re-exported as `derailleur::avec::decode_path`.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_path<E>(path: impl AsRef<Path>, o: &mut impl FromRecords<E>) -> Result<(), Error<E>> {
+    let mut r = BufReader::new(File::open(path)?);
+    decode(&mut r, o)
+}
+
+/// Decode records from a reader of a document, publishing to a receiver, with
+/// options.
+///
+/// This method is also re-exported as `derailleur::avec::decode_reader_with`.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_with<E>(
+    r: &mut impl Read,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    decode_with_policy(r, o, options, RetryPolicy::default())
+}
+
+/// Decode records from a reader of a document, publishing to a receiver, with
+/// options, and applying a policy to interrupted and short reads.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_with_policy<E>(
+    r: &mut impl Read,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+    p: RetryPolicy,
+) -> Result<(), Error<E>> {
+    decode_receiver(r, o, options, p)
+}
+
+/// Decode records from a reader of a document, publishing to a receiver of a
+/// single record type without dynamic dispatch.
+///
+/// This method is also re-exported as `derailleur::avec::decode_reader_static`.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_static(r: &mut impl Read, o: &mut impl FromRecordsStatic) -> Result<(), Error> {
+    let options = &DecodeOptions::default();
+    decode_receiver(r, &mut Static(o), options, RetryPolicy::default())
+}
 
-    let (size, successor) = Decoder::advance(take(r, Some((i, c)))?)?;
+/// Decode records from a seekable reader of a document, publishing to a
+/// receiver.
+///
+/// As for [`decode`], except that compressed documents aren't detected.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_seekable<E>(
+    r: &mut (impl Read + Seek),
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<E>> {
+    decode_seekable_with(r, o, &DecodeOptions::default())
+}
 
-    let mut record_header = match successor {
-        Left(state) => state.advance(take(r, Some((i, c)))?),
-        Right(state) => state,
+/// Decode records from a seekable reader of a document, publishing to a
+/// receiver, with options.
+///
+/// Where [`DecodeOptions::verify_crc`] is disabled, data records without a
+/// receiver are passed over by seeking past them, rather than read and decoded.
+/// Records other records depend on, such as developer field descriptions and
+/// those holding timestamps, are still decoded.
+///
+/// Bytes are read only as needed, so that as many as possible are passed over.
+/// Wrap unbuffered readers in a [`BufReader`], which also seeks within its
+/// buffer.
+///
+/// _Requires Cargo feature `std`._
+pub fn decode_seekable_with<R: Read + Seek, E>(
+    r: &mut R,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    let s = &mut ReaderSource {
+        seek: Some(|r: &mut R, n| r.seek_relative(n)),
+        ..ReaderSource::new(r, RetryPolicy::default())
     };
 
-    let end = *i + size as usize; // Offset to the end of the record section.
+    decode_documents(s, o, options)
+}
 
-    // Store of previous definition records, used to decode data records.
-    let mut definitions: [_; 16] = Default::default();
+/// Decode records from a reader of a document, detecting compression.
+fn decode_receiver<E>(
+    r: &mut impl Read,
+    o: &mut impl for<'a> Receiver<'a, E>,
+    options: &DecodeOptions,
+    p: RetryPolicy,
+) -> Result<(), Error<E>> {
+    #[cfg(feature = "flate2")]
+    {
+        // Sniff the gzip magic bytes, which can never begin a document.
+        let mut magic = [0; 2];
+        ReaderSource::new(r, p).read_exact(&mut magic)?;
+        let r = &mut (&magic[..]).chain(r);
+
+        if magic == GZIP_MAGIC {
+            let r = &mut GzDecoder::new(r);
+            decode_documents(&mut ReaderSource::chunked(r, p), o, options)
+        } else {
+            decode_documents(&mut ReaderSource::chunked(r, p), o, options)
+        }
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    decode_documents(&mut ReaderSource::chunked(r, p), o, options)
+}
 
-    while *i < end {
-        let (local, successor) = record_header.advance(take(r, Some((i, c)))?)?;
+/// Decode a document, followed by any chained documents the options allow.
+fn decode_documents<R: Read, E>(
+    s: &mut ReaderSource<R>,
+    o: &mut impl for<'a> Receiver<'a, E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    let mut options = *options;
+
+    loop {
+        let decoded = source::decode(s, o, &options)
+            .map_err(Error::widen)?
+            .map_err(Error::Receiver)?;
+
+        if !options.allow_chained || decoded.stopped {
+            return Ok(());
+        }
+
+        // End at the end of the input, between documents.
+        s.read_ahead()?;
+        if s.n == 0 {
+            return Ok(());
+        }
+
+        options.max_records = options.max_records.map(|n| n - decoded.records);
+        (s.i, s.c, s.skipping) = (0, 0, false);
+        (s.read, s.limit) = (s.n, 0);
+    }
+}
 
-        record_header = match successor {
-            Left(state) => {
-                let d = &mut definitions[local as usize];
-                decode_definition(state, r, i, c, d)?
+/// Magic bytes beginning a gzip stream.
+#[cfg(feature = "flate2")]
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Policy for reads that are interrupted or return fewer bytes than requested.
+///
+/// The policy is applied by the decoder itself, independently of how the
+/// reader implements [`Read::read_exact`]. The default policy retries
+/// interrupted reads indefinitely, and accumulates short reads.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of consecutive reads failing with
+    /// [`ErrorKind::Interrupted`] to retry before returning the error, or
+    /// `None` to retry indefinitely.
+    pub interrupted: Option<u32>,
+    /// Whether to continue reading after a read returns fewer bytes than
+    /// requested. Otherwise, a short read is an error.
+    pub accumulate: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            interrupted: None,
+            accumulate: true,
+        }
+    }
+}
+
+/// Decoder for a non-blocking reader of a document, resumed as bytes become
+/// available.
+///
+/// _Requires Cargo feature `std`._
+pub struct NonBlockingDecoder(PushDecoder);
+
+/// Progress made by a [`NonBlockingDecoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The reader would block before the document was decoded. Poll again once
+    /// it is ready.
+    Pending,
+    /// The whole document was decoded.
+    Complete,
+}
+
+impl NonBlockingDecoder {
+    /// Create a decoder at the start of a document.
+    pub fn new() -> Self {
+        Self::with_options(DecodeOptions::default())
+    }
+
+    /// Create a decoder at the start of a document, with options.
+    pub fn with_options(options: DecodeOptions) -> Self {
+        Self(PushDecoder::with_options(options))
+    }
+
+    /// Decode records from a reader of a document, publishing to a receiver,
+    /// until the reader would block or the document ends.
+    ///
+    /// On [`ErrorKind::WouldBlock`], returns
+    /// [`Status::Pending`], retaining partial progress. Bytes are never read
+    /// past the end of the document.
+    pub fn poll<E>(
+        &mut self,
+        r: &mut impl Read,
+        o: &mut impl FromRecords<E>,
+    ) -> Result<Status, Error<E>> {
+        let mut buf = [0; 256];
+
+        while !self.0.is_done() {
+            let n = self.0.wanted().min(buf.len());
+
+            // Units without bytes, such as empty data records, need no read.
+            if n == 0 {
+                self.0.push(&[], o)?;
+                continue;
             }
-            Right((time, state)) => {
-                let d = &mut definitions[local as usize].as_slice();
-                decode_data(state, time, r, i, c, d, o)?
+
+            match r.read(&mut buf[..n]) {
+                Ok(0) if self.0.end_of_input() => break,
+                Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+                Ok(n) => _ = self.0.push(&buf[..n], o)?,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(Status::Pending),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => Err(err)?,
             }
-        };
+        }
+
+        Ok(Status::Complete)
+    }
+}
+
+impl Default for NonBlockingDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the records of a document from a reader, decoding one
+/// record at a time.
+///
+/// ```
+/// let file = std::fs::File::open("activity.fit")?;
+///
+/// let heart_rates = RecordStream::new(BufReader::new(file))
+///     .filter_map(|message| message.ok())
+///     .filter(|message| message.global() == 20)
+///     .filter_map(|message| message.get(3).cloned());
+/// ```
+///
+/// Compressed timestamps are expanded into full timestamps, as for
+/// [`raw::decode`](super::raw::decode). Bytes are never read past the end of
+/// the document, and after an error, the iterator ends.
+///
+/// _Requires Cargo feature `std`._
+pub struct RecordStream<R> {
+    r: R,
+    decoder: PushDecoder,
+    queue: Queue,
+    failed: bool,
+}
+
+impl<R: Read> RecordStream<R> {
+    /// Create an iterator over the records of a document from a reader.
+    pub fn new(r: R) -> Self {
+        Self::with_options(
+            r,
+            DecodeOptions {
+                synthesize_timestamps: true,
+                ..Default::default()
+            },
+        )
     }
 
-    let calculated = *c;
-    let found = u16::from_le_bytes(take(r, None)?);
+    /// Create an iterator over the records of a document from a reader, with
+    /// options.
+    pub fn with_options(r: R, options: DecodeOptions) -> Self {
+        Self {
+            r,
+            decoder: PushDecoder::with_options(options),
+            queue: Queue::default(),
+            failed: false,
+        }
+    }
 
-    if found != calculated {
-        Err(Error::CyclicRedundancyCheck { found, calculated })?;
+    /// Unwrap the reader, positioned after the bytes decoded so far.
+    pub fn into_inner(self) -> R {
+        self.r
     }
 
-    Ok(())
+    /// Read and decode bytes up to the end of the next unit.
+    fn fill(&mut self) -> Result<(), Error> {
+        let mut buf = [0; 256];
+        let n = self.decoder.wanted().min(buf.len());
+
+        // Units without bytes, such as empty data records, need no read.
+        if n == 0 {
+            self.decoder.push(&[], &mut self.queue)?;
+            return Ok(());
+        }
+
+        match self.r.read(&mut buf[..n]) {
+            Ok(0) if self.decoder.end_of_input() => {}
+            Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+            Ok(n) => _ = self.decoder.push(&buf[..n], &mut self.queue)?,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => Err(err)?,
+        }
+
+        Ok(())
+    }
 }
 
-fn decode_definition(
-    state: Definition,
-    r: &mut impl Read,
-    i: &mut usize,
-    c: &mut u16,
-    d: &mut Vec<u8>,
-) -> Result<RecordHeader, Error> {
-    d.clear();
-
-    let bytes = take(r, Some((i, c)))?;
-    d.extend_from_slice(&bytes);
-
-    let record_header = match state.advance(bytes) {
-        Left(mut state) => loop {
-            let bytes = take(r, Some((i, c)))?;
-            d.extend_from_slice(&bytes);
-
-            state = match state.advance(bytes) {
-                Left(state) => state,
-                Right(state) => break state,
-            };
-        },
-        Right(state) => state,
-    };
+impl<R: Read> Iterator for RecordStream<R> {
+    type Item = Result<Message, Error>;
 
-    Ok(record_header)
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.queue.0.pop_front() {
+                return Some(Ok(message));
+            }
+
+            if self.failed || self.decoder.is_done() {
+                return None;
+            }
+
+            if let Err(err) = self.fill() {
+                self.failed = true;
+                return Some(Err(err));
+            }
+        }
+    }
 }
 
-fn decode_data(
-    state: DefinitionAlt,
-    time: Option<u8>,
-    r: &mut impl Read,
-    i: &mut usize,
-    c: &mut u16,
-    d: &mut &[u8],
-    o: &mut impl FromRecords,
-) -> Result<RecordHeader, Error> {
-    let (global, successor) = state.advance(take(d, None)?);
-
-    // Shadow the document receiver with that of a single record.
-    let mut o = o.add_record(global);
-
-    if let (Some(o), Some(time)) = (&mut o, time) {
-        o.add_time_offset(time);
-    }
-
-    let record_header = match successor {
-        Left(mut state) => loop {
-            let (f, inner_state) = state.advance(take(d, None)?);
-
-            let o = o.as_deref_mut();
-
-            fn decode_field<
-                T: FieldInner<From = [u8; N]>,
-                O: FromRecord + ?Sized,
-                const N: usize,
-            >(
-                mut state: Field<T>,
-                r: &mut impl Read,
-                i: &mut usize,
-                c: &mut u16,
-                f: u8,
-
-                mut o: Option<&mut O>,
-                add: fn(&mut O, u8, T::Into),
-            ) -> Result<Either<DefinitionFieldAlt, RecordHeader>, Error> {
-                loop {
-                    let (value, successor) = state.advance(take(r, Some((i, c)))?);
-
-                    if let (Some(o), Some(value)) = (&mut o, value) {
-                        add(o, f, value);
-                    }
+/// Receiver queueing decoded records, to be taken one at a time.
+#[derive(Default)]
+pub(super) struct Queue(pub(super) VecDeque<Message>);
+
+impl FromRecords for Queue {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.0.push_back(Message::new(id));
+        Ok(self.0.back_mut().map(|m| m as &mut dyn FromRecord))
+    }
+    fn add_record_with(
+        &mut self,
+        _: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.0.push_back(Message::with_definition(definition));
+        Ok(self.0.back_mut().map(|m| m as &mut dyn FromRecord))
+    }
+}
+
+/// Size of the chunks read from a reader within a record section.
+const CHUNK: usize = 8 * 1024;
+
+/// Bytes read from a reader in a chunk, not yet taken.
+#[derive(Default)]
+pub(super) struct Chunk {
+    b: Vec<u8>, // Space for a chunk, or empty if reads aren't chunked.
+    j: usize,   // Offset to the first byte not yet taken.
+    k: usize,   // Offset to the end of the bytes read.
+}
+
+impl Chunk {
+    pub(super) fn new() -> Self {
+        Self {
+            b: vec![0; CHUNK],
+            j: 0,
+            k: 0,
+        }
+    }
+
+    /// Take bytes from the chunk to fill as much of a buffer as possible.
+    fn take(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.skip(buf.len());
+        buf[..n].copy_from_slice(&self.b[self.j - n..self.j]);
+        n
+    }
+
+    /// Pass over up to a number of bytes of the chunk.
+    fn skip(&mut self, n: usize) -> usize {
+        let n = n.min(self.k - self.j);
+        self.j += n;
+        n
+    }
+}
+
+pub(super) struct ReaderSource<'a, R> {
+    pub(super) r: &'a mut R,
+    pub(super) p: RetryPolicy,
+    pub(super) i: usize,   // Counter of bytes read, used to end decoding.
+    pub(super) c: u16,     // Cyclic redundancy check accumulator value.
+    pub(super) a: [u8; 3], // Bytes read ahead, not yet taken.
+    pub(super) n: usize,   // Number of bytes read ahead.
+    pub(super) seek: Option<fn(&mut R, i64) -> std::io::Result<()>>, // Relative seek, if able.
+    pub(super) skipping: bool, // Whether records may be passed over by seeking.
+    pub(super) chunk: Chunk, // Bytes read in a chunk, not yet taken.
+    pub(super) read: usize, // Counter of bytes read from the reader, as with `i`.
+    pub(super) limit: usize, // Offset up to which bytes may be read in chunks.
+}
+
+impl<'a, R> ReaderSource<'a, R> {
+    pub(super) fn new(r: &'a mut R, p: RetryPolicy) -> Self {
+        Self {
+            r,
+            p,
+            i: 0,
+            c: 0,
+            a: [0; 3],
+            n: 0,
+            seek: None,
+            skipping: false,
+            chunk: Chunk::default(),
+            read: 0,
+            limit: 0,
+        }
+    }
+
+    /// Create a source reading in chunks within each record section.
+    fn chunked(r: &'a mut R, p: RetryPolicy) -> Self {
+        Self {
+            chunk: Chunk::new(),
+            ..Self::new(r, p)
+        }
+    }
+}
+
+impl<R: Read> ReaderSource<'_, R> {
+    /// Read exactly enough bytes to fill a buffer, applying the retry policy.
+    ///
+    /// Within a record section, bytes are read in chunks where possible, never
+    /// passing the end of the section.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), std::io::Error> {
+        let mut filled = self.chunk.take(buf);
+        let mut retries = 0;
+
+        while filled < buf.len() {
+            let rest = &mut buf[filled..];
+
+            let ahead = self.chunk.b.len().min(self.limit.saturating_sub(self.read));
+            let chunked = ahead > rest.len();
+
+            let result = if chunked {
+                self.r.read(&mut self.chunk.b[..ahead])
+            } else {
+                self.r.read(rest)
+            };
 
-                    state = match successor {
-                        Left(successor) => return Ok(successor),
-                        Right(state) => state,
+            match result {
+                Ok(0) => Err(ErrorKind::UnexpectedEof)?,
+                Ok(n) => {
+                    self.read += n;
+                    retries = 0;
+
+                    filled += if chunked {
+                        (self.chunk.j, self.chunk.k) = (0, n);
+                        self.chunk.take(rest)
+                    } else {
+                        n
+                    };
+
+                    if filled < buf.len() && !self.p.accumulate {
+                        Err(std::io::Error::new(ErrorKind::UnexpectedEof, "short read"))?;
                     }
                 }
+                Err(err)
+                    if err.kind() == ErrorKind::Interrupted
+                        && self.p.interrupted.is_none_or(|n| retries < n) =>
+                {
+                    retries += 1;
+                }
+                Err(err) => Err(err)?,
             }
+        }
 
-            let successor = match inner_state {
-                AnyField::U8(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u8),
-                AnyField::U8Z(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u8),
-                AnyField::U16(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u16),
-                AnyField::U16Z(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u16),
-                AnyField::U32(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u32),
-                AnyField::U32Z(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u32),
-                AnyField::U64(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u64),
-                AnyField::U64Z(s) => decode_field(s, r, i, c, f, o, FromRecord::add_u64),
-
-                AnyField::I8(s) => decode_field(s, r, i, c, f, o, FromRecord::add_i8),
-                AnyField::I16(s) => decode_field(s, r, i, c, f, o, FromRecord::add_i16),
-                AnyField::I32(s) => decode_field(s, r, i, c, f, o, FromRecord::add_i32),
-                AnyField::I64(s) => decode_field(s, r, i, c, f, o, FromRecord::add_i64),
-
-                AnyField::F32(s) => decode_field(s, r, i, c, f, o, FromRecord::add_f32),
-                AnyField::F64(s) => decode_field(s, r, i, c, f, o, FromRecord::add_f64),
-            }?;
-
-            state = match successor {
-                Left(state) => state,
-                Right(state) => break state,
-            };
-        },
-        Right(state) => state,
-    };
+        Ok(())
+    }
+
+    /// Read up to three bytes ahead, stopping early at the end of the input.
+    fn read_ahead(&mut self) -> Result<(), std::io::Error> {
+        while self.n < 3 {
+            let mut b = [0; 1];
+            match self.read_exact(&mut b) {
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                result => result?,
+            }
+
+            self.a[self.n] = b[0];
+            self.n += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Retain<'_> for ReaderSource<'_, R> {}
+
+impl<R: Read> ByteSource for ReaderSource<'_, R> {
+    type Error = Error;
+
+    // Take any bytes read ahead first, following the CRC.
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let k = self.n.min(buf.len());
+        buf[..k].copy_from_slice(&self.a[..k]);
+        self.a.copy_within(k.., 0);
+        self.n -= k;
+
+        self.read_exact(&mut buf[k..])?;
+
+        self.i += buf.len();
+        self.c = compute_crc(self.c, buf);
 
-    Ok(record_header)
+        Ok(buf.len())
+    }
 }
 
-/// Take an exact number of bytes from a reader, optionally advancing a counter
-/// and accumulating a CRC value.
-fn take<const N: usize>(
-    r: &mut impl Read,
-    ic: Option<(&mut usize, &mut u16)>,
-) -> Result<[u8; N], Error> {
-    let mut buf = [0; N];
-    r.read_exact(&mut buf)?;
+impl<R: Read> Source for ReaderSource<'_, R> {
+    type Definition<'s> = DefinitionView;
+
+    fn position(&self) -> usize {
+        self.i
+    }
 
-    if let Some((i, c)) = ic {
-        *i += N;
-        *c = compute_crc(*c, &buf);
+    fn begin(&mut self, end: usize, options: &DecodeOptions) -> Result<(), Error> {
+        // Bytes passed over can't be checked against the CRC.
+        self.skipping = self.seek.is_some() && !options.verify_crc;
+        self.limit = end;
+        Ok(())
     }
 
-    Ok(buf)
+    fn finish(&mut self, options: &DecodeOptions) -> Result<bool, Error> {
+        let calculated = self.c;
+
+        // Read the first byte alone, to distinguish a missing CRC from a
+        // partial one.
+        let mut found = [0; 2];
+        match self.read_exact(&mut found[..1]) {
+            Err(err) if options.allow_missing_crc && err.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(false);
+            }
+            result => result?,
+        }
+        self.read_exact(&mut found[1..])?;
+        let found = u16::from_le_bytes(found);
+
+        if options.verify_crc && found != calculated {
+            Err(Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at: Location::default(),
+            })?;
+        }
+
+        Ok(true)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<bool, Error> {
+        let Some(seek) = self.seek.filter(|_| self.skipping) else {
+            // Read and discard the bytes in bulk, following the CRC.
+            let mut buf = [0; 256];
+            let mut rest = n;
+            while rest > 0 {
+                let k = rest.min(buf.len());
+                self.fill(&mut buf[..k])?;
+                rest -= k;
+            }
+
+            return Ok(true);
+        };
+
+        // Pass over any bytes read ahead first.
+        let k = self.n.min(n);
+        self.a.copy_within(k.., 0);
+        self.n -= k;
+        let k = k + self.chunk.skip(n - k);
+
+        seek(self.r, (n - k) as i64)?;
+        self.read += n - k;
+        self.i += n;
+
+        Ok(true)
+    }
+
+    fn more(&mut self) -> Result<Next, Error> {
+        // Read ahead enough to recognise a trailing CRC followed by the end of
+        // the input.
+        self.read_ahead()?;
+
+        Ok(match self.a[..self.n] {
+            [] => Next::End { crc: false },
+            [a, b] if u16::from_le_bytes([a, b]) == self.c => {
+                self.n = 0;
+                Next::End { crc: true }
+            }
+            _ => Next::Record,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<[u8; 3]>, Error> {
+        self.read_ahead()?;
+        Ok((self.n == 3).then_some(self.a))
+    }
+
+    fn begin_definition(&mut self, d: &mut DefinitionView, header: [u8; 1]) -> Result<(), Error> {
+        d.begin(header);
+        Ok(())
+    }
+
+    fn fill_definition(&mut self, d: &mut DefinitionView, buf: &mut [u8]) -> Result<(), Error> {
+        self.fill(buf)?;
+        d.extend(buf);
+
+        Ok(())
+    }
+
+    // Parse the definition once, rather than for each of its data records.
+    fn end_definition(&mut self, d: &mut DefinitionView) {
+        d.end();
+    }
 }
@@ -0,0 +1,211 @@
+//! Decoders over asynchronous readers.
+//!
+//! _Requires Cargo feature `async`._
+
+use core::{
+    future::poll_fn,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use std::io::ErrorKind;
+
+use futures_core::Stream;
+use futures_io::AsyncRead;
+
+use super::{
+    DecodeOptions, FromRecords,
+    push::PushDecoder,
+    raw::Message,
+    reader::{Error, Queue},
+};
+
+extern crate std;
+
+/// Decode records from an asynchronous reader of a document, publishing to a
+/// receiver.
+///
+/// ```
+/// let mut records = Records::default();
+/// derailleur::avec::reader_async::decode(&mut upload, &mut records).await?;
+/// ```
+///
+/// Bytes are decoded as they arrive, without buffering the whole document, and
+/// are never read past its end.
+///
+/// _Requires Cargo feature `async`._
+pub async fn decode<E>(
+    r: &mut (impl AsyncRead + Unpin),
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<E>> {
+    decode_with(r, o, &DecodeOptions::default()).await
+}
+
+/// Decode records from an asynchronous reader of a document, publishing to a
+/// receiver, with options.
+///
+/// _Requires Cargo feature `async`._
+pub async fn decode_with<E>(
+    r: &mut (impl AsyncRead + Unpin),
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    let read = async |buf: &mut [u8]| poll_fn(|cx| Pin::new(&mut *r).poll_read(cx, buf)).await;
+    decode_from(read, o, options).await
+}
+
+/// Decode records from a Tokio reader of a document, publishing to a receiver.
+///
+/// ```
+/// let mut socket = tokio::net::TcpStream::connect(address).await?;
+///
+/// let mut records = Records::default();
+/// derailleur::avec::reader_async::decode_tokio_reader(&mut socket, &mut records).await?;
+/// ```
+///
+/// Bytes are decoded as for [`decode`].
+///
+/// _Requires Cargo feature `tokio`._
+#[cfg(feature = "tokio")]
+pub async fn decode_tokio_reader<E>(
+    r: &mut (impl tokio::io::AsyncRead + Unpin),
+    o: &mut impl FromRecords<E>,
+) -> Result<(), Error<E>> {
+    decode_tokio_reader_with(r, o, &DecodeOptions::default()).await
+}
+
+/// Decode records from a Tokio reader of a document, publishing to a receiver,
+/// with options.
+///
+/// _Requires Cargo feature `tokio`._
+#[cfg(feature = "tokio")]
+pub async fn decode_tokio_reader_with<E>(
+    r: &mut (impl tokio::io::AsyncRead + Unpin),
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    let read = async |buf: &mut [u8]| {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        poll_fn(|cx| Pin::new(&mut *r).poll_read(cx, &mut buf)).await?;
+        Ok(buf.filled().len())
+    };
+
+    decode_from(read, o, options).await
+}
+
+/// Decode records from a function reading into a buffer, publishing to a
+/// receiver.
+async fn decode_from<E>(
+    mut read: impl AsyncFnMut(&mut [u8]) -> std::io::Result<usize>,
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<(), Error<E>> {
+    let mut decoder = PushDecoder::with_options(*options);
+    let mut buf = [0; 256];
+
+    while !decoder.is_done() {
+        let n = decoder.wanted().min(buf.len());
+
+        match read(&mut buf[..n]).await {
+            Ok(0) if decoder.end_of_input() => break,
+            Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+            Ok(n) => _ = decoder.push(&buf[..n], o)?,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => Err(err)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// A stream of the records of a document from an asynchronous reader,
+/// decoding one record at a time.
+///
+/// ```
+/// let mut stream = MessageStream::new(upload);
+///
+/// while let Some(message) = stream.next().await {
+///     let message = message?;
+///     println!("{}: {:?}", message.global(), message.fields());
+/// }
+/// ```
+///
+/// Bytes are read only as records are polled for, so a slow consumer applies
+/// backpressure to the reader. Compressed timestamps are expanded into full
+/// timestamps, as for [`raw::decode`](super::raw::decode). Bytes are never read
+/// past the end of the document, and after an error, the stream ends.
+///
+/// _Requires Cargo feature `async`._
+pub struct MessageStream<R> {
+    r: R,
+    decoder: PushDecoder,
+    queue: Queue,
+    failed: bool,
+}
+
+impl<R: AsyncRead + Unpin> MessageStream<R> {
+    /// Create a stream of the records of a document from a reader.
+    pub fn new(r: R) -> Self {
+        Self::with_options(
+            r,
+            DecodeOptions {
+                synthesize_timestamps: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create a stream of the records of a document from a reader, with
+    /// options.
+    pub fn with_options(r: R, options: DecodeOptions) -> Self {
+        Self {
+            r,
+            decoder: PushDecoder::with_options(options),
+            queue: Queue::default(),
+            failed: false,
+        }
+    }
+
+    /// Unwrap the reader, positioned after the bytes decoded so far.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Read and decode bytes up to the end of the next unit.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let mut buf = [0; 256];
+        let n = self.decoder.wanted().min(buf.len());
+
+        match ready!(Pin::new(&mut self.r).poll_read(cx, &mut buf[..n])) {
+            Ok(0) if self.decoder.end_of_input() => {}
+            Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+            Ok(n) => _ = self.decoder.push(&buf[..n], &mut self.queue)?,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => Err(err)?,
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MessageStream<R> {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(message) = this.queue.0.pop_front() {
+                return Poll::Ready(Some(Ok(message)));
+            }
+
+            if this.failed || this.decoder.is_done() {
+                return Poll::Ready(None);
+            }
+
+            if let Err(err) = ready!(this.poll_fill(cx)) {
+                this.failed = true;
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Recovery of truncated and damaged documents.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::convert::Infallible;
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use thiserror::Error;
+
+use crate::sans::{
+    Decoder,
+    check::compute_crc,
+    header::{DocumentHeaderError, RecordHeaderError},
+};
+
+use super::{
+    ByteSource, DecodeOptions, FromRecord, FromRecords, Location,
+    source::{self, Exhausted, Locate, MissingDefinition, Next, Retain, Source},
+};
+
+extern crate std;
+
+/// Errors occurring while repairing a document.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// An error from the supplied reader or writer.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Incorrect file header, which cannot be repaired.
+    #[error("Incorrect file header: {0}.")]
+    Header(#[from] DocumentHeaderError),
+}
+
+/// A report of the records salvaged by [`repair`].
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    /// Number of complete records salvaged.
+    pub records: usize,
+    /// Number of record bytes salvaged, as written to the new header.
+    pub data_size: u32,
+    /// Number of trailing bytes discarded, excluding a valid CRC.
+    pub discarded: usize,
+    /// Whether the document had a correct size and CRC.
+    pub intact: bool,
+}
+
+/// Salvage the records of a document from a reader, and write them to a writer
+/// with a corrected header and CRC.
+///
+/// Devices losing power while recording typically leave a document with an
+/// incorrect size in its header, a partial final record, or no trailing CRC.
+/// If the size and CRC of the document agree, records are taken up to that
+/// size. Otherwise, records are taken from the remainder of the input for as
+/// long as they remain complete and valid, and any bytes after the last valid
+/// record are discarded.
+///
+/// The original header is retained, other than its size and (for extended
+/// headers) its CRC. Records are copied byte-for-byte.
+///
+/// This method is also re-exported as `derailleur::avec::repair`.
+///
+/// _Requires Cargo feature `std`._
+pub fn repair(r: &mut impl Read, w: &mut impl Write) -> Result<Repair, Error> {
+    let mut d = Vec::new();
+    r.read_to_end(&mut d)?;
+
+    let header = d
+        .first_chunk()
+        .ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    let (size, _) = Decoder::advance(*header)?;
+
+    let start = header[0] as usize;
+    if d.len() < start {
+        Err(io::Error::from(io::ErrorKind::UnexpectedEof))?
+    }
+
+    // Trust the size in the header only if the CRC found there agrees.
+    let end = start + size as usize;
+    let intact = d
+        .get(end..end + 2)
+        .is_some_and(|found| compute_crc(0, &d[..end]) == u16::from_le_bytes([found[0], found[1]]));
+
+    let end = if intact { end } else { d.len() };
+
+    // Decode with a size spanning the records to be taken.
+    d[4..8].copy_from_slice(&((end - start) as u32).to_le_bytes());
+
+    let mut s = RepairSource {
+        r: &d,
+        i: 0,
+        j: 0,
+        k: start,
+        n: 0,
+    };
+
+    let (salvaged, records) = match source::decode(&mut s, &mut Discard, &DecodeOptions::default())
+    {
+        Ok(_) if s.i <= end => (s.i, s.n),
+        _ => (s.k, s.n.saturating_sub(1)),
+    };
+
+    let data_size = (salvaged - start) as u32;
+
+    d[4..8].copy_from_slice(&data_size.to_le_bytes());
+    if start >= 14 && data_size != size {
+        let crc = compute_crc(0, &d[..12]);
+        d[12..14].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    let crc = compute_crc(0, &d[..salvaged]);
+    w.write_all(&d[..salvaged])?;
+    w.write_all(&crc.to_le_bytes())?;
+
+    let discarded = d.len() - salvaged - if intact { 2 } else { 0 };
+    let intact = intact && size == data_size && salvaged == end;
+
+    Ok(Repair {
+        records,
+        data_size,
+        discarded,
+        intact,
+    })
+}
+
+/// Reached a record which is incomplete or invalid.
+struct Stop;
+
+impl From<DocumentHeaderError> for Stop {
+    fn from(_: DocumentHeaderError) -> Self {
+        Self
+    }
+}
+
+impl From<RecordHeaderError> for Stop {
+    fn from(_: RecordHeaderError) -> Self {
+        Self
+    }
+}
+
+impl From<MissingDefinition> for Stop {
+    fn from(_: MissingDefinition) -> Self {
+        Self
+    }
+}
+
+impl From<Exhausted> for Stop {
+    fn from(_: Exhausted) -> Self {
+        Self
+    }
+}
+
+impl Locate for Stop {
+    fn locate(self, _: Location) -> Self {
+        self
+    }
+}
+
+struct RepairSource<'a> {
+    r: &'a [u8],
+    i: usize, // Counter of bytes read, used to read bytes from the tip.
+    j: usize, // Offset to the start of the definition record being stored.
+    k: usize, // Offset to the start of the record being decoded.
+    n: usize, // Counter of records begun.
+}
+
+impl Retain<'_> for RepairSource<'_> {}
+
+impl ByteSource for RepairSource<'_> {
+    type Error = Stop;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Stop> {
+        let rest = &self.r[self.i.min(self.r.len())..];
+        let n = buf.len().min(rest.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.i += n;
+
+        Ok(n)
+    }
+}
+
+impl<'a> Source for RepairSource<'a> {
+    type Definition<'s> = &'a [u8];
+
+    fn position(&self) -> usize {
+        self.i
+    }
+
+    fn begin(&mut self, _: usize, _: &DecodeOptions) -> Result<(), Stop> {
+        Ok(())
+    }
+
+    fn finish(&mut self, _: &DecodeOptions) -> Result<bool, Stop> {
+        Ok(true)
+    }
+
+    fn more(&mut self) -> Result<Next, Stop> {
+        let rest = &self.r[self.i.min(self.r.len())..];
+
+        Ok(match rest {
+            [] => Next::End { crc: false },
+            [a, b] if u16::from_le_bytes([*a, *b]) == compute_crc(0, &self.r[..self.i]) => {
+                Next::End { crc: true }
+            }
+            _ => Next::Record,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<[u8; 3]>, Stop> {
+        let next = self.r.get(self.i..self.i + 3);
+        Ok(next.map(|b| b.try_into().unwrap()))
+    }
+
+    fn boundary(&mut self) {
+        self.k = self.i;
+        self.n += 1;
+    }
+
+    fn begin_definition(&mut self, _: &mut Self::Definition<'_>, _: [u8; 1]) -> Result<(), Stop> {
+        self.j = self.i - 1; // Include the record header.
+        Ok(())
+    }
+
+    fn fill_definition(
+        &mut self,
+        _: &mut Self::Definition<'_>,
+        buf: &mut [u8],
+    ) -> Result<(), Stop> {
+        self.fill(buf)
+    }
+
+    fn end_definition(&mut self, d: &mut Self::Definition<'_>) {
+        *d = &self.r[self.j..self.i];
+    }
+}
+
+/// A receiver ignoring all records.
+struct Discard;
+
+impl FromRecords for Discard {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,309 @@
+//! Random-access decoder implementation.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::{convert::Infallible, ops::RangeBounds};
+use std::{
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    vec::Vec,
+};
+
+use crate::sans::header::{
+    DocumentHeader, DocumentHeaderSuccessor, ExtendedHeaderSuccessor, RecordHeader,
+    RecordHeaderSuccessor,
+};
+
+use super::{
+    DecodeOptions, DefinitionView, FromRecord, FromRecords, Location,
+    developer::Registry,
+    reader::{Error, ReaderSource},
+    source::{self, Context, MissingDefinition, Source},
+};
+
+extern crate std;
+
+/// Decoder for random access to the records of a seekable document.
+///
+/// On first use, the document is decoded in full to build an [`Index`] of its
+/// data records, verifying the CRC. Records can then be decoded individually,
+/// by message number, or by timestamp, seeking directly to each. An index can
+/// be retained and supplied again with [`SeekableDecoder::with_index`].
+///
+/// _Requires Cargo feature `std`._
+pub struct SeekableDecoder<F> {
+    f: F,
+    index: Option<Index>,
+}
+
+/// Index of the data records in a document.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    pub(super) entries: Vec<Entry>,
+    pub(super) definitions: Vec<DefinitionView>,
+    pub(super) registry: Registry, // Developer fields described by the document.
+}
+
+/// An indexed data record.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub(super) offset: u64,
+    pub(super) global: u16,
+    pub(super) timestamp: Option<u32>,
+    pub(super) definition: usize,
+}
+
+impl Index {
+    /// The indexed data records, in document order.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+}
+
+impl Entry {
+    /// Offset of the record from the start of the document.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Global message number of the record.
+    pub fn global(&self) -> u16 {
+        self.global
+    }
+
+    /// Timestamp of the record, if present or expanded from a compressed
+    /// timestamp header.
+    pub fn timestamp(&self) -> Option<u32> {
+        self.timestamp
+    }
+}
+
+impl<F: Read + Seek> SeekableDecoder<F> {
+    /// Create a decoder for a seekable document, building an index on first
+    /// use.
+    pub fn new(f: F) -> Self {
+        Self { f, index: None }
+    }
+
+    /// Create a decoder for a seekable document with a previously built index.
+    ///
+    /// The index must have been built from the same document.
+    pub fn with_index(f: F, index: Index) -> Self {
+        Self {
+            f,
+            index: Some(index),
+        }
+    }
+
+    /// Retrieve the index, building it if necessary.
+    pub fn index(&mut self) -> Result<&Index, Error> {
+        if self.index.is_none() {
+            self.index = Some(build(&mut self.f)?);
+        }
+
+        Ok(self.index.as_ref().unwrap())
+    }
+
+    /// Release the document and the index, if built.
+    pub fn into_parts(self) -> (F, Option<Index>) {
+        (self.f, self.index)
+    }
+
+    /// Decode the `n`th data record, publishing to a receiver.
+    ///
+    /// Returns `false` if the document has no such record.
+    pub fn record_at(&mut self, n: usize, o: &mut impl FromRecords) -> Result<bool, Error> {
+        self.decode(o, |i, _| i == n).map(|count| count != 0)
+    }
+
+    /// Decode all data records of a global message number, publishing to a
+    /// receiver.
+    ///
+    /// Returns the number of records decoded.
+    pub fn messages_of(&mut self, global: u16, o: &mut impl FromRecords) -> Result<usize, Error> {
+        self.decode(o, |_, e| e.global == global)
+    }
+
+    /// Decode all data records with a timestamp in a range, publishing to a
+    /// receiver.
+    ///
+    /// Returns the number of records decoded.
+    pub fn records_between(
+        &mut self,
+        range: impl RangeBounds<u32>,
+        o: &mut impl FromRecords,
+    ) -> Result<usize, Error> {
+        self.decode(o, |_, e| e.timestamp.is_some_and(|t| range.contains(&t)))
+    }
+
+    /// Decode a selection of indexed data records, publishing to a receiver.
+    fn decode(
+        &mut self,
+        o: &mut impl FromRecords,
+        mut select: impl FnMut(usize, &Entry) -> bool,
+    ) -> Result<usize, Error> {
+        self.index()?;
+        let index = self.index.as_ref().unwrap();
+
+        let mut count = 0;
+
+        let x = &mut Context::default();
+        x.registry = index.registry.clone();
+
+        let entries = index.entries.iter().enumerate();
+
+        for (_, entry) in entries.filter(|(i, e)| select(*i, e)) {
+            self.f.seek(SeekFrom::Start(entry.offset))?;
+            decode_entry(&mut self.f, index, entry, x, o)?;
+            count += 1;
+        }
+
+        let Ok(()) = o.finish();
+
+        Ok(count)
+    }
+}
+
+/// Decode an indexed data record from a reader positioned at its offset,
+/// publishing to a receiver.
+pub(super) fn decode_entry(
+    r: &mut impl Read,
+    index: &Index,
+    entry: &Entry,
+    x: &mut Context,
+    o: &mut impl FromRecords,
+) -> Result<(), Error> {
+    let s = &mut ReaderSource {
+        i: entry.offset as usize,
+        ..ReaderSource::new(r, Default::default())
+    };
+
+    let (local, RecordHeaderSuccessor::Data(time, state)) =
+        s.step(RecordHeader::random_access())??
+    else {
+        Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "index does not match document",
+        ))?
+    };
+
+    x.location = Location {
+        local: Some(local),
+        ..Default::default()
+    };
+
+    let d = &index.definitions[entry.definition];
+    let Ok(_) = source::decode_data(state, time, s, d, x, o).map_err(x.locate(s.i))?;
+
+    Ok(())
+}
+
+/// Build an index by decoding a document in full.
+fn build(f: &mut (impl Read + Seek)) -> Result<Index, Error> {
+    f.seek(SeekFrom::Start(0))?;
+
+    let s = &mut ReaderSource::new(f, Default::default());
+
+    let (size, successor) = s.step(DocumentHeader)??;
+
+    let record_header = match successor {
+        DocumentHeaderSuccessor::Extended(state) => {
+            let mut successor = s.step(state)?;
+            loop {
+                successor = match successor {
+                    ExtendedHeaderSuccessor::MoreBytes(state) => s.step(state)?,
+                    ExtendedHeaderSuccessor::NextRecord(state) => break state,
+                };
+            }
+        }
+        DocumentHeaderSuccessor::NextRecord(state) => state,
+    };
+
+    let end = s.i + size as usize; // Offset to the end of the record section.
+
+    // Expand compressed timestamps, so these records can be found by time.
+    let x = &mut Context::new(DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    });
+
+    index(s, record_header, end, x).map_err(x.locate(s.i))
+}
+
+/// Index the records of a document, following its header.
+fn index(
+    s: &mut ReaderSource<impl Read>,
+    mut record_header: RecordHeader,
+    end: usize,
+    x: &mut Context,
+) -> Result<Index, Error> {
+    let mut index = Index::default();
+
+    // Indices of the definitions for each local message number.
+    let mut slots = [None; 16];
+
+    while s.i < end {
+        x.location = Location::default();
+
+        let offset = s.i as u64;
+        let header = s.take_array()?;
+        let (local, successor) = record_header.advance(header)?;
+        x.location.local = Some(local);
+
+        record_header = match successor {
+            RecordHeaderSuccessor::Definition(state) => {
+                let mut d = DefinitionView::default();
+                let state = source::decode_definition(state, header, s, &mut d)?;
+
+                slots[local as usize] = Some(index.definitions.len());
+                index.definitions.push(d);
+
+                state
+            }
+            RecordHeaderSuccessor::Data(time, state) => {
+                let definition = slots[local as usize].ok_or(MissingDefinition(local))?;
+                let d = &index.definitions[definition];
+
+                let mut o = Summary::default();
+                let Ok(state) = source::decode_data(state, time, s, d, x, &mut o)?;
+
+                index.entries.push(Entry {
+                    offset,
+                    global: o.global,
+                    timestamp: o.timestamp,
+                    definition,
+                });
+
+                state
+            }
+        };
+    }
+
+    x.location = Location::default();
+    s.finish(&x.options)?;
+
+    index.registry = x.registry.clone();
+
+    Ok(index)
+}
+
+/// Receiver noting the global message number and timestamp of a record.
+#[derive(Default)]
+pub(super) struct Summary {
+    pub(super) global: u16,
+    pub(super) timestamp: Option<u32>,
+}
+
+impl FromRecords for Summary {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.global = id;
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for Summary {
+    fn add_u32(&mut self, field: u8, value: u32) {
+        if field == 253 {
+            self.timestamp = Some(value);
+        }
+    }
+}
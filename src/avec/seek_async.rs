@@ -0,0 +1,227 @@
+//! Random-access decoder over asynchronous readers.
+//!
+//! _Requires Cargo feature `async`._
+
+use core::{future::poll_fn, ops::RangeBounds, pin::Pin};
+use std::{
+    io::{self, ErrorKind, SeekFrom},
+    vec,
+};
+
+use futures_io::{AsyncRead, AsyncSeek};
+
+use super::{
+    DecodeOptions, FromRecords,
+    push::PushDecoder,
+    reader::Error,
+    seek::{self, Entry, Index, Summary},
+    source::{Context, MissingDefinition},
+};
+
+extern crate std;
+
+/// Decoder for random access to the records of a seekable document, from an
+/// asynchronous reader.
+///
+/// ```
+/// let mut decoder = SeekableDecoder::new(object);
+///
+/// let mut records = Records::default();
+/// decoder.records_between(start..end, &mut records).await?;
+/// ```
+///
+/// As for [`seek::SeekableDecoder`], the
+/// document is decoded in full on first use to build an [`Index`]. Records are
+/// then each read with a single seek and read of exactly their bytes, so a
+/// reader backed by ranged requests (as to an object store) fetches only the
+/// records selected. An index can be retained and supplied again with
+/// [`SeekableDecoder::with_index`], avoiding the first pass altogether.
+///
+/// _Requires Cargo feature `async`._
+pub struct SeekableDecoder<F> {
+    f: F,
+    index: Option<Index>,
+}
+
+impl<F: AsyncRead + AsyncSeek + Unpin> SeekableDecoder<F> {
+    /// Create a decoder for a seekable document, building an index on first
+    /// use.
+    pub fn new(f: F) -> Self {
+        Self { f, index: None }
+    }
+
+    /// Create a decoder for a seekable document with a previously built index.
+    ///
+    /// The index must have been built from the same document.
+    pub fn with_index(f: F, index: Index) -> Self {
+        Self {
+            f,
+            index: Some(index),
+        }
+    }
+
+    /// Retrieve the index, building it if necessary.
+    pub async fn index(&mut self) -> Result<&Index, Error> {
+        if self.index.is_none() {
+            self.index = Some(build(&mut self.f).await?);
+        }
+
+        Ok(self.index.as_ref().unwrap())
+    }
+
+    /// Release the document and the index, if built.
+    pub fn into_parts(self) -> (F, Option<Index>) {
+        (self.f, self.index)
+    }
+
+    /// Decode the `n`th data record, publishing to a receiver.
+    ///
+    /// Returns `false` if the document has no such record.
+    pub async fn record_at(&mut self, n: usize, o: &mut impl FromRecords) -> Result<bool, Error> {
+        let count = self.decode(o, |i, _| i == n).await?;
+        Ok(count != 0)
+    }
+
+    /// Decode all data records of a global message number, publishing to a
+    /// receiver.
+    ///
+    /// Returns the number of records decoded.
+    pub async fn messages_of(
+        &mut self,
+        global: u16,
+        o: &mut impl FromRecords,
+    ) -> Result<usize, Error> {
+        self.decode(o, |_, e| e.global() == global).await
+    }
+
+    /// Decode all data records with a timestamp in a range, publishing to a
+    /// receiver.
+    ///
+    /// Returns the number of records decoded.
+    pub async fn records_between(
+        &mut self,
+        range: impl RangeBounds<u32>,
+        o: &mut impl FromRecords,
+    ) -> Result<usize, Error> {
+        let select = |_, e: &Entry| e.timestamp().is_some_and(|t| range.contains(&t));
+        self.decode(o, select).await
+    }
+
+    /// Decode a selection of indexed data records, publishing to a receiver.
+    async fn decode(
+        &mut self,
+        o: &mut impl FromRecords,
+        mut select: impl FnMut(usize, &Entry) -> bool,
+    ) -> Result<usize, Error> {
+        self.index().await?;
+        let index = self.index.as_ref().unwrap();
+
+        let mut count = 0;
+        let mut buf = vec![];
+
+        let x = &mut Context::default();
+        x.registry = index.registry.clone();
+
+        let entries = index.entries.iter().enumerate();
+
+        for (_, entry) in entries.filter(|(i, e)| select(*i, e)) {
+            // Read the whole record, with its header, before decoding it.
+            let size = 1 + index.definitions[entry.definition].data_size();
+            buf.resize(size, 0);
+
+            seek_to(&mut self.f, entry.offset).await?;
+            read_exact(&mut self.f, &mut buf).await?;
+
+            seek::decode_entry(&mut buf.as_slice(), index, entry, x, o)?;
+            count += 1;
+        }
+
+        let Ok(()) = o.finish();
+
+        Ok(count)
+    }
+}
+
+/// Build an index by decoding a document in full.
+async fn build(f: &mut (impl AsyncRead + AsyncSeek + Unpin)) -> Result<Index, Error> {
+    seek_to(f, 0).await?;
+
+    // Expand compressed timestamps, so these records can be found by time.
+    let mut decoder = PushDecoder::with_options(DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    });
+
+    let mut index = Index::default();
+    let mut o = Summary::default();
+    let mut buf = [0; 256];
+
+    // Indices of the definitions for each local message number.
+    let mut slots = [None; 16];
+    let mut offset = 0;
+
+    while !decoder.is_done() {
+        let record = decoder.record();
+
+        // Records begin where their header is pushed.
+        if record.is_none() {
+            offset = decoder.offset() as u64;
+        }
+
+        let n = decoder.wanted().min(buf.len());
+
+        match poll_fn(|cx| Pin::new(&mut *f).poll_read(cx, &mut buf[..n])).await {
+            Ok(0) if decoder.end_of_input() => break,
+            Ok(0) => Err(io::Error::from(ErrorKind::UnexpectedEof))?,
+            Ok(n) => _ = decoder.push(&buf[..n], &mut o)?,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => Err(err)?,
+        }
+
+        // Note each record once all of its bytes are decoded.
+        match record {
+            Some(_) if decoder.record().is_some() => {}
+            Some((local, true)) => {
+                slots[local as usize] = Some(index.definitions.len());
+                index.definitions.push(decoder.definition(local).clone());
+            }
+            Some((local, false)) => {
+                let definition = slots[local as usize].ok_or(MissingDefinition(local))?;
+
+                index.entries.push(Entry {
+                    offset,
+                    global: o.global,
+                    timestamp: o.timestamp,
+                    definition,
+                });
+
+                o = Summary::default();
+            }
+            None => {}
+        }
+    }
+
+    index.registry = decoder.registry().clone();
+
+    Ok(index)
+}
+
+/// Seek to an offset from the start of a document.
+async fn seek_to(f: &mut (impl AsyncSeek + Unpin), offset: u64) -> io::Result<()> {
+    poll_fn(|cx| Pin::new(&mut *f).poll_seek(cx, SeekFrom::Start(offset))).await?;
+    Ok(())
+}
+
+/// Read exactly enough bytes to fill a buffer.
+async fn read_exact(f: &mut (impl AsyncRead + Unpin), mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match poll_fn(|cx| Pin::new(&mut *f).poll_read(cx, buf)).await {
+            Ok(0) => Err(io::Error::from(ErrorKind::UnexpectedEof))?,
+            Ok(n) => buf = &mut buf[n..],
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => Err(err)?,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1086 @@
+//! Encoding core shared by the slice- and writer-based encoders.
+
+use thiserror::Error;
+
+use crate::sans::{
+    Encoder,
+    check::compute_crc,
+    data::{BaseType, FieldInner, U8Z},
+    encode::{
+        AnyField, DataSuccessor, DefinitionSuccessor, Developer, DeveloperDataSuccessor,
+        DeveloperDefinition, DeveloperDefinitionSuccessor, DeveloperFieldSuccessor, Field,
+        FieldKind, FieldSuccessor, RecordHeader, Standard,
+    },
+};
+
+use super::{FieldInfo, IntoRecord, IntoRecords, Value};
+
+#[cfg(feature = "std")]
+use std::{collections::HashMap, vec, vec::Vec};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// A destination for document bytes.
+pub(crate) trait Sink {
+    /// Errors occurring while encoding to this sink.
+    type Error: From<RecordError>;
+
+    /// Put bytes at the tip of the sink.
+    fn put(&mut self, w: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// An error representing records as a document.
+#[derive(Debug, Error)]
+pub enum RecordError {
+    /// A record published more fields than a definition can hold.
+    #[error("Record ({0}) has more than 255 fields.")]
+    FieldCount(u16),
+    /// A field value was empty, or too long to be stored.
+    #[error("Field ({field}) of record ({global}) is empty or exceeds 255 bytes.")]
+    FieldSize { global: u16, field: u8 },
+    /// The values of a field don't share a base type, or don't fit the size
+    /// and base type declared for it.
+    #[error("Field ({field}) of record ({global}) has values not fitting its base type or size.")]
+    FieldType { global: u16, field: u8 },
+    /// A record published values of a field apart from each other.
+    #[error("Field ({field}) of record ({global}) is published more than once.")]
+    RepeatedField { global: u16, field: u8 },
+    /// A definition record exceeded its share of the storage given for
+    /// definitions.
+    #[error("Definition of record ({0}) exceeds its storage.")]
+    DefinitionStorage(u16),
+    /// A record published different fields when visited again.
+    #[error("Record ({0}) published different fields when visited again.")]
+    Inconsistent(u16),
+    /// The records exceed the size of a document.
+    #[error("Records exceed the maximum size of a document.")]
+    DocumentSize,
+}
+
+/// Encode records to a sink, as a whole document, storing definitions in the
+/// given storage.
+///
+/// The records are visited twice: once to plan their definitions and find the
+/// size of the document, and once to encode them.
+#[cfg(feature = "std")]
+pub(crate) fn encode<S: Sink>(
+    w: &mut S,
+    i: &(impl IntoRecords + ?Sized),
+    storage: &mut [u8],
+) -> Result<(), S::Error> {
+    let (plan, data_size) = plan(i, &mut Definition::shares(storage).1)?;
+    let mut plan = plan.into_iter();
+
+    encode_sized(w, i, data_size, |_, _| plan.next(), storage)
+}
+
+/// Encode records to a sink, as a whole document, storing definitions in the
+/// given storage.
+///
+/// The records are visited twice: once to find the size of the document, and
+/// once to encode them.
+#[cfg(not(feature = "std"))]
+pub(crate) fn encode<S: Sink>(
+    w: &mut S,
+    i: &(impl IntoRecords + ?Sized),
+    storage: &mut [u8],
+) -> Result<(), S::Error> {
+    let mut counter = Counter(0);
+    let (_, header) = Encoder::advance(0);
+    encode_records(&mut counter, header, i, least_recently_used, storage)?;
+    let data_size = u32::try_from(counter.0).map_err(|_| RecordError::DocumentSize)?;
+
+    encode_sized(w, i, data_size, least_recently_used, storage)
+}
+
+/// Encode records to a sink, as a whole document of a known size.
+fn encode_sized<S: Sink>(
+    w: &mut S,
+    i: &(impl IntoRecords + ?Sized),
+    data_size: u32,
+    assign: impl FnMut(&[Definition; SLOTS], &Definition) -> Option<Slot>,
+    storage: &mut [u8],
+) -> Result<(), S::Error> {
+    let mut w = Checked { w, c: 0, n: 0 };
+
+    let (bytes, header) = Encoder::advance(data_size);
+    w.put(&bytes)?;
+
+    encode_records(&mut w, header, i, assign, storage)?;
+
+    let Checked { w, c, .. } = w;
+    w.put(&c.to_le_bytes())
+}
+
+/// Encode records to a sink, as a whole document, visiting the records once
+/// and storing definitions in the given storage.
+///
+/// The document header is first written with a placeholder size. Once the
+/// records are encoded, the actual header is passed to `patch`, which must
+/// write it in place, before the trailing CRC is written.
+#[cfg(feature = "std")]
+pub(crate) fn encode_deferred<S: Sink>(
+    w: &mut S,
+    i: &(impl IntoRecords + ?Sized),
+    storage: &mut [u8],
+    patch: impl FnOnce(&mut S, &[u8; 12]) -> Result<(), S::Error>,
+) -> Result<(), S::Error> {
+    let (bytes, header) = Encoder::advance(0);
+    w.put(&bytes)?;
+
+    let mut w = Checked { w, c: 0, n: 0 };
+    encode_records(&mut w, header, i, least_recently_used, storage)?;
+
+    let Checked { w, c, n } = w;
+    let data_size = u32::try_from(n).map_err(|_| RecordError::DocumentSize)?;
+
+    let (bytes, _) = Encoder::advance(data_size);
+    patch(w, &bytes)?;
+
+    let c = combine_crc(compute_crc(0, &bytes), c, n);
+    w.put(&c.to_le_bytes())
+}
+
+/// Combine the CRC of a prefix with that of a number of bytes following it,
+/// computed separately from zero.
+///
+/// The CRC is linear, so the prefix contributes as if followed by zeros.
+#[cfg(feature = "std")]
+fn combine_crc(prefix: u16, c: u16, len: usize) -> u16 {
+    const ZEROS: [u8; 64] = [0; 64];
+
+    let mut x = prefix;
+    let mut n = len;
+
+    while n != 0 {
+        let k = n.min(ZEROS.len());
+        x = compute_crc(x, &ZEROS[..k]);
+        n -= k;
+    }
+
+    x ^ c
+}
+
+/// Maximum size of the standard or developer fields of a definition record.
+const FIELDS: usize = 255 * 3;
+
+/// Maximum size of a definition record.
+const DEFINITION: usize = 6 + FIELDS + 1 + FIELDS;
+
+/// Number of local message numbers, each holding a definition.
+const SLOTS: usize = 16;
+
+/// Size of the storage holding any definitions, for each local message number
+/// and the record being encoded.
+pub(crate) const STORAGE: usize = (SLOTS + 1) * DEFINITION;
+
+/// A definition record, as encoded, within a share of the storage given for
+/// definitions.
+#[derive(Default)]
+struct Definition<'s> {
+    bytes: &'s mut [u8],
+    len: usize,       // Zero for an empty slot.
+    developer: usize, // End of the standard fields, and start of any others.
+    size: usize,      // Size of the data records described.
+    global: u16,      // Global message number, also encoded in the message.
+    used: usize,      // Tick of the last record using this definition.
+}
+
+impl Definition<'_> {
+    /// Divide storage evenly among the local message numbers, and the
+    /// definition of the record being encoded.
+    fn shares(storage: &mut [u8]) -> ([Definition<'_>; SLOTS], Definition<'_>) {
+        let mut shares = storage.chunks_exact_mut((storage.len() / (SLOTS + 1)).max(1));
+        let mut share = || Definition {
+            bytes: shares.next().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let candidate = share();
+        (core::array::from_fn(|_| share()), candidate)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Whether two definitions have the same message and fields, regardless of
+    /// local message number.
+    fn matches(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self.developer == other.developer
+            && self.global == other.global
+            && self.bytes[6..self.len] == other.bytes[6..other.len]
+    }
+}
+
+/// Where a record is defined, as a local message number.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    /// Reuse the definition kept under a local message number.
+    Reuse(u8),
+    /// Define the record under a local message number, replacing any
+    /// definition kept there.
+    Define(u8),
+}
+
+/// Encode each record, defining them as needed.
+///
+/// Definitions are kept under a local message number, given for each record
+/// by `assign` from the definitions kept and that of the record.
+fn encode_records<S: Sink>(
+    w: &mut S,
+    header: RecordHeader,
+    i: &(impl IntoRecords + ?Sized),
+    mut assign: impl FnMut(&[Definition; SLOTS], &Definition) -> Option<Slot>,
+    storage: &mut [u8],
+) -> Result<(), S::Error> {
+    let mut header = Some(header);
+    let mut result = Ok(());
+
+    let (mut slots, mut candidate) = Definition::shares(storage);
+    let mut tick = 0;
+
+    i.records(&mut |global, r| {
+        let Some(mut state) = header.take() else {
+            return;
+        };
+
+        tick += 1;
+
+        let encoded = collect(&mut candidate, global, r).and_then(|()| {
+            let local = match assign(&slots, &candidate) {
+                Some(Slot::Reuse(local)) if slots[local as usize].matches(&candidate) => local,
+                Some(Slot::Define(local)) => {
+                    let d = &mut slots[local as usize];
+                    core::mem::swap(d, &mut candidate);
+                    state = define(w, state, local, d)?;
+                    local
+                }
+                _ => Err(RecordError::Inconsistent(global))?,
+            };
+
+            let d = &mut slots[local as usize];
+            d.used = tick;
+            encode_record(w, state, local, d, r)
+        });
+
+        match encoded {
+            Ok(state) => header = Some(state),
+            Err(err) => result = Err(err),
+        }
+    });
+
+    result
+}
+
+/// Reuse a definition with identical fields, or else replace the least
+/// recently used.
+fn least_recently_used(slots: &[Definition; SLOTS], candidate: &Definition) -> Option<Slot> {
+    if let Some(local) = slots.iter().position(|d| d.matches(candidate)) {
+        return Some(Slot::Reuse(local as u8));
+    }
+
+    // Free slots are never used, so are taken first.
+    let (local, _) = slots.iter().enumerate().min_by_key(|(_, d)| d.used)?;
+    Some(Slot::Define(local as u8))
+}
+
+/// Plan where each record is defined, knowing every record up front, and find
+/// the size of the document.
+///
+/// Definitions with identical fields are coalesced under one local message
+/// number, which is kept until another definition is needed. The definition
+/// replaced is then the one next used furthest ahead, so that as few
+/// definition records as possible are encoded.
+#[cfg(feature = "std")]
+fn plan(
+    i: &(impl IntoRecords + ?Sized),
+    candidate: &mut Definition,
+) -> Result<(Vec<Slot>, u32), RecordError> {
+    let mut result = Ok(());
+
+    // Index of each distinct definition, and its size.
+    let mut shapes = HashMap::new();
+    let mut sizes = Vec::new();
+
+    // Definition of each record, and the size of the records.
+    let mut sequence = Vec::new();
+    let mut size = 0;
+
+    i.records(&mut |global, r| {
+        if result.is_err() {
+            return;
+        }
+
+        if let Err(err) = collect::<RecordError>(candidate, global, r) {
+            return result = Err(err);
+        }
+
+        let fields = &candidate.bytes[6..candidate.len];
+        let next = shapes.len();
+        let shape = *shapes.entry((global, fields.to_vec())).or_insert(next);
+        if shape == next {
+            sizes.push(candidate.len);
+        }
+
+        sequence.push(shape);
+        size += 1 + candidate.size;
+    });
+
+    result?;
+
+    // Index of the next record with the same definition as each record.
+    let mut next_use = vec![usize::MAX; sequence.len()];
+    let mut last = vec![usize::MAX; sizes.len()];
+    for (t, &shape) in sequence.iter().enumerate().rev() {
+        next_use[t] = last[shape];
+        last[shape] = t;
+    }
+
+    // Definition kept under each local message number, and its next use.
+    let mut slots: [Option<(usize, usize)>; SLOTS] = [None; SLOTS];
+    let mut plan = Vec::with_capacity(sequence.len());
+
+    for (t, &shape) in sequence.iter().enumerate() {
+        let kept = slots
+            .iter()
+            .position(|s| s.is_some_and(|(s, _)| s == shape));
+
+        let slot = match kept {
+            Some(local) => Slot::Reuse(local as u8),
+            None => {
+                // Take a free slot, or else replace the definition next used
+                // furthest ahead.
+                let free = slots.iter().position(Option::is_none);
+                let local = free.unwrap_or_else(|| {
+                    (0..SLOTS)
+                        .max_by_key(|&local| slots[local].map(|(_, next)| next))
+                        .unwrap()
+                });
+
+                size += sizes[shape];
+                Slot::Define(local as u8)
+            }
+        };
+
+        let (Slot::Reuse(local) | Slot::Define(local)) = slot;
+        slots[local as usize] = Some((shape, next_use[t]));
+        plan.push(slot);
+    }
+
+    let size = u32::try_from(size).map_err(|_| RecordError::DocumentSize)?;
+
+    Ok((plan, size))
+}
+
+/// Collect the fields of a record into a definition.
+///
+/// Consecutive values of a field are collected into an array.
+fn collect<E: From<RecordError>>(
+    d: &mut Definition,
+    global: u16,
+    r: &dyn IntoRecord,
+) -> Result<(), E> {
+    let mut result = Ok(());
+    let mut open = None;
+    d.len = 6;
+    d.size = 0;
+    d.global = global;
+
+    if d.bytes.len() < d.len {
+        Err(RecordError::DefinitionStorage(global))?
+    }
+
+    r.fields(&mut |f, v| {
+        if result.is_err() {
+            return;
+        }
+
+        result = add_value(d, &mut open, [f, 0], false, v, || r.field_info(f));
+    });
+
+    if let Some(field) = open.take()
+        && result.is_ok()
+    {
+        result = field.close(d);
+    }
+
+    // Developer fields follow their count, if the record has any.
+    d.developer = d.len;
+
+    r.developer_fields(&mut |f, developer, v| {
+        if result.is_err() {
+            return;
+        }
+
+        let declare = || r.developer_field_info(f, developer);
+        result = add_value(d, &mut open, [f, developer], true, v, declare);
+    });
+
+    if let Some(field) = open.take()
+        && result.is_ok()
+    {
+        result = field.close(d);
+    }
+
+    if d.len != d.developer {
+        d.bytes[d.developer] = ((d.len - d.developer - 1) / 3) as u8;
+    }
+
+    Ok(result?)
+}
+
+/// A field of a definition, whose values are being collected.
+struct Open {
+    key: [u8; 2],         // Field number, and any developer data index.
+    developer: bool,      // Whether this is a developer field.
+    base_type: BaseType,  // Base type declared, or of the first value.
+    declared: Option<u8>, // Size declared for the field.
+    size: usize,          // Size of the values collected.
+}
+
+impl Open {
+    /// Add the field to the definition, once all of its values are collected.
+    fn close(self, d: &mut Definition) -> Result<(), RecordError> {
+        let (global, field) = (d.global, self.key[0]);
+
+        let size = match self.declared {
+            Some(size) if self.size == size as usize => size,
+            // Strings are padded to the size declared for them.
+            Some(size) if self.base_type == BaseType::String && self.size <= size as usize => size,
+            Some(_) => Err(RecordError::FieldType { global, field })?,
+            None => {
+                u8::try_from(self.size).map_err(|_| RecordError::FieldSize { global, field })?
+            }
+        };
+
+        let (limit, entry) = match self.developer {
+            true => (d.developer + 1 + FIELDS, [field, size, self.key[1]]),
+            false => (6 + FIELDS, [field, size, self.base_type.byte()]),
+        };
+
+        if self.developer && d.len == d.developer {
+            d.len += 1;
+        }
+
+        if d.len == limit {
+            Err(RecordError::FieldCount(global))?
+        }
+
+        if d.len + 3 > d.bytes.len() {
+            Err(RecordError::DefinitionStorage(global))?
+        }
+
+        d.bytes[d.len..d.len + 3].copy_from_slice(&entry);
+        d.len += 3;
+        d.size += size as usize;
+
+        Ok(())
+    }
+}
+
+/// Add a value of a standard or developer field to a definition, extending
+/// the open field if the value continues it.
+///
+/// Developer fields are keyed by their developer data index, as well as their
+/// field number.
+fn add_value(
+    d: &mut Definition,
+    open: &mut Option<Open>,
+    key: [u8; 2],
+    developer: bool,
+    v: Value,
+    declare: impl FnOnce() -> Option<FieldInfo>,
+) -> Result<(), RecordError> {
+    let (global, field) = (d.global, key[0]);
+
+    let Some((size, derived)) = layout(v) else {
+        Err(RecordError::FieldSize { global, field })?
+    };
+
+    if let Some(o) = open
+        && o.key == key
+    {
+        if !fits(o.base_type, v) {
+            Err(RecordError::FieldType { global, field })?
+        }
+
+        o.size += size as usize;
+        return Ok(());
+    }
+
+    if let Some(o) = open.take() {
+        o.close(d)?;
+    }
+
+    // Fields are defined once, so their values must be published together.
+    let start = match developer {
+        true => (d.developer + 1).min(d.len),
+        false => 6,
+    };
+    let repeated = d.bytes[start..d.len]
+        .as_chunks()
+        .0
+        .iter()
+        .any(|&[f, _, i]| f == field && (!developer || i == key[1]));
+
+    if repeated {
+        Err(RecordError::RepeatedField { global, field })?
+    }
+
+    let (base_type, declared) = match declare() {
+        Some(info) => match BaseType::from_byte(info.base_type) {
+            Some(base_type) => (base_type, Some(info.size)),
+            None => Err(RecordError::FieldType { global, field })?,
+        },
+        None => (derived, None),
+    };
+
+    if !fits(base_type, v) {
+        Err(RecordError::FieldType { global, field })?
+    }
+
+    *open = Some(Open {
+        key,
+        developer,
+        base_type,
+        declared,
+        size: size as usize,
+    });
+
+    Ok(())
+}
+/// Whether a single value fills a field of a declared size and base type.
+#[cfg(feature = "std")]
+pub(super) fn fills(info: FieldInfo, v: Value) -> bool {
+    let (Some((size, _)), Some(base_type)) = (layout(v), BaseType::from_byte(info.base_type))
+    else {
+        return false;
+    };
+
+    // Strings are padded to the size declared for them.
+    let size = match base_type {
+        BaseType::String => size <= info.size,
+        _ => size == info.size,
+    };
+
+    size && fits(base_type, v)
+}
+
+/// Encode a definition record, under a local message number.
+fn define<S: Sink>(
+    w: &mut S,
+    header: RecordHeader,
+    local: u8,
+    d: &mut Definition,
+) -> Result<RecordHeader, S::Error> {
+    let header = match d.len != d.developer {
+        true => header.developer_definition(local),
+        false => header.definition(local),
+    };
+    let Ok(([h], state)) = header else {
+        unreachable!()
+    };
+
+    let fields = ((d.developer - 6) / 3) as u8;
+    let (message, mut state) = state.advance(d.global, fields);
+
+    d.bytes[0] = h;
+    d.bytes[1..6].copy_from_slice(&message);
+
+    for &[field, size, base_type] in d.bytes[6..d.developer].as_chunks().0 {
+        let DefinitionSuccessor::NextField(s) = state else {
+            unreachable!()
+        };
+
+        let (_, successor) =
+            s.advance(field, size, base_type)
+                .map_err(|_| RecordError::FieldSize {
+                    global: d.global,
+                    field,
+                })?;
+
+        state = successor;
+    }
+
+    let header = match state {
+        DefinitionSuccessor::NextRecord(header) => header,
+        DefinitionSuccessor::Developer(state) => define_developer(state, d)?,
+        DefinitionSuccessor::NextField(_) => unreachable!(),
+    };
+
+    w.put(d.as_bytes())?;
+
+    Ok(header)
+}
+
+/// Encode the developer fields of a definition record, following its
+/// standard fields.
+fn define_developer(
+    state: DeveloperDefinition,
+    d: &Definition,
+) -> Result<RecordHeader, RecordError> {
+    let [count, fields @ ..] = &d.bytes[d.developer..d.len] else {
+        unreachable!()
+    };
+
+    let (_, mut state) = state.advance(*count);
+
+    for &[field, size, developer] in fields.as_chunks().0 {
+        let DeveloperDefinitionSuccessor::NextField(s) = state else {
+            unreachable!()
+        };
+
+        let (_, successor) =
+            s.advance(field, size, developer)
+                .map_err(|_| RecordError::FieldSize {
+                    global: d.global,
+                    field,
+                })?;
+
+        state = successor;
+    }
+
+    let DeveloperDefinitionSuccessor::NextRecord(header) = state else {
+        unreachable!()
+    };
+
+    Ok(header)
+}
+
+/// Size and base type of a field value.
+fn layout(v: Value) -> Option<(u8, BaseType)> {
+    Some(match v {
+        Value::U8(_) => (1, BaseType::Uint8),
+        Value::U16(_) => (2, BaseType::Uint16),
+        Value::U32(_) => (4, BaseType::Uint32),
+        Value::U64(_) => (8, BaseType::Uint64),
+        Value::I8(_) => (1, BaseType::Sint8),
+        Value::I16(_) => (2, BaseType::Sint16),
+        Value::I32(_) => (4, BaseType::Sint32),
+        Value::I64(_) => (8, BaseType::Sint64),
+        Value::F32(_) => (4, BaseType::Float32),
+        Value::F64(_) => (8, BaseType::Float64),
+        Value::String(s) => (u8::try_from(s.len() + 1).ok()?, BaseType::String),
+        Value::Bytes(b) => (
+            u8::try_from(b.len()).ok().filter(|&n| n != 0)?,
+            BaseType::Byte,
+        ),
+        Value::Invalid(b) => {
+            let base_type = BaseType::from_byte(b)?;
+            (base_type.size(), base_type)
+        }
+    })
+}
+
+/// Whether a value can be encoded in a field of a base type.
+///
+/// Bytes fit any base type represented by `u8`, and strings only `string`.
+fn fits(base_type: BaseType, v: Value) -> bool {
+    use BaseType::*;
+
+    match v {
+        Value::U8(_) | Value::Bytes(_) => {
+            matches!(base_type, Enum | Uint8 | Uint8z | Byte | String)
+        }
+        Value::U16(_) => matches!(base_type, Uint16 | Uint16z),
+        Value::U32(_) => matches!(base_type, Uint32 | Uint32z),
+        Value::U64(_) => matches!(base_type, Uint64 | Uint64z),
+        Value::I8(_) => base_type == Sint8,
+        Value::I16(_) => base_type == Sint16,
+        Value::I32(_) => base_type == Sint32,
+        Value::I64(_) => base_type == Sint64,
+        Value::F32(_) => base_type == Float32,
+        Value::F64(_) => base_type == Float64,
+        Value::String(_) => base_type == String,
+        Value::Invalid(b) => b == base_type.byte(),
+    }
+}
+
+/// Encode the fields of a data record, following its definition.
+fn encode_record<S: Sink>(
+    w: &mut S,
+    header: RecordHeader,
+    local: u8,
+    d: &Definition,
+    r: &dyn IntoRecord,
+) -> Result<RecordHeader, S::Error> {
+    let global = d.global;
+
+    let Ok((h, state)) = header.data(local) else {
+        unreachable!()
+    };
+    w.put(&h)?;
+
+    let d = d.as_bytes();
+    let (_, state) = state.advance(d[..6].try_into().unwrap());
+
+    let mut d = &d[6..];
+    let mut cursor = Some(Cursor::Between(state));
+    let mut result = Ok(());
+
+    r.fields(&mut |f, v| {
+        if result.is_err() {
+            return;
+        }
+
+        let Some(c) = cursor.take() else {
+            return result = Err(RecordError::Inconsistent(global).into());
+        };
+
+        match encode_field(w, c, &mut d, f, v) {
+            Ok(Some(c)) => cursor = Some(c),
+            Ok(None) => result = Err(RecordError::Inconsistent(global).into()),
+            Err(err) => result = Err(err),
+        }
+    });
+
+    result?;
+
+    let state = match (close(w, cursor)?, d.split_first_chunk()) {
+        (Some(DataSuccessor::NextRecord(header)), None) => return Ok(header),
+        (Some(DataSuccessor::Developer(s)), Some((count, rest))) => {
+            d = rest;
+            s.advance(*count)
+        }
+        _ => Err(RecordError::Inconsistent(global))?,
+    };
+
+    let mut cursor = Some(Cursor::Between(state));
+    let mut result = Ok(());
+
+    r.developer_fields(&mut |f, developer, v| {
+        if result.is_err() {
+            return;
+        }
+
+        let Some(c) = cursor.take() else {
+            return result = Err(RecordError::Inconsistent(global).into());
+        };
+
+        let declare = || r.developer_field_info(f, developer);
+        match encode_developer_field(w, c, &mut d, [f, developer], v, declare) {
+            Ok(Some(c)) => cursor = Some(c),
+            Ok(None) => result = Err(RecordError::Inconsistent(global).into()),
+            Err(err) => result = Err(err),
+        }
+    });
+
+    result?;
+
+    match close(w, cursor)? {
+        Some(DeveloperDataSuccessor::NextRecord(header)) => Ok(header),
+        _ => Err(RecordError::Inconsistent(global))?,
+    }
+}
+
+/// Position within the standard or developer fields of a data record, while
+/// encoding their values.
+enum Cursor<K: Kind> {
+    /// Between fields, or past the last.
+    Between(K::Next),
+    /// Within a field, whose values remain to be encoded.
+    Within {
+        key: [u8; 2], // Field number, and any developer data index.
+        string: bool, // Whether the field is a `string`, padded once ended.
+        state: AnyField<K>,
+    },
+}
+
+impl<K: Kind> Cursor<K> {
+    /// The position following values put in a field.
+    fn new(progress: Progress<K>, key: [u8; 2], string: bool) -> Self {
+        match progress {
+            Ok(next) => Cursor::Between(next),
+            Err(state) => Cursor::Within { key, string, state },
+        }
+    }
+}
+
+/// The state following a field once values fill it, or else the field.
+type Progress<K> = Result<<K as Kind>::Next, AnyField<K>>;
+
+/// End the field being encoded, if any, padding strings with the 'invalid'
+/// marker value.
+///
+/// Returns the state following the field, or `None` if values of the field
+/// remain.
+fn close<S: Sink, K: Kind>(
+    w: &mut S,
+    cursor: Option<Cursor<K>>,
+) -> Result<Option<K::Next>, S::Error> {
+    match cursor {
+        Some(Cursor::Between(next)) => Ok(Some(next)),
+        Some(Cursor::Within {
+            string: true,
+            state: AnyField::U8Z(s),
+            ..
+        }) => pad(w, s).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Encode a value of a standard field, continuing the field being encoded, or
+/// else beginning the next.
+///
+/// Returns the position following the value, or `None` if the value does not
+/// match the definition.
+fn encode_field<S: Sink>(
+    w: &mut S,
+    cursor: Cursor<Standard>,
+    d: &mut &[u8],
+    f: u8,
+    v: Value,
+) -> Result<Option<Cursor<Standard>>, S::Error> {
+    let next = match cursor {
+        Cursor::Within { key, string, state } if key[0] == f => {
+            let progress = put_value(w, state, v)?;
+            return Ok(progress.map(|p| Cursor::new(p, key, string)));
+        }
+        cursor => close(w, Some(cursor))?,
+    };
+
+    let (Some(DataSuccessor::NextField(s)), Some((r, rest))) = (next, d.split_first_chunk()) else {
+        return Ok(None);
+    };
+
+    *d = rest;
+
+    let string = r[2] == BaseType::String.byte();
+    match s.advance(*r) {
+        Ok((field, state)) if field == f => {
+            let progress = put_value(w, state, v)?;
+            Ok(progress.map(|p| Cursor::new(p, [f, 0], string)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Encode a value of a developer field, as for [`encode_field`], given the
+/// field declared for it, if any.
+fn encode_developer_field<S: Sink>(
+    w: &mut S,
+    cursor: Cursor<Developer>,
+    d: &mut &[u8],
+    key: [u8; 2],
+    v: Value,
+    declare: impl FnOnce() -> Option<FieldInfo>,
+) -> Result<Option<Cursor<Developer>>, S::Error> {
+    let next = match cursor {
+        Cursor::Within {
+            key: k,
+            string,
+            state,
+        } if k == key => {
+            let progress = put_value(w, state, v)?;
+            return Ok(progress.map(|p| Cursor::new(p, key, string)));
+        }
+        cursor => close(w, Some(cursor))?,
+    };
+
+    let (Some(DeveloperDataSuccessor::NextField(s)), Some((r, rest))) =
+        (next, d.split_first_chunk())
+    else {
+        return Ok(None);
+    };
+
+    *d = rest;
+
+    // The base type is found as when collecting the definition.
+    let declared = declare().map(|info| info.base_type);
+    let Some(base_type) = declared.or_else(|| layout(v).map(|(_, b)| b.byte())) else {
+        return Ok(None);
+    };
+
+    let string = base_type == BaseType::String.byte();
+    match s.advance(*r, base_type) {
+        Ok((field, i, state)) if [field, i] == key => {
+            let progress = put_value(w, state, v)?;
+            Ok(progress.map(|p| Cursor::new(p, key, string)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Encode a value of a standard or developer field, if it matches the base
+/// type of the field and fits within it.
+///
+/// Returns the state following the field once it is filled, or else the
+/// field, or `None` if the value does not match.
+fn put_value<S: Sink, K: Kind>(
+    w: &mut S,
+    state: AnyField<K>,
+    v: Value,
+) -> Result<Option<Progress<K>>, S::Error> {
+    match (state, v) {
+        (AnyField::U8(s), Value::U8(v)) => put(w, s, [Some(v)], AnyField::U8),
+        (AnyField::U8Z(s), Value::U8(v)) => put(w, s, [Some(v)], AnyField::U8Z),
+        (AnyField::U16(s), Value::U16(v)) => put(w, s, [Some(v)], AnyField::U16),
+        (AnyField::U16Z(s), Value::U16(v)) => put(w, s, [Some(v)], AnyField::U16Z),
+        (AnyField::U32(s), Value::U32(v)) => put(w, s, [Some(v)], AnyField::U32),
+        (AnyField::U32Z(s), Value::U32(v)) => put(w, s, [Some(v)], AnyField::U32Z),
+        (AnyField::U64(s), Value::U64(v)) => put(w, s, [Some(v)], AnyField::U64),
+        (AnyField::U64Z(s), Value::U64(v)) => put(w, s, [Some(v)], AnyField::U64Z),
+        (AnyField::I8(s), Value::I8(v)) => put(w, s, [Some(v)], AnyField::I8),
+        (AnyField::I16(s), Value::I16(v)) => put(w, s, [Some(v)], AnyField::I16),
+        (AnyField::I32(s), Value::I32(v)) => put(w, s, [Some(v)], AnyField::I32),
+        (AnyField::I64(s), Value::I64(v)) => put(w, s, [Some(v)], AnyField::I64),
+        (AnyField::F32(s), Value::F32(v)) => put(w, s, [Some(v)], AnyField::F32),
+        (AnyField::F64(s), Value::F64(v)) => put(w, s, [Some(v)], AnyField::F64),
+
+        // Strings are terminated by the 'invalid' marker value.
+        (AnyField::U8Z(s), Value::String(v)) => {
+            put(w, s, v.bytes().map(Some).chain([None]), AnyField::U8Z)
+        }
+        (AnyField::U8(s), Value::Bytes(v)) => put(w, s, v.iter().copied().map(Some), AnyField::U8),
+        (AnyField::U8Z(s), Value::Bytes(v)) => {
+            put(w, s, v.iter().copied().map(Some), AnyField::U8Z)
+        }
+
+        (state, Value::Invalid(_)) => put_invalid(w, state),
+
+        _ => Ok(None),
+    }
+}
+
+/// Encode the 'invalid' marker value of a field, if it fits within it.
+fn put_invalid<S: Sink, K: Kind>(
+    w: &mut S,
+    state: AnyField<K>,
+) -> Result<Option<Progress<K>>, S::Error> {
+    match state {
+        AnyField::U8(s) => put(w, s, [None], AnyField::U8),
+        AnyField::U8Z(s) => put(w, s, [None], AnyField::U8Z),
+        AnyField::U16(s) => put(w, s, [None], AnyField::U16),
+        AnyField::U16Z(s) => put(w, s, [None], AnyField::U16Z),
+        AnyField::U32(s) => put(w, s, [None], AnyField::U32),
+        AnyField::U32Z(s) => put(w, s, [None], AnyField::U32Z),
+        AnyField::U64(s) => put(w, s, [None], AnyField::U64),
+        AnyField::U64Z(s) => put(w, s, [None], AnyField::U64Z),
+
+        AnyField::I8(s) => put(w, s, [None], AnyField::I8),
+        AnyField::I16(s) => put(w, s, [None], AnyField::I16),
+        AnyField::I32(s) => put(w, s, [None], AnyField::I32),
+        AnyField::I64(s) => put(w, s, [None], AnyField::I64),
+
+        AnyField::F32(s) => put(w, s, [None], AnyField::F32),
+        AnyField::F64(s) => put(w, s, [None], AnyField::F64),
+    }
+}
+
+/// Encode the values of a field, if they fit within it.
+///
+/// Returns the state following the field once it is filled, or else the
+/// field (as wrapped by `any`), or `None` if values remain once it is filled.
+fn put<T, K, S, const N: usize>(
+    w: &mut S,
+    mut state: Field<T, K>,
+    values: impl IntoIterator<Item = Option<T::Into>>,
+    any: fn(Field<T, K>) -> AnyField<K>,
+) -> Result<Option<Progress<K>>, S::Error>
+where
+    T: FieldInner<From = [u8; N]>,
+    K: Kind,
+    S: Sink,
+{
+    let mut values = values.into_iter();
+
+    while let Some(v) = values.next() {
+        let (bytes, successor) = state.advance(v);
+        w.put(&bytes)?;
+
+        state = match K::next(successor) {
+            Ok(next) => return Ok(values.next().is_none().then_some(Ok(next))),
+            Err(next) => next,
+        };
+    }
+
+    Ok(Some(Err(any(state))))
+}
+
+/// Fill the remainder of a `string` field with the 'invalid' marker value.
+fn pad<S: Sink, K: Kind>(w: &mut S, mut state: Field<U8Z, K>) -> Result<K::Next, S::Error> {
+    loop {
+        let (bytes, successor) = state.advance(None);
+        w.put(&bytes)?;
+
+        state = match K::next(successor) {
+            Ok(next) => return Ok(next),
+            Err(next) => next,
+        };
+    }
+}
+
+/// A kind of field, standard or developer, as followed while encoding a data
+/// record.
+trait Kind: FieldKind {
+    /// State following the last value of a field.
+    type Next;
+
+    /// The state following a field, or the field if values remain.
+    fn next<T>(successor: Self::Successor<T>) -> Result<Self::Next, Field<T, Self>>;
+}
+
+impl Kind for Standard {
+    type Next = DataSuccessor;
+
+    fn next<T>(successor: FieldSuccessor<T>) -> Result<DataSuccessor, Field<T>> {
+        match successor {
+            FieldSuccessor::NextField(state) => Ok(DataSuccessor::NextField(state)),
+            FieldSuccessor::Developer(state) => Ok(DataSuccessor::Developer(state)),
+            FieldSuccessor::NextRecord(header) => Ok(DataSuccessor::NextRecord(header)),
+            FieldSuccessor::MoreBytes(next) => Err(next),
+        }
+    }
+}
+
+impl Kind for Developer {
+    type Next = DeveloperDataSuccessor;
+
+    fn next<T>(
+        successor: DeveloperFieldSuccessor<T>,
+    ) -> Result<DeveloperDataSuccessor, Field<T, Developer>> {
+        match successor {
+            DeveloperFieldSuccessor::NextField(state) => {
+                Ok(DeveloperDataSuccessor::NextField(state))
+            }
+            DeveloperFieldSuccessor::NextRecord(header) => {
+                Ok(DeveloperDataSuccessor::NextRecord(header))
+            }
+            DeveloperFieldSuccessor::MoreBytes(next) => Err(next),
+        }
+    }
+}
+
+/// A sink counting the bytes put to it.
+#[cfg(not(feature = "std"))]
+struct Counter(usize);
+
+#[cfg(not(feature = "std"))]
+impl Sink for Counter {
+    type Error = RecordError;
+
+    fn put(&mut self, w: &[u8]) -> Result<(), RecordError> {
+        self.0 += w.len();
+        Ok(())
+    }
+}
+
+/// A sink computing the cyclic redundancy check of the bytes put to it.
+struct Checked<'a, S> {
+    w: &'a mut S,
+    c: u16,   // Cyclic redundancy check accumulator value.
+    n: usize, // Counter of bytes put.
+}
+
+impl<S: Sink> Sink for Checked<'_, S> {
+    type Error = S::Error;
+
+    fn put(&mut self, w: &[u8]) -> Result<(), S::Error> {
+        self.c = compute_crc(self.c, w);
+        self.n += w.len();
+        self.w.put(w)
+    }
+}
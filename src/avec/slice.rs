@@ -1,181 +1,776 @@
 //! Slice-based decoder implementation.
 
-use either::Either::{self, Left, Right};
+use core::{convert::Infallible, marker::PhantomData};
+
 use thiserror::Error;
 
 use crate::sans::{
     Decoder,
     check::compute_crc,
-    data::{AnyField, Field, FieldInner},
-    definition::{Definition, DefinitionAlt, DefinitionFieldAlt},
-    header::{DocumentHeaderError, RecordHeader, RecordHeaderError},
+    header::{
+        DocumentHeaderError, DocumentHeaderSuccessor, ExtendedHeaderSuccessor, RecordHeader,
+        RecordHeaderError, RecordHeaderSuccessor,
+    },
+};
+
+use super::{
+    ByteSource, DecodeOptions, DefinitionInfo, FieldInfo, FromRecord, FromRecords, FromRecordsRef,
+    FromRecordsStatic, HeaderInfo, IntoRecords, Location, RecordError, TimestampTracker,
+    sink::{self, Sink},
+    source::{
+        self, Borrowed, Context, Exhausted, Locate, MissingDefinition, Next, Receiver, Retain,
+        Source, Static, Stored,
+    },
+    timestamp::TIMESTAMP,
 };
 
-use super::{FromRecord, FromRecords};
+#[cfg(feature = "std")]
+use super::raw::Message;
+
+#[cfg(feature = "std")]
+extern crate std;
 
 /// Errors occurring while decoding from a slice.
 #[derive(Debug, Error)]
-pub enum Error {
+pub enum Error<E = Infallible> {
     /// Unexpectedly reached the end of the slice.
-    #[error("Unexpectedly reached the end of the slice.")]
-    EndOfSlice,
+    #[error("Unexpectedly reached the end of the slice, {at}.")]
+    EndOfSlice { at: Location },
     /// Calculated and found CRC values do not match.
-    #[error("Calculated ({calculated}) and found ({found}) CRC values do not match.")]
-    CyclicRedundancyCheck { found: u16, calculated: u16 },
+    #[error("Calculated ({calculated}) and found ({found}) CRC values do not match, {at}.")]
+    CyclicRedundancyCheck {
+        found: u16,
+        calculated: u16,
+        at: Location,
+    },
     /// Incorrect file header.
-    #[error("Incorrect file header: {0}.")]
-    Header(#[from] DocumentHeaderError),
-    /// Found unsupported developer data.
-    #[error("Found unsupported developer data.")]
-    Developer,
+    #[error("Incorrect file header: {source}, {at}.")]
+    Header {
+        source: DocumentHeaderError,
+        at: Location,
+    },
+    /// Found the developer data flag on a data record header.
+    #[error("Found the developer data flag on a data record header, {at}.")]
+    Developer { at: Location },
+    /// Found a data record for a local message number without a preceding
+    /// definition record.
+    #[error("Found a data record for local message {local} without a definition, {at}.")]
+    MissingDefinition { local: u8, at: Location },
+    /// The receiver failed, as returned from [`FromRecords::add_record`] or
+    /// [`FromRecords::finish`].
+    #[error("Receiver failed: {0}.")]
+    Receiver(E),
+}
+
+impl<E> Error<E> {
+    /// Position in the document at which the error occurred, or the default
+    /// for a failure of the receiver.
+    pub fn location(&self) -> Location {
+        match self {
+            Self::EndOfSlice { at, .. }
+            | Self::CyclicRedundancyCheck { at, .. }
+            | Self::Header { at, .. }
+            | Self::Developer { at }
+            | Self::MissingDefinition { at, .. } => *at,
+            Self::Receiver(_) => Location::default(),
+        }
+    }
+}
+
+impl Error {
+    /// Carry an error of the decoder into that of a fallible receiver.
+    fn widen<E>(self) -> Error<E> {
+        match self {
+            Self::EndOfSlice { at } => Error::EndOfSlice { at },
+            Self::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            } => Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            },
+            Self::Header { source, at } => Error::Header { source, at },
+            Self::Developer { at } => Error::Developer { at },
+            Self::MissingDefinition { local, at } => Error::MissingDefinition { local, at },
+            Self::Receiver(never) => match never {},
+        }
+    }
+}
+
+impl<E> Locate for Error<E> {
+    fn locate(mut self, location: Location) -> Self {
+        let (Self::EndOfSlice { at, .. }
+        | Self::CyclicRedundancyCheck { at, .. }
+        | Self::Header { at, .. }
+        | Self::Developer { at }
+        | Self::MissingDefinition { at, .. }) = &mut self
+        else {
+            return self;
+        };
+
+        // Retain a location given where the error was found.
+        if *at == Location::default() {
+            *at = location;
+        }
+
+        self
+    }
+}
+
+impl<E> From<DocumentHeaderError> for Error<E> {
+    fn from(source: DocumentHeaderError) -> Self {
+        Self::Header {
+            source,
+            at: Location::default(),
+        }
+    }
 }
 
-impl From<RecordHeaderError> for Error {
+impl<E> From<RecordHeaderError> for Error<E> {
     fn from(err: RecordHeaderError) -> Self {
         match err {
-            RecordHeaderError::DeveloperData => Self::Developer,
+            RecordHeaderError::DeveloperData => Self::Developer {
+                at: Location::default(),
+            },
+        }
+    }
+}
+
+impl<E> From<MissingDefinition> for Error<E> {
+    fn from(MissingDefinition(local): MissingDefinition) -> Self {
+        Self::MissingDefinition {
+            local,
+            at: Location::default(),
+        }
+    }
+}
+
+impl<E> From<Exhausted> for Error<E> {
+    fn from(_: Exhausted) -> Self {
+        Self::EndOfSlice {
+            at: Location::default(),
         }
     }
 }
 
 /// Decode records from a slice of a document, publishing to a receiver.
 ///
+/// Returns the number of bytes consumed, including the trailing CRC. Any bytes
+/// beyond this (such as a chained document, or appended metadata) are left for
+/// the caller to handle.
+///
 /// This method is also re-exported as `derailleur::avec::decode_slice`.
-pub fn decode(r: &[u8], o: &mut impl FromRecords) -> Result<(), Error> {
-    let i = &mut 0; // Counter of bytes read, used to read bytes from the tip.
+pub fn decode<E>(r: &[u8], o: &mut impl FromRecords<E>) -> Result<usize, Error<E>> {
+    decode_with(r, o, &DecodeOptions::default())
+}
+
+/// Decode records from a slice of a document, publishing to a receiver, with
+/// options.
+///
+/// Returns the number of bytes consumed, as for [`decode`].
+///
+/// This method is also re-exported as `derailleur::avec::decode_slice_with`.
+pub fn decode_with<E>(
+    r: &[u8],
+    o: &mut impl FromRecords<E>,
+    options: &DecodeOptions,
+) -> Result<usize, Error<E>> {
+    decode_receiver(r, o, options)
+}
 
-    let (size, successor) = Decoder::advance(take(r, i)?)?;
+/// Decode records from a slice of a document, publishing to a receiver of a
+/// single record type without dynamic dispatch.
+///
+/// Returns the number of bytes consumed, as for [`decode`].
+///
+/// This method is also re-exported as `derailleur::avec::decode_slice_static`.
+pub fn decode_static(r: &[u8], o: &mut impl FromRecordsStatic) -> Result<usize, Error> {
+    decode_receiver(r, &mut Static(o), &DecodeOptions::default())
+}
 
-    let mut record_header = match successor {
-        Left(state) => state.advance(take(r, i)?),
-        Right(state) => state,
+/// Decode a document, followed by any chained documents the options allow.
+fn decode_receiver<'a, E>(
+    r: &'a [u8],
+    o: &mut impl Receiver<'a, E>,
+    options: &DecodeOptions,
+) -> Result<usize, Error<E>> {
+    let mut options = *options;
+    let mut i = 0;
+
+    loop {
+        let s = &mut SliceSource {
+            r: &r[i..],
+            i: 0,
+            j: 0,
+        };
+        let decoded = source::decode(s, o, &options)
+            .map_err(Error::widen)?
+            .map_err(Error::Receiver)?;
+        i += decoded.len;
+
+        // End at the end of the slice, between documents.
+        if !options.allow_chained || decoded.stopped || i == r.len() {
+            return Ok(i);
+        }
+
+        options.max_records = options.max_records.map(|n| n - decoded.records);
+    }
+}
+
+/// Decode records from a document embedded at an offset in a slice, publishing
+/// to a receiver.
+///
+/// Returns the number of bytes consumed from the offset, including the trailing
+/// CRC. Adding this to the offset gives the end of the document, from which
+/// [`locate`] can find the next embedded document.
+///
+/// This method is also re-exported as `derailleur::avec::decode_slice_at`.
+pub fn decode_at<E>(
+    r: &[u8],
+    offset: usize,
+    o: &mut impl FromRecords<E>,
+) -> Result<usize, Error<E>> {
+    let r = r.get(offset..).ok_or(Exhausted)?;
+    let decoded = source::decode(
+        &mut SliceSource { r, i: 0, j: 0 },
+        o,
+        &DecodeOptions::default(),
+    )
+    .map_err(Error::widen)?
+    .map_err(Error::Receiver)?;
+
+    Ok(decoded.len)
+}
+
+/// Decode records from a slice of a document, publishing to a receiver which
+/// borrows strings and bytes from the slice.
+///
+/// Returns the number of bytes consumed, as for [`decode`].
+///
+/// This method is also re-exported as `derailleur::avec::decode_slice_ref`.
+pub fn decode_ref<'a>(r: &'a [u8], o: &mut impl FromRecordsRef<'a>) -> Result<usize, Error> {
+    let s = &mut SliceSource { r, i: 0, j: 0 };
+    let Ok(decoded) = source::decode(s, &mut Borrowed(o, PhantomData), &DecodeOptions::default())?;
+
+    Ok(decoded.len)
+}
+
+/// Read metadata from the header of a document in a slice, without decoding
+/// any records.
+///
+/// The header is checked to be plausible (with a known length and the file
+/// type marker), but neither CRC is checked.
+///
+/// This method is also re-exported as `derailleur::avec::peek_header`.
+pub fn peek_header(r: &[u8]) -> Result<HeaderInfo, Error> {
+    let s = &mut SliceSource { r, i: 0, j: 0 };
+
+    let header = s.take_array()?;
+    let crc = match Decoder::advance(header)?.1 {
+        DocumentHeaderSuccessor::Extended(_) => Some(s.take_array()?),
+        DocumentHeaderSuccessor::NextRecord(_) => None,
     };
 
-    let end = *i + size as usize; // Offset to the end of the record section.
+    Ok(HeaderInfo::new(header, crc))
+}
 
-    // Apply the cyclic redundancy check before continuing.
-    let found = u16::from_le_bytes(r.get(end..).ok_or(Error::EndOfSlice)?.try_into().unwrap());
-    let calculated = compute_crc(0, r.get(..end).ok_or(Error::EndOfSlice)?);
+/// Iterate over the data records of a document in a slice, without
+/// implementing a receiver.
+///
+/// ```
+/// for message in derailleur::avec::slice::messages(&data) {
+///     let message = message?;
+///
+///     if message.global() == 21 {
+///         println!("First event: {:?}", message.to_message().fields());
+///         break;
+///     }
+/// }
+/// ```
+///
+/// Records are found only as the iterator is advanced, so stopping early
+/// leaves the rest of the document unread, and the values of each record are
+/// decoded only on demand from its [`RawMessage`]. After an error, the iterator
+/// ends.
+pub fn messages(r: &[u8]) -> MessageIter<'_> {
+    let options = DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    };
 
-    if found != calculated {
-        Err(Error::CyclicRedundancyCheck { found, calculated })?;
+    MessageIter {
+        s: SliceSource { r, i: 0, j: 0 },
+        x: Context::new(options),
+        definitions: Default::default(),
+        record_header: None,
+        end: 0,
+        done: false,
     }
+}
+
+/// An iterator over the data records of a document in a slice, created by
+/// [`messages`].
+pub struct MessageIter<'a> {
+    s: SliceSource<'a>,
+    x: Context,
+    definitions: [DecodePlan<'a>; 16],
+    record_header: Option<RecordHeader>, // None before the document header.
+    end: usize,                          // Offset to the end of the record section.
+    done: bool,
+}
 
-    // Store of previous definition record offsets, used to decode data records.
-    let mut definition_table = [0; 16];
+impl<'a> MessageIter<'a> {
+    /// Decode the document header, returning the state to decode the first
+    /// record header.
+    fn begin(&mut self) -> Result<RecordHeader, Error> {
+        let s = &mut self.s;
 
-    while *i < end {
-        let (local, successor) = record_header.advance(take(r, i)?)?;
+        let header = s.take_array()?;
+        let (size, successor) = Decoder::advance(header)?;
 
-        record_header = match successor {
-            Left(state) => {
-                definition_table[local as usize] = *i;
-                decode_definition(state, r, i)?
-            }
-            Right((time, state)) => {
-                let j = definition_table[local as usize];
-                decode_data(state, time, r, i, j, o)?
+        let record_header = match successor {
+            DocumentHeaderSuccessor::Extended(state) => {
+                let mut successor = s.step(state)?;
+                loop {
+                    successor = match successor {
+                        ExtendedHeaderSuccessor::MoreBytes(state) => s.step(state)?,
+                        ExtendedHeaderSuccessor::NextRecord(state) => break state,
+                    };
+                }
             }
+            DocumentHeaderSuccessor::NextRecord(state) => state,
         };
+
+        self.end = s.position() + size as usize;
+        s.begin(self.end, &self.x.options)?;
+
+        Ok(record_header)
     }
 
-    Ok(())
-}
+    /// Decode records up to and including the next data record.
+    fn step(&mut self) -> Result<Option<RawMessage<'a>>, Error> {
+        let mut record_header = match self.record_header.take() {
+            Some(state) => state,
+            None => self.begin()?,
+        };
 
-fn decode_definition(state: Definition, r: &[u8], i: &mut usize) -> Result<RecordHeader, Error> {
-    Ok(match state.advance(take(r, i)?) {
-        Left(mut state) => loop {
-            state = match state.advance(take(r, i)?) {
-                Left(state) => state,
-                Right(state) => break state,
-            };
-        },
-        Right(state) => state,
-    })
-}
+        let s = &mut self.s;
+        let x = &mut self.x;
 
-fn decode_data(
-    state: DefinitionAlt,
-    time: Option<u8>,
-    r: &[u8],
-    i: &mut usize,
-    mut j: usize,
-    o: &mut impl FromRecords,
-) -> Result<RecordHeader, Error> {
-    let (global, successor) = state.advance(take(r, &mut j)?);
-
-    // Shadow the document receiver with that of a single record.
-    let mut o = o.add_record(global);
-
-    if let (Some(o), Some(time)) = (&mut o, time) {
-        o.add_time_offset(time);
-    }
-
-    Ok(match successor {
-        Left(mut state) => loop {
-            let (f, inner_state) = state.advance(take(r, &mut j)?);
-
-            let o = o.as_deref_mut();
-
-            fn decode_field<
-                T: FieldInner<From = [u8; N]>,
-                O: FromRecord + ?Sized,
-                const N: usize,
-            >(
-                mut state: Field<T>,
-                r: &[u8],
-                i: &mut usize,
-                f: u8,
-
-                mut o: Option<&mut O>,
-                add: fn(&mut O, u8, T::Into),
-            ) -> Result<Either<DefinitionFieldAlt, RecordHeader>, Error> {
-                loop {
-                    let (value, successor) = state.advance(take(r, i)?);
+        loop {
+            x.location = Location::default();
 
-                    if let (Some(o), Some(value)) = (&mut o, value) {
-                        add(o, f, value);
+            if s.position() >= self.end {
+                return Ok(None);
+            }
+
+            let header = s.take_array()?;
+
+            match record_header.advance(header)? {
+                (local, RecordHeaderSuccessor::Definition(state)) => {
+                    x.location.local = Some(local);
+
+                    let d = &mut self.definitions[local as usize];
+                    record_header = source::decode_definition(state, header, s, d)?;
+                }
+                (local, RecordHeaderSuccessor::Data(time, _)) => {
+                    x.location.local = Some(local);
+
+                    // Stored definitions always include their record header.
+                    let DecodePlan {
+                        r: definition,
+                        size,
+                    } = self.definitions[local as usize];
+                    if definition.is_empty() {
+                        Err(MissingDefinition(local))?
                     }
 
-                    state = match successor {
-                        Left(successor) => return Ok(successor),
-                        Right(y) => y,
+                    let start = s.position();
+                    let bytes = s.r.get(start..start + size);
+                    s.i += size;
+
+                    let message = RawMessage {
+                        header: header[0],
+                        definition,
+                        bytes: bytes.ok_or(Exhausted)?,
+                        timestamp: x.timestamp,
+                    };
+
+                    // Follow timestamps, to expand the time offsets of later
+                    // records without decoding them.
+                    if let Some(time) = time {
+                        x.timestamp.expand(time);
+                    }
+                    if let Some(timestamp) = message.timestamp() {
+                        x.timestamp.update(timestamp);
                     }
+
+                    self.record_header = Some(RecordHeader::random_access());
+                    return Ok(Some(message));
                 }
             }
+        }
+    }
+}
+
+impl<'a> Iterator for MessageIter<'a> {
+    type Item = Result<RawMessage<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let next = self.step().map_err(self.x.locate(self.s.position()));
+        self.done = !matches!(next, Ok(Some(_)));
+
+        next.transpose()
+    }
+}
+
+/// A data record borrowed from a document in a slice, found by a
+/// [`MessageIter`].
+///
+/// Values are decoded only when requested, so passing over records is cheap.
+/// Developer fields are not decoded.
+#[derive(Debug, Clone, Copy)]
+pub struct RawMessage<'a> {
+    header: u8,
+    definition: &'a [u8],
+    bytes: &'a [u8],
+    timestamp: TimestampTracker, // As of the previous record.
+}
+
+impl<'a> RawMessage<'a> {
+    /// Global message number of the record.
+    pub fn global(&self) -> u16 {
+        self.definition().global()
+    }
+
+    /// The definition record describing the record.
+    pub fn definition(&self) -> DefinitionInfo<'a> {
+        DefinitionInfo::new(self.definition)
+    }
 
-            let successor = match inner_state {
-                AnyField::U8(s) => decode_field(s, r, i, f, o, FromRecord::add_u8),
-                AnyField::U8Z(s) => decode_field(s, r, i, f, o, FromRecord::add_u8),
-                AnyField::U16(s) => decode_field(s, r, i, f, o, FromRecord::add_u16),
-                AnyField::U16Z(s) => decode_field(s, r, i, f, o, FromRecord::add_u16),
-                AnyField::U32(s) => decode_field(s, r, i, f, o, FromRecord::add_u32),
-                AnyField::U32Z(s) => decode_field(s, r, i, f, o, FromRecord::add_u32),
-                AnyField::U64(s) => decode_field(s, r, i, f, o, FromRecord::add_u64),
-                AnyField::U64Z(s) => decode_field(s, r, i, f, o, FromRecord::add_u64),
-
-                AnyField::I8(s) => decode_field(s, r, i, f, o, FromRecord::add_i8),
-                AnyField::I16(s) => decode_field(s, r, i, f, o, FromRecord::add_i16),
-                AnyField::I32(s) => decode_field(s, r, i, f, o, FromRecord::add_i32),
-                AnyField::I64(s) => decode_field(s, r, i, f, o, FromRecord::add_i64),
-
-                AnyField::F32(s) => decode_field(s, r, i, f, o, FromRecord::add_f32),
-                AnyField::F64(s) => decode_field(s, r, i, f, o, FromRecord::add_f64),
-            }?;
-
-            state = match successor {
-                Left(state) => state,
-                Right(state) => break state,
-            };
-        },
-        Right(state) => state,
-    })
-}
-
-/// Take an exact number of bytes from an offset in a slice, advancing the offset.
-fn take<const N: usize>(r: &[u8], i: &mut usize) -> Result<[u8; N], Error> {
-    let s = *i;
-    *i += N;
-
-    Ok(r.get(s..*i).ok_or(Error::EndOfSlice)?.try_into().unwrap())
+    /// The compressed time offset of the record, if present.
+    pub fn time_offset(&self) -> Option<u8> {
+        match RecordHeader::random_access().advance([self.header]) {
+            Ok((_, RecordHeaderSuccessor::Data(time, _))) => time,
+            _ => None,
+        }
+    }
+
+    /// Bytes of the record, following its record header.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Fields of the record with their undecoded bytes, in order.
+    pub fn fields(&self) -> impl Iterator<Item = (FieldInfo, &'a [u8])> + use<'a> {
+        let mut bytes = self.bytes;
+
+        self.definition().fields().map(move |field| {
+            let (value, rest) = bytes.split_at(field.size as usize);
+            bytes = rest;
+
+            (field, value)
+        })
+    }
+
+    /// Decode the values of the record, publishing to a record receiver.
+    ///
+    /// Compressed timestamps are expanded into full timestamps.
+    pub fn decode(&self, o: &mut dyn FromRecord) {
+        // The record header was already decoded as that of a data record.
+        let Ok((_, RecordHeaderSuccessor::Data(time, state))) =
+            RecordHeader::random_access().advance([self.header])
+        else {
+            return;
+        };
+
+        let mut x = Context::new(DecodeOptions {
+            synthesize_timestamps: true,
+            ..Default::default()
+        });
+        x.timestamp = self.timestamp;
+
+        let s = &mut SliceSource {
+            r: self.bytes,
+            i: 0,
+            j: 0,
+        };
+
+        // The bytes were sized by the definition, so can't be exhausted.
+        _ = source::decode_data(state, time, s, &self.definition, &mut x, &mut Single(o));
+    }
+
+    /// Decode the values of the record to an owned record.
+    ///
+    /// _Requires Cargo feature `std`._
+    #[cfg(feature = "std")]
+    pub fn to_message(&self) -> Message {
+        let mut message = Message::with_definition(self.definition());
+        self.decode(&mut message);
+        message
+    }
+
+    /// The full timestamp of the record, if present as a field.
+    fn timestamp(&self) -> Option<u32> {
+        let (field, bytes) = self
+            .fields()
+            .find(|(field, _)| field.number == TIMESTAMP && field.base_type & 0x1F == 0x06)?;
+
+        let bytes = bytes.try_into().ok()?;
+        let timestamp = match self.definition().is_little_endian() {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        };
+
+        (field.size == 4 && timestamp != u32::MAX).then_some(timestamp)
+    }
+}
+
+/// Receiver publishing the single record of a [`RawMessage`].
+struct Single<'o>(&'o mut dyn FromRecord);
+
+impl FromRecords for Single<'_> {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self.0))
+    }
+}
+
+/// Errors occurring while encoding to a slice.
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    /// Reached the end of the slice before the document was complete.
+    #[error("Reached the end of the slice before the document was complete.")]
+    EndOfSlice,
+    /// Records could not be represented as a document.
+    #[error("Records could not be represented: {0}.")]
+    Record(#[from] RecordError),
+}
+
+/// Encode records to a slice as a document, with headers, definitions, and the
+/// trailing CRC.
+///
+/// Returns the number of bytes written.
+///
+/// Records are visited twice. With Cargo feature `std`, their definitions are
+/// planned on the first visit, as for the writer-based encoder. Otherwise, the
+/// least recently used definition is replaced once local message numbers run
+/// out.
+///
+/// Definitions are stored on the heap with Cargo feature `std`, and otherwise
+/// in a buffer of about 26 KB held on the stack while encoding. To store them
+/// elsewhere, or in less space, use [`encode_in`].
+///
+/// This method is also re-exported as `derailleur::avec::encode_slice`.
+pub fn encode(w: &mut [u8], i: &impl IntoRecords) -> Result<usize, EncodeError> {
+    #[cfg(feature = "std")]
+    let storage = &mut std::vec![0; sink::STORAGE];
+    #[cfg(not(feature = "std"))]
+    let storage = &mut [0; sink::STORAGE];
+
+    encode_in(w, i, storage)
+}
+
+/// Encode records to a slice as a document, storing definitions in the given
+/// storage.
+///
+/// Returns the number of bytes written.
+///
+/// ```
+/// let mut storage = [0; 2048];
+/// let len = derailleur::avec::slice::encode_in(&mut buf, &records, &mut storage)?;
+/// ```
+///
+/// The storage is divided evenly among the 16 local message numbers and the
+/// definition of the record being encoded, and a definition exceeding its share
+/// fails with [`RecordError::DefinitionStorage`]. Storage of 17 times
+/// [`DEFINITION_SIZE`] bytes, as used by [`encode`], holds any definition.
+/// Otherwise, records are encoded as for [`encode`].
+///
+/// [`DEFINITION_SIZE`]: super::iter::DEFINITION_SIZE
+pub fn encode_in(
+    w: &mut [u8],
+    i: &impl IntoRecords,
+    storage: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let mut sink = SliceSink { w, i: 0 };
+    sink::encode(&mut sink, i, storage)?;
+
+    Ok(sink.i)
+}
+
+/// Find the offset of the first plausible document header in a slice.
+///
+/// A header is plausible if it has a known length and the file type marker.
+/// Since this may also match arbitrary bytes, the document found should still
+/// be decoded with [`decode_at`] to confirm.
+pub fn locate(r: &[u8]) -> Option<usize> {
+    r.windows(12)
+        .position(|w| matches!(w[0], 12 | 14) && &w[8..12] == b".FIT")
+}
+
+/// A definition record stored in place, with the size of its data records
+/// computed once it is taken.
+#[derive(Clone, Copy, Default)]
+struct DecodePlan<'a> {
+    r: &'a [u8], // The definition record, beginning with its record header.
+    size: usize, // Size of each data record, following its record header.
+}
+
+impl AsRef<[u8]> for DecodePlan<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.r
+    }
+}
+
+impl Stored for DecodePlan<'_> {
+    fn data_size(&self) -> usize {
+        self.size
+    }
+}
+
+struct SliceSource<'a> {
+    r: &'a [u8],
+    i: usize, // Counter of bytes read, used to read bytes from the tip.
+    j: usize, // Offset to the start of the definition record being stored.
+}
+
+impl ByteSource for SliceSource<'_> {
+    type Error = Error;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let rest = &self.r[self.i.min(self.r.len())..];
+        let n = buf.len().min(rest.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.i += n;
+
+        Ok(n)
+    }
+
+    fn take_slice(&mut self, n: usize) -> Result<Option<&[u8]>, Error> {
+        let s = self.i;
+        let bytes = self.r.get(s..s + n).ok_or(Exhausted)?;
+        self.i += n;
+
+        Ok(Some(bytes))
+    }
+}
+
+impl<'a> Source for SliceSource<'a> {
+    type Definition<'s> = DecodePlan<'a>;
+
+    fn position(&self) -> usize {
+        self.i
+    }
+
+    fn begin(&mut self, end: usize, options: &DecodeOptions) -> Result<(), Error> {
+        if options.allow_missing_crc && self.r.len() == end {
+            return Ok(());
+        }
+
+        // Apply the cyclic redundancy check before continuing.
+        let at = Location {
+            offset: end,
+            ..Default::default()
+        };
+
+        let found = self.r.get(end..end + 2).ok_or(Error::EndOfSlice { at })?;
+        let found = u16::from_le_bytes(found.try_into().unwrap());
+        let calculated = compute_crc(0, &self.r[..end]);
+
+        if options.verify_crc && found != calculated {
+            Err(Error::CyclicRedundancyCheck {
+                found,
+                calculated,
+                at,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, _: &DecodeOptions) -> Result<bool, Error> {
+        Ok(self.r.len() >= self.i + 2)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<bool, Error> {
+        // The CRC is computed from the slice itself, so bytes can always be
+        // passed over.
+        if self.i + n > self.r.len() {
+            Err(Exhausted)?
+        }
+
+        self.i += n;
+        Ok(true)
+    }
+
+    fn more(&mut self) -> Result<Next, Error> {
+        let rest = &self.r[self.i.min(self.r.len())..];
+
+        Ok(match rest {
+            [] => Next::End { crc: false },
+            [a, b] if u16::from_le_bytes([*a, *b]) == compute_crc(0, &self.r[..self.i]) => {
+                Next::End { crc: true }
+            }
+            _ => Next::Record,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<[u8; 3]>, Error> {
+        let next = self.r.get(self.i..self.i + 3);
+        Ok(next.map(|b| b.try_into().unwrap()))
+    }
+
+    fn begin_definition(&mut self, _: &mut Self::Definition<'_>, _: [u8; 1]) -> Result<(), Error> {
+        self.j = self.i - 1; // Include the record header.
+        Ok(())
+    }
+
+    fn fill_definition(
+        &mut self,
+        _: &mut Self::Definition<'_>,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        self.fill(buf)
+    }
+
+    fn end_definition(&mut self, d: &mut Self::Definition<'_>) {
+        let r = &self.r[self.j..self.i];
+        *d = DecodePlan {
+            r,
+            size: source::data_size(r),
+        };
+    }
+}
+
+impl<'a> Retain<'a> for SliceSource<'a> {
+    fn retained(&self, start: usize) -> Option<&'a [u8]> {
+        self.r.get(start..self.i)
+    }
+}
+
+struct SliceSink<'a> {
+    w: &'a mut [u8],
+    i: usize, // Counter of bytes written.
+}
+
+impl Sink for SliceSink<'_> {
+    type Error = EncodeError;
+
+    fn put(&mut self, w: &[u8]) -> Result<(), EncodeError> {
+        let s = self.i;
+        self.i += w.len();
+
+        self.w
+            .get_mut(s..self.i)
+            .ok_or(EncodeError::EndOfSlice)?
+            .copy_from_slice(w);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,1214 @@
+//! Decoding core shared by the slice- and reader-based decoders.
+
+use core::{convert::Infallible, marker::PhantomData, ops::ControlFlow};
+
+use zerocopy::{FromBytes, Immutable};
+
+use crate::sans::{
+    Decoder,
+    data::{
+        AnyField, Developer, DeveloperField, DeveloperFieldSuccessor, Field, FieldInner, FieldKind,
+        FieldSuccessor, Unknown,
+    },
+    definition::{
+        DataSuccessor, Definition, DefinitionAlt, DefinitionSuccessor, DeveloperDataSuccessor,
+        DeveloperDefinitionAlt, DeveloperDefinitionSuccessor,
+    },
+    header::{
+        DocumentHeaderError, DocumentHeaderSuccessor, ExtendedHeaderSuccessor, RecordHeader,
+        RecordHeaderError, RecordHeaderSuccessor,
+    },
+    step::{Step, drive},
+};
+
+use super::{
+    ByteSource, DecodeOptions, DefinitionInfo, Element, FieldDescription, FromRecord,
+    FromRecordRef, FromRecords, FromRecordsRef, FromRecordsStatic, HeaderInfo, Location, Malformed,
+    Skipped, Warning,
+    developer::{DEVELOPER_DATA_ID, FIELD_DESCRIPTION, Registry},
+    timestamp::{TIMESTAMP, TimestampTracker},
+};
+
+/// A source of document bytes, taken through its [`ByteSource`]
+/// implementation.
+pub(crate) trait Source: ByteSource<Error: SourceError> {
+    /// Storage for a definition record, retained to decode later data records,
+    /// which may borrow from storage provided for a lifetime.
+    type Definition<'s>: Stored;
+
+    /// Take bytes from the tip of the source, filling a buffer exactly.
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        if ByteSource::take(self, buf)? != buf.len() {
+            Err(Exhausted)?
+        }
+
+        Ok(())
+    }
+
+    /// Take an exact number of bytes from the tip of the source.
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Self::Error> {
+        let mut buf = [0; N];
+        self.fill(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// Advance a state with bytes taken from the tip of the source.
+    fn step<T: Step>(&mut self, state: T) -> Result<T::Output, Self::Error> {
+        drive(state, |buf| self.fill(buf))
+    }
+
+    /// Number of bytes taken from the source.
+    fn position(&self) -> usize;
+
+    /// Prepare to decode a record section ending at an offset, with options
+    /// for the trailing CRC.
+    fn begin(&mut self, end: usize, options: &DecodeOptions) -> Result<(), Self::Error>;
+
+    /// Conclude decoding once the record section has been taken, with options
+    /// for the trailing CRC.
+    ///
+    /// Returns whether the CRC was found.
+    fn finish(&mut self, options: &DecodeOptions) -> Result<bool, Self::Error>;
+
+    /// Note the start of a record, before its header is taken.
+    fn boundary(&mut self) {}
+
+    /// Pass over an exact number of bytes in bulk, without decoding them, if
+    /// the source allows it. Bytes passed over are still counted, and followed
+    /// by the CRC unless the options disable its verification.
+    ///
+    /// Returns whether the bytes were passed over.
+    fn skip(&mut self, n: usize) -> Result<bool, Self::Error> {
+        _ = n;
+        Ok(false)
+    }
+
+    /// Determine whether another record follows, in a document of unknown
+    /// length. A trailing CRC ending the document is taken.
+    fn more(&mut self) -> Result<Next, Self::Error>;
+
+    /// Look at the next three bytes without taking them, if available.
+    fn peek(&mut self) -> Result<Option<[u8; 3]>, Self::Error>;
+
+    /// Prepare to store a definition record, whose record header was the last
+    /// byte taken.
+    fn begin_definition(
+        &mut self,
+        d: &mut Self::Definition<'_>,
+        header: [u8; 1],
+    ) -> Result<(), Self::Error>;
+
+    /// Take bytes of a definition record from the tip of the source, filling
+    /// a buffer exactly and adding them to its storage.
+    fn fill_definition(
+        &mut self,
+        d: &mut Self::Definition<'_>,
+        buf: &mut [u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Advance a definition state with bytes taken from the tip of the source,
+    /// adding them to its storage.
+    fn step_definition<T: Step>(
+        &mut self,
+        state: T,
+        d: &mut Self::Definition<'_>,
+    ) -> Result<T::Output, Self::Error> {
+        drive(state, |buf| self.fill_definition(d, buf))
+    }
+
+    /// Conclude storing a definition record.
+    fn end_definition(&mut self, d: &mut Self::Definition<'_>);
+}
+
+/// Errors occurring while decoding from a source.
+pub(crate) trait SourceError:
+    From<DocumentHeaderError>
+    + From<RecordHeaderError>
+    + From<Exhausted>
+    + From<MissingDefinition>
+    + Locate
+{
+}
+
+impl<E> SourceError for E where
+    E: From<DocumentHeaderError>
+        + From<RecordHeaderError>
+        + From<Exhausted>
+        + From<MissingDefinition>
+        + Locate
+{
+}
+
+/// Storage for a definition record, beginning with its record header.
+pub(crate) trait Stored: Default + AsRef<[u8]> {
+    /// Size of each data record in bytes, following its record header.
+    fn data_size(&self) -> usize {
+        data_size(self.as_ref())
+    }
+}
+
+impl Stored for &[u8] {}
+
+/// A source retaining the bytes taken from it for a lifetime, so that they can
+/// be published without a copy.
+pub(crate) trait Retain<'a> {
+    /// Bytes taken since an offset, if retained.
+    fn retained(&self, start: usize) -> Option<&'a [u8]> {
+        _ = start;
+        None
+    }
+}
+
+/// A receiver of documents, whose records may borrow from a source for a
+/// lifetime.
+pub(crate) trait Receiver<'a, E> {
+    type Record<'s>: AsRecord<'a>
+    where
+        Self: 's;
+
+    fn add_record(&mut self, definition: DefinitionInfo<'_>)
+    -> Result<Option<Self::Record<'_>>, E>;
+    fn add_header(&mut self, info: HeaderInfo);
+    fn add_skipped(&mut self, skipped: Skipped);
+    fn unknown_record(&mut self, id: u16);
+    fn control(&mut self) -> ControlFlow<()>;
+    fn finish(&mut self) -> Result<(), E>;
+}
+
+impl<'a, E, O: FromRecords<E> + ?Sized> Receiver<'a, E> for O {
+    type Record<'s>
+        = &'s mut (dyn FromRecord + 's)
+    where
+        Self: 's;
+
+    fn add_record(
+        &mut self,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<Self::Record<'_>>, E> {
+        FromRecords::add_record_with(self, definition.global(), definition)
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        FromRecords::add_header(self, info);
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        FromRecords::add_skipped(self, skipped);
+    }
+    fn unknown_record(&mut self, id: u16) {
+        FromRecords::unknown_record(self, id);
+    }
+    fn control(&mut self) -> ControlFlow<()> {
+        FromRecords::control(self)
+    }
+    fn finish(&mut self) -> Result<(), E> {
+        FromRecords::finish(self)
+    }
+}
+
+/// A receiver of documents whose records borrow from the source.
+pub(crate) struct Borrowed<'a, 'o, O: ?Sized>(pub(crate) &'o mut O, pub(crate) PhantomData<&'a ()>);
+
+impl<'a, O: FromRecordsRef<'a> + ?Sized> Receiver<'a, Infallible> for Borrowed<'a, '_, O> {
+    type Record<'s>
+        = &'s mut (dyn FromRecordRef<'a> + 's)
+    where
+        Self: 's;
+
+    fn add_record(
+        &mut self,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<Self::Record<'_>>, Infallible> {
+        Ok(self.0.add_record(definition.global()))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.0.add_header(info);
+    }
+    fn add_skipped(&mut self, _: Skipped) {}
+    fn unknown_record(&mut self, _: u16) {}
+    fn control(&mut self) -> ControlFlow<()> {
+        self.0.control()
+    }
+    fn finish(&mut self) -> Result<(), Infallible> {
+        self.0.finish();
+        Ok(())
+    }
+}
+
+/// A receiver of documents whose records are of a single type, without
+/// dynamic dispatch.
+pub(crate) struct Static<'o, O: ?Sized>(pub(crate) &'o mut O);
+
+impl<O: FromRecordsStatic + ?Sized> Receiver<'_, Infallible> for Static<'_, O> {
+    type Record<'s>
+        = O::Receiver<'s>
+    where
+        Self: 's;
+
+    fn add_record(
+        &mut self,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<Self::Record<'_>>, Infallible> {
+        Ok(self.0.add_record(definition.global()))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.0.add_header(info);
+    }
+    fn add_skipped(&mut self, _: Skipped) {}
+    fn unknown_record(&mut self, _: u16) {}
+    fn control(&mut self) -> ControlFlow<()> {
+        self.0.control()
+    }
+    fn finish(&mut self) -> Result<(), Infallible> {
+        self.0.finish();
+        Ok(())
+    }
+}
+
+/// A receiver of a single record, as held while decoding it.
+pub(crate) trait AsRecord<'a> {
+    type Target: FromRecord + Borrowing<'a> + ?Sized;
+
+    fn as_record(&mut self) -> &mut Self::Target;
+}
+
+impl<'a, 'r> AsRecord<'a> for &'r mut (dyn FromRecord + 'r) {
+    type Target = dyn FromRecord + 'r;
+
+    fn as_record(&mut self) -> &mut Self::Target {
+        &mut **self
+    }
+}
+
+impl<'a, 'r> AsRecord<'a> for &'r mut (dyn FromRecordRef<'a> + 'r) {
+    type Target = dyn FromRecordRef<'a> + 'r;
+
+    fn as_record(&mut self) -> &mut Self::Target {
+        &mut **self
+    }
+}
+
+impl<'a, R: FromRecord> AsRecord<'a> for R {
+    type Target = R;
+
+    fn as_record(&mut self) -> &mut Self::Target {
+        self
+    }
+}
+
+/// A receiver of a record, taking strings and bytes borrowed from a source
+/// where it can.
+pub(crate) trait Borrowing<'a> {
+    fn add_string_from(&mut self, field: u8, value: &'a [u8]);
+    fn add_unknown_from(&mut self, field: u8, base_type: u8, bytes: &'a [u8]);
+}
+
+impl<'a, R: FromRecord> Borrowing<'a> for R {
+    fn add_string_from(&mut self, field: u8, value: &'a [u8]) {
+        self.add_string(field, value);
+    }
+    fn add_unknown_from(&mut self, field: u8, base_type: u8, bytes: &'a [u8]) {
+        self.add_unknown(field, base_type, bytes);
+    }
+}
+
+impl<'a> Borrowing<'a> for dyn FromRecord + '_ {
+    fn add_string_from(&mut self, field: u8, value: &'a [u8]) {
+        self.add_string(field, value);
+    }
+    fn add_unknown_from(&mut self, field: u8, base_type: u8, bytes: &'a [u8]) {
+        self.add_unknown(field, base_type, bytes);
+    }
+}
+
+impl<'a> Borrowing<'a> for dyn FromRecordRef<'a> + '_ {
+    fn add_string_from(&mut self, field: u8, value: &'a [u8]) {
+        self.add_string_ref(field, value);
+    }
+    fn add_unknown_from(&mut self, field: u8, base_type: u8, bytes: &'a [u8]) {
+        self.add_unknown_ref(field, base_type, bytes);
+    }
+}
+
+/// Whether another record follows, in a document of unknown length.
+pub(crate) enum Next {
+    Record,
+    End { crc: bool },
+}
+
+/// An error which can be attributed to a position in a document.
+pub(crate) trait Locate {
+    /// Attach a position to the error, unless it already has one.
+    fn locate(self, location: Location) -> Self;
+}
+
+/// Unexpectedly reached the end of the available bytes.
+pub(crate) struct Exhausted;
+
+/// Found a data record for a local message number without a definition.
+pub(crate) struct MissingDefinition(pub(crate) u8);
+
+/// Extent of a decoded document.
+pub(crate) struct Decoded {
+    /// Length of the document, including its trailing CRC if present, or up to
+    /// the record at which decoding stopped.
+    pub(crate) len: usize,
+    /// Number of data records decoded.
+    pub(crate) records: usize,
+    /// Whether decoding stopped early, as the options direct.
+    pub(crate) stopped: bool,
+}
+
+/// State retained between records while decoding a document.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Context {
+    pub(crate) options: DecodeOptions,
+    pub(crate) registry: Registry,
+    pub(crate) location: Location, // Record and field being decoded.
+    pub(crate) timestamp: TimestampTracker,
+}
+
+impl Context {
+    pub(crate) fn new(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Locate an error at an offset, in the record and field being decoded.
+    pub(crate) fn locate<E: Locate>(&self, offset: usize) -> impl FnOnce(E) -> E {
+        let location = Location {
+            offset,
+            ..self.location
+        };
+
+        move |err| {
+            #[cfg(feature = "tracing")]
+            tracing::error!(%location, "decoding failed");
+
+            err.locate(location)
+        }
+    }
+}
+
+/// Take the value of a result from the receiver, or return its failure within
+/// a successful result.
+macro_rules! receive {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => return Ok(Err(err)),
+        }
+    };
+}
+
+/// Decode records from a source, publishing to a receiver.
+///
+/// Failures of the receiver are returned within a successful result, to be
+/// wrapped by the caller.
+pub(crate) fn decode<'a, S: Source + Retain<'a>, E>(
+    r: &mut S,
+    o: &mut impl Receiver<'a, E>,
+    options: &DecodeOptions,
+) -> Result<Result<Decoded, E>, S::Error> {
+    decode_in(r, o, options, &mut Default::default())
+}
+
+/// Decode records from a source, publishing to a receiver, storing definition
+/// records for each local message number in the given slots.
+pub(crate) fn decode_in<'a, S: Source + Retain<'a>, E>(
+    r: &mut S,
+    o: &mut impl Receiver<'a, E>,
+    options: &DecodeOptions,
+    definitions: &mut [S::Definition<'_>; 16],
+) -> Result<Result<Decoded, E>, S::Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("document", offset = r.position()).entered();
+
+    let x = &mut Context::new(*options);
+    decode_document(r, o, x, definitions).map_err(x.locate(r.position()))
+}
+
+fn decode_document<'a, S: Source + Retain<'a>, E>(
+    r: &mut S,
+    o: &mut impl Receiver<'a, E>,
+    x: &mut Context,
+    definitions: &mut [S::Definition<'_>; 16],
+) -> Result<Result<Decoded, E>, S::Error> {
+    let options = x.options;
+
+    let header = r.take_array()?;
+    let (size, successor) = Decoder::advance(header)?;
+
+    let mut crc = None;
+    let mut record_header = match successor {
+        DocumentHeaderSuccessor::Extended(state) => {
+            let mut successor = state.advance(*crc.insert(r.take_array()?));
+            loop {
+                successor = match successor {
+                    ExtendedHeaderSuccessor::MoreBytes(state) => r.step(state)?,
+                    ExtendedHeaderSuccessor::NextRecord(state) => break state,
+                };
+            }
+        }
+        DocumentHeaderSuccessor::NextRecord(state) => state,
+    };
+
+    let info = HeaderInfo::new(header, crc);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        data_size = info.data_size,
+        protocol_version = info.protocol_version,
+        profile_version = info.profile_version,
+        "document header",
+    );
+    o.add_header(info);
+
+    let end = r.position() + size as usize; // Offset to the end of the record section.
+
+    let unknown = size == 0 && options.unknown_length;
+    if !unknown {
+        r.begin(end, &options)?;
+    }
+
+    let mut records = 0;
+
+    let crc = loop {
+        x.location = Location::default();
+
+        if unknown {
+            if let Next::End { crc } = r.more()? {
+                receive!(o.finish());
+                break crc;
+            }
+        } else if r.position() >= end {
+            receive!(o.finish());
+            break r.finish(&options)?;
+        }
+
+        let offset = r.position();
+
+        let stopped = Decoded {
+            len: offset,
+            records,
+            stopped: true,
+        };
+
+        if options.max_records.is_some_and(|n| records >= n) || o.control().is_break() {
+            receive!(o.finish());
+            return Ok(Ok(stopped));
+        }
+
+        r.boundary();
+
+        let header = r.take_array()?;
+
+        let error = match record_header.advance(header) {
+            Ok((local, RecordHeaderSuccessor::Definition(state))) => {
+                x.location.local = Some(local);
+
+                let d = &mut definitions[local as usize];
+                record_header = decode_definition(state, header, r, d)?;
+
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    offset,
+                    local,
+                    global = DefinitionInfo::new(d.as_ref()).global(),
+                    size = d.data_size(),
+                    "definition record",
+                );
+
+                continue;
+            }
+            Ok((local, RecordHeaderSuccessor::Data(time, state))) => {
+                x.location.local = Some(local);
+
+                let d = &definitions[local as usize];
+
+                // Stored definitions always include their record header.
+                if d.as_ref().is_empty() {
+                    if !options.lenient {
+                        Err(MissingDefinition(local))?
+                    }
+
+                    Malformed::MissingDefinition { local }
+                } else if options.stop_at == Some(DefinitionInfo::new(d.as_ref()).global()) {
+                    receive!(o.finish());
+                    return Ok(Ok(stopped));
+                } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        offset,
+                        local,
+                        global = DefinitionInfo::new(d.as_ref()).global(),
+                        "data record",
+                    );
+
+                    record_header = receive!(decode_data(state, time, r, d, x, o)?);
+                    records += 1;
+                    continue;
+                }
+            }
+            Err(err) if !options.lenient => Err(err)?,
+            Err(RecordHeaderError::DeveloperData) => Malformed::DeveloperData,
+        };
+
+        resynchronize(r, (!unknown).then_some(end))?;
+        record_header = RecordHeader::random_access();
+
+        let length = r.position() - offset;
+
+        #[cfg(feature = "tracing")]
+        tracing::warn!(offset, length, ?error, "skipped malformed bytes");
+
+        o.add_skipped(Skipped {
+            offset,
+            length,
+            error,
+        });
+    };
+
+    let end = r.position();
+
+    Ok(Ok(Decoded {
+        len: if crc { end + 2 } else { end },
+        records,
+        stopped: false,
+    }))
+}
+
+/// Skip bytes up to the start of a plausible definition record, or the end of
+/// the record section.
+fn resynchronize<S: Source>(r: &mut S, end: Option<usize>) -> Result<(), S::Error> {
+    while end.is_none_or(|end| r.position() < end) {
+        let next = match end {
+            Some(end) if r.position() + 3 > end => None,
+            _ => r.peek()?,
+        };
+
+        match next {
+            // A definition record header, reserved byte, and architecture.
+            Some([h, 0, 0 | 1]) if h & 0xD0 == 0x40 => break,
+            // Leave the last bytes of a document of unknown length, which may
+            // hold its CRC.
+            None if end.is_none() => break,
+            _ => _ = r.take_array::<1>()?,
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn decode_definition<S: Source>(
+    state: Definition,
+    header: [u8; 1],
+    r: &mut S,
+    d: &mut S::Definition<'_>,
+) -> Result<RecordHeader, S::Error> {
+    r.begin_definition(d, header)?;
+
+    let mut successor = r.step_definition(state, d)?;
+    let record_header = loop {
+        successor = match successor {
+            DefinitionSuccessor::NextField(state) => r.step_definition(state, d)?,
+            DefinitionSuccessor::Developer(state) => {
+                let mut successor = r.step_definition(state, d)?;
+                break loop {
+                    successor = match successor {
+                        DeveloperDefinitionSuccessor::NextField(state) => {
+                            r.step_definition(state, d)?
+                        }
+                        DeveloperDefinitionSuccessor::NextRecord(state, _) => break state,
+                    };
+                };
+            }
+            DefinitionSuccessor::NextRecord(state, _) => break state,
+        };
+    };
+
+    r.end_definition(d);
+
+    Ok(record_header)
+}
+
+pub(super) fn decode_data<'a, S: Source + Retain<'a>, E>(
+    state: DefinitionAlt,
+    time: Option<u8>,
+    r: &mut S,
+    d: &impl Stored,
+    x: &mut Context,
+    receiver: &mut impl Receiver<'a, E>,
+) -> Result<Result<RecordHeader, E>, S::Error> {
+    let size = d.data_size();
+    let d = &mut d.as_ref();
+    let definition = DefinitionInfo::new(d);
+
+    let (global, mut successor) = drive(state, |buf| replay(d, buf))?;
+    x.location.global = Some(global);
+
+    let mut record = receive!(receiver.add_record(definition));
+    let unknown = record.is_none();
+
+    // Pass over records without a receiver that later records don't depend on,
+    // where the source allows it.
+    if unknown && is_independent(definition, &x.options) && r.skip(size)? {
+        if let Some(time) = time {
+            x.timestamp.expand(time);
+        }
+
+        drop(record);
+        receiver.unknown_record(global);
+
+        return Ok(Ok(RecordHeader::random_access()));
+    }
+    let mut o = record.as_mut().map(AsRecord::as_record);
+
+    if let Some(o) = &mut o {
+        o.begin();
+    }
+
+    if let Some(time) = time {
+        if let Some(o) = &mut o {
+            o.add_time_offset(time);
+        }
+
+        // Expand the offset relative to the previous timestamp, even if it
+        // isn't published, to follow later offsets.
+        let timestamp = x.timestamp.expand(time);
+
+        if let (Some(o), Some(timestamp), true) =
+            (&mut o, timestamp, x.options.synthesize_timestamps)
+        {
+            o.add_u32(TIMESTAMP, timestamp);
+        }
+    }
+
+    let record_header = loop {
+        let state = match successor {
+            DataSuccessor::NextField(state) => state,
+            DataSuccessor::Developer(state) => {
+                x.location.field = None;
+                break decode_developer_data(state, r, d, x, &mut o)?;
+            }
+            DataSuccessor::NextRecord(state) => break state,
+        };
+
+        let mut field = [0; 3];
+        replay(d, &mut field)?;
+        let base_type = field[2];
+
+        let (f, inner_state) = state.advance(field);
+        x.location.field = Some(f);
+
+        let o = &mut o;
+
+        successor = match inner_state {
+            AnyField::U8(s) => {
+                // Collect developer field declarations.
+                decode_values(s, r, o, f, |v| x.registry.capture(global, f, v))
+            }
+            AnyField::U8Z(s) => {
+                // Collect developer field names.
+                decode_values(s, r, o, f, |v| x.registry.capture(global, f, v))
+            }
+            AnyField::U16(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::U16Z(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::U32(s) => {
+                // Track full timestamps, used to expand time offsets.
+                decode_values(s, r, o, f, |v| {
+                    if f == TIMESTAMP {
+                        x.timestamp.update(v);
+                    }
+                })
+            }
+            AnyField::U32Z(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::U64(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::U64Z(s) => decode_values(s, r, o, f, |_| {}),
+
+            AnyField::I8(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::I16(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::I32(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::I64(s) => decode_values(s, r, o, f, |_| {}),
+
+            AnyField::F32(s) => decode_values(s, r, o, f, |_| {}),
+            AnyField::F64(s) => decode_values(s, r, o, f, |_| {}),
+
+            AnyField::String(s) => {
+                let start = r.position();
+                let mut buf = [0; u8::MAX as usize];
+                let mut len = 0;
+
+                // Collect developer field names.
+                let successor = decode_field(s, r, |v| {
+                    buf[len] = v;
+                    len += 1;
+
+                    if v != 0 {
+                        x.registry.capture(global, f, v);
+                    }
+                });
+
+                // Publish the bytes of each whole string, once the field
+                // is complete, and then the strings themselves.
+                if let (Ok(_), Some(o)) = (&successor, o) {
+                    if strings(&buf[..len]).next().is_none() {
+                        o.add_invalid(f, base_type);
+                    }
+
+                    for s in strings(&buf[..len]) {
+                        o.add_u8_slice(f, s);
+                    }
+
+                    match r.retained(start) {
+                        Some(bytes) => strings(bytes).for_each(|s| o.add_string_from(f, s)),
+                        None => strings(&buf[..len]).for_each(|s| o.add_string(f, s)),
+                    }
+                }
+
+                successor
+            }
+
+            AnyField::Unknown(s) => decode_raw(s, r, o, f, base_type),
+            AnyField::Misaligned(s) => {
+                if let Some(o) = o {
+                    let size = s.bytes_remaining();
+                    o.add_warning(Warning::MisalignedField { field: f, size });
+                }
+
+                decode_raw(s, r, o, f, base_type)
+            }
+            AnyField::Empty(s) => {
+                if let Some(o) = o {
+                    o.add_warning(Warning::EmptyField { field: f });
+                }
+
+                Ok(s.advance())
+            }
+        }?;
+    };
+
+    x.registry.commit(global);
+
+    if let Some(o) = &mut o {
+        o.end();
+    }
+
+    // Report records without a receiver, once passed over.
+    drop(record);
+    if unknown {
+        receiver.unknown_record(global);
+    }
+
+    Ok(Ok(record_header))
+}
+
+/// Whether a data record can be passed over without being decoded, holding no
+/// developer field descriptions, timestamps or accumulated developer fields.
+fn is_independent(definition: DefinitionInfo<'_>, options: &DecodeOptions) -> bool {
+    !matches!(definition.global(), FIELD_DESCRIPTION | DEVELOPER_DATA_ID)
+        && definition.fields().all(|f| f.number != TIMESTAMP)
+        && (!options.accumulate_developer_fields || definition.developer_fields().next().is_none())
+}
+
+/// Compute the size of a data record from its stored definition record,
+/// beginning with its record header.
+pub(crate) fn data_size(d: &[u8]) -> usize {
+    let fields = d.get(6..).unwrap_or_default();
+    let count = d.get(5).copied().unwrap_or_default() as usize;
+    let (fields, developer_fields) = fields.split_at((3 * count).min(fields.len()));
+
+    // Developer fields follow their count, if flagged in the record header.
+    let developer_fields = match d.first() {
+        Some(header) if header & 0x20 != 0 => developer_fields.get(1..).unwrap_or_default(),
+        _ => &[],
+    };
+
+    let size = |fields: &[u8]| fields.chunks_exact(3).map(|f| f[1] as usize).sum::<usize>();
+    size(fields) + size(developer_fields)
+}
+
+/// Decode the developer fields of a data record, publishing to a receiver.
+fn decode_developer_data<S: Source, O: FromRecord + ?Sized>(
+    state: DeveloperDefinitionAlt,
+    r: &mut S,
+    d: &mut &[u8],
+    x: &mut Context,
+    o: &mut Option<&mut O>,
+) -> Result<RecordHeader, S::Error> {
+    let mut successor = drive(state, |buf| replay(d, buf))?;
+
+    loop {
+        let state = match successor {
+            DeveloperDataSuccessor::NextField(state) => state,
+            DeveloperDataSuccessor::NextRecord(state) => return Ok(state),
+        };
+
+        let (f, developer, inner_state) = drive(state, |buf| replay(d, buf))?;
+
+        let field = x.registry.get(developer, f).copied();
+
+        successor = match field.filter(|_| x.options.allow_developer) {
+            Some(field) => decode_developer_field(inner_state, r, &field, x, o)?,
+            None => decode_field(inner_state.skip(), r, |_| {})?,
+        };
+    }
+}
+
+/// Decode a described developer field, publishing to a receiver.
+fn decode_developer_field<S: Source, O: FromRecord + ?Sized>(
+    state: DeveloperField,
+    r: &mut S,
+    field: &FieldDescription,
+    x: &mut Context,
+    o: &mut Option<&mut O>,
+) -> Result<<Developer as FieldKind>::Successor, S::Error> {
+    let state = match state.advance(field.base_type()) {
+        DeveloperFieldSuccessor::Known(state) => state,
+        // Skip fields not matching their description.
+        DeveloperFieldSuccessor::Unsupported(state) => {
+            return decode_field(state.skip(), r, |_| {});
+        }
+    };
+
+    /// Forward values for a developer field to a receiver, if one exists.
+    fn to<O: FromRecord + ?Sized, V>(
+        o: &mut Option<&mut O>,
+        field: &FieldDescription,
+        add: fn(&mut O, &FieldDescription, V),
+    ) -> impl FnMut(V) {
+        move |v| {
+            if let Some(o) = o {
+                add(o, field, v)
+            }
+        }
+    }
+
+    /// Forward values for a developer field to a receiver, if one exists,
+    /// along with those holding the 'invalid' marker value.
+    fn to_all<O: FromRecord + ?Sized, V>(
+        o: &mut Option<&mut O>,
+        field: &FieldDescription,
+        add: fn(&mut O, &FieldDescription, V),
+    ) -> impl FnMut(Option<V>) {
+        move |v| match (o.as_deref_mut(), v) {
+            (Some(o), Some(v)) => add(o, field, v),
+            (Some(o), None) => o.add_developer_invalid(field),
+            (None, _) => {}
+        }
+    }
+
+    // Expand accumulated counters into running totals.
+    let state = if x.options.accumulate_developer_fields && field.is_accumulated() {
+        let registry = &mut x.registry;
+        let mut add = to(o, field, FromRecord::add_developer_u32);
+        let mut total = |v: u32, bits| add(registry.accumulate(field, v, bits));
+
+        match state {
+            AnyField::U8(s) => return decode_field(s, r, |v| total(v.into(), u8::BITS)),
+            AnyField::U8Z(s) => return decode_field(s, r, |v| total(v.into(), u8::BITS)),
+            AnyField::U16(s) => return decode_field(s, r, |v| total(v.into(), u16::BITS)),
+            AnyField::U16Z(s) => return decode_field(s, r, |v| total(v.into(), u16::BITS)),
+            AnyField::U32(s) => return decode_field(s, r, |v| total(v, u32::BITS)),
+            AnyField::U32Z(s) => return decode_field(s, r, |v| total(v, u32::BITS)),
+            state => state,
+        }
+    } else {
+        state
+    };
+
+    match state {
+        AnyField::U8(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u8)),
+        AnyField::U8Z(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u8)),
+        AnyField::U16(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u16)),
+        AnyField::U16Z(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u16)),
+        AnyField::U32(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u32)),
+        AnyField::U32Z(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u32)),
+        AnyField::U64(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u64)),
+        AnyField::U64Z(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_u64)),
+
+        AnyField::I8(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_i8)),
+        AnyField::I16(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_i16)),
+        AnyField::I32(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_i32)),
+        AnyField::I64(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_i64)),
+
+        AnyField::F32(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_f32)),
+        AnyField::F64(s) => decode_all(s, r, to_all(o, field, FromRecord::add_developer_f64)),
+
+        AnyField::String(s) => {
+            let mut add = to_all(o, field, FromRecord::add_developer_u8);
+            let mut empty = true;
+
+            let successor = decode_field(s, r, |v| {
+                if v != 0 {
+                    empty = false;
+                    add(Some(v))
+                }
+            });
+
+            // Publish fields without any strings as invalid.
+            if empty {
+                add(None);
+            }
+
+            successor
+        }
+
+        AnyField::Unknown(s) | AnyField::Misaligned(s) => decode_field(s, r, |_| {}),
+        AnyField::Empty(s) => Ok(s.advance()),
+    }
+}
+
+/// Decode a field, passing each valid value to a closure.
+fn decode_field<T, K, S, const N: usize>(
+    state: Field<T, K>,
+    r: &mut S,
+    mut add: impl FnMut(T::Into),
+) -> Result<K::Successor, S::Error>
+where
+    T: FieldInner<From = [u8; N]>,
+    [u8; N]: Default,
+    K: FieldKind,
+    S: Source,
+{
+    decode_all(state, r, |value| {
+        if let Some(value) = value {
+            add(value);
+        }
+    })
+}
+
+/// Decode a field, passing each value to a closure, or `None` for those
+/// holding the 'invalid' marker value.
+fn decode_all<T, K, S, const N: usize>(
+    mut state: Field<T, K>,
+    r: &mut S,
+    mut add: impl FnMut(Option<T::Into>),
+) -> Result<K::Successor, S::Error>
+where
+    T: FieldInner<From = [u8; N]>,
+    [u8; N]: Default,
+    K: FieldKind,
+    S: Source,
+{
+    loop {
+        let (value, successor) = r.step(state)?;
+        add(value);
+
+        state = match successor {
+            FieldSuccessor::Next(successor) => return Ok(successor),
+            FieldSuccessor::MoreBytes(state) => state,
+        }
+    }
+}
+
+/// Decode a field, publishing the values of an array to a receiver at once, or
+/// a single value alone, after inspecting each.
+fn decode_values<T, K, S, O, const N: usize>(
+    mut state: Field<T, K>,
+    r: &mut S,
+    o: &mut Option<&mut O>,
+    f: u8,
+    mut inspect: impl FnMut(T::Into),
+) -> Result<K::Successor, S::Error>
+where
+    T: FieldInner<From = [u8; N]>,
+    [u8; N]: Default,
+    T::Into: Primitive,
+    K: FieldKind,
+    S: Source,
+    O: FromRecord + ?Sized,
+{
+    let base_type = state.base_type();
+
+    if state.bytes_remaining() as usize == N {
+        return decode_all(state, r, |v| match (v, o.as_deref_mut()) {
+            (Some(v), o) => {
+                inspect(v);
+
+                if let Some(o) = o {
+                    T::Into::add(o, f, v);
+                }
+            }
+            (None, Some(o)) => o.add_invalid(f, base_type),
+            (None, None) => {}
+        });
+    }
+
+    let count = state.bytes_remaining() / N as u8;
+    let len = state.bytes_remaining() as usize;
+
+    // Where the source holds the whole array, view little-endian values in
+    // place if aligned, publishing them without a copy.
+    let bytes = r.take_slice(len)?;
+    if let Some(bytes) = bytes {
+        state = match state.advance_in_place(bytes) {
+            Ok((values, successor)) => {
+                publish::<T, O>(values, o, f, base_type, count, inspect);
+                return Ok(successor);
+            }
+            Err(state) => state,
+        };
+    }
+
+    // Otherwise, gather the values in native byte order (keeping invalid
+    // values as their marker) to view them alike.
+    let mut buf = Aligned([0; u8::MAX as usize + 1]);
+    let mut i = 0;
+
+    let mut collect = |value| {
+        buf.0[i..i + N].copy_from_slice(&T::to(value, cfg!(target_endian = "little")));
+        i += N;
+    };
+
+    let successor = 'decode: {
+        if let Some(bytes) = bytes {
+            let (values, successor) = state.advance_slice(bytes);
+            values.for_each(&mut collect);
+
+            state = match successor {
+                FieldSuccessor::Next(successor) => break 'decode successor,
+                FieldSuccessor::MoreBytes(state) => state,
+            };
+        }
+
+        loop {
+            let (value, successor) = r.step(state)?;
+            collect(value);
+
+            state = match successor {
+                FieldSuccessor::Next(successor) => break 'decode successor,
+                FieldSuccessor::MoreBytes(state) => state,
+            };
+        }
+    };
+
+    // The buffer is aligned for any primitive.
+    let values = <[T::Into]>::ref_from_bytes(&buf.0[..len]).unwrap();
+    publish::<T, O>(values, o, f, base_type, count, inspect);
+
+    Ok(successor)
+}
+
+/// Bytes aligned for any primitive.
+#[repr(C, align(8))]
+struct Aligned<const N: usize>([u8; N]);
+
+/// Publish the values of an array to a receiver at once, or one value at a
+/// time following the position of each if any is invalid, after inspecting
+/// each valid value.
+fn publish<T, O>(
+    values: &[T::Into],
+    o: &mut Option<&mut O>,
+    f: u8,
+    base_type: u8,
+    count: u8,
+    mut inspect: impl FnMut(T::Into),
+) where
+    T: FieldInner,
+    T::Into: Primitive,
+    O: FromRecord + ?Sized,
+{
+    let is_valid = |v: &&T::Into| !T::is_invalid(v);
+
+    let mut valid = 0;
+    for &v in values.iter().filter(is_valid) {
+        inspect(v);
+        valid += 1;
+    }
+
+    if let Some(o) = o {
+        if valid == values.len() {
+            T::Into::add_slice(*o, f, values);
+        } else {
+            for (index, v) in (0..).zip(values) {
+                o.add_element(f, Element { index, count });
+
+                match is_valid(&v) {
+                    true => T::Into::add(*o, f, *v),
+                    false => o.add_invalid(f, base_type),
+                }
+            }
+        }
+    }
+}
+
+/// A primitive published to record receivers, alone or in arrays, which can be
+/// viewed in place.
+trait Primitive: Copy + Default + FromBytes + Immutable {
+    fn add<O: FromRecord + ?Sized>(o: &mut O, field: u8, value: Self);
+    fn add_slice<O: FromRecord + ?Sized>(o: &mut O, field: u8, values: &[Self]);
+}
+
+macro_rules! primitive {
+    ($($t:ty => $add:ident, $add_slice:ident;)*) => {$(
+        impl Primitive for $t {
+            fn add<O: FromRecord + ?Sized>(o: &mut O, field: u8, value: Self) {
+                o.$add(field, value)
+            }
+            fn add_slice<O: FromRecord + ?Sized>(o: &mut O, field: u8, values: &[Self]) {
+                o.$add_slice(field, values)
+            }
+        }
+    )*};
+}
+
+primitive! {
+    u8 => add_u8, add_u8_slice;
+    u16 => add_u16, add_u16_slice;
+    u32 => add_u32, add_u32_slice;
+    u64 => add_u64, add_u64_slice;
+    i8 => add_i8, add_i8_slice;
+    i16 => add_i16, add_i16_slice;
+    i32 => add_i32, add_i32_slice;
+    i64 => add_i64, add_i64_slice;
+    f32 => add_f32, add_f32_slice;
+    f64 => add_f64, add_f64_slice;
+}
+
+/// Split the bytes of a `string` field into its non-empty strings.
+fn strings(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    bytes.split(|&b| b == 0).filter(|s| !s.is_empty())
+}
+
+/// Decode a field without a typed representation, publishing its bytes whole.
+fn decode_raw<'a, K, S, O>(
+    state: Field<Unknown, K>,
+    r: &mut S,
+    o: &mut Option<&mut O>,
+    f: u8,
+    base_type: u8,
+) -> Result<K::Successor, S::Error>
+where
+    K: FieldKind,
+    S: Source + Retain<'a>,
+    O: FromRecord + Borrowing<'a> + ?Sized,
+{
+    let start = r.position();
+    let mut buf = [0; u8::MAX as usize];
+    let mut len = 0;
+
+    let successor = decode_field(state, r, |v| {
+        buf[len] = v;
+        len += 1;
+    })?;
+
+    if let Some(o) = o {
+        match r.retained(start) {
+            Some(bytes) => o.add_unknown_from(f, base_type, bytes),
+            None => o.add_unknown(f, base_type, &buf[..len]),
+        }
+    }
+
+    Ok(successor)
+}
+
+/// Take bytes from a stored definition record to fill a buffer exactly,
+/// advancing past them.
+fn replay(d: &mut &[u8], buf: &mut [u8]) -> Result<(), Exhausted> {
+    let (bytes, rest) = d.split_at_checked(buf.len()).ok_or(Exhausted)?;
+    buf.copy_from_slice(bytes);
+    *d = rest;
+
+    Ok(())
+}
@@ -0,0 +1,337 @@
+//! Recording and replay of decoded records.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::convert::Infallible;
+use std::vec::Vec;
+
+use super::{Element, FieldDescription, FromRecord, FromRecords, HeaderInfo, Skipped, Warning};
+
+extern crate std;
+
+/// An owned recording of the records published by a decoder.
+///
+/// A tape is a receiver accepting every record, which can be replayed into any
+/// other receiver, any number of times. This allows a document to be decoded
+/// (and its CRC checked) once, then processed repeatedly without the original
+/// bytes.
+///
+/// ```
+/// let mut tape = Tape::new();
+/// derailleur::avec::decode_slice(&data, &mut tape)?;
+///
+/// tape.replay(&mut activity)?;
+/// tape.replay(&mut export)?;
+/// ```
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tape {
+    events: Vec<Event>,
+    descriptions: Vec<FieldDescription>, // Referenced by developer field events.
+    bytes: Vec<u8>,                      // Referenced by string and unknown field events.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    Header(HeaderInfo),
+    Skipped(Skipped),
+    Finish,
+    Record(u16),
+    TimeOffset(u8),
+    Warning(Warning),
+    Element(u8, Element),
+    Field(u8, Value),
+    String(u8, usize, u8),
+    Unknown(u8, u8, usize, u8),
+    Developer(u16, Value),
+}
+
+/// A field value, as published to a receiver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Invalid(u8),
+}
+
+impl Value {
+    /// Publish the value for a field to a receiver.
+    pub(crate) fn add_to(self, field: u8, o: &mut dyn FromRecord) {
+        match self {
+            Value::U8(v) => o.add_u8(field, v),
+            Value::U16(v) => o.add_u16(field, v),
+            Value::U32(v) => o.add_u32(field, v),
+            Value::U64(v) => o.add_u64(field, v),
+            Value::I8(v) => o.add_i8(field, v),
+            Value::I16(v) => o.add_i16(field, v),
+            Value::I32(v) => o.add_i32(field, v),
+            Value::I64(v) => o.add_i64(field, v),
+            Value::F32(v) => o.add_f32(field, v),
+            Value::F64(v) => o.add_f64(field, v),
+            Value::Invalid(base_type) => o.add_invalid(field, base_type),
+        }
+    }
+
+    /// Publish the value for a developer field to a receiver.
+    pub(crate) fn add_developer_to(self, field: &FieldDescription, o: &mut dyn FromRecord) {
+        match self {
+            Value::U8(v) => o.add_developer_u8(field, v),
+            Value::U16(v) => o.add_developer_u16(field, v),
+            Value::U32(v) => o.add_developer_u32(field, v),
+            Value::U64(v) => o.add_developer_u64(field, v),
+            Value::I8(v) => o.add_developer_i8(field, v),
+            Value::I16(v) => o.add_developer_i16(field, v),
+            Value::I32(v) => o.add_developer_i32(field, v),
+            Value::I64(v) => o.add_developer_i64(field, v),
+            Value::F32(v) => o.add_developer_f32(field, v),
+            Value::F64(v) => o.add_developer_f64(field, v),
+            Value::Invalid(_) => o.add_developer_invalid(field),
+        }
+    }
+}
+
+impl Tape {
+    /// Create an empty tape.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of records on the tape.
+    pub fn len(&self) -> usize {
+        let records = self.events.iter();
+        records.filter(|e| matches!(e, Event::Record(_))).count()
+    }
+
+    /// Whether the tape holds no records.
+    pub fn is_empty(&self) -> bool {
+        let mut records = self.events.iter();
+        !records.any(|e| matches!(e, Event::Record(_)))
+    }
+
+    /// Publish the recorded records to a receiver.
+    ///
+    /// A failure of the receiver ends the replay, and is returned.
+    pub fn replay<E>(&self, o: &mut impl FromRecords<E>) -> Result<(), E> {
+        let mut record: Option<&mut dyn FromRecord> = None;
+
+        for event in &self.events {
+            // End the previous record before any other event of the document.
+            if let (
+                Event::Header(_) | Event::Skipped(_) | Event::Finish | Event::Record(_),
+                Some(r),
+            ) = (event, &mut record)
+            {
+                r.end();
+            }
+
+            match *event {
+                Event::Header(info) => {
+                    record = None;
+                    o.add_header(info)
+                }
+                Event::Skipped(skipped) => {
+                    record = None;
+                    o.add_skipped(skipped)
+                }
+                Event::Finish => {
+                    record = None;
+                    o.finish()?
+                }
+                Event::Record(id) => {
+                    // Stop early as the receiver directs.
+                    if o.control().is_break() {
+                        return o.finish();
+                    }
+
+                    record = o.add_record(id)?;
+
+                    if let Some(r) = &mut record {
+                        r.begin()
+                    }
+                }
+                event => {
+                    if let Some(r) = &mut record {
+                        self.replay_field(event, *r)
+                    }
+                }
+            }
+        }
+
+        if let Some(r) = &mut record {
+            r.end();
+        }
+
+        Ok(())
+    }
+}
+
+impl Tape {
+    /// Remove all events from the tape.
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.descriptions.clear();
+        self.bytes.clear();
+    }
+
+    /// Publish the events of a tape holding a single record, without its
+    /// document events, to a record receiver.
+    pub(crate) fn replay_record(&self, o: &mut dyn FromRecord) {
+        o.begin();
+
+        for &event in &self.events {
+            self.replay_field(event, o);
+        }
+
+        o.end();
+    }
+
+    /// Publish an event within a record to the record receiver.
+    fn replay_field(&self, event: Event, r: &mut dyn FromRecord) {
+        match event {
+            Event::Header(_) | Event::Skipped(_) | Event::Finish | Event::Record(_) => {}
+            Event::TimeOffset(offset) => r.add_time_offset(offset),
+            Event::Warning(warning) => r.add_warning(warning),
+            Event::Element(field, element) => r.add_element(field, element),
+            Event::Field(field, value) => value.add_to(field, r),
+            Event::String(field, start, len) => {
+                r.add_string(field, &self.bytes[start..start + len as usize])
+            }
+            Event::Unknown(field, base_type, start, len) => {
+                let bytes = &self.bytes[start..start + len as usize];
+                r.add_unknown(field, base_type, bytes)
+            }
+            Event::Developer(i, value) => value.add_developer_to(&self.descriptions[i as usize], r),
+        }
+    }
+
+    /// Record a value for a developer field, storing its description once.
+    fn add_developer(&mut self, field: &FieldDescription, value: Value) {
+        let i = match self.descriptions.iter().rposition(|d| d == field) {
+            Some(i) => i,
+            None => {
+                self.descriptions.push(*field);
+                self.descriptions.len() - 1
+            }
+        };
+
+        self.events.push(Event::Developer(i as u16, value));
+    }
+}
+
+impl FromRecords for Tape {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.events.push(Event::Record(id));
+        Ok(Some(self))
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.events.push(Event::Header(info));
+    }
+    fn add_skipped(&mut self, skipped: Skipped) {
+        self.events.push(Event::Skipped(skipped));
+    }
+    fn finish(&mut self) -> Result<(), Infallible> {
+        self.events.push(Event::Finish);
+        Ok(())
+    }
+}
+
+impl FromRecord for Tape {
+    fn add_time_offset(&mut self, offset: u8) {
+        self.events.push(Event::TimeOffset(offset));
+    }
+    fn add_warning(&mut self, warning: Warning) {
+        self.events.push(Event::Warning(warning));
+    }
+    fn add_element(&mut self, field: u8, element: Element) {
+        self.events.push(Event::Element(field, element));
+    }
+    fn add_u8(&mut self, field: u8, value: u8) {
+        self.events.push(Event::Field(field, Value::U8(value)));
+    }
+    fn add_u16(&mut self, field: u8, value: u16) {
+        self.events.push(Event::Field(field, Value::U16(value)));
+    }
+    fn add_u32(&mut self, field: u8, value: u32) {
+        self.events.push(Event::Field(field, Value::U32(value)));
+    }
+    fn add_u64(&mut self, field: u8, value: u64) {
+        self.events.push(Event::Field(field, Value::U64(value)));
+    }
+    fn add_i8(&mut self, field: u8, value: i8) {
+        self.events.push(Event::Field(field, Value::I8(value)));
+    }
+    fn add_i16(&mut self, field: u8, value: i16) {
+        self.events.push(Event::Field(field, Value::I16(value)));
+    }
+    fn add_i32(&mut self, field: u8, value: i32) {
+        self.events.push(Event::Field(field, Value::I32(value)));
+    }
+    fn add_i64(&mut self, field: u8, value: i64) {
+        self.events.push(Event::Field(field, Value::I64(value)));
+    }
+    fn add_f32(&mut self, field: u8, value: f32) {
+        self.events.push(Event::Field(field, Value::F32(value)));
+    }
+    fn add_f64(&mut self, field: u8, value: f64) {
+        self.events.push(Event::Field(field, Value::F64(value)));
+    }
+    fn add_string(&mut self, field: u8, value: &[u8]) {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(value);
+        self.events
+            .push(Event::String(field, start, value.len() as u8));
+    }
+    fn add_unknown(&mut self, field: u8, base_type: u8, bytes: &[u8]) {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.events
+            .push(Event::Unknown(field, base_type, start, bytes.len() as u8));
+    }
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.events
+            .push(Event::Field(field, Value::Invalid(base_type)));
+    }
+
+    fn add_developer_u8(&mut self, field: &FieldDescription, value: u8) {
+        self.add_developer(field, Value::U8(value));
+    }
+    fn add_developer_u16(&mut self, field: &FieldDescription, value: u16) {
+        self.add_developer(field, Value::U16(value));
+    }
+    fn add_developer_u32(&mut self, field: &FieldDescription, value: u32) {
+        self.add_developer(field, Value::U32(value));
+    }
+    fn add_developer_u64(&mut self, field: &FieldDescription, value: u64) {
+        self.add_developer(field, Value::U64(value));
+    }
+    fn add_developer_i8(&mut self, field: &FieldDescription, value: i8) {
+        self.add_developer(field, Value::I8(value));
+    }
+    fn add_developer_i16(&mut self, field: &FieldDescription, value: i16) {
+        self.add_developer(field, Value::I16(value));
+    }
+    fn add_developer_i32(&mut self, field: &FieldDescription, value: i32) {
+        self.add_developer(field, Value::I32(value));
+    }
+    fn add_developer_i64(&mut self, field: &FieldDescription, value: i64) {
+        self.add_developer(field, Value::I64(value));
+    }
+    fn add_developer_f32(&mut self, field: &FieldDescription, value: f32) {
+        self.add_developer(field, Value::F32(value));
+    }
+    fn add_developer_f64(&mut self, field: &FieldDescription, value: f64) {
+        self.add_developer(field, Value::F64(value));
+    }
+    fn add_developer_invalid(&mut self, field: &FieldDescription) {
+        self.add_developer(field, Value::Invalid(field.base_type()));
+    }
+}
@@ -0,0 +1,58 @@
+//! Reconstruction of compressed timestamps.
+
+/// Field number of the timestamp field common to all messages.
+pub(crate) const TIMESTAMP: u8 = 253;
+
+/// Tracker of the most recent timestamp in a document, expanding compressed
+/// timestamps against it.
+///
+/// Records with a compressed timestamp header carry only the five least
+/// significant bits of their timestamp, as a time offset. Its full value is
+/// found relative to the most recent timestamp, allowing the offset to roll
+/// over. The decoders do this themselves with
+/// [`DecodeOptions::synthesize_timestamps`](super::DecodeOptions::synthesize_timestamps),
+/// while this tracker suits receivers handling time offsets themselves:
+///
+/// ```
+/// fn add_u32(&mut self, field: u8, value: u32) {
+///     if field == 253 {
+///         self.tracker.update(value);
+///     }
+/// }
+/// fn add_time_offset(&mut self, offset: u8) {
+///     self.timestamp = self.tracker.expand(offset);
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampTracker {
+    timestamp: Option<u32>,
+}
+
+impl TimestampTracker {
+    /// Create a tracker, before any timestamp.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a full timestamp, as from field 253 of a record.
+    pub fn update(&mut self, timestamp: u32) {
+        self.timestamp = Some(timestamp);
+    }
+
+    /// Expand a compressed time offset into a full timestamp, which is noted
+    /// in turn.
+    ///
+    /// Returns `None` before any full timestamp has been noted.
+    pub fn expand(&mut self, offset: u8) -> Option<u32> {
+        let previous = self.timestamp?;
+        let timestamp = previous + (offset.wrapping_sub(previous as u8) & 0x1F) as u32;
+        self.timestamp = Some(timestamp);
+
+        Some(timestamp)
+    }
+
+    /// The most recent timestamp, whether full or expanded.
+    pub fn timestamp(&self) -> Option<u32> {
+        self.timestamp
+    }
+}
@@ -0,0 +1,204 @@
+//! Decoding and re-encoding of documents, for read-modify-write workflows.
+//!
+//! _Requires Cargo feature `std`._
+
+use std::{
+    io::{Read, Write},
+    vec::Vec,
+};
+
+use thiserror::Error;
+
+use crate::sans::data::BaseType;
+
+pub use super::raw::{FieldValue, Message};
+use super::{
+    DecodeOptions, DefinitionInfo, FromRecord, FromRecords, HeaderInfo,
+    raw::RawDocument,
+    reader,
+    writer::{self, EncodeError},
+};
+
+extern crate std;
+
+/// Errors occurring while transcoding a document.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// An error decoding the original document, including fields which can't
+    /// be encoded again.
+    #[error("Failed to decode: {0}")]
+    Decode(#[from] reader::Error<UnsupportedField>),
+    /// An error encoding the new document.
+    #[error("Failed to encode: {0}")]
+    Encode(#[from] EncodeError),
+}
+
+/// A field of the original document which can't be encoded again, as its base
+/// type is unknown to this library, or its size isn't a multiple of the size
+/// of its base type.
+#[derive(Debug, Error)]
+#[error(
+    "Field ({field}) of record ({global}) of base type {base_type:#04x} and size {size} can't be encoded."
+)]
+pub struct UnsupportedField {
+    pub global: u16,
+    pub field: u8,
+    pub base_type: u8,
+    pub size: u8,
+}
+
+/// Decode a document from a reader, passing each record to a closure, and
+/// encode the records it produces to a writer.
+///
+/// The closure receives each record with a list to push records to: push none
+/// to drop the record, push it (after any modification) to keep it, or push
+/// others alongside it to inject records. Document sizes, definitions, and
+/// CRCs are recomputed.
+///
+/// ```
+/// derailleur::avec::transcode(&mut input, &mut output, |mut message, out| {
+///     // Strip the serial number from `file_id` records.
+///     if message.global() == 0 {
+///         message.remove(3);
+///     }
+///     out.push(message);
+/// })?;
+/// ```
+///
+/// Each record keeps the size and base type of its fields from the definition
+/// it was decoded with, along with values holding the 'invalid' marker value
+/// and developer fields (see [`Message::set`] for modified fields). Local
+/// message numbers are assigned anew, and compressed timestamps are expanded
+/// into full timestamps. Fields of base types unknown to this library can't
+/// be encoded, so fail transcoding as [`UnsupportedField`], while developer
+/// fields without a description are dropped.
+///
+/// Developer fields refer to the `developer_data_id` and `field_description`
+/// records describing them, which must be kept alongside them.
+///
+/// _Requires Cargo feature `std`._
+pub fn transcode(
+    r: &mut impl Read,
+    w: &mut impl Write,
+    mut f: impl FnMut(Message, &mut Vec<Message>),
+) -> Result<(), Error> {
+    let options = DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    };
+
+    let mut document = Supported(RawDocument::new());
+    reader::decode_with(r, &mut document, &options)?;
+
+    let messages = document.0.into_messages();
+    let mut out = Vec::with_capacity(messages.len());
+    for message in messages {
+        f(message, &mut out);
+    }
+
+    writer::encode(w, &RawDocument::from(out))?;
+
+    Ok(())
+}
+
+/// Receiver collecting a document to transcode, refusing fields which can't be
+/// encoded again.
+struct Supported(RawDocument);
+
+impl FromRecords<UnsupportedField> for Supported {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, UnsupportedField> {
+        let Ok(record) = self.0.add_record(id);
+        Ok(record)
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, UnsupportedField> {
+        for f in definition.fields() {
+            let supported = BaseType::from_byte(f.base_type)
+                .is_some_and(|b| f.size != 0 && f.size.is_multiple_of(b.size()));
+
+            if !supported {
+                Err(UnsupportedField {
+                    global: id,
+                    field: f.number,
+                    base_type: f.base_type,
+                    size: f.size,
+                })?
+            }
+        }
+
+        let Ok(record) = self.0.add_record_with(id, definition);
+        Ok(record)
+    }
+    fn add_header(&mut self, info: HeaderInfo) {
+        self.0.add_header(info);
+    }
+}
+
+/// Messages and fields to strip from a document with [`anonymize`].
+///
+/// The default strips locations (from `record`, `lap`, `session`, and
+/// `course_point` messages), serial numbers (from `file_id` and `device_info`
+/// messages), and `user_profile` messages entirely.
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    /// Global message numbers of records to remove.
+    pub messages: Vec<u16>,
+    /// Global message and field numbers of fields to remove.
+    pub fields: Vec<(u16, u8)>,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self {
+            messages: Vec::from([3]),
+            fields: Vec::from([
+                (0, 3),   // `file_id.serial_number`
+                (18, 3),  // `session.start_position_lat`
+                (18, 4),  // `session.start_position_long`
+                (18, 29), // `session.nec_lat`
+                (18, 30), // `session.nec_long`
+                (18, 31), // `session.swc_lat`
+                (18, 32), // `session.swc_long`
+                (19, 3),  // `lap.start_position_lat`
+                (19, 4),  // `lap.start_position_long`
+                (19, 5),  // `lap.end_position_lat`
+                (19, 6),  // `lap.end_position_long`
+                (20, 0),  // `record.position_lat`
+                (20, 1),  // `record.position_long`
+                (23, 3),  // `device_info.serial_number`
+                (32, 2),  // `course_point.position_lat`
+                (32, 3),  // `course_point.position_long`
+            ]),
+        }
+    }
+}
+
+/// Decode a document from a reader, and encode it to a writer without the
+/// messages and fields of a redaction.
+///
+/// This is suited to sharing activities publicly. Records keep the definitions
+/// they were decoded with, less the fields removed. See [`transcode`] for how
+/// the document is rewritten.
+///
+/// _Requires Cargo feature `std`._
+pub fn anonymize(
+    r: &mut impl Read,
+    w: &mut impl Write,
+    redaction: &Redaction,
+) -> Result<(), Error> {
+    transcode(r, w, |mut message, out| {
+        let global = message.global();
+        if redaction.messages.contains(&global) {
+            return;
+        }
+
+        message.retain(|f| !redaction.fields.contains(&(global, f)));
+
+        out.push(message);
+    })
+}
@@ -0,0 +1,147 @@
+//! Parsed definition records.
+//!
+//! _Requires Cargo feature `std`._
+
+use std::vec::Vec;
+
+use super::{DefinitionInfo, DeveloperFieldInfo, FieldInfo, source::Stored};
+
+extern crate std;
+
+/// A definition record parsed from its stored bytes, describing the schema of
+/// the data records following it.
+///
+/// ```
+/// let view = DefinitionView::parse(&stored).unwrap();
+/// for (number, size, base_type, is_little_endian) in view.entries() {
+///     println!("field {number}: {size} bytes of {base_type:#04X}");
+/// }
+/// ```
+///
+/// _Requires Cargo feature `std`._
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefinitionView {
+    r: Vec<u8>, // The stored definition record, beginning with its record header.
+    fields: Vec<FieldInfo>,
+    developer_fields: Vec<DeveloperFieldInfo>,
+    size: usize, // Size of each data record, following its record header.
+}
+
+impl DefinitionView {
+    /// Parse the stored bytes of a definition record, beginning with its
+    /// record header.
+    ///
+    /// Returns `None` if the bytes are not a whole definition record.
+    pub fn parse(r: &[u8]) -> Option<Self> {
+        let [header, _, _, _, _, count, ..] = *r else {
+            return None;
+        };
+
+        let mut len = 6 + count as usize * 3;
+
+        // Developer fields follow their count, if flagged in the record header.
+        if header & 0x20 != 0 {
+            len += 1 + *r.get(len)? as usize * 3;
+        }
+
+        (header & 0x40 != 0 && r.len() == len).then(|| DefinitionInfo::new(r).into())
+    }
+
+    /// A view of the definition record, as received by [`FromRecords`].
+    ///
+    /// [`FromRecords`]: super::FromRecords
+    pub fn info(&self) -> DefinitionInfo<'_> {
+        DefinitionInfo::new(&self.r)
+    }
+
+    /// The stored bytes of the definition record, beginning with its record
+    /// header.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.r
+    }
+
+    /// Global message number of the records.
+    pub fn global(&self) -> u16 {
+        self.info().global()
+    }
+
+    /// Whether multi-byte values of the records are little-endian.
+    pub fn is_little_endian(&self) -> bool {
+        self.info().is_little_endian()
+    }
+
+    /// Fields of the records, in the order their values are received.
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    /// Developer fields of the records, following their fields.
+    pub fn developer_fields(&self) -> &[DeveloperFieldInfo] {
+        &self.developer_fields
+    }
+
+    /// Fields of the records as tuples of their field number, size, base type
+    /// and endianness.
+    pub fn entries(&self) -> impl Iterator<Item = (u8, u8, u8, bool)> + '_ {
+        let is_little_endian = self.is_little_endian();
+
+        self.fields
+            .iter()
+            .map(move |f| (f.number, f.size, f.base_type, is_little_endian))
+    }
+
+    /// Size of each data record in bytes, following its record header.
+    pub fn data_size(&self) -> usize {
+        self.size
+    }
+
+    /// Begin storing a definition record in place, reusing the allocations of
+    /// the previous one.
+    pub(crate) fn begin(&mut self, header: [u8; 1]) {
+        self.r.clear();
+        self.r.extend_from_slice(&header);
+    }
+
+    /// Add bytes of the definition record being stored.
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.r.extend_from_slice(bytes);
+    }
+
+    /// Parse the stored definition record, once all of its bytes are added.
+    pub(crate) fn end(&mut self) {
+        let definition = DefinitionInfo::new(&self.r);
+
+        self.fields.clear();
+        self.fields.extend(definition.fields());
+        self.developer_fields.clear();
+        self.developer_fields.extend(definition.developer_fields());
+
+        let fields = self.fields.iter().map(|f| f.size);
+        let developer_fields = self.developer_fields.iter().map(|f| f.size);
+        self.size = fields.chain(developer_fields).map(usize::from).sum();
+    }
+}
+
+impl From<DefinitionInfo<'_>> for DefinitionView {
+    fn from(definition: DefinitionInfo<'_>) -> Self {
+        let mut view = Self {
+            r: definition.r.to_vec(),
+            ..Default::default()
+        };
+        view.end();
+
+        view
+    }
+}
+
+impl AsRef<[u8]> for DefinitionView {
+    fn as_ref(&self) -> &[u8] {
+        &self.r
+    }
+}
+
+impl Stored for DefinitionView {
+    fn data_size(&self) -> usize {
+        self.size
+    }
+}
@@ -0,0 +1,146 @@
+//! Writer-based decoder and encoder implementations.
+//!
+//! _Requires Cargo feature `std`._
+
+use core::{convert::Infallible, marker::PhantomData};
+use std::{
+    io::{self, ErrorKind, Seek, SeekFrom, Write},
+    vec,
+};
+
+use thiserror::Error;
+
+use super::{
+    DecodeOptions, FromRecords, IntoRecords, RecordError,
+    push::PushDecoder,
+    reader::Error,
+    sink::{self, Sink},
+};
+
+extern crate std;
+
+/// A writer decoding the bytes written to it, publishing to a receiver.
+///
+/// This allows the decoder to be placed at the end of an existing pipeline,
+/// for example with [`io::copy`] from a decompressor. Errors while decoding are
+/// returned from [`Write::write`] with [`ErrorKind::InvalidData`].
+///
+/// Once the end of the document is reached, further writes return `Ok(0)`.
+/// Call [`DecodeWriter::finish`] to check that a whole document was written.
+/// A failure of the receiver, of error type `E`, is returned from
+/// [`Write::write`] in the same way.
+///
+/// _Requires Cargo feature `std`._
+pub struct DecodeWriter<'a, O, E = Infallible> {
+    decoder: PushDecoder,
+    o: &'a mut O,
+    error: PhantomData<fn() -> E>,
+}
+
+impl<'a, O: FromRecords<E>, E> DecodeWriter<'a, O, E> {
+    /// Create a writer at the start of a document, publishing to a receiver.
+    pub fn new(o: &'a mut O) -> Self {
+        Self::with_options(o, DecodeOptions::default())
+    }
+
+    /// Create a writer at the start of a document, publishing to a receiver,
+    /// with options.
+    pub fn with_options(o: &'a mut O, options: DecodeOptions) -> Self {
+        Self {
+            decoder: PushDecoder::with_options(options),
+            o,
+            error: PhantomData,
+        }
+    }
+
+    /// Check that the whole document was written.
+    pub fn finish(mut self) -> Result<(), Error<E>> {
+        if !self.decoder.end_of_input() {
+            Err(io::Error::from(ErrorKind::UnexpectedEof))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<O: FromRecords<E>, E> Write for DecodeWriter<'_, O, E>
+where
+    Error<E>: Into<std::boxed::Box<dyn std::error::Error + Send + Sync>>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.decoder.push(buf, self.o).map_err(|err| match err {
+            Error::Io { source, .. } => source,
+            err => io::Error::new(ErrorKind::InvalidData, err),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Errors occurring while encoding to a writer.
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    /// An error from the supplied writer.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Records could not be represented as a document.
+    #[error("Records could not be represented: {0}.")]
+    Record(#[from] RecordError),
+}
+
+/// Encode records to a writer as a document, with headers, definitions, and
+/// the trailing CRC.
+///
+/// Records are visited twice. Their definitions are planned on the first
+/// visit, knowing every record, so that as few definition records as possible
+/// are written.
+///
+/// Consider wrapping the writer in an [`io::BufWriter`], as records are
+/// written in many small pieces.
+///
+/// This method is also re-exported as `derailleur::avec::encode_writer`.
+///
+/// _Requires Cargo feature `std`._
+pub fn encode(w: &mut impl Write, i: &impl IntoRecords) -> Result<(), EncodeError> {
+    sink::encode(&mut WriterSink { w }, i, &mut vec![0; sink::STORAGE])
+}
+
+/// Encode records to a seekable writer as a document, visiting the records only
+/// once.
+///
+/// The document header is written with a placeholder size, then patched once
+/// all records are written, so the document need not be held in memory or
+/// encoded twice. The writer is left at the end of the document.
+///
+/// _Requires Cargo feature `std`._
+pub fn encode_seekable(
+    w: &mut (impl Write + Seek),
+    i: &impl IntoRecords,
+) -> Result<(), EncodeError> {
+    let start = w.stream_position()?;
+
+    let storage = &mut vec![0; sink::STORAGE];
+
+    sink::encode_deferred(&mut WriterSink { w }, i, storage, |s, header| {
+        let end = s.w.stream_position()?;
+        s.w.seek(SeekFrom::Start(start))?;
+        s.w.write_all(header)?;
+        s.w.seek(SeekFrom::Start(end))?;
+
+        Ok(())
+    })
+}
+
+struct WriterSink<'a, W> {
+    w: &'a mut W,
+}
+
+impl<W: Write> Sink for WriterSink<'_, W> {
+    type Error = EncodeError;
+
+    fn put(&mut self, w: &[u8]) -> Result<(), EncodeError> {
+        Ok(self.w.write_all(w)?)
+    }
+}
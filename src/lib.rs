@@ -17,8 +17,25 @@
 //!
 //! The following crate feature flags are available:
 //!
+//! - `async`: enable decoders over asynchronous readers, using the `futures`
+//!   traits.
+//! - `bytes`: enable decoding from buffers of the `bytes` crate.
 //! - `derive`: enable derive macros (default).
-//! - `std`: enable reader-based decoder (default).
+//! - `embedded-io-async`: enable a decoder over asynchronous readers, using the
+//!   `embedded-io-async` traits (without requiring `std`).
+//! - `flate2`: enable transparent decompression of gzip-compressed documents
+//!   in reader-based decoders.
+//! - `profile`: enable prebuilt record sets for common documents.
+//! - `small-crc`: compute cyclic redundancy checks with a smaller, slower
+//!   table, for targets short of flash memory.
+//! - `std`: enable reader- and writer-based decoders (default).
+//! - `testing`: enable a builder of synthetic documents, for testing.
+//! - `tokio`: enable decoders over asynchronous readers, using the Tokio
+//!   traits.
+//! - `tracing`: emit events for headers, definitions, data records, and
+//!   errors while decoding, using the `tracing` crate.
 
 pub mod avec;
 pub mod sans;
+#[cfg(feature = "testing")]
+pub mod testing;
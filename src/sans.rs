@@ -14,16 +14,29 @@
 //! All states are represented by a zero-size, non-copy token. Once enough bytes
 //! are ready, transition to another state by calling the token's `advance`
 //! method. This will return a successor state token, along with any extracted
-//! data.
+//! data. Where more than one state may follow, the successor is an enum naming
+//! each possibility, such as [`data::FieldSuccessor`]. States taking bytes of
+//! the document implement [`step::Step`], naming the number of bytes required,
+//! and can be advanced from any source of bytes with [`step::drive`].
 //!
 //! When decoding a data record, the finite-state machine performs a second,
 //! interwoven pass over the definition record. This frees implementations to
 //! choose how they manage memory constraints. The bytes used to advance a
 //! sequence of these `Alt`-suffixed definition states must match those used to
 //! advance through their first-pass counterparts with the same field number.
+//! The second pass begins by replaying the definition record's header, which
+//! signals whether developer fields follow the standard fields.
+//!
+//! Developer fields do not declare their base type in the definition record.
+//! Instead, it is given by a `field_description` message earlier in the
+//! document, which the implementation must keep track of. On reaching a
+//! [`data::DeveloperField`], advance with the described base type, or skip the
+//! field if it has no known description.
 //!
 //! Only the initial state, re-exported for convenience as [`Decoder`], can be
-//! constructed.
+//! constructed. For random access to a previously decoded document, a
+//! [`header::RecordHeader`] state can also be constructed at a known record
+//! boundary.
 //!
 //! This architecture enables the compiler and type system to guide applications
 //! toward a correct implementation. However, some areas of the decoding process
@@ -31,21 +44,33 @@
 //! written:
 //!
 //! - Reading bytes from the correct place in the document, including buffering
-//! or seeking as necessary.
+//!   or seeking as necessary.
 //!
 //! - Ending decoding once the specified number of document bytes have been
-//! read.
+//!   read.
 //!
 //! - Applying cyclic redundancy checks. A helper function is provided in the
-//! [`check`] module.
+//!   [`check`] module.
 //!
 //! Implementers are recommended to begin by studying and modifying a decoder
 //! from the [`crate::avec`] module.
+//!
+//! # Encoding
+//!
+//! The [`encode`] module holds a mirror-image finite-state machine, entered
+//! through [`Encoder`], whose state tokens return the bytes to write rather
+//! than consuming them.
 
 pub mod check;
 pub mod data;
 pub mod definition;
+mod developer;
+pub mod encode;
 pub mod header;
+pub mod step;
 
 /// Entrypoint to the finite-state machine.
 pub type Decoder = header::DocumentHeader;
+
+/// Entrypoint to the encoding finite-state machine.
+pub type Encoder = encode::DocumentHeader;
@@ -1,4 +1,8 @@
 //! Helper for computing cyclic redundancy checks.
+//!
+//! By default, bytes are accumulated with a 256-entry table. With Cargo feature
+//! `small-crc`, a 16-entry table taking two steps per byte is used instead, for
+//! targets short of flash memory.
 
 /// Accumulate a slice of bytes into a cyclic redundancy check value.
 pub fn compute_crc(init: u16, r: &[u8]) -> u16 {
@@ -6,6 +10,38 @@ pub fn compute_crc(init: u16, r: &[u8]) -> u16 {
 }
 
 /// Accumulate a single byte into a cyclic redundancy check value.
+#[cfg(not(feature = "small-crc"))]
+fn crc_byte(crc: u16, b: u8) -> u16 {
+    /// Values of each byte, generated from the reflected polynomial.
+    const CRC_TABLE: [u16; 256] = {
+        let mut table = [0; 256];
+
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u16;
+
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xA001
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+
+            table[i] = crc;
+            i += 1;
+        }
+
+        table
+    };
+
+    (crc >> 8) ^ CRC_TABLE[((crc ^ b as u16) & 0xFF) as usize]
+}
+
+/// Accumulate a single byte into a cyclic redundancy check value.
+#[cfg(feature = "small-crc")]
 fn crc_byte(mut crc: u16, b: u8) -> u16 {
     const CRC_TABLE: [u16; 16] = [
         0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800,
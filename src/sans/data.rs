@@ -2,57 +2,231 @@
 
 use core::marker::PhantomData;
 
-use either::Either::{self, Left, Right};
+use zerocopy::{FromBytes, Immutable};
 
-use super::{definition::DefinitionFieldAlt, header::RecordHeader};
+use super::{
+    definition::{DataSuccessor, DefinitionFieldAlt, DeveloperDefinitionAlt},
+    header::RecordHeader,
+    step::Step,
+};
+
+pub use super::developer::{Developer, DeveloperField, DeveloperFieldSuccessor};
 
 /// State token to decode a field of base type `T`.
 #[derive(Debug)]
-pub struct Field<T> {
+pub struct Field<T, K = Standard> {
     pub(super) fields_remaining: u8,
     pub(super) bytes_remaining: u8,
     pub(super) is_little_endian: bool,
-    pub(super) _phantom: PhantomData<T>,
+    pub(super) has_developer: bool,
+    pub(super) base_type: u8,
+    pub(super) _phantom: PhantomData<(T, K)>,
+}
+
+impl<T, K> Field<T, K> {
+    pub(super) fn new(
+        fields_remaining: u8,
+        bytes_remaining: u8,
+        is_little_endian: bool,
+        has_developer: bool,
+        base_type: u8,
+    ) -> Self {
+        Self {
+            fields_remaining,
+            bytes_remaining,
+            is_little_endian,
+            has_developer,
+            base_type,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of bytes remaining to be decoded in the field.
+    pub fn bytes_remaining(&self) -> u8 {
+        self.bytes_remaining
+    }
+
+    /// The base type byte declared for the field, distinguishing base types
+    /// decoded alike (such as `enum`, `uint8` and `byte`).
+    ///
+    /// Use [`BaseType::from_byte`] to interpret it.
+    pub fn base_type(&self) -> u8 {
+        self.base_type
+    }
 }
 
-impl<T: FieldInner> Field<T> {
+impl<T: FieldInner, K: FieldKind> Field<T, K> {
     /// Transition to another state by decoding a field of base type `T`.
     ///
     /// Returns the field value as a Rust primitive if the field did not contain
     /// its 'invalid' marker value, and a successor state.
-    pub fn advance(
-        self,
-        r: T::From,
-    ) -> (
-        Option<T::Into>,
-        Either<Either<DefinitionFieldAlt, RecordHeader>, Self>,
-    ) {
+    pub fn advance(self, r: T::From) -> (Option<T::Into>, FieldSuccessor<T, K>) {
         let value = T::from(r, self.is_little_endian);
 
         let size = size_of::<T::From>() as u8;
 
         let successor = if self.bytes_remaining == size {
-            Left(if self.fields_remaining != 0 {
-                Left(DefinitionFieldAlt {
-                    fields_remaining: self.fields_remaining,
-                    is_little_endian: self.is_little_endian,
-                })
-            } else {
-                Right(RecordHeader(()))
-            })
+            FieldSuccessor::Next(K::successor(Field::new(
+                self.fields_remaining,
+                0,
+                self.is_little_endian,
+                self.has_developer,
+                self.base_type,
+            )))
         } else {
-            Right(Self {
-                fields_remaining: self.fields_remaining,
-                bytes_remaining: self.bytes_remaining - size,
-                is_little_endian: self.is_little_endian,
-                _phantom: PhantomData,
-            })
+            FieldSuccessor::MoreBytes(Self::new(
+                self.fields_remaining,
+                self.bytes_remaining - size,
+                self.is_little_endian,
+                self.has_developer,
+                self.base_type,
+            ))
         };
 
         (value, successor)
     }
 }
 
+impl<T, K, const N: usize> Field<T, K>
+where
+    T: FieldInner<From = [u8; N]>,
+    K: FieldKind,
+{
+    /// Transition to another state by decoding the values of an array field
+    /// from a contiguous buffer.
+    ///
+    /// Values are decoded from the start of the buffer, up to the bytes
+    /// remaining in the field. Bytes beyond the field, or not forming a whole
+    /// value, are left undecoded.
+    ///
+    /// Returns the field values as Rust primitives, each if the value did not
+    /// contain its 'invalid' marker value, and a successor state.
+    pub fn advance_slice(
+        self,
+        r: &[u8],
+    ) -> (
+        impl Iterator<Item = Option<T::Into>> + use<'_, T, K, N>,
+        FieldSuccessor<T, K>,
+    ) {
+        let len = r.len().min(self.bytes_remaining as usize) / N * N;
+        let is_little_endian = self.is_little_endian;
+
+        let (values, _) = r[..len].as_chunks::<N>();
+        let values = values.iter().map(move |&r| T::from(r, is_little_endian));
+
+        let successor = if self.bytes_remaining as usize == len {
+            FieldSuccessor::Next(K::successor(Field::new(
+                self.fields_remaining,
+                0,
+                self.is_little_endian,
+                self.has_developer,
+                self.base_type,
+            )))
+        } else {
+            FieldSuccessor::MoreBytes(Self::new(
+                self.fields_remaining,
+                self.bytes_remaining - len as u8,
+                self.is_little_endian,
+                self.has_developer,
+                self.base_type,
+            ))
+        };
+
+        (values, successor)
+    }
+
+    /// Transition to another state by viewing the values of a whole array
+    /// field in place, without copying them.
+    ///
+    /// Values are viewed only for a little-endian record on a little-endian
+    /// target, where the buffer holds the remaining bytes of the field and is
+    /// aligned for the primitive. Otherwise, the state is returned unchanged,
+    /// to be advanced by other means.
+    ///
+    /// Returns the field values as Rust primitives, including those containing
+    /// their 'invalid' marker value (see [`FieldInner::is_invalid`]), and a
+    /// successor state.
+    pub fn advance_in_place(self, r: &[u8]) -> Result<(&[T::Into], K::Successor), Self>
+    where
+        T::Into: FromBytes + Immutable,
+    {
+        if !(cfg!(target_endian = "little") && self.is_little_endian) {
+            return Err(self);
+        }
+
+        let Some(r) = r.get(..self.bytes_remaining as usize) else {
+            return Err(self);
+        };
+
+        let Ok(values) = <[T::Into]>::ref_from_bytes(r) else {
+            return Err(self);
+        };
+
+        let successor = K::successor(Field::new(
+            self.fields_remaining,
+            0,
+            self.is_little_endian,
+            self.has_developer,
+            self.base_type,
+        ));
+
+        Ok((values, successor))
+    }
+}
+
+impl<K: FieldKind> Field<(), K> {
+    /// Transition to another state past a field with a size of zero, without
+    /// decoding any bytes.
+    ///
+    /// Returns a successor state.
+    pub fn advance(self) -> K::Successor {
+        K::successor(self)
+    }
+}
+
+/// State following a value of a field of base type `T`.
+#[derive(Debug)]
+pub enum FieldSuccessor<T, K: FieldKind = Standard> {
+    /// The field is complete, followed by the state following a field of its
+    /// kind.
+    Next(K::Successor),
+    /// Values of the field remain to be decoded.
+    MoreBytes(Field<T, K>),
+}
+
+/// The kind of a field, determining the state following it.
+pub trait FieldKind: Sized {
+    /// The state following the last field of this kind.
+    type Successor;
+
+    #[doc(hidden)]
+    fn successor(field: Field<(), Self>) -> Self::Successor;
+}
+
+/// Marker for fields defined by the FIT profile.
+#[derive(Debug)]
+pub struct Standard;
+
+impl FieldKind for Standard {
+    type Successor = DataSuccessor;
+
+    fn successor(field: Field<(), Self>) -> Self::Successor {
+        if field.fields_remaining != 0 {
+            DataSuccessor::NextField(DefinitionFieldAlt {
+                fields_remaining: field.fields_remaining,
+                is_little_endian: field.is_little_endian,
+                has_developer: field.has_developer,
+            })
+        } else if field.has_developer {
+            DataSuccessor::Developer(DeveloperDefinitionAlt {
+                is_little_endian: field.is_little_endian,
+            })
+        } else {
+            DataSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
+
 pub trait FieldInner {
     /// The data storing this base type.
     type From;
@@ -61,10 +235,39 @@ pub trait FieldInner {
 
     /// Convert data of this base type to the corresponding primitive, if valid.
     fn from(r: Self::From, is_le: bool) -> Option<Self::Into>;
+
+    /// Whether a primitive is the 'invalid' marker value of this base type.
+    fn is_invalid(value: &Self::Into) -> bool {
+        _ = value;
+        false
+    }
+
+    /// Convert a primitive to data of this base type, storing the 'invalid'
+    /// marker value in place of `None`.
+    fn to(value: Option<Self::Into>, is_le: bool) -> Self::From;
 }
 
 macro_rules! field_inner {
+    // Floats are invalid with all bits set (a NaN), so compare their bits.
+    ($t:ident, $into:ident, bits $bits:ident, $(#[$attr:meta])*) => {
+        field_inner!(
+            $t,
+            $into,
+            |x: $into| x.to_bits() == $bits::MAX,
+            $into::from_bits($bits::MAX),
+            $(#[$attr])*
+        );
+    };
     ($t:ident, $into:ident, $invalid:ident, $(#[$attr:meta])*) => {
+        field_inner!(
+            $t,
+            $into,
+            |x: $into| x == $into::$invalid,
+            $into::$invalid,
+            $(#[$attr])*
+        );
+    };
+    ($t:ident, $into:ident, $is_invalid:expr, $invalid:expr, $(#[$attr:meta])*) => {
         $(#[$attr])*
         #[derive(Debug)]
         pub struct $t;
@@ -80,18 +283,32 @@ macro_rules! field_inner {
                     Self::Into::from_be_bytes(r)
                 };
 
-                if x != Self::Into::$invalid {
+                if !Self::is_invalid(&x) {
                     Some(x)
                 } else {
                     None
                 }
             }
+
+            fn is_invalid(value: &Self::Into) -> bool {
+                ($is_invalid)(*value)
+            }
+
+            fn to(value: Option<Self::Into>, is_le: bool) -> Self::From {
+                let x = value.unwrap_or($invalid);
+
+                if is_le {
+                    x.to_le_bytes()
+                } else {
+                    x.to_be_bytes()
+                }
+            }
         }
     };
 }
 
 field_inner!(U8, u8, MAX, /** `uint8`, `enum`, `byte` */);
-field_inner!(U8Z, u8, MIN, /** `uint8z`, `string` */);
+field_inner!(U8Z, u8, MIN, /** `uint8z` */);
 field_inner!(U16, u16, MAX,/** `uint16` */);
 field_inner!(U16Z, u16, MIN, /** `uint16z` */);
 field_inner!(U32, u32, MAX, /** `uint32` */);
@@ -104,25 +321,283 @@ field_inner!(I16, i16, MAX, /** `sint16` */);
 field_inner!(I32, i32, MAX, /** `sint32` */);
 field_inner!(I64, i64, MAX, /** `sint64` */);
 
-field_inner!(F32, f32, MAX, /** `float32` */);
-field_inner!(F64, f64, MAX, /** `float64` */);
+field_inner!(F32, f32, bits u32, /** `float32` */);
+field_inner!(F64, f64, bits u64, /** `float64` */);
+
+/// `string`, decoded byte-by-byte including NUL terminators, so that multiple
+/// strings in a field can be separated.
+#[derive(Debug)]
+pub struct Str;
+
+impl FieldInner for Str {
+    type From = [u8; 1];
+    type Into = u8;
+
+    fn from(r: Self::From, _is_le: bool) -> Option<Self::Into> {
+        Some(r[0])
+    }
+
+    fn to(value: Option<Self::Into>, _is_le: bool) -> Self::From {
+        [value.unwrap_or(0)]
+    }
+}
+
+/// A base type unknown to this library, decoded as raw bytes.
+#[derive(Debug)]
+pub struct Unknown;
+
+impl FieldInner for Unknown {
+    type From = [u8; 1];
+    type Into = u8;
+
+    fn from(r: Self::From, _is_le: bool) -> Option<Self::Into> {
+        Some(r[0])
+    }
+
+    fn to(value: Option<Self::Into>, _is_le: bool) -> Self::From {
+        [value.unwrap_or(u8::MAX)]
+    }
+}
+
+/// A base type of the FIT protocol, as declared by a field definition.
+///
+/// Multi-byte base types set the high bit of their protocol byte, marking them
+/// as subject to the endianness of the record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum BaseType {
+    /// `enum`
+    Enum = 0x00,
+    /// `sint8`
+    Sint8 = 0x01,
+    /// `uint8`
+    Uint8 = 0x02,
+    /// `sint16`
+    Sint16 = 0x83,
+    /// `uint16`
+    Uint16 = 0x84,
+    /// `sint32`
+    Sint32 = 0x85,
+    /// `uint32`
+    Uint32 = 0x86,
+    /// `string`, NUL-terminated.
+    String = 0x07,
+    /// `float32`
+    Float32 = 0x88,
+    /// `float64`
+    Float64 = 0x89,
+    /// `uint8z`
+    Uint8z = 0x0A,
+    /// `uint16z`
+    Uint16z = 0x8B,
+    /// `uint32z`
+    Uint32z = 0x8C,
+    /// `byte`
+    Byte = 0x0D,
+    /// `sint64`
+    Sint64 = 0x8E,
+    /// `uint64`
+    Uint64 = 0x8F,
+    /// `uint64z`
+    Uint64z = 0x90,
+}
+
+impl BaseType {
+    /// The base type declared by a byte of a field definition, if known.
+    pub const fn from_byte(base_type: u8) -> Option<Self> {
+        Some(match base_type {
+            0x00 => BaseType::Enum,
+            0x01 => BaseType::Sint8,
+            0x02 => BaseType::Uint8,
+            0x83 => BaseType::Sint16,
+            0x84 => BaseType::Uint16,
+            0x85 => BaseType::Sint32,
+            0x86 => BaseType::Uint32,
+            0x07 => BaseType::String,
+            0x88 => BaseType::Float32,
+            0x89 => BaseType::Float64,
+            0x0A => BaseType::Uint8z,
+            0x8B => BaseType::Uint16z,
+            0x8C => BaseType::Uint32z,
+            0x0D => BaseType::Byte,
+            0x8E => BaseType::Sint64,
+            0x8F => BaseType::Uint64,
+            0x90 => BaseType::Uint64z,
+            _ => return None,
+        })
+    }
+
+    /// The byte declaring this base type in a field definition.
+    pub const fn byte(self) -> u8 {
+        self as u8
+    }
+
+    /// Size of a single value of this base type in bytes.
+    pub const fn size(self) -> u8 {
+        match self {
+            BaseType::Enum
+            | BaseType::Sint8
+            | BaseType::Uint8
+            | BaseType::String
+            | BaseType::Uint8z
+            | BaseType::Byte => 1,
+            BaseType::Sint16 | BaseType::Uint16 | BaseType::Uint16z => 2,
+            BaseType::Sint32 | BaseType::Uint32 | BaseType::Float32 | BaseType::Uint32z => 4,
+            BaseType::Float64 | BaseType::Sint64 | BaseType::Uint64 | BaseType::Uint64z => 8,
+        }
+    }
+
+    /// The 'invalid' marker value of this base type, as its bits.
+    pub const fn invalid(self) -> u64 {
+        match self {
+            BaseType::Enum | BaseType::Uint8 | BaseType::Byte => u8::MAX as u64,
+            BaseType::Sint8 => i8::MAX as u64,
+            BaseType::Sint16 => i16::MAX as u64,
+            BaseType::Uint16 => u16::MAX as u64,
+            BaseType::Sint32 => i32::MAX as u64,
+            BaseType::Uint32 | BaseType::Float32 => u32::MAX as u64,
+            BaseType::Float64 | BaseType::Uint64 => u64::MAX,
+            BaseType::Sint64 => i64::MAX as u64,
+            BaseType::String | BaseType::Uint8z | BaseType::Uint16z | BaseType::Uint32z => 0,
+            BaseType::Uint64z => 0,
+        }
+    }
+}
+
+impl From<BaseType> for u8 {
+    fn from(base_type: BaseType) -> Self {
+        base_type.byte()
+    }
+}
+
+impl TryFrom<u8> for BaseType {
+    type Error = u8;
+
+    /// Convert a byte of a field definition, returning it if unknown.
+    fn try_from(base_type: u8) -> Result<Self, u8> {
+        BaseType::from_byte(base_type).ok_or(base_type)
+    }
+}
 
 /// A `Field` state token for a base type.
-pub enum AnyField {
-    U8(Field<U8>),
-    U8Z(Field<U8Z>),
-    U16(Field<U16>),
-    U16Z(Field<U16Z>),
-    U32(Field<U32>),
-    U32Z(Field<U32Z>),
-    U64(Field<U64>),
-    U64Z(Field<U64Z>),
-
-    I8(Field<I8>),
-    I16(Field<I16>),
-    I32(Field<I32>),
-    I64(Field<I64>),
-
-    F32(Field<F32>),
-    F64(Field<F64>),
+#[derive(Debug)]
+pub enum AnyField<K = Standard> {
+    U8(Field<U8, K>),
+    U8Z(Field<U8Z, K>),
+    U16(Field<U16, K>),
+    U16Z(Field<U16Z, K>),
+    U32(Field<U32, K>),
+    U32Z(Field<U32Z, K>),
+    U64(Field<U64, K>),
+    U64Z(Field<U64Z, K>),
+
+    I8(Field<I8, K>),
+    I16(Field<I16, K>),
+    I32(Field<I32, K>),
+    I64(Field<I64, K>),
+
+    F32(Field<F32, K>),
+    F64(Field<F64, K>),
+
+    String(Field<Str, K>),
+
+    /// A field of an unknown base type (for example, from a corrupt document
+    /// or a future profile), to be skipped or interpreted by the caller.
+    Unknown(Field<Unknown, K>),
+    /// A field whose size is not a multiple of the size of its base type,
+    /// decoded as raw bytes.
+    Misaligned(Field<Unknown, K>),
+    /// A field with a size of zero, holding no values.
+    Empty(Field<(), K>),
+}
+
+impl<K> AnyField<K> {
+    /// Create a state token for a field of a base type, if the base type is
+    /// known.
+    pub(super) fn new(base_type: u8, field: Field<(), K>) -> Option<Self> {
+        let Field {
+            fields_remaining: f,
+            bytes_remaining: b,
+            is_little_endian: e,
+            has_developer: d,
+            ..
+        } = field;
+        let t = base_type;
+
+        Some(match BaseType::from_byte(base_type)? {
+            BaseType::Enum | BaseType::Uint8 | BaseType::Byte => {
+                AnyField::U8(Field::new(f, b, e, d, t))
+            }
+            BaseType::Sint8 => AnyField::I8(Field::new(f, b, e, d, t)),
+            BaseType::Sint16 => AnyField::I16(Field::new(f, b, e, d, t)),
+            BaseType::Uint16 => AnyField::U16(Field::new(f, b, e, d, t)),
+            BaseType::Sint32 => AnyField::I32(Field::new(f, b, e, d, t)),
+            BaseType::Uint32 => AnyField::U32(Field::new(f, b, e, d, t)),
+            BaseType::String => AnyField::String(Field::new(f, b, e, d, t)),
+            BaseType::Float32 => AnyField::F32(Field::new(f, b, e, d, t)),
+            BaseType::Float64 => AnyField::F64(Field::new(f, b, e, d, t)),
+            BaseType::Uint8z => AnyField::U8Z(Field::new(f, b, e, d, t)),
+            BaseType::Uint16z => AnyField::U16Z(Field::new(f, b, e, d, t)),
+            BaseType::Uint32z => AnyField::U32Z(Field::new(f, b, e, d, t)),
+            BaseType::Sint64 => AnyField::I64(Field::new(f, b, e, d, t)),
+            BaseType::Uint64 => AnyField::U64(Field::new(f, b, e, d, t)),
+            BaseType::Uint64z => AnyField::U64Z(Field::new(f, b, e, d, t)),
+        })
+    }
+
+    /// The base type byte declared for the field, as with
+    /// [`Field::base_type`].
+    pub fn base_type(&self) -> u8 {
+        match self {
+            AnyField::U8(f) => f.base_type(),
+            AnyField::U8Z(f) => f.base_type(),
+            AnyField::U16(f) => f.base_type(),
+            AnyField::U16Z(f) => f.base_type(),
+            AnyField::U32(f) => f.base_type(),
+            AnyField::U32Z(f) => f.base_type(),
+            AnyField::U64(f) => f.base_type(),
+            AnyField::U64Z(f) => f.base_type(),
+            AnyField::I8(f) => f.base_type(),
+            AnyField::I16(f) => f.base_type(),
+            AnyField::I32(f) => f.base_type(),
+            AnyField::I64(f) => f.base_type(),
+            AnyField::F32(f) => f.base_type(),
+            AnyField::F64(f) => f.base_type(),
+            AnyField::String(f) => f.base_type(),
+            AnyField::Unknown(f) | AnyField::Misaligned(f) => f.base_type(),
+            AnyField::Empty(f) => f.base_type(),
+        }
+    }
+}
+
+impl<K: FieldKind> AnyField<K> {
+    /// Size of a single value of the field's base type, taken by each
+    /// `advance` of the state token it holds (as with [`Step::LEN`]).
+    ///
+    /// Fields with a size of zero take no bytes.
+    pub fn value_size(&self) -> u8 {
+        fn len<S: Step>(_: &S) -> u8 {
+            S::LEN as u8
+        }
+
+        match self {
+            AnyField::U8(s) => len(s),
+            AnyField::U8Z(s) => len(s),
+            AnyField::U16(s) => len(s),
+            AnyField::U16Z(s) => len(s),
+            AnyField::U32(s) => len(s),
+            AnyField::U32Z(s) => len(s),
+            AnyField::U64(s) => len(s),
+            AnyField::U64Z(s) => len(s),
+            AnyField::I8(s) => len(s),
+            AnyField::I16(s) => len(s),
+            AnyField::I32(s) => len(s),
+            AnyField::I64(s) => len(s),
+            AnyField::F32(s) => len(s),
+            AnyField::F64(s) => len(s),
+            AnyField::String(s) => len(s),
+            AnyField::Unknown(s) | AnyField::Misaligned(s) => len(s),
+            AnyField::Empty(s) => len(s),
+        }
+    }
 }
@@ -1,32 +1,49 @@
 //! States processing definition records.
 
-use core::marker::PhantomData;
-
-use either::Either::{self, Left, Right};
+use tartan_bitfield::bitfield;
 use zerocopy::FromBytes;
 
-use crate::sans::data::Field;
+use super::{
+    data::{AnyField, Field},
+    header::RecordHeader,
+};
 
-use super::{data::AnyField, header::RecordHeader};
+pub use super::developer::{
+    DeveloperDataSuccessor, DeveloperDefinition, DeveloperDefinitionAlt, DeveloperDefinitionField,
+    DeveloperDefinitionFieldAlt, DeveloperDefinitionSuccessor,
+};
 
 /// State token to perform a first-pass decoding of a definition message.
 #[derive(Debug)]
-pub struct Definition(pub(super) ());
+pub struct Definition {
+    pub(super) has_developer: bool,
+}
 
 impl Definition {
+    /// Whether the definition message describes developer fields, following
+    /// its standard fields.
+    pub fn has_developer_data(&self) -> bool {
+        self.has_developer
+    }
+
     /// Transition to another state by performing a first-pass decoding of a
     /// definition message.
     ///
-    /// Returns a successor state token.
-    pub fn advance(self, r: [u8; 5]) -> Either<DefinitionField, RecordHeader> {
+    /// Returns a successor state token, giving the size of the data records
+    /// described once the definition record is complete.
+    pub fn advance(self, r: [u8; 5]) -> DefinitionSuccessor {
         let DefinitionMessage {
             fields_remaining, ..
         } = zerocopy::transmute!(r);
 
         if fields_remaining != 0 {
-            Left(DefinitionField { fields_remaining })
+            DefinitionSuccessor::NextField(DefinitionField {
+                fields_remaining,
+                has_developer: self.has_developer,
+                data_size: 0,
+            })
         } else {
-            Right(RecordHeader(()))
+            end_of_fields(self.has_developer, 0)
         }
     }
 }
@@ -35,25 +52,57 @@ impl Definition {
 #[derive(Debug)]
 pub struct DefinitionField {
     pub(super) fields_remaining: u8,
+    pub(super) has_developer: bool,
+    pub(super) data_size: u32,
 }
 
 impl DefinitionField {
     /// Transition to another state by performing a first-pass decoding of a
     /// definition field.
     ///
-    /// Returns a successor state token.
-    pub fn advance(self, _r: [u8; 3]) -> Either<DefinitionField, RecordHeader> {
+    /// Returns a successor state token, giving the size of the data records
+    /// described once the definition record is complete.
+    pub fn advance(self, r: [u8; 3]) -> DefinitionSuccessor {
+        let [_, size, _] = r;
+
         let fields_remaining = self.fields_remaining - 1;
+        let data_size = self.data_size + size as u32;
 
         if fields_remaining != 0 {
-            Left(DefinitionField { fields_remaining })
+            DefinitionSuccessor::NextField(DefinitionField {
+                fields_remaining,
+                has_developer: self.has_developer,
+                data_size,
+            })
         } else {
-            Right(RecordHeader(()))
+            end_of_fields(self.has_developer, data_size)
         }
     }
 }
 
+/// Select the state following the standard fields of a definition message.
+fn end_of_fields(has_developer: bool, data_size: u32) -> DefinitionSuccessor {
+    if has_developer {
+        DefinitionSuccessor::Developer(DeveloperDefinition { data_size })
+    } else {
+        DefinitionSuccessor::NextRecord(RecordHeader(()), data_size)
+    }
+}
+
+/// State following a first-pass decoding of a definition message or field.
+#[derive(Debug)]
+pub enum DefinitionSuccessor {
+    /// A standard field of the definition message follows.
+    NextField(DefinitionField),
+    /// The developer field count of the definition message follows.
+    Developer(DeveloperDefinition),
+    /// The definition record is complete, describing data records of a size
+    /// in bytes, following their record header.
+    NextRecord(RecordHeader, u32),
+}
+
 /// State token to decode a definition message.
+#[derive(Debug)]
 pub struct DefinitionAlt(pub(super) ());
 
 #[repr(C, packed)]
@@ -66,14 +115,25 @@ struct DefinitionMessage {
 }
 
 impl DefinitionAlt {
-    /// Transition to another state by decoding a definition message.
+    /// Transition to another state by decoding the record header and message
+    /// of a definition record.
     ///
     /// **This method expects bytes not read from the tip of the cursor.** See
     /// the architecture description in the [`crate::sans`] module documentation
     /// for clarification.
     ///
     /// Returns the global message number, and a successor state token.
-    pub fn advance(self, r: [u8; 5]) -> (u16, Either<DefinitionFieldAlt, RecordHeader>) {
+    pub fn advance(self, r: [u8; 6]) -> (u16, DataSuccessor) {
+        let [header, r @ ..] = r;
+
+        bitfield! {
+            struct DefinitionHeader(u8) {
+                [5] is_developer,
+            }
+        }
+
+        let has_developer = DefinitionHeader(header).is_developer();
+
         let DefinitionMessage {
             architecture,
             global_message,
@@ -89,23 +149,39 @@ impl DefinitionAlt {
         };
 
         let successor = if fields_remaining != 0 {
-            Left(DefinitionFieldAlt {
+            DataSuccessor::NextField(DefinitionFieldAlt {
                 fields_remaining,
                 is_little_endian,
+                has_developer,
             })
+        } else if has_developer {
+            DataSuccessor::Developer(DeveloperDefinitionAlt { is_little_endian })
         } else {
-            Right(RecordHeader(()))
+            DataSuccessor::NextRecord(RecordHeader(()))
         };
 
         (global_message, successor)
     }
 }
 
+/// State following the definition message or a standard field of a data
+/// record.
+#[derive(Debug)]
+pub enum DataSuccessor {
+    /// A standard field of the data record follows.
+    NextField(DefinitionFieldAlt),
+    /// The developer fields of the data record follow.
+    Developer(DeveloperDefinitionAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+}
+
 /// State token to decode a definition field.
 #[derive(Debug)]
 pub struct DefinitionFieldAlt {
     pub(super) fields_remaining: u8,
     pub(super) is_little_endian: bool,
+    pub(super) has_developer: bool,
 }
 
 impl DefinitionFieldAlt {
@@ -115,7 +191,11 @@ impl DefinitionFieldAlt {
     /// the architecture description in the [`crate::sans`] module documentation
     /// for clarification.
     ///
-    /// Returns the field number, and the successor state.
+    /// Returns the field number, and the successor state. Fields of unknown
+    /// base types, or with a size which is not a multiple of the size of their
+    /// base type, are decoded byte-by-byte (as [`AnyField::Unknown`] and
+    /// [`AnyField::Misaligned`] respectively). Fields with a size of zero hold
+    /// no bytes, and are passed over with [`AnyField::Empty`].
     pub fn advance(self, r: [u8; 3]) -> (u8, AnyField) {
         #[repr(C, packed)]
         #[derive(FromBytes)]
@@ -131,38 +211,17 @@ impl DefinitionFieldAlt {
             base_type,
         } = zerocopy::transmute!(r);
 
-        fn new_any_field<T>(
-            (fields_remaining, is_little_endian, bytes_remaining): (u8, bool, u8),
-        ) -> Field<T> {
-            Field {
-                fields_remaining,
-                bytes_remaining,
-                is_little_endian,
-                _phantom: PhantomData,
-            }
-        }
-
-        let parameters = (self.fields_remaining - 1, self.is_little_endian, size);
-
-        let successor = match base_type {
-            0x00 => AnyField::U8(new_any_field(parameters)),
-            0x01 => AnyField::I8(new_any_field(parameters)),
-            0x02 => AnyField::U8(new_any_field(parameters)),
-            0x83 => AnyField::I16(new_any_field(parameters)),
-            0x84 => AnyField::U16(new_any_field(parameters)),
-            0x85 => AnyField::I32(new_any_field(parameters)),
-            0x86 => AnyField::U32(new_any_field(parameters)),
-            0x07 => AnyField::U8Z(new_any_field(parameters)),
-            0x88 => AnyField::F32(new_any_field(parameters)),
-            0x89 => AnyField::F64(new_any_field(parameters)),
-            0x0A => AnyField::U8Z(new_any_field(parameters)),
-            0x8B => AnyField::U16Z(new_any_field(parameters)),
-            0x8C => AnyField::U32Z(new_any_field(parameters)),
-            0x0D => AnyField::U8(new_any_field(parameters)),
-            0x8E => AnyField::I64(new_any_field(parameters)),
-            0x8F => AnyField::U64(new_any_field(parameters)),
-            0x90 => AnyField::U64Z(new_any_field(parameters)),
-            _ => unreachable!(),
+        let (f, e, d) = (
+            self.fields_remaining - 1,
+            self.is_little_endian,
+            self.has_developer,
+        );
+
+        let successor = match AnyField::new(base_type, Field::new(f, size, e, d, base_type)) {
+            _ if size == 0 => AnyField::Empty(Field::new(f, 0, e, d, base_type)),
+            Some(state) if size.is_multiple_of(state.value_size()) => state,
+            Some(_) => AnyField::Misaligned(Field::new(f, size, e, d, base_type)),
+            None => AnyField::Unknown(Field::new(f, size, e, d, base_type)),
         };
 
         (field, successor)
@@ -0,0 +1,235 @@
+//! States processing developer fields.
+
+use zerocopy::FromBytes;
+
+use super::{
+    data::{AnyField, BaseType, Field, FieldKind, U8},
+    header::RecordHeader,
+};
+
+/// State token to perform a first-pass decoding of the developer field count
+/// of a definition message.
+#[derive(Debug)]
+pub struct DeveloperDefinition {
+    pub(super) data_size: u32,
+}
+
+impl DeveloperDefinition {
+    /// Transition to another state by performing a first-pass decoding of the
+    /// developer field count of a definition message.
+    ///
+    /// Returns a successor state token, giving the size of the data records
+    /// described once the definition record is complete.
+    pub fn advance(self, r: [u8; 1]) -> DeveloperDefinitionSuccessor {
+        let [fields_remaining] = r;
+        let data_size = self.data_size;
+
+        if fields_remaining != 0 {
+            DeveloperDefinitionSuccessor::NextField(DeveloperDefinitionField {
+                fields_remaining,
+                data_size,
+            })
+        } else {
+            DeveloperDefinitionSuccessor::NextRecord(RecordHeader(()), data_size)
+        }
+    }
+}
+
+/// State following a first-pass decoding of the developer field count or a
+/// developer field of a definition message.
+#[derive(Debug)]
+pub enum DeveloperDefinitionSuccessor {
+    /// A developer field of the definition message follows.
+    NextField(DeveloperDefinitionField),
+    /// The definition record is complete, describing data records of a size
+    /// in bytes, following their record header.
+    NextRecord(RecordHeader, u32),
+}
+
+/// State token to perform a first-pass decoding of a developer definition
+/// field.
+#[derive(Debug)]
+pub struct DeveloperDefinitionField {
+    pub(super) fields_remaining: u8,
+    pub(super) data_size: u32,
+}
+
+impl DeveloperDefinitionField {
+    /// Transition to another state by performing a first-pass decoding of a
+    /// developer definition field.
+    ///
+    /// Returns a successor state token, giving the size of the data records
+    /// described once the definition record is complete.
+    pub fn advance(self, r: [u8; 3]) -> DeveloperDefinitionSuccessor {
+        let [_, size, _] = r;
+
+        let fields_remaining = self.fields_remaining - 1;
+        let data_size = self.data_size + size as u32;
+
+        if fields_remaining != 0 {
+            DeveloperDefinitionSuccessor::NextField(DeveloperDefinitionField {
+                fields_remaining,
+                data_size,
+            })
+        } else {
+            DeveloperDefinitionSuccessor::NextRecord(RecordHeader(()), data_size)
+        }
+    }
+}
+
+/// State token to decode the developer field count of a definition message.
+#[derive(Debug)]
+pub struct DeveloperDefinitionAlt {
+    pub(super) is_little_endian: bool,
+}
+
+impl DeveloperDefinitionAlt {
+    /// Transition to another state by decoding the developer field count of a
+    /// definition message.
+    ///
+    /// **This method expects bytes not read from the tip of the cursor.** See
+    /// the architecture description in the [`crate::sans`] module documentation
+    /// for clarification.
+    ///
+    /// Returns a successor state token.
+    pub fn advance(self, r: [u8; 1]) -> DeveloperDataSuccessor {
+        let [fields_remaining] = r;
+
+        if fields_remaining != 0 {
+            DeveloperDataSuccessor::NextField(DeveloperDefinitionFieldAlt {
+                fields_remaining,
+                is_little_endian: self.is_little_endian,
+            })
+        } else {
+            DeveloperDataSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
+
+/// State following the developer field count or a developer field of a data
+/// record.
+#[derive(Debug)]
+pub enum DeveloperDataSuccessor {
+    /// A developer field of the data record follows.
+    NextField(DeveloperDefinitionFieldAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+}
+
+/// State token to decode a developer definition field.
+#[derive(Debug)]
+pub struct DeveloperDefinitionFieldAlt {
+    pub(super) fields_remaining: u8,
+    pub(super) is_little_endian: bool,
+}
+
+impl DeveloperDefinitionFieldAlt {
+    /// Transition to another state by decoding a developer definition field.
+    ///
+    /// **This method expects bytes not read from the tip of the cursor.** See
+    /// the architecture description in the [`crate::sans`] module documentation
+    /// for clarification.
+    ///
+    /// Returns the field number, the developer data index, and the successor
+    /// state.
+    pub fn advance(self, r: [u8; 3]) -> (u8, u8, DeveloperField) {
+        #[repr(C, packed)]
+        #[derive(FromBytes)]
+        struct FieldHeader {
+            field: u8,
+            size: u8,
+            developer: u8,
+        }
+
+        let FieldHeader {
+            field,
+            size,
+            developer,
+        } = zerocopy::transmute!(r);
+
+        let successor = DeveloperField {
+            fields_remaining: self.fields_remaining - 1,
+            size,
+            is_little_endian: self.is_little_endian,
+        };
+
+        (field, developer, successor)
+    }
+}
+
+/// State token to decode a developer field, once its base type is known.
+///
+/// The base type of a developer field is declared by a `field_description`
+/// message earlier in the document, rather than in the definition record.
+#[derive(Debug)]
+pub struct DeveloperField {
+    pub(super) fields_remaining: u8,
+    pub(super) size: u8,
+    pub(super) is_little_endian: bool,
+}
+
+impl DeveloperField {
+    /// Transition to another state given the base type of the developer field.
+    ///
+    /// Returns a successor state token, or this state token if the base type
+    /// is unknown or does not evenly divide the size of the field.
+    pub fn advance(self, base_type: u8) -> DeveloperFieldSuccessor {
+        let field = Field::new(
+            self.fields_remaining,
+            self.size,
+            self.is_little_endian,
+            false,
+            base_type,
+        );
+
+        match AnyField::new(base_type, field) {
+            Some(field) if self.size != 0 && self.size.is_multiple_of(field.value_size()) => {
+                DeveloperFieldSuccessor::Known(field)
+            }
+            _ => DeveloperFieldSuccessor::Unsupported(self),
+        }
+    }
+
+    /// Transition to another state, decoding the developer field as bytes to
+    /// be discarded.
+    ///
+    /// Use this when the field has no known description.
+    pub fn skip(self) -> Field<U8, Developer> {
+        Field::new(
+            self.fields_remaining,
+            self.size,
+            self.is_little_endian,
+            false,
+            BaseType::Byte.byte(),
+        )
+    }
+}
+
+/// State following the base type of a developer field.
+#[derive(Debug)]
+pub enum DeveloperFieldSuccessor {
+    /// The base type is known, and evenly divides the size of the field.
+    Known(AnyField<Developer>),
+    /// The base type is unknown or does not evenly divide the size of the
+    /// field, which should be skipped.
+    Unsupported(DeveloperField),
+}
+
+/// Marker for developer fields, described by `field_description` messages.
+#[derive(Debug)]
+pub struct Developer;
+
+impl FieldKind for Developer {
+    type Successor = DeveloperDataSuccessor;
+
+    fn successor(field: Field<(), Self>) -> Self::Successor {
+        if field.fields_remaining != 0 {
+            DeveloperDataSuccessor::NextField(DeveloperDefinitionFieldAlt {
+                fields_remaining: field.fields_remaining,
+                is_little_endian: field.is_little_endian,
+            })
+        } else {
+            DeveloperDataSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
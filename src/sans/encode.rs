@@ -0,0 +1,700 @@
+//! States producing document and record bytes.
+//!
+//! Encoding mirrors decoding: each state token's `advance` method takes the
+//! values to be stored, and returns the bytes to write along with a successor
+//! state token. When encoding a data record, the finite-state machine performs
+//! a second pass over the definition record, which must be replayed from the
+//! bytes produced when encoding it (as in decoding, see the [`crate::sans`]
+//! module documentation).
+//!
+//! Definitions are always encoded with little-endian architecture. Developer
+//! fields are encoded once given the base type declared for them by their
+//! `field_description` message, as when decoding. The trailing cyclic
+//! redundancy check is not represented in the finite-state machine, but can be
+//! computed over the written bytes with the [`super::check`] module.
+
+use core::marker::PhantomData;
+
+use tartan_bitfield::bitfield;
+use thiserror::Error;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+use super::data::{
+    self, F32, F64, FieldInner, I8, I16, I32, I64, U8, U8Z, U16, U16Z, U32, U32Z, U64, U64Z,
+};
+
+/// Protocol version written to document headers (2.0).
+const PROTOCOL_VERSION: u8 = 0x20;
+/// Profile version written to document headers (20.56).
+const PROFILE_VERSION: u16 = 2056;
+
+/// State token to encode a document header.
+#[derive(Debug)]
+pub struct DocumentHeader;
+
+impl DocumentHeader {
+    /// Transition to another state by encoding a document header.
+    ///
+    /// Takes the number of record bytes in the document, excluding headers and
+    /// the trailing CRC. Returns the header bytes, and a successor state token.
+    pub fn advance(data_size: u32) -> ([u8; 12], RecordHeader) {
+        #[repr(C, packed)]
+        #[derive(IntoBytes, Immutable)]
+        struct FileHeader {
+            header_size: u8,
+            protocol_version: u8,
+            profile_version: [u8; 2],
+            data_size: [u8; 4],
+            data_type: [u8; 4],
+        }
+
+        let header = FileHeader {
+            header_size: 12,
+            protocol_version: PROTOCOL_VERSION,
+            profile_version: PROFILE_VERSION.to_le_bytes(),
+            data_size: data_size.to_le_bytes(),
+            data_type: *b".FIT",
+        };
+
+        (zerocopy::transmute!(header), RecordHeader(()))
+    }
+}
+
+/// An error encoding a record header.
+#[derive(Debug, Error)]
+pub enum RecordHeaderError {
+    /// Local message number out of range for the header.
+    #[error("Local message number out of range ({0}).")]
+    LocalMessage(u8),
+    /// Time offset out of range for a compressed timestamp header.
+    #[error("Time offset out of range ({0}).")]
+    TimeOffset(u8),
+}
+
+/// State token to encode a record header.
+#[derive(Debug)]
+pub struct RecordHeader(pub(super) ());
+
+impl RecordHeader {
+    /// Transition to another state by encoding the header of a definition
+    /// record, for a local message number below 16.
+    ///
+    /// Returns the header byte, and a successor state token.
+    pub fn definition(self, local: u8) -> Result<([u8; 1], Definition), RecordHeaderError> {
+        if local >= 16 {
+            Err(RecordHeaderError::LocalMessage(local))?
+        }
+
+        Ok((
+            [0x40 | local],
+            Definition {
+                has_developer: false,
+            },
+        ))
+    }
+
+    /// Transition to another state by encoding the header of a definition
+    /// record describing developer fields, for a local message number below
+    /// 16.
+    ///
+    /// Returns the header byte, and a successor state token.
+    pub fn developer_definition(
+        self,
+        local: u8,
+    ) -> Result<([u8; 1], Definition), RecordHeaderError> {
+        if local >= 16 {
+            Err(RecordHeaderError::LocalMessage(local))?
+        }
+
+        Ok((
+            [0x60 | local],
+            Definition {
+                has_developer: true,
+            },
+        ))
+    }
+
+    /// Transition to another state by encoding the header of a data record, for
+    /// a local message number below 16.
+    ///
+    /// Returns the header byte, and a successor state token.
+    pub fn data(self, local: u8) -> Result<([u8; 1], DefinitionAlt), RecordHeaderError> {
+        if local >= 16 {
+            Err(RecordHeaderError::LocalMessage(local))?
+        }
+
+        Ok(([local], DefinitionAlt(())))
+    }
+
+    /// Transition to another state by encoding a compressed timestamp header of
+    /// a data record, for a local message number below 4, and a time offset
+    /// below 32.
+    ///
+    /// Returns the header byte, and a successor state token.
+    pub fn compressed(
+        self,
+        local: u8,
+        time_offset: u8,
+    ) -> Result<([u8; 1], DefinitionAlt), RecordHeaderError> {
+        if local >= 4 {
+            Err(RecordHeaderError::LocalMessage(local))?
+        }
+
+        if time_offset >= 32 {
+            Err(RecordHeaderError::TimeOffset(time_offset))?
+        }
+
+        Ok(([0x80 | local << 5 | time_offset], DefinitionAlt(())))
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, FromBytes, IntoBytes, Immutable)]
+struct DefinitionMessage {
+    _reserved: u8,
+    architecture: u8,
+    global_message: [u8; 2],
+    fields_remaining: u8,
+}
+
+/// State token to encode a definition message.
+#[derive(Debug)]
+pub struct Definition {
+    has_developer: bool,
+}
+
+impl Definition {
+    /// Transition to another state by encoding a definition message, for a
+    /// global message number and number of fields.
+    ///
+    /// Returns the message bytes, and a successor state token.
+    pub fn advance(self, global: u16, fields: u8) -> ([u8; 5], DefinitionSuccessor) {
+        let message = DefinitionMessage {
+            _reserved: 0,
+            architecture: 0,
+            global_message: global.to_le_bytes(),
+            fields_remaining: fields,
+        };
+
+        let successor = if fields != 0 {
+            DefinitionSuccessor::NextField(DefinitionField {
+                fields_remaining: fields,
+                has_developer: self.has_developer,
+            })
+        } else {
+            end_of_fields(self.has_developer)
+        };
+
+        (zerocopy::transmute!(message), successor)
+    }
+}
+
+/// State following the standard fields of a definition message.
+fn end_of_fields(has_developer: bool) -> DefinitionSuccessor {
+    if has_developer {
+        DefinitionSuccessor::Developer(DeveloperDefinition(()))
+    } else {
+        DefinitionSuccessor::NextRecord(RecordHeader(()))
+    }
+}
+
+/// State following a definition message or field.
+#[derive(Debug)]
+pub enum DefinitionSuccessor {
+    /// A field of the definition message follows.
+    NextField(DefinitionField),
+    /// The developer field count of the definition message follows.
+    Developer(DeveloperDefinition),
+    /// The definition record is complete.
+    NextRecord(RecordHeader),
+}
+
+/// An error encoding a definition field.
+#[derive(Debug, Error)]
+pub enum DefinitionFieldError {
+    /// Unknown base type.
+    #[error("Unknown base type ({0:#04X}).")]
+    UnknownBaseType(u8),
+    /// Field size not a non-zero multiple of the size of its base type.
+    #[error("Field size ({0}) is not a non-zero multiple of its base type size.")]
+    InvalidSize(u8),
+}
+
+/// State token to encode a definition field.
+#[derive(Debug)]
+pub struct DefinitionField {
+    fields_remaining: u8,
+    has_developer: bool,
+}
+
+impl DefinitionField {
+    /// Transition to another state by encoding a definition field, for a field
+    /// number, size in bytes, and base type.
+    ///
+    /// Returns the field bytes, and a successor state token.
+    pub fn advance(
+        self,
+        field: u8,
+        size: u8,
+        base_type: u8,
+    ) -> Result<([u8; 3], DefinitionSuccessor), DefinitionFieldError> {
+        let state = data::Field::new(0, size, true, false, base_type);
+        let Some(state) = data::AnyField::<data::Standard>::new(base_type, state) else {
+            Err(DefinitionFieldError::UnknownBaseType(base_type))?
+        };
+
+        if size == 0 || !size.is_multiple_of(state.value_size()) {
+            Err(DefinitionFieldError::InvalidSize(size))?
+        }
+
+        let fields_remaining = self.fields_remaining - 1;
+        let has_developer = self.has_developer;
+
+        let successor = if fields_remaining != 0 {
+            DefinitionSuccessor::NextField(DefinitionField {
+                fields_remaining,
+                has_developer,
+            })
+        } else {
+            end_of_fields(has_developer)
+        };
+
+        Ok(([field, size, base_type], successor))
+    }
+}
+
+/// State token to encode the developer field count of a definition message.
+#[derive(Debug)]
+pub struct DeveloperDefinition(());
+
+impl DeveloperDefinition {
+    /// Transition to another state by encoding the developer field count of a
+    /// definition message.
+    ///
+    /// Returns the count byte, and a successor state token.
+    pub fn advance(self, fields: u8) -> ([u8; 1], DeveloperDefinitionSuccessor) {
+        let successor = if fields != 0 {
+            DeveloperDefinitionSuccessor::NextField(DeveloperDefinitionField {
+                fields_remaining: fields,
+            })
+        } else {
+            DeveloperDefinitionSuccessor::NextRecord(RecordHeader(()))
+        };
+
+        ([fields], successor)
+    }
+}
+
+/// State following the developer field count or a developer field of a
+/// definition message.
+#[derive(Debug)]
+pub enum DeveloperDefinitionSuccessor {
+    /// A developer field of the definition message follows.
+    NextField(DeveloperDefinitionField),
+    /// The definition record is complete.
+    NextRecord(RecordHeader),
+}
+
+/// State token to encode a developer definition field.
+#[derive(Debug)]
+pub struct DeveloperDefinitionField {
+    fields_remaining: u8,
+}
+
+impl DeveloperDefinitionField {
+    /// Transition to another state by encoding a developer definition field,
+    /// for a field number, size in bytes, and developer data index.
+    ///
+    /// Returns the field bytes, and a successor state token.
+    pub fn advance(
+        self,
+        field: u8,
+        size: u8,
+        developer: u8,
+    ) -> Result<([u8; 3], DeveloperDefinitionSuccessor), DefinitionFieldError> {
+        if size == 0 {
+            Err(DefinitionFieldError::InvalidSize(size))?
+        }
+
+        let fields_remaining = self.fields_remaining - 1;
+
+        let successor = if fields_remaining != 0 {
+            DeveloperDefinitionSuccessor::NextField(DeveloperDefinitionField { fields_remaining })
+        } else {
+            DeveloperDefinitionSuccessor::NextRecord(RecordHeader(()))
+        };
+
+        Ok(([field, size, developer], successor))
+    }
+}
+
+/// State token to encode the fields of a data record, guided by its definition.
+#[derive(Debug)]
+pub struct DefinitionAlt(pub(super) ());
+
+impl DefinitionAlt {
+    /// Transition to another state by replaying the record header and message
+    /// of a definition record.
+    ///
+    /// **This method expects the bytes produced when encoding the definition
+    /// record.**
+    ///
+    /// Returns the global message number, and a successor state token.
+    pub fn advance(self, r: [u8; 6]) -> (u16, DataSuccessor) {
+        let [header, r @ ..] = r;
+
+        bitfield! {
+            struct DefinitionHeader(u8) {
+                [5] is_developer,
+            }
+        }
+
+        let has_developer = DefinitionHeader(header).is_developer();
+
+        let DefinitionMessage {
+            architecture,
+            global_message,
+            fields_remaining,
+            ..
+        } = zerocopy::transmute!(r);
+
+        let is_little_endian = architecture == 0;
+        let global_message = if is_little_endian {
+            u16::from_le_bytes(global_message)
+        } else {
+            u16::from_be_bytes(global_message)
+        };
+
+        let successor = if fields_remaining != 0 {
+            DataSuccessor::NextField(DefinitionFieldAlt {
+                fields_remaining,
+                is_little_endian,
+                has_developer,
+            })
+        } else if has_developer {
+            DataSuccessor::Developer(DeveloperDefinitionAlt { is_little_endian })
+        } else {
+            DataSuccessor::NextRecord(RecordHeader(()))
+        };
+
+        (global_message, successor)
+    }
+}
+
+/// State following the definition message of a data record.
+#[derive(Debug)]
+pub enum DataSuccessor {
+    /// A field of the data record follows.
+    NextField(DefinitionFieldAlt),
+    /// The developer fields of the data record follow.
+    Developer(DeveloperDefinitionAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+}
+
+/// State token to encode the field of a data record, guided by its definition.
+#[derive(Debug)]
+pub struct DefinitionFieldAlt {
+    fields_remaining: u8,
+    is_little_endian: bool,
+    has_developer: bool,
+}
+
+impl DefinitionFieldAlt {
+    /// Transition to another state by replaying a definition field.
+    ///
+    /// **This method expects the bytes produced when encoding the definition
+    /// record.**
+    ///
+    /// Returns the field number, and the successor state, or an error if the
+    /// bytes don't describe a field accepted by [`DefinitionField::advance`].
+    pub fn advance(self, r: [u8; 3]) -> Result<(u8, AnyField), DefinitionFieldError> {
+        let [field, size, base_type] = r;
+
+        // States are only created with fields remaining.
+        let fields_remaining = self.fields_remaining.saturating_sub(1);
+        let (e, d) = (self.is_little_endian, self.has_developer);
+
+        let successor = AnyField::new(fields_remaining, size, e, d, base_type)?;
+
+        Ok((field, successor))
+    }
+}
+
+/// State token to encode the developer fields of a data record, guided by its
+/// definition.
+#[derive(Debug)]
+pub struct DeveloperDefinitionAlt {
+    is_little_endian: bool,
+}
+
+impl DeveloperDefinitionAlt {
+    /// Transition to another state by replaying the developer field count of a
+    /// definition record.
+    ///
+    /// **This method expects the bytes produced when encoding the definition
+    /// record.**
+    ///
+    /// Returns a successor state token.
+    pub fn advance(self, r: [u8; 1]) -> DeveloperDataSuccessor {
+        let [fields_remaining] = r;
+
+        if fields_remaining != 0 {
+            DeveloperDataSuccessor::NextField(DeveloperDefinitionFieldAlt {
+                fields_remaining,
+                is_little_endian: self.is_little_endian,
+            })
+        } else {
+            DeveloperDataSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
+
+/// State following the developer field count of a data record.
+#[derive(Debug)]
+pub enum DeveloperDataSuccessor {
+    /// A developer field of the data record follows.
+    NextField(DeveloperDefinitionFieldAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+}
+
+/// State token to encode a developer field of a data record, guided by its
+/// definition.
+#[derive(Debug)]
+pub struct DeveloperDefinitionFieldAlt {
+    fields_remaining: u8,
+    is_little_endian: bool,
+}
+
+impl DeveloperDefinitionFieldAlt {
+    /// Transition to another state by replaying a developer definition field,
+    /// given the base type declared for the field by its `field_description`
+    /// message.
+    ///
+    /// **This method expects the bytes produced when encoding the definition
+    /// record.**
+    ///
+    /// Returns the field number, the developer data index, and the successor
+    /// state, or an error if the base type is unknown or does not evenly
+    /// divide the size of the field.
+    pub fn advance(
+        self,
+        r: [u8; 3],
+        base_type: u8,
+    ) -> Result<(u8, u8, AnyField<Developer>), DefinitionFieldError> {
+        let [field, size, developer] = r;
+
+        // States are only created with fields remaining.
+        let fields_remaining = self.fields_remaining.saturating_sub(1);
+        let e = self.is_little_endian;
+
+        let successor = AnyField::new(fields_remaining, size, e, false, base_type)?;
+
+        Ok((field, developer, successor))
+    }
+}
+
+/// State token to encode a field of base type `T`.
+#[derive(Debug)]
+pub struct Field<T, K = Standard> {
+    fields_remaining: u8,
+    bytes_remaining: u8,
+    is_little_endian: bool,
+    has_developer: bool,
+    _phantom: PhantomData<(T, K)>,
+}
+
+impl<T, K> Field<T, K> {
+    fn new(
+        fields_remaining: u8,
+        bytes_remaining: u8,
+        is_little_endian: bool,
+        has_developer: bool,
+    ) -> Self {
+        Self {
+            fields_remaining,
+            bytes_remaining,
+            is_little_endian,
+            has_developer,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: FieldInner, K: FieldKind> Field<T, K> {
+    /// Transition to another state by encoding a value of base type `T`, or
+    /// the 'invalid' marker value for `None`.
+    ///
+    /// Array fields (including strings) are encoded value-by-value, advancing
+    /// repeatedly.
+    ///
+    /// Returns the value bytes, and a successor state.
+    pub fn advance(self, value: Option<T::Into>) -> (T::From, K::Successor<T>) {
+        let w = T::to(value, self.is_little_endian);
+
+        let size = size_of::<T::From>() as u8;
+
+        let successor = K::successor(Self::new(
+            self.fields_remaining,
+            self.bytes_remaining - size,
+            self.is_little_endian,
+            self.has_developer,
+        ));
+
+        (w, successor)
+    }
+}
+
+/// Kind of a field of a data record, determining the state following it.
+pub trait FieldKind: Sized {
+    /// State following a value of a field of this kind.
+    type Successor<T>;
+
+    /// The state following a value, given the field once the value is
+    /// encoded.
+    fn successor<T>(field: Field<T, Self>) -> Self::Successor<T>;
+}
+
+/// Marker for standard fields, described by their definition.
+#[derive(Debug)]
+pub struct Standard;
+
+impl FieldKind for Standard {
+    type Successor<T> = FieldSuccessor<T>;
+
+    fn successor<T>(field: Field<T, Self>) -> FieldSuccessor<T> {
+        if field.bytes_remaining != 0 {
+            FieldSuccessor::MoreBytes(field)
+        } else if field.fields_remaining != 0 {
+            FieldSuccessor::NextField(DefinitionFieldAlt {
+                fields_remaining: field.fields_remaining,
+                is_little_endian: field.is_little_endian,
+                has_developer: field.has_developer,
+            })
+        } else if field.has_developer {
+            FieldSuccessor::Developer(DeveloperDefinitionAlt {
+                is_little_endian: field.is_little_endian,
+            })
+        } else {
+            FieldSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
+
+/// Marker for developer fields, described by `field_description` messages.
+#[derive(Debug)]
+pub struct Developer;
+
+impl FieldKind for Developer {
+    type Successor<T> = DeveloperFieldSuccessor<T>;
+
+    fn successor<T>(field: Field<T, Self>) -> DeveloperFieldSuccessor<T> {
+        if field.bytes_remaining != 0 {
+            DeveloperFieldSuccessor::MoreBytes(field)
+        } else if field.fields_remaining != 0 {
+            DeveloperFieldSuccessor::NextField(DeveloperDefinitionFieldAlt {
+                fields_remaining: field.fields_remaining,
+                is_little_endian: field.is_little_endian,
+            })
+        } else {
+            DeveloperFieldSuccessor::NextRecord(RecordHeader(()))
+        }
+    }
+}
+
+/// State following a value of a field of base type `T`.
+#[derive(Debug)]
+pub enum FieldSuccessor<T> {
+    /// Another field of the data record follows.
+    NextField(DefinitionFieldAlt),
+    /// The developer fields of the data record follow.
+    Developer(DeveloperDefinitionAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+    /// Values of the field remain to be encoded.
+    MoreBytes(Field<T>),
+}
+
+/// State following a value of a developer field of base type `T`.
+#[derive(Debug)]
+pub enum DeveloperFieldSuccessor<T> {
+    /// Another developer field of the data record follows.
+    NextField(DeveloperDefinitionFieldAlt),
+    /// The data record is complete.
+    NextRecord(RecordHeader),
+    /// Values of the field remain to be encoded.
+    MoreBytes(Field<T, Developer>),
+}
+
+/// A `Field` state token for a base type.
+#[derive(Debug)]
+pub enum AnyField<K = Standard> {
+    U8(Field<U8, K>),
+    U8Z(Field<U8Z, K>),
+    U16(Field<U16, K>),
+    U16Z(Field<U16Z, K>),
+    U32(Field<U32, K>),
+    U32Z(Field<U32Z, K>),
+    U64(Field<U64, K>),
+    U64Z(Field<U64Z, K>),
+
+    I8(Field<I8, K>),
+    I16(Field<I16, K>),
+    I32(Field<I32, K>),
+    I64(Field<I64, K>),
+
+    F32(Field<F32, K>),
+    F64(Field<F64, K>),
+}
+
+impl<K> AnyField<K> {
+    /// Create the state token for a field of a base type, if the base type is
+    /// known and evenly divides the size of the field.
+    fn new(
+        fields_remaining: u8,
+        size: u8,
+        is_little_endian: bool,
+        has_developer: bool,
+        base_type: u8,
+    ) -> Result<Self, DefinitionFieldError> {
+        let state = data::Field::new(fields_remaining, size, is_little_endian, false, base_type);
+        let Some(state) = data::AnyField::<data::Standard>::new(base_type, state) else {
+            Err(DefinitionFieldError::UnknownBaseType(base_type))?
+        };
+
+        if size == 0 || !size.is_multiple_of(state.value_size()) {
+            Err(DefinitionFieldError::InvalidSize(size))?
+        }
+
+        let (f, b, e, d) = (fields_remaining, size, is_little_endian, has_developer);
+
+        Ok(match state {
+            data::AnyField::U8(_) => AnyField::U8(Field::new(f, b, e, d)),
+            data::AnyField::U8Z(_) => AnyField::U8Z(Field::new(f, b, e, d)),
+            data::AnyField::U16(_) => AnyField::U16(Field::new(f, b, e, d)),
+            data::AnyField::U16Z(_) => AnyField::U16Z(Field::new(f, b, e, d)),
+            data::AnyField::U32(_) => AnyField::U32(Field::new(f, b, e, d)),
+            data::AnyField::U32Z(_) => AnyField::U32Z(Field::new(f, b, e, d)),
+            data::AnyField::U64(_) => AnyField::U64(Field::new(f, b, e, d)),
+            data::AnyField::U64Z(_) => AnyField::U64Z(Field::new(f, b, e, d)),
+
+            data::AnyField::I8(_) => AnyField::I8(Field::new(f, b, e, d)),
+            data::AnyField::I16(_) => AnyField::I16(Field::new(f, b, e, d)),
+            data::AnyField::I32(_) => AnyField::I32(Field::new(f, b, e, d)),
+            data::AnyField::I64(_) => AnyField::I64(Field::new(f, b, e, d)),
+
+            data::AnyField::F32(_) => AnyField::F32(Field::new(f, b, e, d)),
+            data::AnyField::F64(_) => AnyField::F64(Field::new(f, b, e, d)),
+
+            // Strings are encoded as `uint8z`, terminated by the 'invalid'
+            // marker value.
+            data::AnyField::String(_) => AnyField::U8Z(Field::new(f, b, e, d)),
+
+            data::AnyField::Unknown(_) => Err(DefinitionFieldError::UnknownBaseType(base_type))?,
+            data::AnyField::Misaligned(_) | data::AnyField::Empty(_) => {
+                Err(DefinitionFieldError::InvalidSize(size))?
+            }
+        })
+    }
+}
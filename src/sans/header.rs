@@ -1,6 +1,5 @@
 //! States processing document and record headers.
 
-use either::Either::{self, Left, Right};
 use tartan_bitfield::bitfield;
 use thiserror::Error;
 use zerocopy::FromBytes;
@@ -27,9 +26,7 @@ impl DocumentHeader {
     ///
     /// Returns the number of record bytes in this document, and a successor
     /// state token.
-    pub fn advance(
-        r: [u8; 12],
-    ) -> Result<(u32, Either<ExtendedDocumentHeader, RecordHeader>), DocumentHeaderError> {
+    pub fn advance(r: [u8; 12]) -> Result<(u32, DocumentHeaderSuccessor), DocumentHeaderError> {
         #[repr(C, packed)]
         #[derive(FromBytes)]
         struct FileHeader {
@@ -51,9 +48,13 @@ impl DocumentHeader {
             Err(DocumentHeaderError::NotFitData)?;
         }
 
+        // Headers longer than 14 bytes (from future protocol revisions) are
+        // assumed to extend the 14 byte header.
         let successor = match header_size {
-            14 => Left(ExtendedDocumentHeader(())),
-            12 => Right(RecordHeader(())),
+            14.. => DocumentHeaderSuccessor::Extended(ExtendedDocumentHeader {
+                bytes_remaining: header_size - 14,
+            }),
+            12 => DocumentHeaderSuccessor::NextRecord(RecordHeader(())),
             _ => Err(DocumentHeaderError::UnknownHeaderLength(header_size))?,
         };
 
@@ -61,25 +62,68 @@ impl DocumentHeader {
     }
 }
 
+/// State following a document header.
+#[derive(Debug)]
+pub enum DocumentHeaderSuccessor {
+    /// The document header is extended, beyond the 12 byte header.
+    Extended(ExtendedDocumentHeader),
+    /// The document header is complete.
+    NextRecord(RecordHeader),
+}
+
 /// State token to decode additional bytes of an extended document header.
 #[derive(Debug)]
-pub struct ExtendedDocumentHeader(pub(super) ());
+pub struct ExtendedDocumentHeader {
+    bytes_remaining: u8,
+}
 
 impl ExtendedDocumentHeader {
     /// Transition to another state by decoding the additional bytes of an
     /// extended document header.
     ///
     /// Returns the successor state token.
-    pub fn advance(self, _r: [u8; 2]) -> RecordHeader {
-        RecordHeader(())
+    pub fn advance(self, _r: [u8; 2]) -> ExtendedHeaderSuccessor {
+        match self.bytes_remaining {
+            0 => ExtendedHeaderSuccessor::NextRecord(RecordHeader(())),
+            bytes_remaining => {
+                ExtendedHeaderSuccessor::MoreBytes(UnknownDocumentHeader { bytes_remaining })
+            }
+        }
+    }
+}
+
+/// State following bytes of an extended document header.
+#[derive(Debug)]
+pub enum ExtendedHeaderSuccessor {
+    /// Bytes of the document header beyond those known remain to be skipped.
+    MoreBytes(UnknownDocumentHeader),
+    /// The document header is complete.
+    NextRecord(RecordHeader),
+}
+
+/// State token to skip bytes of a document header beyond those known.
+#[derive(Debug)]
+pub struct UnknownDocumentHeader {
+    bytes_remaining: u8,
+}
+
+impl UnknownDocumentHeader {
+    /// Transition to another state by skipping a byte of a document header.
+    ///
+    /// Returns the successor state.
+    pub fn advance(self, _r: [u8; 1]) -> ExtendedHeaderSuccessor {
+        match self.bytes_remaining - 1 {
+            0 => ExtendedHeaderSuccessor::NextRecord(RecordHeader(())),
+            bytes_remaining => ExtendedHeaderSuccessor::MoreBytes(Self { bytes_remaining }),
+        }
     }
 }
 
 /// An error advancing over a record header.
 #[derive(Debug, Error)]
 pub enum RecordHeaderError {
-    /// Found developer data (not yet supported).
-    #[error("Found developer data.")]
+    /// Found the developer data flag on a data record header.
+    #[error("Found the developer data flag on a data record header.")]
     DeveloperData,
 }
 
@@ -88,14 +132,22 @@ pub enum RecordHeaderError {
 pub struct RecordHeader(pub(super) ());
 
 impl RecordHeader {
+    /// Create a state token to decode a record header at an arbitrary record
+    /// boundary, for random access to a document.
+    ///
+    /// **The bytes used to advance must be read from the start of a record, and
+    /// data records must be decoded using the definition record most recently
+    /// preceding them.** Typically, these are found by first decoding the
+    /// document in full, noting the offsets of records.
+    pub fn random_access() -> Self {
+        RecordHeader(())
+    }
+
     /// Transition to another state by decoding a record header.
     ///
-    /// Returns the local message number, a successor state token, and for
-    /// record headers, the time offset if present.
-    pub fn advance(
-        self,
-        r: [u8; 1],
-    ) -> Result<(u8, Either<Definition, (Option<u8>, DefinitionAlt)>), RecordHeaderError> {
+    /// Returns the local message number, and a successor state token holding,
+    /// for data records, the time offset if present.
+    pub fn advance(self, r: [u8; 1]) -> Result<(u8, RecordHeaderSuccessor), RecordHeaderError> {
         let r = r[0];
 
         bitfield! {
@@ -119,7 +171,7 @@ impl RecordHeader {
             let local_message = header.local_message();
             let time_offset = header.time_offset();
 
-            let successor = Right((Some(time_offset), DefinitionAlt(())));
+            let successor = RecordHeaderSuccessor::Data(Some(time_offset), DefinitionAlt(()));
 
             Ok((local_message, successor))
         } else {
@@ -134,17 +186,28 @@ impl RecordHeader {
             let header = NormalHeader(r);
 
             let local_message = header.local_message();
-            if header.is_developer() {
-                Err(RecordHeaderError::DeveloperData)?;
-            }
 
             let successor = if header.is_definition() {
-                Left(Definition(()))
+                RecordHeaderSuccessor::Definition(Definition {
+                    has_developer: header.is_developer(),
+                })
+            } else if header.is_developer() {
+                Err(RecordHeaderError::DeveloperData)?
             } else {
-                Right((None, DefinitionAlt(())))
+                RecordHeaderSuccessor::Data(None, DefinitionAlt(()))
             };
 
             Ok((local_message, successor))
         }
     }
 }
+
+/// State following a record header.
+#[derive(Debug)]
+pub enum RecordHeaderSuccessor {
+    /// A definition record follows.
+    Definition(Definition),
+    /// A data record follows, with the time offset of a compressed timestamp
+    /// header if present.
+    Data(Option<u8>, DefinitionAlt),
+}
@@ -0,0 +1,129 @@
+//! A common interface to the decoding states, taking a fixed number of bytes.
+//!
+//! Each state token taking bytes from the document implements [`Step`], which
+//! names the number of bytes its `advance` method requires. The [`drive`]
+//! function advances any such state with bytes taken from a source, so that a
+//! decoder's loops can be written once over its source of bytes.
+//!
+//! The size of a field state held by an [`AnyField`] is only known once the
+//! field is defined, and is given by [`AnyField::value_size`].
+//! [`DeveloperField`] is advanced with a base type found elsewhere, rather than
+//! bytes of the document, and so does not implement [`Step`].
+//!
+//! [`DeveloperField`]: super::data::DeveloperField
+
+use super::{
+    data::{AnyField, DeveloperField, Field, FieldInner, FieldKind, FieldSuccessor},
+    definition::{
+        DataSuccessor, Definition, DefinitionAlt, DefinitionField, DefinitionFieldAlt,
+        DefinitionSuccessor, DeveloperDataSuccessor, DeveloperDefinition, DeveloperDefinitionAlt,
+        DeveloperDefinitionField, DeveloperDefinitionFieldAlt, DeveloperDefinitionSuccessor,
+    },
+    header::{
+        DocumentHeader, DocumentHeaderError, DocumentHeaderSuccessor, ExtendedDocumentHeader,
+        ExtendedHeaderSuccessor, RecordHeader, RecordHeaderError, RecordHeaderSuccessor,
+        UnknownDocumentHeader,
+    },
+};
+
+/// A state token advanced with a fixed number of bytes.
+pub trait Step: Sized {
+    /// The bytes taken to advance, as an array.
+    type Bytes: Default + AsMut<[u8]>;
+    /// Data extracted on advancing, along with the successor state.
+    type Output;
+
+    /// Number of bytes taken to advance.
+    const LEN: usize = size_of::<Self::Bytes>();
+
+    /// Transition to another state, as with the state token's own `advance`
+    /// method.
+    fn advance(self, r: Self::Bytes) -> Self::Output;
+}
+
+/// Advance a state with bytes taken from a source.
+///
+/// The source fills a buffer of exactly [`Step::LEN`] bytes, or fails.
+///
+/// ```
+/// let mut r = document.as_slice();
+/// let mut take = |buf: &mut [u8]| r.read_exact(buf);
+///
+/// let (size, successor) = drive(DocumentHeader, &mut take)??;
+///
+/// if let DocumentHeaderSuccessor::NextRecord(state) = successor {
+///     let (local, successor) = drive(state, &mut take)??;
+/// }
+/// ```
+pub fn drive<S: Step, E>(
+    state: S,
+    take: impl FnOnce(&mut [u8]) -> Result<(), E>,
+) -> Result<S::Output, E> {
+    let mut r = S::Bytes::default();
+    take(r.as_mut())?;
+
+    Ok(state.advance(r))
+}
+
+macro_rules! step {
+    ($state:ty, $n:literal, $output:ty) => {
+        impl Step for $state {
+            type Bytes = [u8; $n];
+            type Output = $output;
+
+            fn advance(self, r: [u8; $n]) -> $output {
+                <$state>::advance(self, r)
+            }
+        }
+    };
+}
+
+impl Step for DocumentHeader {
+    type Bytes = [u8; 12];
+    type Output = Result<(u32, DocumentHeaderSuccessor), DocumentHeaderError>;
+
+    fn advance(self, r: [u8; 12]) -> Self::Output {
+        DocumentHeader::advance(r)
+    }
+}
+
+step!(ExtendedDocumentHeader, 2, ExtendedHeaderSuccessor);
+step!(UnknownDocumentHeader, 1, ExtendedHeaderSuccessor);
+step!(
+    RecordHeader,
+    1,
+    Result<(u8, RecordHeaderSuccessor), RecordHeaderError>
+);
+
+step!(Definition, 5, DefinitionSuccessor);
+step!(DefinitionField, 3, DefinitionSuccessor);
+step!(DeveloperDefinition, 1, DeveloperDefinitionSuccessor);
+step!(DeveloperDefinitionField, 3, DeveloperDefinitionSuccessor);
+
+step!(DefinitionAlt, 6, (u16, DataSuccessor));
+step!(DefinitionFieldAlt, 3, (u8, AnyField));
+step!(DeveloperDefinitionAlt, 1, DeveloperDataSuccessor);
+step!(DeveloperDefinitionFieldAlt, 3, (u8, u8, DeveloperField));
+
+impl<T, K, const N: usize> Step for Field<T, K>
+where
+    T: FieldInner<From = [u8; N]>,
+    K: FieldKind,
+    [u8; N]: Default,
+{
+    type Bytes = [u8; N];
+    type Output = (Option<T::Into>, FieldSuccessor<T, K>);
+
+    fn advance(self, r: [u8; N]) -> Self::Output {
+        Field::<T, K>::advance(self, r)
+    }
+}
+
+impl<K: FieldKind> Step for Field<(), K> {
+    type Bytes = [u8; 0];
+    type Output = K::Successor;
+
+    fn advance(self, _: [u8; 0]) -> K::Successor {
+        Field::<(), K>::advance(self)
+    }
+}
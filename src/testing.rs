@@ -0,0 +1,205 @@
+//! Construction of synthetic documents, for testing decoders.
+//!
+//! A [`DocumentBuilder`] assembles a document record-by-record, and can
+//! deliberately produce malformed documents (with incorrect sizes, bad CRCs,
+//! or arbitrary bytes) to exercise error handling.
+//!
+//! ```
+//! use derailleur::testing::DocumentBuilder;
+//!
+//! let data = DocumentBuilder::new()
+//!     .definition(0, 20, &[(253, 4, 0x86), (3, 1, 0x02)])
+//!     .data(0, &[0xE8, 0x03, 0, 0, 100]) // Timestamp 1000, heart rate 100.
+//!     .definition(1, 20, &[(3, 1, 0x02)])
+//!     .compressed(1, 13, &[101]) // Timestamp 1005, heart rate 101.
+//!     .build();
+//! ```
+//!
+//! _Requires Cargo feature `testing`._
+
+use std::vec::Vec;
+
+use crate::sans::check::compute_crc;
+
+extern crate std;
+
+/// A builder of synthetic documents.
+///
+/// Unless overridden, the document header holds the size of the records added,
+/// and the document ends with a correct CRC.
+///
+/// _Requires Cargo feature `testing`._
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder {
+    extended: bool,
+    data_size: Option<u32>,
+    crc: Crc,
+    records: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Crc {
+    Computed,
+    Value(u16),
+    Omitted,
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocumentBuilder {
+    /// Create a builder for a document without records.
+    pub fn new() -> Self {
+        Self {
+            extended: false,
+            data_size: None,
+            crc: Crc::Computed,
+            records: Vec::new(),
+        }
+    }
+
+    /// Use an extended (14 byte) document header, with a header CRC.
+    pub fn extended_header(mut self) -> Self {
+        self.extended = true;
+        self
+    }
+
+    /// Write a size to the document header, rather than that of the records
+    /// added.
+    pub fn data_size(mut self, size: u32) -> Self {
+        self.data_size = Some(size);
+        self
+    }
+
+    /// End the document with a CRC value, rather than a correct one.
+    pub fn crc(mut self, crc: u16) -> Self {
+        self.crc = Crc::Value(crc);
+        self
+    }
+
+    /// End the document without a CRC.
+    pub fn without_crc(mut self) -> Self {
+        self.crc = Crc::Omitted;
+        self
+    }
+
+    /// Add a little-endian definition record, for a local message number, a
+    /// global message number, and fields of (number, size, base type).
+    ///
+    /// # Panics
+    ///
+    /// If the local message number is not below 16, or there are more than
+    /// 255 fields.
+    pub fn definition(self, local: u8, global: u16, fields: &[(u8, u8, u8)]) -> Self {
+        self.any_definition(local, global, false, fields, None)
+    }
+
+    /// Add a big-endian definition record. See [`Self::definition`].
+    pub fn big_endian_definition(self, local: u8, global: u16, fields: &[(u8, u8, u8)]) -> Self {
+        self.any_definition(local, global, true, fields, None)
+    }
+
+    /// Add a little-endian definition record with developer fields of
+    /// (number, size, developer data index). See [`Self::definition`].
+    pub fn developer_definition(
+        self,
+        local: u8,
+        global: u16,
+        fields: &[(u8, u8, u8)],
+        developer_fields: &[(u8, u8, u8)],
+    ) -> Self {
+        self.any_definition(local, global, false, fields, Some(developer_fields))
+    }
+
+    fn any_definition(
+        mut self,
+        local: u8,
+        global: u16,
+        big_endian: bool,
+        fields: &[(u8, u8, u8)],
+        developer_fields: Option<&[(u8, u8, u8)]>,
+    ) -> Self {
+        assert!(local < 16, "local message number out of range");
+
+        let developer = developer_fields.is_some() as u8;
+        let global = match big_endian {
+            true => global.to_be_bytes(),
+            false => global.to_le_bytes(),
+        };
+
+        let r = &mut self.records;
+        r.extend([0x40 | developer << 5 | local, 0, big_endian as u8]);
+        r.extend(global);
+
+        for fields in [Some(fields), developer_fields].into_iter().flatten() {
+            r.push(u8::try_from(fields.len()).expect("too many fields"));
+            r.extend(fields.iter().flat_map(|&(f, s, b)| [f, s, b]));
+        }
+
+        self
+    }
+
+    /// Add a data record for a local message number, with the bytes of its
+    /// field values.
+    ///
+    /// # Panics
+    ///
+    /// If the local message number is not below 16.
+    pub fn data(mut self, local: u8, values: &[u8]) -> Self {
+        assert!(local < 16, "local message number out of range");
+
+        self.records.push(local);
+        self.records.extend(values);
+        self
+    }
+
+    /// Add a data record with a compressed timestamp header, for a local
+    /// message number and time offset, with the bytes of its field values.
+    ///
+    /// # Panics
+    ///
+    /// If the local message number is not below 4, or the time offset is not
+    /// below 32.
+    pub fn compressed(mut self, local: u8, time_offset: u8, values: &[u8]) -> Self {
+        assert!(local < 4, "local message number out of range");
+        assert!(time_offset < 32, "time offset out of range");
+
+        self.records.push(0x80 | local << 5 | time_offset);
+        self.records.extend(values);
+        self
+    }
+
+    /// Add arbitrary bytes to the records of the document.
+    pub fn raw(mut self, bytes: &[u8]) -> Self {
+        self.records.extend(bytes);
+        self
+    }
+
+    /// Assemble the document.
+    pub fn build(&self) -> Vec<u8> {
+        let size = self.data_size.unwrap_or(self.records.len() as u32);
+
+        let mut w = Vec::with_capacity(self.records.len() + 16);
+        w.extend([if self.extended { 14 } else { 12 }, 0x20]);
+        w.extend(2056u16.to_le_bytes());
+        w.extend(size.to_le_bytes());
+        w.extend(b".FIT");
+
+        if self.extended {
+            w.extend(compute_crc(0, &w).to_le_bytes());
+        }
+
+        w.extend(&self.records);
+
+        match self.crc {
+            Crc::Computed => w.extend(compute_crc(0, &w).to_le_bytes()),
+            Crc::Value(crc) => w.extend(crc.to_le_bytes()),
+            Crc::Omitted => {}
+        }
+
+        w
+    }
+}
@@ -1,9 +1,17 @@
 #![cfg(feature = "std")]
 
-use std::path::Path;
+use std::{
+    convert::Infallible,
+    io::{ErrorKind, Read},
+    path::Path,
+};
 
 use csv::ReaderBuilder;
-use derailleur::avec::{FromRecord, FromRecords};
+use derailleur::avec::{
+    DecodeOptions, DecodeWriter, DefinitionInfo, DeveloperFieldInfo, FieldInfo, FromRecord,
+    FromRecords, Tape,
+    reader::{Error, NonBlockingDecoder, RetryPolicy, Status, decode_with_policy},
+};
 
 #[test]
 fn decode_slice_cycling() {
@@ -29,6 +37,33 @@ fn decode_slice_running_course() {
     derailleur::avec::decode_slice(&data, &mut validator).unwrap();
 }
 
+#[test]
+fn decode_slice_embedded() {
+    const PATHS: [&str; 2] = [
+        "fixtures/afternoon-ride.fit",
+        "fixtures/trail-run-course.fit",
+    ];
+
+    // Wrap the documents with arbitrary bytes.
+    let mut data = b"prefix".to_vec();
+    let mut ends = vec![];
+    for path in PATHS {
+        data.extend(std::fs::read(path).unwrap());
+        ends.push(data.len());
+        data.extend(b"suffix");
+    }
+
+    let mut offset = 0;
+    for (path, end) in PATHS.into_iter().zip(ends) {
+        offset += derailleur::avec::slice::locate(&data[offset..]).unwrap();
+        let mut validator = Validator::new(path);
+        offset += derailleur::avec::decode_slice_at(&data, offset, &mut validator).unwrap();
+        assert_eq!(offset, end);
+    }
+
+    assert_eq!(derailleur::avec::slice::locate(&data[offset..]), None);
+}
+
 #[test]
 fn decode_reader_cycling() {
     const PATH: &str = "fixtures/afternoon-ride.fit";
@@ -53,6 +88,162 @@ fn decode_reader_running_course() {
     derailleur::avec::decode_reader(&mut file, &mut validator).unwrap();
 }
 
+#[test]
+fn decode_path_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut validator = Validator::new(PATH);
+    derailleur::avec::decode_path(PATH, &mut validator).unwrap();
+}
+
+#[test]
+#[cfg(feature = "flate2")]
+fn decode_reader_gzip() {
+    use std::io::Write;
+
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut encoder = flate2::write::GzEncoder::new(vec![], Default::default());
+    encoder.write_all(&std::fs::read(PATH).unwrap()).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let mut validator = Validator::new(PATH);
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut validator).unwrap();
+}
+
+#[test]
+fn decode_non_blocking_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut file = Trickle(std::fs::File::open(PATH).unwrap(), false);
+    let mut validator = Validator::new(PATH);
+    decode_non_blocking(&mut file, &mut validator);
+}
+
+#[test]
+fn decode_non_blocking_running() {
+    const PATH: &str = "fixtures/morning-trail-run.fit";
+    let mut file = Trickle(std::fs::File::open(PATH).unwrap(), false);
+    let mut validator = Validator::new(PATH);
+    decode_non_blocking(&mut file, &mut validator);
+}
+
+#[test]
+fn decode_non_blocking_running_course() {
+    const PATH: &str = "fixtures/trail-run-course.fit";
+    let mut file = Trickle(std::fs::File::open(PATH).unwrap(), false);
+    let mut validator = Validator::new(PATH);
+    decode_non_blocking(&mut file, &mut validator);
+}
+
+#[test]
+fn decode_writer_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut file = std::fs::File::open(PATH).unwrap();
+    let mut validator = Validator::new(PATH);
+    let mut writer = DecodeWriter::new(&mut validator);
+    std::io::copy(&mut file, &mut writer).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn decode_writer_running() {
+    const PATH: &str = "fixtures/morning-trail-run.fit";
+    let mut file = std::fs::File::open(PATH).unwrap();
+    let mut validator = Validator::new(PATH);
+    let mut writer = DecodeWriter::new(&mut validator);
+    std::io::copy(&mut file, &mut writer).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn decode_writer_running_course() {
+    const PATH: &str = "fixtures/trail-run-course.fit";
+    let mut file = std::fs::File::open(PATH).unwrap();
+    let mut validator = Validator::new(PATH);
+    let mut writer = DecodeWriter::new(&mut validator);
+    std::io::copy(&mut file, &mut writer).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn decode_interrupted_retry() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut file = Interrupting(std::fs::File::open(PATH).unwrap(), false);
+    let mut validator = Validator::new(PATH);
+    derailleur::avec::decode_reader(&mut file, &mut validator).unwrap();
+}
+
+#[test]
+fn decode_interrupted_propagate() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let mut file = Interrupting(std::fs::File::open(PATH).unwrap(), false);
+    let mut validator = Validator::new(PATH);
+    let policy = RetryPolicy {
+        interrupted: Some(0),
+        ..Default::default()
+    };
+    let options = Default::default();
+    let result = decode_with_policy(&mut file, &mut validator, &options, policy);
+    assert!(
+        matches!(result, Err(Error::Io { source, .. }) if source.kind() == ErrorKind::Interrupted)
+    );
+}
+
+#[test]
+fn replay_tape_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let data = std::fs::read(PATH).unwrap();
+    let mut tape = Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    // Replay repeatedly, without the original bytes.
+    drop(data);
+    for _ in 0..2 {
+        let mut validator = Validator::new(PATH);
+        tape.replay(&mut validator).unwrap();
+        assert!(validator.1.is_empty());
+    }
+}
+
+fn decode_non_blocking(r: &mut impl Read, o: &mut impl FromRecords) {
+    let mut decoder = NonBlockingDecoder::new();
+    while decoder.poll(r, o).unwrap() == Status::Pending {}
+}
+
+/// Reader yielding a few bytes at a time, blocking on every other read.
+struct Trickle<R>(R, bool);
+
+impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.1 = !self.1;
+
+        if self.1 {
+            Err(ErrorKind::WouldBlock.into())
+        } else {
+            let n = buf.len().min(7);
+            self.0.read(&mut buf[..n])
+        }
+    }
+}
+
+/// Reader interrupted on every other read, and failing to fill its buffer.
+struct Interrupting<R>(R, bool);
+
+impl<R: Read> Read for Interrupting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.1 = !self.1;
+
+        if self.1 {
+            Err(ErrorKind::Interrupted.into())
+        } else {
+            let n = buf.len().div_ceil(2);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    fn read_exact(&mut self, _: &mut [u8]) -> std::io::Result<()> {
+        Err(ErrorKind::Interrupted.into())
+    }
+}
+
 struct Validator(Vec<String>, Vec<Vec<String>>, Option<u8>);
 
 impl Validator {
@@ -82,10 +273,10 @@ impl Validator {
 }
 
 impl FromRecords for Validator {
-    fn add_record(&mut self, id: u16) -> Option<&mut dyn FromRecord> {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
         self.0 = self.1.remove(0);
         assert_eq!(self.0.remove(0), id.to_string());
-        Some(self)
+        Ok(Some(self))
     }
 }
 
@@ -131,3 +322,111 @@ impl FromRecord for Validator {
         assert_eq!(self.0.remove(0), value.to_string());
     }
 }
+
+#[test]
+fn transcode_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let data = std::fs::read(PATH).unwrap();
+
+    let mut transcoded = Vec::new();
+    derailleur::avec::transcode(&mut data.as_slice(), &mut transcoded, |m, out| out.push(m))
+        .unwrap();
+
+    let mut validator = Validator::new(PATH);
+    derailleur::avec::decode_slice(&transcoded, &mut validator).unwrap();
+    assert!(validator.1.is_empty());
+}
+
+/// Receiver collecting the definition of each record.
+#[derive(Debug, Default, PartialEq)]
+struct Definitions(Vec<(u16, Vec<FieldInfo>, Vec<DeveloperFieldInfo>)>);
+
+impl FromRecords for Definitions {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(None)
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        let fields = definition.fields().collect();
+        let developer_fields = definition.developer_fields().collect();
+        self.0.push((id, fields, developer_fields));
+        Ok(None)
+    }
+}
+
+#[test]
+fn transcode_unedited() {
+    for path in [
+        "fixtures/afternoon-ride.fit",
+        "fixtures/morning-trail-run.fit",
+        "fixtures/trail-run-course.fit",
+        "fixtures/daily-monitoring.fit",
+    ] {
+        let data = std::fs::read(path).unwrap();
+        let mut transcoded = Vec::new();
+        derailleur::avec::transcode(&mut data.as_slice(), &mut transcoded, |m, out| out.push(m))
+            .unwrap();
+
+        // Each record is defined as it was, and holds the same values.
+        let mut expected = Definitions::default();
+        derailleur::avec::decode_slice(&data, &mut expected).unwrap();
+        let mut definitions = Definitions::default();
+        derailleur::avec::decode_slice(&transcoded, &mut definitions).unwrap();
+        assert_eq!(definitions, expected, "{path}");
+
+        let original = derailleur::avec::raw::decode(&data).unwrap();
+        let decoded = derailleur::avec::raw::decode(&transcoded).unwrap();
+        assert_eq!(decoded.messages(), original.messages(), "{path}");
+    }
+}
+
+#[test]
+fn anonymize_cycling() {
+    use derailleur::avec::transcode::{Redaction, anonymize};
+
+    /// Receiver failing on any redacted field.
+    struct Redacted(u16);
+
+    impl FromRecords for Redacted {
+        fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+            assert_ne!(id, 3);
+            self.0 = id;
+            Ok(Some(self))
+        }
+    }
+
+    impl FromRecord for Redacted {
+        fn add_i32(&mut self, field: u8, _: i32) {
+            assert!(!(self.0 == 20 && field <= 1));
+        }
+        fn add_u32(&mut self, field: u8, _: u32) {
+            assert!(!(self.0 == 0 && field == 3));
+        }
+    }
+
+    let data = std::fs::read("fixtures/afternoon-ride.fit").unwrap();
+    let mut anonymized = Vec::new();
+    let redaction = Redaction::default();
+    anonymize(&mut data.as_slice(), &mut anonymized, &redaction).unwrap();
+
+    derailleur::avec::decode_slice(&anonymized, &mut Redacted(0)).unwrap();
+    assert!(anonymized.len() < data.len());
+
+    // Records are defined as they were, without the redacted fields.
+    let mut expected = Definitions::default();
+    derailleur::avec::decode_slice(&data, &mut expected).unwrap();
+    expected
+        .0
+        .retain(|(id, ..)| !redaction.messages.contains(id));
+    for (id, fields, _) in &mut expected.0 {
+        fields.retain(|f| !redaction.fields.contains(&(*id, f.number)));
+    }
+
+    let options = DecodeOptions::default().verify_crc(true);
+    let mut definitions = Definitions::default();
+    derailleur::avec::decode_slice_with(&anonymized, &mut definitions, &options).unwrap();
+    assert_eq!(definitions, expected);
+}
@@ -19,6 +19,46 @@ fn decode_slice_cycling() {
     assert_eq!(format!("{records:?}"), debug.trim());
 }
 
+#[test]
+fn decode_seekable_cycling() {
+    const PATH: &str = "fixtures/afternoon-ride.fit";
+    let data = std::fs::read(PATH).unwrap();
+    let mut expected = ActivityRecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut expected).unwrap();
+
+    let file = std::fs::File::open(PATH).unwrap();
+    let mut decoder = derailleur::avec::SeekableDecoder::new(file);
+
+    let mut records = ActivityRecordSet::default();
+    let count = decoder.messages_of(20, &mut records).unwrap();
+    assert_eq!(count, expected.records.len());
+    assert_eq!(
+        format!("{:?}", records.records),
+        format!("{:?}", expected.records)
+    );
+
+    let mut records = ActivityRecordSet::default();
+    let n = decoder.index().unwrap().entries().len();
+    assert!(decoder.record_at(0, &mut records).unwrap());
+    assert!(!decoder.record_at(n, &mut records).unwrap());
+    assert_eq!(
+        format!("{:?}", records.file_id),
+        format!("{:?}", expected.file_id)
+    );
+
+    let first = expected.records[100].timestamp.unwrap();
+    let last = expected.records[199].timestamp.unwrap();
+    let mut records = ActivityRecordSet::default();
+    decoder.records_between(first..=last, &mut records).unwrap();
+    let timestamps = records.records.iter().map(|r| r.timestamp);
+    assert!(
+        timestamps
+            .clone()
+            .all(|t| (first..=last).contains(&t.unwrap()))
+    );
+    assert_eq!(timestamps.count(), 100);
+}
+
 #[derive(Debug, Default, FromRecords)]
 struct ActivityRecordSet {
     #[record(0)]
@@ -91,7 +131,7 @@ struct Course {
 
 impl Course {
     fn name(&self) -> Option<&str> {
-        if self.name.len() != 0 {
+        if !self.name.is_empty() {
             std::str::from_utf8(&self.name).ok()
         } else {
             None
@@ -144,3 +184,66 @@ impl CoursePoint {
         }
     }
 }
+
+#[test]
+#[cfg(feature = "profile")]
+fn decode_slice_profile_activity() {
+    use derailleur::avec::profile::ActivityRecordSet;
+
+    let data = std::fs::read("fixtures/afternoon-ride.fit").unwrap();
+    let mut activity = ActivityRecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut activity).unwrap();
+
+    assert_eq!(activity.file_id.unwrap().type_, Some(4));
+    assert_eq!(activity.sessions.len(), 1);
+    assert_eq!(activity.laps.len(), 6);
+    assert_eq!(activity.records.len(), 2446);
+    assert_eq!(activity.events.len(), 32);
+}
+
+#[test]
+#[cfg(feature = "profile")]
+fn decode_slice_profile_course() {
+    use derailleur::avec::profile::CourseRecordSet;
+
+    let data = std::fs::read("fixtures/trail-run-course.fit").unwrap();
+    let mut course = CourseRecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut course).unwrap();
+
+    assert!(course.course.unwrap().name().is_some());
+    assert_eq!(course.records.len(), 848);
+    assert_eq!(course.course_points.len(), 7);
+}
+
+#[test]
+#[cfg(feature = "profile")]
+fn decode_slice_profile_monitoring() {
+    use derailleur::avec::profile::MonitoringRecordSet;
+
+    let data = std::fs::read("fixtures/daily-monitoring.fit").unwrap();
+    let mut monitoring = MonitoringRecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut monitoring).unwrap();
+
+    assert_eq!(monitoring.file_id.unwrap().type_, Some(32));
+    assert!(
+        monitoring
+            .monitoring_info
+            .unwrap()
+            .local_timestamp
+            .is_some()
+    );
+    assert_eq!(monitoring.monitoring.len(), 96);
+    assert_eq!(monitoring.monitoring[0].cycles, Some(0));
+    assert_eq!(monitoring.monitoring[1].heart_rate, Some(58));
+    assert_eq!(
+        monitoring.monitoring[1].timestamp_16,
+        Some((1_097_000_960 & 0xFFFF) as u16)
+    );
+}
+
+#[test]
+fn derive_diagnostics() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
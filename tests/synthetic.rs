@@ -0,0 +1,3247 @@
+#![cfg(all(feature = "derive", feature = "std"))]
+
+use std::{convert::Infallible, io::Write};
+
+use derailleur::{
+    avec::{
+        DecodeOptions, DecodeWriter, FieldDescription, FromRecord, FromRecords, IntoRecord,
+        IntoRecords, LimitExceeded, Value,
+    },
+    sans::check::compute_crc,
+};
+
+/// Wrap record bytes in a document header and trailing CRC.
+fn document(records: &[u8]) -> Vec<u8> {
+    let mut data = vec![12, 0x20, 0x08, 0x08];
+    data.extend((records.len() as u32).to_le_bytes());
+    data.extend(b".FIT");
+    data.extend(records);
+    data.extend(compute_crc(0, &data).to_le_bytes());
+    data
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct RecordSet {
+    #[record(20)]
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Default, PartialEq, FromRecord)]
+struct Record {
+    #[field(time)]
+    time_offset: Option<u8>,
+    #[field(253)]
+    timestamp: Option<u32>,
+    #[field(3)]
+    heart_rate: Option<u8>,
+}
+
+/// Records with a full timestamp, followed by compressed timestamp headers.
+const COMPRESSED_TIMESTAMPS: &[u8] = &[
+    0x40,
+    0,
+    0,
+    20,
+    0,
+    2,
+    253,
+    4,
+    0x86,
+    3,
+    1,
+    0x02, // Definition (local 0).
+    0x00,
+    0xE8,
+    0x03,
+    0,
+    0,
+    100, // Timestamp 1000.
+    0x41,
+    0,
+    0,
+    20,
+    0,
+    1,
+    3,
+    1,
+    0x02, // Definition (local 1).
+    0x80 | 0x20 | 13,
+    101, // Offset 13 (timestamp 1005).
+    0x80 | 0x20 | 2,
+    102, // Offset 2, rolling over (timestamp 1026).
+];
+
+#[test]
+fn synthesize_timestamps() {
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let mut records = RecordSet::default();
+    let options = DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    };
+    derailleur::avec::decode_slice_with(&data, &mut records, &options).unwrap();
+
+    let timestamps: Vec<_> = records.records.iter().map(|r| r.timestamp).collect();
+    assert_eq!(timestamps, [Some(1000), Some(1005), Some(1026)]);
+    assert_eq!(records.records[2].time_offset, Some(2));
+}
+
+#[test]
+fn compressed_timestamps_unsynthesized() {
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let mut records = RecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let timestamps: Vec<_> = records.records.iter().map(|r| r.timestamp).collect();
+    assert_eq!(timestamps, [Some(1000), None, None]);
+}
+
+#[test]
+fn track_timestamps() {
+    use derailleur::avec::TimestampTracker;
+
+    let mut tracker = TimestampTracker::new();
+    assert_eq!(tracker.expand(13), None);
+
+    // As for the records of `COMPRESSED_TIMESTAMPS`.
+    tracker.update(1000);
+    assert_eq!(tracker.expand(13), Some(1005));
+    assert_eq!(tracker.expand(2), Some(1026));
+    assert_eq!(tracker.timestamp(), Some(1026));
+}
+
+/// Two `file_id` records with different serial numbers.
+const DUPLICATE_RECORDS: &[u8] = &[
+    0x40, 0, 0, 0, 0, 1, 3, 4, 0x8C, // Definition (local 0).
+    0x00, 1, 0, 0, 0, // Serial number 1.
+    0x00, 2, 0, 0, 0, // Serial number 2.
+];
+
+#[derive(Debug, Default, PartialEq, FromRecord)]
+struct FileId {
+    #[field(3)]
+    serial_number: Option<u32>,
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct KeepFirst {
+    #[record(0, keep = first, duplicate = Self::count)]
+    file_id: Option<FileId>,
+    duplicates: usize,
+}
+
+impl KeepFirst {
+    fn count(&mut self, _: u16) {
+        self.duplicates += 1;
+    }
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct KeepLast {
+    #[record(0, keep = last)]
+    file_id: Option<FileId>,
+}
+
+#[test]
+fn keep_first_record() {
+    let data = document(DUPLICATE_RECORDS);
+    let mut records = KeepFirst::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(records.file_id.unwrap().serial_number, Some(1));
+    assert_eq!(records.duplicates, 1);
+}
+
+#[test]
+fn trailing_bytes() {
+    let mut data = document(DUPLICATE_RECORDS);
+    let len = data.len();
+    data.extend(b"metadata");
+
+    let mut records = KeepLast::default();
+    let consumed = derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(consumed, len);
+    assert_eq!(&data[consumed..], b"metadata");
+}
+
+#[test]
+fn missing_crc() {
+    let mut data = document(DUPLICATE_RECORDS);
+    data.truncate(data.len() - 2);
+
+    let mut records = KeepLast::default();
+    let result = derailleur::avec::decode_slice(&data, &mut records);
+    assert!(result.is_err());
+
+    let options = DecodeOptions {
+        allow_missing_crc: true,
+        ..Default::default()
+    };
+
+    let consumed = derailleur::avec::decode_slice_with(&data, &mut records, &options).unwrap();
+    assert_eq!(consumed, data.len());
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_reader_with(&mut data.as_slice(), &mut records, &options).unwrap();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+
+    // A partial CRC is still an error.
+    data.push(0);
+    let mut records = KeepLast::default();
+    let result = derailleur::avec::decode_reader_with(&mut data.as_slice(), &mut records, &options);
+    assert!(result.is_err());
+}
+
+#[test]
+fn unknown_length() {
+    let options = DecodeOptions {
+        unknown_length: true,
+        ..Default::default()
+    };
+
+    let mut data = document(DUPLICATE_RECORDS);
+    data[4..8].copy_from_slice(&0u32.to_le_bytes());
+
+    // Without a known length, the trailing CRC covers the zero size.
+    let len = data.len();
+    let crc = compute_crc(0, &data[..len - 2]);
+    data[len - 2..].copy_from_slice(&crc.to_le_bytes());
+
+    for data in [&data[..], &data[..len - 2]] {
+        let mut records = KeepLast::default();
+        let consumed = derailleur::avec::decode_slice_with(data, &mut records, &options).unwrap();
+        assert_eq!(consumed, data.len());
+        assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+
+        let mut records = KeepLast::default();
+        derailleur::avec::decode_reader_with(&mut &data[..], &mut records, &options).unwrap();
+        assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+    }
+}
+
+#[test]
+fn longer_header() {
+    let mut data = vec![16, 0x20, 0x08, 0x08];
+    data.extend((DUPLICATE_RECORDS.len() as u32).to_le_bytes());
+    data.extend(b".FIT");
+    data.extend([0, 0, 0xAA, 0xBB]); // Header CRC, and unknown bytes.
+    data.extend(DUPLICATE_RECORDS);
+    data.extend(compute_crc(0, &data).to_le_bytes());
+
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+
+    let mut records = KeepLast::default();
+    let mut w = DecodeWriter::new(&mut records);
+    for b in &data {
+        w.write_all(std::slice::from_ref(b)).unwrap();
+    }
+    w.finish().unwrap();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+}
+
+#[test]
+fn peek_header() {
+    use derailleur::avec::HeaderInfo;
+
+    let data = document(DUPLICATE_RECORDS);
+    let info = derailleur::avec::peek_header(&data[..12]).unwrap();
+
+    assert_eq!(
+        info,
+        HeaderInfo {
+            header_size: 12,
+            protocol_version: 0x20,
+            profile_version: 2056,
+            data_size: DUPLICATE_RECORDS.len() as u32,
+            has_header_crc: false,
+        }
+    );
+
+    let result = derailleur::avec::peek_header(b"not a document");
+    assert!(result.is_err());
+}
+
+#[derive(Default)]
+struct Headers(Vec<derailleur::avec::HeaderInfo>);
+
+impl FromRecords for Headers {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(None)
+    }
+    fn add_header(&mut self, info: derailleur::avec::HeaderInfo) {
+        self.0.push(info);
+    }
+}
+
+#[test]
+fn receive_header() {
+    let data = document(DUPLICATE_RECORDS);
+    let expected = derailleur::avec::peek_header(&data).unwrap();
+
+    let mut headers = Headers::default();
+    derailleur::avec::decode_slice(&data, &mut headers).unwrap();
+    assert_eq!(headers.0, [expected]);
+
+    let mut headers = Headers::default();
+    let mut w = DecodeWriter::new(&mut headers);
+    w.write_all(&data).unwrap();
+    w.finish().unwrap();
+    assert_eq!(headers.0, [expected]);
+
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut headers = Headers::default();
+    tape.replay(&mut headers).unwrap();
+    assert_eq!(headers.0, [expected]);
+}
+
+/// A record with a field of an unknown base type, preceding a known field.
+#[rustfmt::skip]
+const UNKNOWN_BASE_TYPE: &[u8] = &[
+    0x40, 0, 0, 0, 0, 2, 9, 2, 0x99, 3, 4, 0x8C, // Definition (local 0).
+    0x00, 0xAA, 0xBB, 1, 0, 0, 0, // Unknown field, serial number 1.
+];
+
+#[test]
+fn skip_unknown_base_type() {
+    let data = document(UNKNOWN_BASE_TYPE);
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(records.file_id.unwrap().serial_number, Some(1));
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct RawFileIds {
+    #[record(0)]
+    file_ids: Vec<RawFileId>,
+}
+
+#[derive(Debug, Default, FromRecord)]
+struct RawFileId {
+    #[field(3)]
+    serial_number: Option<u32>,
+    #[field(unknown)]
+    unknown: Vec<(u8, u8, Vec<u8>)>,
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct RawSizeSet {
+    #[record(0)]
+    file_ids: Vec<RawSizes>,
+}
+
+#[derive(Debug, Default, FromRecord)]
+struct RawSizes {
+    #[field(unknown, |v, (f, _, b): (u8, u8, &[u8])| v.push((f, b.len())))]
+    sizes: Vec<(u8, usize)>,
+}
+
+#[test]
+fn receive_unknown_fields() {
+    let data = document(UNKNOWN_BASE_TYPE);
+    let mut records = RawFileIds::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let file_id = &records.file_ids[0];
+    assert_eq!(file_id.serial_number, Some(1));
+    assert_eq!(file_id.unknown, [(9, 0x99, vec![0xAA, 0xBB])]);
+
+    let data = document(MISALIGNED_FIELD);
+    let mut records = RawFileIds::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(
+        records.file_ids[0].unknown,
+        [(9, 0x84, vec![0xAA, 0xBB, 0xCC])]
+    );
+
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut records = RawSizeSet::default();
+    tape.replay(&mut records).unwrap();
+    assert_eq!(records.file_ids[0].sizes, [(9, 3)]);
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct Speeds {
+    #[record(20)]
+    records: Vec<Speed>,
+}
+
+#[derive(Debug, Default, FromRecord)]
+struct Speed {
+    #[field(6)]
+    speed: Option<f32>,
+}
+
+#[test]
+fn invalid_floats() {
+    let mut records = vec![0x40, 0, 0, 20, 0, 1, 6, 4, 0x88]; // Definition (local 0).
+    for value in [f32::MAX.to_le_bytes(), [0xFF; 4], 1.5f32.to_le_bytes()] {
+        records.push(0x00);
+        records.extend(value);
+    }
+
+    let data = document(&records);
+    let mut speeds = Speeds::default();
+    derailleur::avec::decode_slice(&data, &mut speeds).unwrap();
+
+    let speeds: Vec<_> = speeds.records.iter().map(|r| r.speed).collect();
+    assert_eq!(speeds, [Some(f32::MAX), None, Some(1.5)]);
+}
+
+#[test]
+fn missing_definition() {
+    use derailleur::avec::{Location, reader, slice};
+
+    // Data records for local 0 (defined) and local 3 (undefined).
+    let data = document(&[&DUPLICATE_RECORDS[..14], &[0x03, 1, 0, 0, 0]].concat());
+
+    let result = derailleur::avec::decode_slice(&data, &mut KeepLast::default());
+    assert!(matches!(
+        result,
+        Err(slice::Error::MissingDefinition { local: 3, .. })
+    ));
+
+    // The record header has been taken.
+    let location = Location {
+        offset: 27,
+        local: Some(3),
+        global: None,
+        field: None,
+    };
+    assert_eq!(result.unwrap_err().location(), location);
+
+    let result = derailleur::avec::decode_reader(&mut data.as_slice(), &mut KeepLast::default());
+    assert!(matches!(
+        result,
+        Err(reader::Error::MissingDefinition { local: 3, .. })
+    ));
+    assert_eq!(result.unwrap_err().location(), location);
+
+    let mut records = KeepLast::default();
+    let mut w = DecodeWriter::new(&mut records);
+    assert!(w.write_all(&data).is_err());
+}
+
+#[derive(Debug, Default)]
+struct Resynchronized {
+    serial_numbers: Vec<u32>,
+    skipped: Vec<derailleur::avec::Skipped>,
+}
+
+impl FromRecords for Resynchronized {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self))
+    }
+    fn add_skipped(&mut self, skipped: derailleur::avec::Skipped) {
+        self.skipped.push(skipped);
+    }
+}
+
+impl FromRecord for Resynchronized {
+    fn add_u32(&mut self, field: u8, value: u32) {
+        if field == 3 {
+            self.serial_numbers.push(value);
+        }
+    }
+}
+
+#[test]
+fn lenient_resynchronization() {
+    use derailleur::avec::{DecodeOptions, Malformed, Skipped};
+
+    // An undefined data record (local 3) with trailing garbage, followed by a
+    // repeated definition and a valid data record.
+    let data = document(
+        &[
+            &DUPLICATE_RECORDS[..14],
+            &[0x03, 1, 0, 0, 0, 0x2A, 0x40, 7],
+            &DUPLICATE_RECORDS[..9],
+            &[0x00, 2, 0, 0, 0],
+        ]
+        .concat(),
+    );
+
+    let options = DecodeOptions {
+        lenient: true,
+        ..Default::default()
+    };
+
+    let expected = [Skipped {
+        offset: 26,
+        length: 8,
+        error: Malformed::MissingDefinition { local: 3 },
+    }];
+
+    let mut records = Resynchronized::default();
+    derailleur::avec::decode_slice_with(&data, &mut records, &options).unwrap();
+    assert_eq!(records.serial_numbers, [1, 2]);
+    assert_eq!(records.skipped, expected);
+
+    let mut records = Resynchronized::default();
+    derailleur::avec::decode_reader_with(&mut data.as_slice(), &mut records, &options).unwrap();
+    assert_eq!(records.serial_numbers, [1, 2]);
+    assert_eq!(records.skipped, expected);
+}
+
+#[test]
+fn decode_options() {
+    use derailleur::avec::DecodeOptions;
+
+    let data = document(DUPLICATE_RECORDS);
+    let chained = [&data[..], &data[..]].concat();
+
+    let decode = |data: &[u8], options: DecodeOptions| {
+        let mut records = Resynchronized::default();
+        let len = derailleur::avec::decode_slice_with(data, &mut records, &options).unwrap();
+        (len, records.serial_numbers)
+    };
+
+    let options = DecodeOptions::new();
+    assert_eq!(decode(&chained, options), (data.len(), vec![1, 2]));
+
+    let options = DecodeOptions::new().allow_chained(true);
+    assert_eq!(decode(&chained, options), (chained.len(), vec![1, 2, 1, 2]));
+
+    let mut records = Resynchronized::default();
+    derailleur::avec::decode_reader_with(&mut chained.as_slice(), &mut records, &options).unwrap();
+    assert_eq!(records.serial_numbers, [1, 2, 1, 2]);
+
+    // Stop at the start of the second data record of the second document.
+    let options = options.max_records(Some(3));
+    assert_eq!(decode(&chained, options), (data.len() + 26, vec![1, 2, 1]));
+
+    let options = DecodeOptions::new().stop_at(Some(0));
+    assert_eq!(decode(&chained, options), (21, vec![]));
+
+    let mut corrupt = data.clone();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+
+    let options = DecodeOptions::new().verify_crc(false);
+    assert_eq!(decode(&corrupt, options), (data.len(), vec![1, 2]));
+}
+
+#[derive(Debug, Default)]
+struct Lifecycle(Vec<&'static str>);
+
+impl FromRecords for Lifecycle {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self))
+    }
+    fn finish(&mut self) -> Result<(), Infallible> {
+        self.0.push("finish");
+        Ok(())
+    }
+}
+
+impl FromRecord for Lifecycle {
+    fn begin(&mut self) {
+        self.0.push("begin");
+    }
+    fn end(&mut self) {
+        self.0.push("end");
+    }
+    fn add_u32(&mut self, _: u8, _: u32) {
+        self.0.push("field");
+    }
+}
+
+#[test]
+fn record_lifecycle() {
+    let data = document(DUPLICATE_RECORDS);
+    let expected = ["begin", "field", "end", "begin", "field", "end", "finish"];
+
+    let mut records = Lifecycle::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.0, expected);
+
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut records = Lifecycle::default();
+    tape.replay(&mut records).unwrap();
+    assert_eq!(records.0, expected);
+
+    let mut records = Lifecycle::default();
+    DecodeWriter::new(&mut records).write_all(&data).unwrap();
+    assert_eq!(records.0, expected);
+}
+
+/// A receiver of serial numbers, stopping once one is found.
+#[derive(Debug, Default)]
+struct FirstSerialNumber(Resynchronized);
+
+impl FromRecords for FirstSerialNumber {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.0.add_record(id)
+    }
+    fn control(&mut self) -> std::ops::ControlFlow<()> {
+        match self.0.serial_numbers.is_empty() {
+            true => std::ops::ControlFlow::Continue(()),
+            false => std::ops::ControlFlow::Break(()),
+        }
+    }
+}
+
+#[test]
+fn stop_early() {
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut records = FirstSerialNumber::default();
+    let len = derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(len, 26);
+    assert_eq!(records.0.serial_numbers, [1]);
+
+    let mut records = FirstSerialNumber::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.0.serial_numbers, [1]);
+
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut records = FirstSerialNumber::default();
+    tape.replay(&mut records).unwrap();
+    assert_eq!(records.0.serial_numbers, [1]);
+}
+
+/// Serial numbers, received without dynamic dispatch.
+#[derive(Debug, Default)]
+struct SerialNumbers(Vec<u32>);
+
+struct SerialNumber<'a>(&'a mut Vec<u32>);
+
+impl derailleur::avec::FromRecordsStatic for SerialNumbers {
+    type Receiver<'s> = SerialNumber<'s>;
+
+    fn add_record(&mut self, id: u16) -> Option<SerialNumber<'_>> {
+        (id == 0).then_some(SerialNumber(&mut self.0))
+    }
+}
+
+impl FromRecord for SerialNumber<'_> {
+    fn add_u32(&mut self, field: u8, value: u32) {
+        if field == 3 {
+            self.0.push(value);
+        }
+    }
+}
+
+#[test]
+fn static_dispatch() {
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut records = SerialNumbers::default();
+    let len = derailleur::avec::decode_slice_static(&data, &mut records).unwrap();
+    assert_eq!(len, data.len());
+    assert_eq!(records.0, [1, 2]);
+
+    let mut records = SerialNumbers::default();
+    derailleur::avec::decode_reader_static(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.0, [1, 2]);
+}
+
+/// A receiver of serial numbers, failing if more than one is found.
+#[derive(Debug, Default)]
+struct SingleSerialNumber(Resynchronized);
+
+impl SingleSerialNumber {
+    fn check(&self) -> Result<(), String> {
+        match self.0.serial_numbers.as_slice() {
+            [_, n, ..] => Err(format!("Found a second serial number ({n})")),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromRecords<String> for SingleSerialNumber {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, String> {
+        self.check()?;
+        let Ok(record) = self.0.add_record(id);
+        Ok(record)
+    }
+    fn finish(&mut self) -> Result<(), String> {
+        self.check()
+    }
+}
+
+#[test]
+fn receiver_errors() {
+    use derailleur::avec::{
+        push::PushDecoder,
+        reader::{self, NonBlockingDecoder},
+        slice,
+    };
+
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut records = SingleSerialNumber::default();
+    let result = derailleur::avec::decode_slice(&data, &mut records);
+    assert!(matches!(result, Err(slice::Error::Receiver(ref e)) if e.ends_with("(2)")));
+    assert_eq!(records.0.serial_numbers, [1, 2]);
+
+    let mut records = SingleSerialNumber::default();
+    let result = derailleur::avec::decode_reader(&mut data.as_slice(), &mut records);
+    assert!(matches!(result, Err(reader::Error::Receiver(ref e)) if e.ends_with("(2)")));
+
+    // Incremental decoders return failures of the receiver too.
+    let mut records = SingleSerialNumber::default();
+    let result = PushDecoder::new().push(&data, &mut records);
+    assert!(matches!(result, Err(reader::Error::Receiver(ref e)) if e.ends_with("(2)")));
+
+    let mut records = SingleSerialNumber::default();
+    let result = NonBlockingDecoder::new().poll(&mut data.as_slice(), &mut records);
+    assert!(matches!(result, Err(reader::Error::Receiver(ref e)) if e.ends_with("(2)")));
+
+    let mut records = SingleSerialNumber::default();
+    let err = DecodeWriter::new(&mut records)
+        .write_all(&data)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().ends_with("(2)."));
+}
+
+#[test]
+fn locate_errors() {
+    use derailleur::avec::Location;
+
+    // A data record (local 0, `file_id`) truncated within its serial number.
+    let records = &DUPLICATE_RECORDS[..12];
+    let mut data = vec![12, 0x20, 0x08, 0x08];
+    data.extend((records.len() as u32 + 3).to_le_bytes());
+    data.extend(b".FIT");
+    data.extend(records);
+
+    // Without a trailing CRC, the whole document is checked first.
+    let err = derailleur::avec::decode_slice(&data, &mut KeepLast::default()).unwrap_err();
+    let expected = Location {
+        offset: 27,
+        local: None,
+        global: None,
+        field: None,
+    };
+    assert_eq!(err.location(), expected);
+
+    let err = derailleur::avec::decode_reader(&mut data.as_slice(), &mut KeepLast::default())
+        .unwrap_err();
+    let expected = Location {
+        offset: 22,
+        local: Some(0),
+        global: Some(0),
+        field: Some(3),
+    };
+    assert_eq!(err.location(), expected);
+    assert_eq!(
+        err.to_string(),
+        "unexpected end of file, at offset 22, local message 0, global message 0, field 3."
+    );
+}
+
+/// A record with two NUL-separated strings in one field.
+#[rustfmt::skip]
+const STRINGS: &[u8] = &[
+    0x40, 0, 0, 31, 0, 1, 5, 8, 0x07, // Definition (local 0, `course`).
+    0x00, b'a', b'b', 0, b'c', b'd', 0, 0, 0, // Names "ab" and "cd".
+];
+
+#[derive(Debug, Default)]
+struct Strings {
+    bytes: Vec<u8>,
+    strings: Vec<Vec<u8>>,
+}
+
+impl FromRecords for Strings {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for Strings {
+    fn add_u8(&mut self, _: u8, value: u8) {
+        self.bytes.push(value);
+    }
+    fn add_string(&mut self, _: u8, value: &[u8]) {
+        self.strings.push(value.to_vec());
+    }
+}
+
+#[test]
+fn whole_strings() {
+    let data = document(STRINGS);
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut strings = Strings::default();
+    tape.replay(&mut strings).unwrap();
+
+    assert_eq!(strings.bytes, b"abcd");
+    assert_eq!(strings.strings, [b"ab", b"cd"]);
+}
+
+#[derive(Debug, Default)]
+struct BorrowedBytes<'a> {
+    strings: Vec<&'a [u8]>,
+    unknown: Vec<&'a [u8]>,
+}
+
+impl<'a> derailleur::avec::FromRecordsRef<'a> for BorrowedBytes<'a> {
+    fn add_record(&mut self, _: u16) -> Option<&mut dyn derailleur::avec::FromRecordRef<'a>> {
+        Some(self)
+    }
+}
+
+impl FromRecord for BorrowedBytes<'_> {}
+
+impl<'a> derailleur::avec::FromRecordRef<'a> for BorrowedBytes<'a> {
+    fn add_string_ref(&mut self, _: u8, value: &'a [u8]) {
+        self.strings.push(value);
+    }
+    fn add_unknown_ref(&mut self, _: u8, _: u8, bytes: &'a [u8]) {
+        self.unknown.push(bytes);
+    }
+}
+
+#[test]
+fn borrow_strings() {
+    let data = document(STRINGS);
+    let mut records = BorrowedBytes::default();
+    derailleur::avec::decode_slice_ref(&data, &mut records).unwrap();
+
+    assert_eq!(records.strings, [b"ab", b"cd"]);
+    assert!(
+        records
+            .strings
+            .iter()
+            .all(|s| data.as_ptr_range().contains(&s.as_ptr()))
+    );
+
+    let data = document(MISALIGNED_FIELD);
+    let mut records = BorrowedBytes::default();
+    derailleur::avec::decode_slice_ref(&data, &mut records).unwrap();
+
+    assert_eq!(records.unknown, [&[0xAA, 0xBB, 0xCC]]);
+    assert!(data.as_ptr_range().contains(&records.unknown[0].as_ptr()));
+}
+
+/// A record with a three byte `uint16` field, preceding a known field.
+#[rustfmt::skip]
+const MISALIGNED_FIELD: &[u8] = &[
+    0x40, 0, 0, 0, 0, 2, 9, 3, 0x84, 3, 4, 0x8C, // Definition (local 0).
+    0x00, 0xAA, 0xBB, 0xCC, 1, 0, 0, 0, // Misaligned field, serial number 1.
+];
+
+#[derive(Debug, Default)]
+struct Warnings {
+    serial_number: Option<u32>,
+    warnings: Vec<derailleur::avec::Warning>,
+}
+
+impl FromRecords for Warnings {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for Warnings {
+    fn add_warning(&mut self, warning: derailleur::avec::Warning) {
+        self.warnings.push(warning);
+    }
+    fn add_u32(&mut self, field: u8, value: u32) {
+        if field == 3 {
+            self.serial_number = Some(value);
+        }
+    }
+}
+
+#[test]
+fn skip_misaligned_field() {
+    use derailleur::avec::Warning;
+
+    let data = document(MISALIGNED_FIELD);
+    let mut records = Warnings::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(records.serial_number, Some(1));
+    assert_eq!(
+        records.warnings,
+        [Warning::MisalignedField { field: 9, size: 3 }]
+    );
+}
+
+const EMPTY_FIELD: &[u8] = &[
+    0x40, 0, 0, 0, 0, 2, 9, 0, 0x84, 3, 4, 0x8C, // Definition (local 0).
+    0x00, 1, 0, 0, 0, // Empty field, serial number 1.
+];
+
+#[test]
+fn skip_empty_field() {
+    use derailleur::avec::Warning;
+
+    let data = document(EMPTY_FIELD);
+    let mut records = Warnings::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(records.serial_number, Some(1));
+    assert_eq!(records.warnings, [Warning::EmptyField { field: 9 }]);
+}
+
+/// An empty data record, followed by a record with a heart rate.
+const EMPTY_RECORD: &[u8] = &[
+    0x40, 0, 0, 20, 0, 0,    // Definition (local 0), without fields.
+    0x00, // Empty record.
+    0x41, 0, 0, 20, 0, 1, 3, 1, 0x02, // Definition (local 1).
+    0x01, 100, // Heart rate 100.
+];
+
+#[test]
+fn poll_empty_record() {
+    use derailleur::avec::reader::{NonBlockingDecoder, Status};
+
+    let data = document(EMPTY_RECORD);
+    let mut records = RecordSet::default();
+    let mut decoder = NonBlockingDecoder::new();
+
+    // Read a byte at a time, so the empty record is reached between reads.
+    let mut r = OneByte(data.as_slice());
+    assert_eq!(
+        decoder.poll(&mut r, &mut records).unwrap(),
+        Status::Complete
+    );
+
+    assert_eq!(records.records.len(), 2);
+    assert_eq!(records.records[0], Record::default());
+    assert_eq!(records.records[1].heart_rate, Some(100));
+}
+
+/// Reader yielding one byte at a time.
+struct OneByte<R>(R);
+
+impl<R: std::io::Read> std::io::Read for OneByte<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(1);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+const ARRAY_FIELD: &[u8] = &[
+    0x40, 0, 0, 0, 0, 2, 9, 6, 0x84, 3, 4, 0x8C, // Definition (local 0).
+    0x00, 1, 0, 0xFF, 0xFF, 3, 0, 1, 0, 0, 0, // Array with an invalid value, serial number 1.
+    0x00, 1, 0, 2, 0, 3, 0, 2, 0, 0, 0, // Array, serial number 2.
+];
+
+#[derive(Debug, Default)]
+struct Arrays {
+    elements: Vec<(u8, derailleur::avec::Element)>,
+    values: Vec<(u8, u16)>,
+    invalid: Vec<(u8, u8)>,
+    slices: Vec<(u8, Vec<u16>)>,
+    serial_number: Option<u32>,
+}
+
+impl FromRecords for Arrays {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for Arrays {
+    fn add_element(&mut self, field: u8, element: derailleur::avec::Element) {
+        self.elements.push((field, element));
+    }
+    fn add_u16(&mut self, field: u8, value: u16) {
+        self.values.push((field, value));
+    }
+    fn add_u16_slice(&mut self, field: u8, values: &[u16]) {
+        self.slices.push((field, values.to_vec()));
+    }
+    fn add_u32(&mut self, _: u8, value: u32) {
+        self.serial_number = Some(value);
+    }
+    fn add_invalid(&mut self, field: u8, base_type: u8) {
+        self.invalid.push((field, base_type));
+    }
+}
+
+#[test]
+fn receive_arrays() {
+    use derailleur::avec::Element;
+
+    let element = |index| (9, Element { index, count: 3 });
+
+    let data = document(ARRAY_FIELD);
+    let mut records = Arrays::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    // Arrays with invalid values are received value by value, with positions.
+    assert_eq!(records.slices, [(9, vec![1, 2, 3])]);
+    assert_eq!(records.values, [(9, 1), (9, 3)]);
+    assert_eq!(records.invalid, [(9, 0x84)]);
+    assert_eq!(records.elements, [element(0), element(1), element(2)]);
+    assert_eq!(records.serial_number, Some(2));
+
+    // Arrays are received value by value unless the receiver asks otherwise.
+    let mut tape = derailleur::avec::Tape::new();
+    derailleur::avec::decode_slice(&data, &mut tape).unwrap();
+
+    let mut records = Arrays::default();
+    tape.replay(&mut records).unwrap();
+    assert_eq!(records.values, [(9, 1), (9, 3), (9, 1), (9, 2), (9, 3)]);
+    assert_eq!(records.invalid, [(9, 0x84)]);
+    let elements = [0, 1, 2, 0, 1, 2].map(element);
+    assert_eq!(records.elements, elements);
+}
+
+#[test]
+fn receive_big_endian_arrays() {
+    #[rustfmt::skip]
+    let data = document(&[
+        0x40, 0, 1, 0, 0, 2, 9, 6, 0x84, 3, 4, 0x8C, // Definition (local 0), big-endian.
+        0x00, 0, 1, 0, 2, 0, 3, 0, 0, 0, 2, // Array, serial number 2.
+        0x00, 0, 1, 0xFF, 0xFF, 0, 3, 0, 0, 0, 3, // Array with an invalid value, serial number 3.
+    ]);
+
+    // Arrays decoded at once from a slice match those decoded value by value.
+    let mut from_slice = Arrays::default();
+    derailleur::avec::decode_slice(&data, &mut from_slice).unwrap();
+    let mut from_reader = Arrays::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut from_reader).unwrap();
+
+    assert_eq!(from_slice.slices, [(9, vec![1, 2, 3])]);
+    assert_eq!(from_slice.values, [(9, 1), (9, 3)]);
+    assert_eq!(from_slice.serial_number, Some(3));
+
+    assert_eq!(from_slice.slices, from_reader.slices);
+    assert_eq!(from_slice.values, from_reader.values);
+    assert_eq!(from_slice.elements, from_reader.elements);
+}
+
+#[test]
+fn receive_arrays_in_place() {
+    use derailleur::sans::{
+        data::AnyField,
+        definition::DataSuccessor,
+        header::{RecordHeader, RecordHeaderSuccessor},
+    };
+
+    #[rustfmt::skip]
+    let data = document(&[
+        0x40, 0, 0, 0, 0, 2, 9, 6, 0x84, 3, 4, 0x8C, // Definition (local 0).
+        0x00, 1, 0, 2, 0, 3, 0, 2, 0, 0, 0, // Array, serial number 2.
+        0x00, 1, 0, 0xFF, 0xFF, 3, 0, 3, 0, 0, 0, // Array with an invalid value, serial number 3.
+    ]);
+
+    #[repr(align(8))]
+    struct Aligned([u8; 128]);
+
+    // Arrays viewed in place, at any alignment, match those decoded value by
+    // value.
+    let mut from_reader = Arrays::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut from_reader).unwrap();
+
+    for shift in 0..8 {
+        let mut buf = Aligned([0; 128]);
+        buf.0[shift..shift + data.len()].copy_from_slice(&data);
+
+        let mut from_slice = Arrays::default();
+        derailleur::avec::decode_slice(&buf.0[shift..shift + data.len()], &mut from_slice).unwrap();
+
+        assert_eq!(from_slice.slices, [(9, vec![1, 2, 3])]);
+        assert_eq!(from_slice.values, [(9, 1), (9, 3)]);
+        assert_eq!(from_slice.serial_number, Some(3));
+
+        assert_eq!(from_slice.slices, from_reader.slices);
+        assert_eq!(from_slice.values, from_reader.values);
+        assert_eq!(from_slice.elements, from_reader.elements);
+    }
+
+    // Values are only viewed in place when aligned and little-endian.
+    let view = |bytes: &[u8], is_little_endian: bool| {
+        let (_, RecordHeaderSuccessor::Data(None, state)) =
+            RecordHeader::random_access().advance([0x00]).unwrap()
+        else {
+            panic!("expected a data header");
+        };
+        let architecture = (!is_little_endian).into();
+        let (_, DataSuccessor::NextField(field)) = state.advance([0x40, 0, architecture, 0, 0, 1])
+        else {
+            panic!("expected a definition field");
+        };
+        let (_, AnyField::U16(field)) = field.advance([9, 6, 0x84]) else {
+            panic!("expected a `uint16` field");
+        };
+
+        field
+            .advance_in_place(bytes)
+            .ok()
+            .map(|(values, _)| values.to_vec())
+    };
+
+    let buf = Aligned([0; 128]);
+    let expected = cfg!(target_endian = "little").then(|| vec![0; 3]);
+    assert_eq!(view(&buf.0[..6], true), expected);
+    assert_eq!(view(&buf.0[1..7], true), None);
+    assert_eq!(view(&buf.0[..4], true), None);
+    assert_eq!(view(&buf.0[..6], false), None);
+}
+
+#[test]
+fn keep_last_record() {
+    let data = document(DUPLICATE_RECORDS);
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(records.file_id.unwrap().serial_number, Some(2));
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct Limited {
+    #[record(0, limit = 1)]
+    file_ids: Vec<FileId>,
+}
+
+#[test]
+fn limit_records() {
+    let data = document(DUPLICATE_RECORDS);
+    let mut records = Limited::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    assert_eq!(
+        records.file_ids,
+        [FileId {
+            serial_number: Some(1)
+        }]
+    );
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct LimitedStrictly {
+    #[record(0, limit = 1, overflow = error)]
+    file_ids: Vec<FileId>,
+}
+
+#[test]
+fn limit_records_strictly() {
+    use derailleur::avec::slice::Error;
+
+    let data = document(DUPLICATE_RECORDS);
+    let mut records = LimitedStrictly::default();
+    let result = derailleur::avec::decode_slice(&data, &mut records);
+
+    let expected = LimitExceeded { id: 0, limit: 1 };
+    assert!(matches!(result, Err(Error::Receiver(e)) if e == expected));
+    assert_eq!(records.file_ids.len(), 1);
+}
+
+/// A record with a described and an undescribed developer field.
+#[rustfmt::skip]
+const DEVELOPER_FIELDS: &[u8] = &[
+    0x40, 0, 0, 207, 0, 1, 3, 1, 0x02, // Definition (local 0, `developer_data_id`).
+    0x00, 0, // Developer 0.
+    0x41, 0, 0, 206, 0, 4, 0, 1, 0x02, 1, 1, 0x02, 2, 1, 0x02, 3, 8, 0x07, // Definition (local 1).
+    0x01, 0, 0, 0x84, b'P', b'o', b'w', b'e', b'r', 0, 0, 0, // Developer 0, field 0, `uint16`.
+    0x62, 0, 0, 20, 0, 1, 3, 1, 0x02, 2, 0, 2, 0, 1, 3, 0, // Definition (local 2).
+    0x02, 150, 0x2C, 0x01, 1, 2, 3, // Heart rate 150, field 0 300.
+    0x80 | 0x40 | 5, 151, 0x2D, 0x01, 4, 5, 6, // Heart rate 151, field 0 301.
+];
+
+/// A definition describing a record, as its global message number, fields, and
+/// developer fields.
+type Definition = (u16, Vec<(u8, u8, u8)>, Vec<(u8, u8, u8)>);
+
+#[derive(Debug, Default)]
+struct Definitions(Vec<Definition>);
+
+impl FromRecords for Definitions {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(None)
+    }
+    fn add_record_with(
+        &mut self,
+        id: u16,
+        definition: derailleur::avec::DefinitionInfo<'_>,
+    ) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        assert!(definition.is_little_endian());
+
+        let fields = definition.fields();
+        let developer_fields = definition.developer_fields();
+        self.0.push((
+            id,
+            fields.map(|f| (f.number, f.size, f.base_type)).collect(),
+            developer_fields
+                .map(|f| (f.number, f.size, f.developer))
+                .collect(),
+        ));
+        Ok(None)
+    }
+}
+
+#[test]
+fn receive_definitions() {
+    let data = document(DEVELOPER_FIELDS);
+    let mut records = Definitions::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let developer = (207, vec![(3, 1, 0x02)], vec![]);
+    let description = (
+        206,
+        vec![(0, 1, 0x02), (1, 1, 0x02), (2, 1, 0x02), (3, 8, 0x07)],
+        vec![],
+    );
+    let record = (20, vec![(3, 1, 0x02)], vec![(0, 2, 0), (1, 3, 0)]);
+    assert_eq!(records.0, [developer, description, record.clone(), record]);
+}
+
+/// Heart rates, with the developer, number, name, and value of developer
+/// fields.
+type DeveloperRecord = (Option<u8>, Vec<(u8, u8, Option<String>, u16)>);
+
+#[derive(Debug, Default)]
+struct DeveloperRecords(Vec<DeveloperRecord>);
+
+impl FromRecords for DeveloperRecords {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok((id == 20).then(|| {
+            self.0.push(Default::default());
+            self as &mut dyn FromRecord
+        }))
+    }
+}
+
+impl FromRecord for DeveloperRecords {
+    fn add_u8(&mut self, field: u8, value: u8) {
+        if field == 3 {
+            self.0.last_mut().unwrap().0 = Some(value);
+        }
+    }
+
+    fn add_developer_u16(&mut self, field: &FieldDescription, value: u16) {
+        let values = &mut self.0.last_mut().unwrap().1;
+        let name = field.name().map(String::from);
+        values.push((field.developer(), field.number(), name, value));
+    }
+}
+
+#[test]
+fn decode_developer_fields() {
+    let data = document(DEVELOPER_FIELDS);
+    let power = || Some("Power".to_string());
+    let expected = [
+        (Some(150), vec![(0, 0, power(), 300)]),
+        (Some(151), vec![(0, 0, power(), 301)]),
+    ];
+
+    let mut records = DeveloperRecords::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.0, expected);
+
+    let mut records = DeveloperRecords::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.0, expected);
+
+    let mut records = DeveloperRecords::default();
+    let mut writer = DecodeWriter::new(&mut records);
+    for byte in &data {
+        writer.write_all(std::slice::from_ref(byte)).unwrap();
+    }
+    writer.finish().unwrap();
+    assert_eq!(records.0, expected);
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct DerivedDeveloperRecords {
+    #[record(20)]
+    records: Vec<DerivedDeveloperRecord>,
+}
+
+#[derive(Debug, Default, PartialEq, FromRecord)]
+struct DerivedDeveloperRecord {
+    #[field(3)]
+    heart_rate: Option<u8>,
+    #[developer_field(developer = 0, number = 0)]
+    power: Option<u16>,
+    #[developer_field(name = "Power", |v, p: u16| v.push(p))]
+    powers: Vec<u16>,
+    #[developer_field(name = "Cadence")]
+    cadence: Option<u16>,
+}
+
+#[test]
+fn derive_developer_fields() {
+    let data = document(DEVELOPER_FIELDS);
+    let mut records = DerivedDeveloperRecords::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let record = |heart_rate, power| DerivedDeveloperRecord {
+        heart_rate: Some(heart_rate),
+        power: Some(power),
+        powers: vec![power],
+        cadence: None,
+    };
+
+    assert_eq!(records.records, [record(150, 300), record(151, 301)]);
+}
+
+#[test]
+fn walk_developer_fields() {
+    use derailleur::sans::{
+        data::{AnyField, DeveloperFieldSuccessor, FieldSuccessor},
+        definition::{
+            DataSuccessor, DefinitionSuccessor, DeveloperDataSuccessor,
+            DeveloperDefinitionSuccessor,
+        },
+        header::{RecordHeader, RecordHeaderSuccessor},
+    };
+
+    // Definition (local 2) and the first data record following it.
+    let d = &DEVELOPER_FIELDS[41..];
+
+    let Ok((2, RecordHeaderSuccessor::Definition(definition))) =
+        RecordHeader::random_access().advance([d[0]])
+    else {
+        panic!("expected a definition header");
+    };
+    assert!(definition.has_developer_data());
+
+    let DefinitionSuccessor::NextField(field) = definition.advance(d[1..6].try_into().unwrap())
+    else {
+        panic!("expected a definition field");
+    };
+    let DefinitionSuccessor::Developer(developer) = field.advance([3, 1, 0x02]) else {
+        panic!("expected developer definitions");
+    };
+    let DeveloperDefinitionSuccessor::NextField(field) = developer.advance([2]) else {
+        panic!("expected a developer definition field");
+    };
+    let DeveloperDefinitionSuccessor::NextField(field) = field.advance([0, 2, 0]) else {
+        panic!("expected a developer definition field");
+    };
+
+    // One standard and two developer fields, of 1, 2 and 3 bytes.
+    let DeveloperDefinitionSuccessor::NextRecord(header, 6) = field.advance([1, 3, 0]) else {
+        panic!("expected a record header");
+    };
+
+    let Ok((2, RecordHeaderSuccessor::Data(None, definition))) = header.advance([d[16]]) else {
+        panic!("expected a data header");
+    };
+    let (20, DataSuccessor::NextField(field)) = definition.advance(d[..6].try_into().unwrap())
+    else {
+        panic!("expected a definition field");
+    };
+    let (3, AnyField::U8(field)) = field.advance([3, 1, 0x02]) else {
+        panic!("expected a `uint8` field");
+    };
+    let (Some(150), FieldSuccessor::Next(DataSuccessor::Developer(developer))) =
+        field.advance([150])
+    else {
+        panic!("expected developer definitions");
+    };
+    let DeveloperDataSuccessor::NextField(field) = developer.advance([2]) else {
+        panic!("expected a developer definition field");
+    };
+
+    // Described as `uint16` by an earlier `field_description` message.
+    let (0, 0, field) = field.advance([0, 2, 0]) else {
+        panic!("expected developer 0, field 0");
+    };
+    let DeveloperFieldSuccessor::Known(AnyField::U16(field)) = field.advance(0x84) else {
+        panic!("expected a `uint16` field");
+    };
+    let (Some(300), FieldSuccessor::Next(DeveloperDataSuccessor::NextField(field))) =
+        field.advance([0x2C, 0x01])
+    else {
+        panic!("expected a developer definition field");
+    };
+
+    // Not described, so skipped.
+    let (1, 0, field) = field.advance([1, 3, 0]) else {
+        panic!("expected developer 0, field 1");
+    };
+    let mut field = field.skip();
+    for _ in 0..2 {
+        let (_, FieldSuccessor::MoreBytes(next)) = field.advance([0]) else {
+            panic!("expected the remaining bytes of the field");
+        };
+        field = next;
+    }
+    let (_, FieldSuccessor::Next(DeveloperDataSuccessor::NextRecord(_))) = field.advance([0])
+    else {
+        panic!("expected a record header");
+    };
+}
+
+/// Records with an accumulated developer field, rolling over.
+#[rustfmt::skip]
+const ACCUMULATED_FIELDS: &[u8] = &[
+    0x40, 0, 0, 207, 0, 1, 3, 1, 0x02, // Definition (local 0, `developer_data_id`).
+    0x00, 0, // Developer 0.
+    0x41, 0, 0, 206, 0, 4, 0, 1, 0x02, 1, 1, 0x02, 2, 1, 0x02, 10, 2, 0x07, // Definition (local 1).
+    0x01, 0, 0, 0x02, b'1', 0, // Developer 0, field 0, accumulated `uint8`.
+    0x62, 0, 0, 20, 0, 1, 3, 1, 0x02, 1, 0, 1, 0, // Definition (local 2).
+    0x02, 150, 250, // Field 0 250.
+    0x02, 151, 4, // Field 0 4 (total 260).
+];
+
+/// Values of developer fields, noting whether they were delivered as `u32`.
+#[derive(Debug, Default)]
+struct AccumulatedRecords(Vec<(bool, u32)>);
+
+impl FromRecords for AccumulatedRecords {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok((id == 20).then_some(self as &mut dyn FromRecord))
+    }
+}
+
+impl FromRecord for AccumulatedRecords {
+    fn add_developer_u8(&mut self, field: &FieldDescription, value: u8) {
+        assert!(field.is_accumulated());
+        self.0.push((false, value.into()));
+    }
+
+    fn add_developer_u32(&mut self, field: &FieldDescription, value: u32) {
+        assert!(field.is_accumulated());
+        self.0.push((true, value));
+    }
+}
+
+#[test]
+fn accumulate_developer_fields() {
+    let data = document(ACCUMULATED_FIELDS);
+
+    let mut records = AccumulatedRecords::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.0, [(false, 250), (false, 4)]);
+
+    let mut records = AccumulatedRecords::default();
+    let options = DecodeOptions {
+        accumulate_developer_fields: true,
+        ..Default::default()
+    };
+    derailleur::avec::decode_slice_with(&data, &mut records, &options).unwrap();
+    assert_eq!(records.0, [(true, 250), (true, 260)]);
+}
+
+#[test]
+fn encode_records() {
+    use derailleur::sans::{
+        Encoder,
+        encode::{
+            AnyField, DataSuccessor, DefinitionFieldError, DefinitionSuccessor, FieldSuccessor,
+        },
+    };
+
+    let mut data = Vec::new();
+
+    let (bytes, header) = Encoder::advance(DUPLICATE_RECORDS.len() as u32);
+    data.extend(bytes);
+
+    // Definition of `file_id`, with a `uint32z` serial number.
+    let (bytes, definition) = header.definition(0).unwrap();
+    data.extend(bytes);
+    let (bytes, DefinitionSuccessor::NextField(field)) = definition.advance(0, 1) else {
+        panic!("expected a definition field");
+    };
+    data.extend(bytes);
+    let (bytes, DefinitionSuccessor::NextRecord(mut header)) = field.advance(3, 4, 0x8C).unwrap()
+    else {
+        panic!("expected a record header");
+    };
+    data.extend(bytes);
+
+    let definition = data[12..].to_vec();
+
+    for serial_number in [1, 2] {
+        let (bytes, state) = header.data(0).unwrap();
+        data.extend(bytes);
+        let (0, DataSuccessor::NextField(field)) =
+            state.advance(definition[..6].try_into().unwrap())
+        else {
+            panic!("expected a definition field");
+        };
+        let Ok((3, AnyField::U32Z(field))) = field.advance(definition[6..].try_into().unwrap())
+        else {
+            panic!("expected a `uint32z` field");
+        };
+        let (bytes, FieldSuccessor::NextRecord(next)) = field.advance(Some(serial_number)) else {
+            panic!("expected a record header");
+        };
+        data.extend(bytes);
+        header = next;
+    }
+
+    data.extend(compute_crc(0, &data).to_le_bytes());
+    assert_eq!(data, document(DUPLICATE_RECORDS));
+
+    // Replayed definition fields which couldn't have been encoded are refused.
+    for (r, expected) in [
+        ([3, 4, 0xFF], DefinitionFieldError::UnknownBaseType(0xFF)),
+        ([3, 3, 0x8C], DefinitionFieldError::InvalidSize(3)),
+        ([3, 0, 0x8C], DefinitionFieldError::InvalidSize(0)),
+    ] {
+        let (_, state) = Encoder::advance(0).1.data(0).unwrap();
+        let (_, DataSuccessor::NextField(field)) = state.advance([0x00, 0, 0, 0, 0, 1]) else {
+            panic!("expected a definition field");
+        };
+        let err = field.advance(r).unwrap_err();
+        assert_eq!(err.to_string(), expected.to_string());
+    }
+}
+
+impl IntoRecords for RecordSet {
+    fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+        for record in &self.records {
+            add(20, record);
+        }
+    }
+}
+
+impl IntoRecord for Record {
+    fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+        if let Some(timestamp) = self.timestamp {
+            add(253, Value::U32(timestamp));
+        }
+        if let Some(heart_rate) = self.heart_rate {
+            add(3, Value::U8(heart_rate));
+        }
+    }
+}
+
+#[test]
+fn encode_round_trip() {
+    let record = |timestamp, heart_rate| Record {
+        time_offset: None,
+        timestamp,
+        heart_rate: Some(heart_rate),
+    };
+    let records = RecordSet {
+        records: vec![
+            record(Some(1000), 100),
+            record(Some(1001), 101),
+            record(None, 102),
+        ],
+    };
+
+    let mut data = vec![0; 64];
+    let len = derailleur::avec::encode_slice(&mut data, &records).unwrap();
+    data.truncate(len);
+
+    // Header, two definitions, three data records, and the CRC.
+    assert_eq!(len, 12 + 12 + 6 + 6 + 9 + 2 + 2);
+
+    let mut written = Vec::new();
+    derailleur::avec::encode_writer(&mut written, &records).unwrap();
+    assert_eq!(written, data);
+
+    let mut decoded = RecordSet::default();
+    derailleur::avec::decode_slice(&data, &mut decoded).unwrap();
+    assert_eq!(decoded.records, records.records);
+
+    assert!(derailleur::avec::encode_slice(&mut [0; 32], &records).is_err());
+}
+
+/// A record with a string field.
+struct Named(&'static str);
+
+impl IntoRecords for Named {
+    fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+        add(31, self);
+    }
+}
+
+impl IntoRecord for Named {
+    fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+        add(5, Value::String(self.0));
+    }
+}
+
+#[test]
+fn encode_strings() {
+    let mut data = Vec::new();
+    derailleur::avec::encode_writer(&mut data, &Named("Loop")).unwrap();
+
+    let records = &data[12..data.len() - 2];
+    assert_eq!(
+        records,
+        [
+            0x40, 0, 0, 31, 0, 1, 5, 5, 0x07, 0x00, b'L', b'o', b'o', b'p', 0
+        ]
+    );
+
+    let long = "x".repeat(255).leak();
+    assert!(derailleur::avec::encode_writer(&mut Vec::new(), &Named(long)).is_err());
+}
+
+/// Records of many messages, each with a single `uint8` field.
+#[derive(Debug, Default, PartialEq)]
+struct Messages(Vec<(u16, Byte)>);
+
+#[derive(Debug, Default, PartialEq)]
+struct Byte(u8);
+
+impl IntoRecords for Messages {
+    fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+        for (global, value) in &self.0 {
+            add(*global, value);
+        }
+    }
+}
+
+impl IntoRecord for Byte {
+    fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+        add(0, Value::U8(self.0));
+    }
+}
+
+impl FromRecords for Messages {
+    fn add_record(&mut self, id: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        self.0.push((id, Byte(0)));
+        Ok(Some(self))
+    }
+}
+
+impl FromRecord for Messages {
+    fn add_u8(&mut self, _: u8, value: u8) {
+        self.0.last_mut().unwrap().1 = Byte(value);
+    }
+}
+
+#[test]
+fn encode_reusing_definitions() {
+    // Each definition takes 9 bytes, and each data record 2. Once local
+    // message numbers run out, the definition next used furthest ahead is
+    // replaced.
+    for (globals, definitions) in [(2, 2), (16, 16), (17, 18)] {
+        let records = Messages(
+            (0..2 * globals)
+                .map(|i| (i % globals, Byte(i as u8)))
+                .collect(),
+        );
+
+        let mut data = Vec::new();
+        derailleur::avec::encode_writer(&mut data, &records).unwrap();
+        assert_eq!(data.len(), 12 + definitions * 9 + records.0.len() * 2 + 2);
+
+        let mut decoded = Messages::default();
+        derailleur::avec::decode_slice(&data, &mut decoded).unwrap();
+        assert_eq!(decoded, records);
+    }
+}
+
+#[test]
+fn encode_seekable() {
+    // With no definitions to replace, records are defined alike.
+    let records = Messages((0..40).map(|i| (i % 16, Byte(i as u8))).collect());
+
+    let mut data = Vec::new();
+    derailleur::avec::encode_writer(&mut data, &records).unwrap();
+
+    // Leave existing bytes before the document untouched.
+    let mut cursor = std::io::Cursor::new(vec![0xAA; 3]);
+    cursor.set_position(3);
+    derailleur::avec::writer::encode_seekable(&mut cursor, &records).unwrap();
+
+    let written = cursor.into_inner();
+    assert_eq!(written[..3], [0xAA; 3]);
+    assert_eq!(written[3..], data);
+
+    // Visiting records once, the least recently used definition is replaced.
+    let records = Messages((0..40).map(|i| (i % 20, Byte(i as u8))).collect());
+
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    derailleur::avec::writer::encode_seekable(&mut cursor, &records).unwrap();
+
+    let written = cursor.into_inner();
+    assert_eq!(written.len(), 12 + 40 * 9 + 40 * 2 + 2);
+
+    let mut decoded = Messages::default();
+    derailleur::avec::decode_slice(&written, &mut decoded).unwrap();
+    assert_eq!(decoded, records);
+}
+
+#[test]
+fn transcode_records() {
+    use derailleur::avec::transcode::{FieldValue, Message};
+
+    let data = document(DUPLICATE_RECORDS);
+    let mut transcoded = Vec::new();
+    derailleur::avec::transcode(&mut data.as_slice(), &mut transcoded, |mut m, out| {
+        match m.get(3) {
+            // Drop the second record, and modify the first.
+            Some(FieldValue::U32(2)) => {}
+            _ => {
+                m.set(3, FieldValue::U32(7));
+                out.push(m);
+
+                // Inject a record following it.
+                let mut record = Message::new(20);
+                record.set(3, FieldValue::U8(120));
+                out.push(record);
+            }
+        }
+    })
+    .unwrap();
+
+    let mut records = Messages::default();
+    derailleur::avec::decode_slice(&transcoded, &mut records).unwrap();
+    assert_eq!(records.0, [(0, Byte(0)), (20, Byte(120))]);
+
+    let mut file_ids = KeepLast::default();
+    derailleur::avec::decode_slice(&transcoded, &mut file_ids).unwrap();
+    assert_eq!(file_ids.file_id.unwrap().serial_number, Some(7));
+}
+
+#[test]
+fn transcode_developer_and_invalid_values() {
+    use derailleur::avec::transcode::FieldValue;
+
+    let transcode = |data: &[u8]| {
+        let mut transcoded = Vec::new();
+        derailleur::avec::transcode(&mut &data[..], &mut transcoded, |m, out| out.push(m)).unwrap();
+        derailleur::avec::raw::decode(&transcoded).unwrap()
+    };
+
+    // Described developer fields are kept, as are the records describing them.
+    let decoded = transcode(&document(DEVELOPER_FIELDS));
+    let globals: Vec<_> = decoded.messages().iter().map(|m| m.global()).collect();
+    assert_eq!(globals, [207, 206, 20, 20]);
+
+    let developer_fields: Vec<_> = decoded.messages()[2..]
+        .iter()
+        .map(|m| m.developer_fields().to_vec())
+        .collect();
+    let expected = [
+        [(0, 0, FieldValue::U16(300))],
+        [(0, 0, FieldValue::U16(301))],
+    ];
+    assert_eq!(developer_fields, expected);
+
+    // Invalid values are kept, as the marker value of their base type.
+    let decoded = transcode(&document(ARRAY_FIELD));
+    let expected = [
+        (9, FieldValue::U16(1)),
+        (9, FieldValue::Invalid(0x84)),
+        (9, FieldValue::U16(3)),
+        (3, FieldValue::U32(1)),
+    ];
+    assert_eq!(decoded.messages()[0].fields(), expected);
+}
+
+#[test]
+fn transcode_definitions() {
+    use derailleur::avec::transcode::Error;
+
+    let definitions = |data: &[u8]| {
+        let mut transcoded = Vec::new();
+        derailleur::avec::transcode(&mut &data[..], &mut transcoded, |m, out| out.push(m)).unwrap();
+
+        let mut definitions = Definitions::default();
+        derailleur::avec::decode_slice(&transcoded, &mut definitions).unwrap();
+        definitions.0
+    };
+
+    // Arrays are kept as one field, and strings keep their size.
+    let array = (0, vec![(9, 6, 0x84), (3, 4, 0x8C)], vec![]);
+    assert_eq!(definitions(&document(ARRAY_FIELD)), [array.clone(), array]);
+    let strings = (31, vec![(5, 8, 0x07)], vec![]);
+    assert_eq!(definitions(&document(STRINGS)), [strings]);
+
+    // Described developer fields keep their size, and others are dropped.
+    let record = (20, vec![(3, 1, 0x02)], vec![(0, 2, 0)]);
+    let transcoded = definitions(&document(DEVELOPER_FIELDS));
+    assert_eq!(transcoded[2..], [record.clone(), record]);
+
+    // Fields of unknown base types can't be encoded again.
+    let data = document(UNKNOWN_BASE_TYPE);
+    let err =
+        derailleur::avec::transcode(&mut data.as_slice(), &mut Vec::new(), |m, out| out.push(m))
+            .unwrap_err();
+    let Error::Decode(derailleur::avec::reader::Error::Receiver(err)) = err else {
+        panic!("expected an unsupported field");
+    };
+    assert_eq!((err.global, err.field, err.base_type), (0, 9, 0x99));
+}
+
+#[test]
+fn encode_repeated_field() {
+    use derailleur::avec::{RecordError, slice::EncodeError};
+
+    struct Repeated;
+
+    impl IntoRecord for Repeated {
+        fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+            add(3, Value::U32(1));
+            add(4, Value::U8(2));
+            add(3, Value::U32(3));
+        }
+    }
+
+    impl IntoRecords for Repeated {
+        fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+            add(0, self);
+        }
+    }
+
+    let err = derailleur::avec::encode_slice(&mut [0; 64], &Repeated).unwrap_err();
+    let EncodeError::Record(RecordError::RepeatedField { global, field }) = err else {
+        panic!("expected a repeated field");
+    };
+    assert_eq!((global, field), (0, 3));
+}
+
+#[test]
+fn encode_in_storage() {
+    use derailleur::avec::{
+        RecordError,
+        slice::{EncodeError, encode_in},
+    };
+
+    struct Pair;
+
+    impl IntoRecord for Pair {
+        fn fields(&self, add: &mut dyn FnMut(u8, Value)) {
+            add(3, Value::U32(1));
+            add(4, Value::U8(2));
+        }
+    }
+
+    impl IntoRecords for Pair {
+        fn records(&self, add: &mut dyn FnMut(u16, &dyn IntoRecord)) {
+            add(0, self);
+            add(0, self);
+        }
+    }
+
+    let mut expected = [0; 64];
+    let len = derailleur::avec::encode_slice(&mut expected, &Pair).unwrap();
+
+    // The definition record takes 12 bytes.
+    let mut buf = [0; 64];
+    assert_eq!(encode_in(&mut buf, &Pair, &mut [0; 17 * 12]).unwrap(), len);
+    assert_eq!(buf, expected);
+
+    let err = encode_in(&mut buf, &Pair, &mut [0; 17 * 11]).unwrap_err();
+    assert!(matches!(
+        err,
+        EncodeError::Record(RecordError::DefinitionStorage(0))
+    ));
+}
+
+#[test]
+fn repair_truncated() {
+    use derailleur::avec::repair::Repair;
+
+    let data = document(DUPLICATE_RECORDS);
+
+    // An intact document is reproduced exactly.
+    let mut repaired = Vec::new();
+    let report = derailleur::avec::repair(&mut data.as_slice(), &mut repaired).unwrap();
+    assert_eq!(repaired, data);
+    assert!(report.intact);
+
+    // Lose power midway through the final record, before updating the size.
+    let mut truncated = data[..data.len() - 4].to_vec();
+    truncated[4..8].copy_from_slice(&0u32.to_le_bytes());
+
+    let mut repaired = Vec::new();
+    let report = derailleur::avec::repair(&mut truncated.as_slice(), &mut repaired).unwrap();
+    assert_eq!(
+        report,
+        Repair {
+            records: 2,
+            data_size: 14,
+            discarded: 3,
+            intact: false,
+        }
+    );
+    assert_eq!(repaired, document(&DUPLICATE_RECORDS[..14]));
+
+    let mut records = KeepLast::default();
+    derailleur::avec::decode_slice(&repaired, &mut records).unwrap();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(1));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn build_documents() {
+    use derailleur::{avec::slice::Error, testing::DocumentBuilder};
+
+    let builder = DocumentBuilder::new()
+        .definition(0, 20, &[(253, 4, 0x86), (3, 1, 0x02)])
+        .data(0, &[0xE8, 0x03, 0, 0, 100])
+        .definition(1, 20, &[(3, 1, 0x02)])
+        .compressed(1, 13, &[101])
+        .compressed(1, 2, &[102]);
+
+    let data = builder.build();
+    assert_eq!(data, document(COMPRESSED_TIMESTAMPS));
+
+    let extended = builder.clone().extended_header().build();
+    let info = derailleur::avec::peek_header(&extended).unwrap();
+    assert!(info.has_header_crc);
+
+    let mut records = RecordSet::default();
+    derailleur::avec::decode_slice(&extended, &mut records).unwrap();
+    assert_eq!(records.records.len(), 3);
+
+    let data = builder.clone().crc(0).build();
+    let result = derailleur::avec::decode_slice(&data, &mut RecordSet::default());
+    assert!(matches!(
+        result,
+        Err(Error::CyclicRedundancyCheck { found: 0, .. })
+    ));
+
+    let data = builder.without_crc().build();
+    let result = derailleur::avec::decode_slice(&data, &mut RecordSet::default());
+    assert!(matches!(result, Err(Error::EndOfSlice { .. })));
+}
+
+/// Global message numbers of records passed over.
+#[derive(Debug, Default)]
+struct UnknownRecords(Vec<u16>);
+
+impl FromRecords for UnknownRecords {
+    fn add_record(&mut self, _: u16) -> Result<Option<&mut dyn FromRecord>, Infallible> {
+        Ok(None)
+    }
+    fn unknown_record(&mut self, id: u16) {
+        self.0.push(id);
+    }
+}
+
+#[test]
+fn report_unknown_records() {
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut records = UnknownRecords::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.0, [0, 0]);
+
+    let mut records = UnknownRecords::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.0, [0, 0]);
+
+    let mut records = UnknownRecords::default();
+    let mut w = DecodeWriter::new(&mut records);
+    w.write_all(&data).unwrap();
+    w.finish().unwrap();
+    assert_eq!(records.0, [0, 0]);
+}
+
+/// Values of any record, with their context.
+#[derive(Debug, Default)]
+struct Contexts(Vec<(derailleur::avec::FieldContext, u32)>);
+
+impl derailleur::avec::FromFields for Contexts {
+    fn add_u16(&mut self, context: derailleur::avec::FieldContext, value: u16) {
+        self.0.push((context, value.into()));
+    }
+    fn add_u32(&mut self, context: derailleur::avec::FieldContext, value: u32) {
+        self.0.push((context, value));
+    }
+}
+
+#[test]
+fn receive_field_contexts() {
+    use derailleur::avec::{FieldContext, WithContext};
+
+    let context = |field, index| FieldContext {
+        global: 0,
+        local: 0,
+        field,
+        index,
+    };
+
+    let data = document(ARRAY_FIELD);
+    let mut records = WithContext::new(Contexts::default());
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let expected = [
+        (context(9, 0), 1),
+        (context(9, 2), 3),
+        (context(3, 0), 1),
+        (context(9, 0), 1),
+        (context(9, 1), 2),
+        (context(9, 2), 3),
+        (context(3, 0), 2),
+    ];
+    assert_eq!(records.into_inner().0, expected);
+}
+
+#[test]
+fn filter_records() {
+    use derailleur::avec::adapters::Filter;
+
+    let data = document(ARRAY_FIELD);
+
+    let mut records = Filter::allow(Resynchronized::default(), [0]);
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.into_inner().serial_numbers, [1, 2]);
+
+    let mut records = Filter::new(Resynchronized::default(), |id| id != 0);
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert!(records.get_ref().serial_numbers.is_empty());
+
+    // Records filtered out are reported as unknown.
+    let mut records = Filter::allow(UnknownRecords::default(), []);
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.into_inner().0, [0, 0]);
+}
+
+#[test]
+fn tee_records() {
+    use derailleur::avec::adapters::Tee;
+
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut records = Tee::new(FirstSerialNumber::default(), Resynchronized::default());
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    let (first, all) = records.into_inner();
+    assert_eq!(first.0.serial_numbers, [1]);
+    assert_eq!(all.serial_numbers, [1, 2]);
+
+    // Decoding stops once both receivers have what they want.
+    let mut records = Tee::new(FirstSerialNumber::default(), FirstSerialNumber::default());
+    let len = derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(len, 26);
+
+    let mut records = Tee::new(RecordSet::default(), UnknownRecords::default());
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+    let (set, unknown) = records.into_inner();
+    assert!(set.records.is_empty());
+    assert_eq!(unknown.0, [0, 0]);
+}
+
+#[test]
+fn inspect_calls() {
+    use derailleur::avec::adapters::{Call, Inspect};
+
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut calls = Vec::new();
+    let mut records = Inspect::new(KeepFirst::default(), |call| {
+        if let Call::Record { .. } | Call::Value(..) = call {
+            calls.push(format!("{call:?}"));
+        }
+    });
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let records = records.into_inner();
+    assert_eq!(records.file_id.unwrap().serial_number, Some(1));
+
+    // Values are reported for records declined by the receiver too.
+    let expected = [
+        "Record { id: 0, received: true }",
+        "Value(3, U32(1))",
+        "Record { id: 0, received: false }",
+        "Value(3, U32(2))",
+    ];
+    assert_eq!(calls, expected);
+}
+
+#[derive(Debug, Default, FromRecords)]
+struct DynRecords {
+    #[record(0)]
+    file_ids: Vec<derailleur::avec::DynRecord>,
+}
+
+#[test]
+fn receive_dynamic_records() {
+    use derailleur::avec::raw::FieldValue;
+
+    let data = document(ARRAY_FIELD);
+    let mut records = DynRecords::default();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+
+    let [first, second] = &records.file_ids[..] else {
+        panic!("expected two records");
+    };
+
+    // Invalid values are received as their base type.
+    let expected = [
+        (9, FieldValue::U16(1)),
+        (9, FieldValue::Invalid(0x84)),
+        (9, FieldValue::U16(3)),
+        (3, FieldValue::U32(1)),
+    ];
+    assert_eq!(first.fields(), expected);
+    assert_eq!(second.get(3), Some(&FieldValue::U32(2)));
+}
+
+#[test]
+fn receive_dynamic_strings() {
+    use derailleur::avec::raw::FieldValue;
+
+    let data = document(STRINGS);
+    let document = derailleur::avec::raw::decode(&data).unwrap();
+
+    // Strings are received whole, without their bytes.
+    let expected = [
+        (5, FieldValue::String("ab".into())),
+        (5, FieldValue::String("cd".into())),
+    ];
+    assert_eq!(document.messages()[0].fields(), expected);
+}
+
+#[test]
+fn decode_raw_document() {
+    use derailleur::avec::raw::FieldValue;
+
+    let data = document(DUPLICATE_RECORDS);
+    let document = derailleur::avec::raw::decode(&data).unwrap();
+    assert!(document.header().is_some());
+
+    let messages = document.messages();
+    assert_eq!(messages.len(), 2);
+    assert!(messages.iter().all(|m| m.global() == 0));
+    assert_eq!(messages[1].fields(), [(3, FieldValue::U32(2))]);
+
+    // Documents are encoded with the records they were decoded with.
+    let mut encoded = Vec::new();
+    derailleur::avec::encode_writer(&mut encoded, &document).unwrap();
+    let decoded = derailleur::avec::raw::decode(&encoded).unwrap();
+    assert_eq!(decoded.messages(), messages);
+}
+
+#[test]
+fn iterate_messages() {
+    use derailleur::avec::{raw::FieldValue, slice::Error};
+
+    let data = document(DUPLICATE_RECORDS);
+    let messages = derailleur::avec::slice::messages(&data);
+    let serial_numbers: Vec<_> = messages
+        .map(|m| m.unwrap().to_message().get(3).cloned())
+        .collect();
+    assert_eq!(
+        serial_numbers,
+        [Some(FieldValue::U32(1)), Some(FieldValue::U32(2))]
+    );
+
+    // Iteration can stop before the end of the document.
+    let first = derailleur::avec::slice::messages(&data).next();
+    assert_eq!(first.unwrap().unwrap().global(), 0);
+
+    // Iteration ends after an error.
+    let mut messages = derailleur::avec::slice::messages(&data[..data.len() - 1]);
+    assert!(matches!(
+        messages.next(),
+        Some(Err(Error::EndOfSlice { .. }))
+    ));
+    assert!(messages.next().is_none());
+}
+
+#[test]
+fn lazy_message_fields() {
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let messages: Vec<_> = derailleur::avec::slice::messages(&data)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(messages.len(), 3);
+
+    // Fields are available undecoded.
+    let fields: Vec<_> = messages[0].fields().map(|(f, b)| (f.number, b)).collect();
+    assert_eq!(fields, [(253, &[0xE8, 0x03, 0, 0][..]), (3, &[100][..])]);
+    assert_eq!(messages[2].time_offset(), Some(2));
+    assert_eq!(messages[2].bytes(), [102]);
+
+    // Time offsets are expanded, even when decoding records out of order.
+    let mut record = Record::default();
+    messages[2].decode(&mut record);
+    assert_eq!(record.timestamp, Some(1026));
+    assert_eq!(record.heart_rate, Some(102));
+}
+
+#[test]
+fn stream_records() {
+    use derailleur::avec::{
+        raw::FieldValue,
+        reader::{Error, RecordStream},
+    };
+
+    let mut data = document(COMPRESSED_TIMESTAMPS);
+    data.extend(b"rest");
+
+    let mut stream = RecordStream::new(data.as_slice());
+    let timestamps: Vec<_> = stream
+        .by_ref()
+        .map(|m| m.unwrap().get(253).cloned())
+        .collect();
+    assert_eq!(
+        timestamps,
+        [1000, 1005, 1026].map(|t| Some(FieldValue::U32(t)))
+    );
+
+    // Bytes past the end of the document are left unread.
+    assert_eq!(stream.into_inner(), b"rest");
+
+    // Iteration ends after an error.
+    let mut stream = RecordStream::new(&data[..32]);
+    assert!(stream.next().unwrap().is_ok());
+    assert!(matches!(stream.next(), Some(Err(Error::Io { .. }))));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn stream_empty_record() {
+    use derailleur::avec::{raw::FieldValue, reader::RecordStream};
+
+    let data = document(EMPTY_RECORD);
+    let messages: Vec<_> = RecordStream::new(OneByte(data.as_slice()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].get(3), None);
+    assert_eq!(messages[1].get(3), Some(&FieldValue::U8(100)));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn stream_records_async() {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use derailleur::avec::reader_async::MessageStream;
+    use futures_core::Stream;
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let mut stream = pin!(MessageStream::new(data.as_slice()));
+
+    // Readers of slices are always ready.
+    let cx = &mut Context::from_waker(Waker::noop());
+    let mut messages = Vec::new();
+    while let Poll::Ready(Some(message)) = stream.as_mut().poll_next(cx) {
+        messages.push(message.unwrap());
+    }
+
+    assert_eq!(messages.len(), 3);
+    assert!(messages.iter().all(|m| m.global() == 20));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn decode_async() {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let mut r = data.as_slice();
+    let mut records = RecordSet::default();
+    let decode = derailleur::avec::reader_async::decode(&mut r, &mut records);
+
+    // Readers of slices are always ready.
+    let cx = &mut Context::from_waker(Waker::noop());
+    assert!(matches!(pin!(decode).poll(cx), Poll::Ready(Ok(()))));
+
+    let heart_rates: Vec<_> = records.records.iter().map(|r| r.heart_rate).collect();
+    assert_eq!(heart_rates, [Some(100), Some(101), Some(102)]);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn decode_tokio() {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let mut r = data.as_slice();
+    let mut records = RecordSet::default();
+    let decode = derailleur::avec::reader_async::decode_tokio_reader(&mut r, &mut records);
+
+    // Readers of slices are always ready.
+    let cx = &mut Context::from_waker(Waker::noop());
+    assert!(matches!(pin!(decode).poll(cx), Poll::Ready(Ok(()))));
+    assert_eq!(records.records.len(), 3);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn seek_async() {
+    use std::{
+        io::{Cursor, Read, Seek, SeekFrom},
+        pin::{Pin, pin},
+        task::{Context, Poll, Waker},
+    };
+
+    use derailleur::avec::seek_async::SeekableDecoder;
+    use futures_io::{AsyncRead, AsyncSeek};
+
+    /// An asynchronous reader over a cursor, counting the bytes read from it.
+    struct Object(Cursor<Vec<u8>>, usize);
+
+    impl AsyncRead for Object {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let n = self.0.read(buf)?;
+            self.1 += n;
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    impl AsyncSeek for Object {
+        fn poll_seek(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<std::io::Result<u64>> {
+            Poll::Ready(self.0.seek(pos))
+        }
+    }
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+
+    // Readers of cursors are always ready.
+    let cx = &mut Context::from_waker(Waker::noop());
+
+    let mut decoder = SeekableDecoder::new(Object(Cursor::new(data.clone()), 0));
+    let Poll::Ready(Ok(index)) = pin!(decoder.index()).poll(cx) else {
+        panic!("index not built");
+    };
+
+    let mut expected = derailleur::avec::SeekableDecoder::new(Cursor::new(&data));
+    let expected = expected.index().unwrap();
+    let offsets = |index: &derailleur::avec::seek::Index| {
+        let entries = index.entries().iter();
+        entries
+            .map(|e| (e.offset(), e.timestamp()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(offsets(index), offsets(expected));
+
+    // With an index, only the bytes of the records selected are read.
+    let (_, index) = decoder.into_parts();
+    let f = Object(Cursor::new(data), 0);
+    let mut decoder = SeekableDecoder::with_index(f, index.unwrap());
+    let mut records = RecordSet::default();
+    let decode = decoder.records_between(1005.., &mut records);
+    assert!(matches!(pin!(decode).poll(cx), Poll::Ready(Ok(2))));
+
+    let heart_rates: Vec<_> = records.records.iter().map(|r| r.heart_rate).collect();
+    assert_eq!(heart_rates, [Some(101), Some(102)]);
+    assert_eq!(decoder.into_parts().0.1, 4);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn trace_decoding() {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    /// Collect the messages of events.
+    #[derive(Default)]
+    struct Messages(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for &Messages {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0.lock().unwrap().push(format!("{value:?}"));
+            }
+        }
+    }
+
+    impl Subscriber for Messages {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut &*self);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    let subscriber = Messages::default();
+    let messages = subscriber.0.clone();
+
+    // Data record (local 1) without a definition.
+    let data = document(&[DUPLICATE_RECORDS, &[0x01, 0]].concat());
+
+    let mut records = RecordSet::default();
+    tracing::subscriber::with_default(subscriber, || {
+        derailleur::avec::decode_slice(&data, &mut records).unwrap_err();
+    });
+
+    assert_eq!(
+        *messages.lock().unwrap(),
+        [
+            "document header",
+            "definition record",
+            "data record",
+            "data record",
+            "decoding failed"
+        ]
+    );
+}
+
+#[cfg(feature = "embedded-io-async")]
+#[test]
+fn decode_embedded() {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use derailleur::avec::embedded::{Error, decode};
+
+    let mut data = document(COMPRESSED_TIMESTAMPS);
+    let len = data.len();
+    data.extend(b"rest");
+
+    // Readers of slices are always ready.
+    let cx = &mut Context::from_waker(Waker::noop());
+
+    let mut r = data.as_slice();
+    let mut buf = [0; 256];
+    let mut records = RecordSet::default();
+    let decoding = decode(&mut r, &mut buf, &mut records);
+    assert!(matches!(pin!(decoding).poll(cx), Poll::Ready(Ok(()))));
+    assert_eq!(records.records.len(), 3);
+    assert_eq!(r, b"rest");
+
+    // The whole document must fit the buffer.
+    let mut r = data.as_slice();
+    let mut buf = [0; 32];
+    let mut records = RecordSet::default();
+    let decoding = decode(&mut r, &mut buf, &mut records);
+    let Poll::Ready(Err(Error::BufferTooSmall { size })) = pin!(decoding).poll(cx) else {
+        panic!("expected the buffer to be too small");
+    };
+    assert_eq!(size, len);
+
+    let mut r = &data[..len - 1];
+    let mut buf = [0; 256];
+    let mut records = RecordSet::default();
+    let decoding = decode(&mut r, &mut buf, &mut records);
+    assert!(matches!(
+        pin!(decoding).poll(cx),
+        Poll::Ready(Err(Error::EndOfInput))
+    ));
+}
+
+#[test]
+fn push_chunks() {
+    use derailleur::avec::PushDecoder;
+
+    let mut data = document(COMPRESSED_TIMESTAMPS);
+    let len = data.len();
+    data.extend(b"rest");
+
+    // Bytes are buffered across chunks of any size.
+    for size in [1, 3, 7, data.len()] {
+        let mut decoder = PushDecoder::new();
+        let mut records = RecordSet::default();
+
+        let mut consumed = 0;
+        for chunk in data.chunks(size) {
+            consumed += decoder.push(chunk, &mut records).unwrap();
+        }
+
+        assert!(decoder.is_done());
+        assert_eq!(consumed, len);
+        assert_eq!(records.records.len(), 3);
+    }
+}
+
+#[test]
+fn resume_push_decoder() {
+    use derailleur::avec::{DecoderState, PushDecoder};
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let options = DecodeOptions {
+        synthesize_timestamps: true,
+        ..Default::default()
+    };
+
+    // Suspend decoding at every offset, restoring from bytes.
+    for offset in 0..data.len() {
+        let mut records = RecordSet::default();
+
+        let mut decoder = PushDecoder::with_options(options);
+        decoder.push(&data[..offset], &mut records).unwrap();
+
+        let bytes = decoder.snapshot().to_bytes();
+        let state = DecoderState::from_bytes(&bytes, options).unwrap();
+
+        let mut decoder = PushDecoder::resume(state);
+        decoder.push(&data[offset..], &mut records).unwrap();
+        assert!(decoder.is_done());
+
+        let timestamps: Vec<_> = records.records.iter().map(|r| r.timestamp).collect();
+        assert_eq!(timestamps, [Some(1000), Some(1005), Some(1026)]);
+    }
+
+    // Developer field descriptions are kept.
+    let data = document(DEVELOPER_FIELDS);
+    let mut records = DeveloperRecords::default();
+
+    let mut decoder = PushDecoder::new();
+    decoder.push(&data[..data.len() - 8], &mut records).unwrap();
+
+    let bytes = decoder.snapshot().to_bytes();
+    let state = DecoderState::from_bytes(&bytes, DecodeOptions::default()).unwrap();
+    PushDecoder::resume(state)
+        .push(&data[data.len() - 8..], &mut records)
+        .unwrap();
+    assert_eq!(records.0.len(), 2);
+    assert_eq!(records.0[1].1[0].3, 301);
+
+    assert!(DecoderState::from_bytes(&[0xFF], options).is_none());
+}
+
+#[test]
+fn restore_mutated_snapshot() {
+    use derailleur::avec::{DecoderState, PushDecoder};
+
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let options = DecodeOptions::default();
+
+    // At a record header, following the document header.
+    let mut decoder = PushDecoder::new();
+    decoder
+        .push(&data[..14], &mut RecordSet::default())
+        .unwrap();
+    let bytes = decoder.snapshot().to_bytes();
+    assert!(DecoderState::from_bytes(&bytes, options).is_some());
+
+    // A data record before any definition.
+    let mut data_position = bytes.clone();
+    data_position.splice(1..2, [5, 0x00]);
+    assert!(DecoderState::from_bytes(&data_position, options).is_none());
+
+    // A buffered unit longer than a record header.
+    let unit = 1 + 1 + 8 + 2 + 8 + 1;
+    let mut long_unit = bytes.clone();
+    long_unit.splice(unit..unit + 4, [2, 0, 0, 0, 0x40, 0x00]);
+    assert!(DecoderState::from_bytes(&long_unit, options).is_none());
+
+    // Any single mutated byte either fails to restore or decodes without
+    // panicking.
+    for offset in 0..data.len() {
+        let mut decoder = PushDecoder::new();
+        decoder
+            .push(&data[..offset], &mut RecordSet::default())
+            .unwrap();
+        let bytes = decoder.snapshot().to_bytes();
+
+        for i in 0..bytes.len() {
+            for value in [0x00, 0x01, 0x05, 0x40, 0xFF] {
+                let mut bytes = bytes.clone();
+                bytes[i] = value;
+
+                if let Some(state) = DecoderState::from_bytes(&bytes, options) {
+                    let mut decoder = PushDecoder::resume(state);
+                    let _ = decoder.wanted();
+                    let _ = decoder.push(&data[offset..], &mut RecordSet::default());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn decode_iterator() {
+    use derailleur::avec::iter::Error;
+
+    let data = document(DEVELOPER_FIELDS);
+    let mut records = DeveloperRecords::default();
+    derailleur::avec::iter::decode(data.iter().copied(), &mut records).unwrap();
+    assert_eq!(records.0.len(), 2);
+
+    // Failures of the iterator end decoding.
+    let bytes = data.iter().enumerate().map(|(i, &b)| match i {
+        20 => Err("radio dropped"),
+        _ => Ok(b),
+    });
+    let mut records = DeveloperRecords::default();
+    let result = derailleur::avec::iter::try_decode(bytes, &mut records);
+    assert!(matches!(
+        result,
+        Err(Error::Source {
+            source: "radio dropped",
+            ..
+        })
+    ));
+
+    let mut records = DeveloperRecords::default();
+    let result = derailleur::avec::iter::decode(data[..30].iter().copied(), &mut records);
+    assert!(matches!(result, Err(Error::EndOfInput { .. })));
+}
+
+#[test]
+fn decode_split_slices() {
+    use std::collections::VecDeque;
+
+    let data = document(DEVELOPER_FIELDS);
+
+    // Wrap the document around the end of a ring buffer.
+    let (head, tail) = data.split_at(data.len() / 2);
+    let mut received = VecDeque::from(tail.to_vec());
+    head.iter().rev().for_each(|&b| received.push_front(b));
+
+    let (front, back) = received.as_slices();
+    assert!(!back.is_empty());
+
+    let mut records = DeveloperRecords::default();
+    derailleur::avec::iter::decode_slices([front, back], &mut records).unwrap();
+    assert_eq!(records.0.len(), 2);
+}
+
+#[test]
+fn decode_slices_at_every_split() {
+    let data = document(ARRAY_FIELD);
+
+    // Arrays are received at once whether or not they cross a boundary.
+    for i in 0..=data.len() {
+        let (head, tail) = data.split_at(i);
+
+        let mut records = Arrays::default();
+        derailleur::avec::iter::decode_slices([head, &[], tail], &mut records).unwrap();
+        assert_eq!(records.slices, [(9, vec![1, 2, 3])], "split at {i}");
+        assert_eq!(records.invalid, [(9, 0x84)], "split at {i}");
+        assert_eq!(records.serial_number, Some(2), "split at {i}");
+    }
+
+    // Bytes are taken alike a byte at a time.
+    let mut records = Arrays::default();
+    derailleur::avec::iter::decode_slices(data.chunks(1), &mut records).unwrap();
+    assert_eq!(records.slices, [(9, vec![1, 2, 3])]);
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn decode_bytes_buf() {
+    use bytes::Buf;
+
+    let data = document(DEVELOPER_FIELDS);
+    let (head, tail) = data.split_at(20);
+
+    // Buffers need not be contiguous.
+    let mut records = DeveloperRecords::default();
+    derailleur::avec::iter::decode_buf(head.chain(tail), &mut records).unwrap();
+    assert_eq!(records.0.len(), 2);
+}
+
+#[test]
+fn count_messages() {
+    use derailleur::avec::{MessageHistogram, histogram::MessageCount};
+
+    let data = document(ARRAY_FIELD);
+    let mut histogram = MessageHistogram::new();
+    derailleur::avec::decode_slice(&data, &mut histogram).unwrap();
+
+    let count = MessageCount {
+        records: 2,
+        bytes: 22,
+    };
+    assert_eq!(histogram.iter().collect::<Vec<_>>(), [(0, count)]);
+    assert_eq!(histogram.get(20), None);
+    assert_eq!(histogram.records(), 2);
+    assert_eq!(histogram.invalid_values().count(), 0);
+
+    // Invalid values are counted per field, if asked.
+    let mut histogram = MessageHistogram::counting_invalid();
+    derailleur::avec::decode_slice(&data, &mut histogram).unwrap();
+    assert_eq!(histogram.get(0), Some(count));
+    assert_eq!(
+        histogram.invalid_values().collect::<Vec<_>>(),
+        [((0, 9), 1)]
+    );
+    assert_eq!(histogram.invalid(0, 9), 1);
+    assert_eq!(histogram.invalid(0, 3), 0);
+}
+
+#[test]
+fn container_receivers() {
+    use std::collections::BTreeMap;
+
+    use derailleur::avec::{DynRecord, adapters::Filter};
+
+    let data = document(DUPLICATE_RECORDS);
+
+    // Receivers can be borrowed by adapters.
+    let mut records = Resynchronized::default();
+    let mut filter = Filter::new(&mut records, |id| id == 0);
+    derailleur::avec::decode_slice(&data, &mut filter).unwrap();
+    assert_eq!(records.serial_numbers, [1, 2]);
+
+    let mut records = BTreeMap::<u16, Vec<DynRecord>>::new();
+    derailleur::avec::decode_slice(&data, &mut records).unwrap();
+    assert_eq!(records.keys().collect::<Vec<_>>(), [&0]);
+    assert_eq!(records[&0].len(), 2);
+}
+
+#[test]
+fn route_records() {
+    use derailleur::avec::raw::{FieldValue, RecordRouter};
+
+    let data = document(DUPLICATE_RECORDS);
+
+    let mut serial_numbers = Vec::new();
+    let mut router = RecordRouter::new()
+        .on(20, |_| panic!("no records of message 20"))
+        .on(0, |record| serial_numbers.push(record.get(3).cloned()));
+    derailleur::avec::decode_slice(&data, &mut router).unwrap();
+    drop(router);
+
+    let expected = [Some(FieldValue::U32(1)), Some(FieldValue::U32(2))];
+    assert_eq!(serial_numbers, expected);
+}
+
+#[test]
+fn send_records() {
+    use derailleur::avec::{MessageSender, raw::FieldValue};
+
+    let data = document(DUPLICATE_RECORDS);
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let decoder = {
+        let data = data.clone();
+        std::thread::spawn(move || {
+            derailleur::avec::decode_slice(&data, &mut MessageSender::new(tx))
+        })
+    };
+
+    let serial_numbers: Vec<_> = rx.iter().map(|m| m.get(3).cloned()).collect();
+    assert_eq!(decoder.join().unwrap().unwrap(), data.len());
+
+    let expected = [Some(FieldValue::U32(1)), Some(FieldValue::U32(2))];
+    assert_eq!(serial_numbers, expected);
+
+    // Decoding stops once records are no longer received.
+    let (tx, rx) = std::sync::mpsc::channel();
+    drop(rx);
+    let len = derailleur::avec::decode_slice(&data, &mut MessageSender::new(tx)).unwrap();
+    assert_eq!(len, 26);
+}
+
+#[test]
+fn collect_records() {
+    use std::collections::VecDeque;
+
+    use derailleur::avec::adapters::Collect;
+
+    let data = document(DUPLICATE_RECORDS);
+    let mut file_ids = Collect::new(0, VecDeque::<FileId>::new());
+    derailleur::avec::decode_slice(&data, &mut file_ids).unwrap();
+
+    let serial_numbers = file_ids.into_inner().into_iter().map(|f| f.serial_number);
+    assert!(serial_numbers.eq([Some(1), Some(2)]));
+}
+
+/// A reader counting the bytes read from it.
+struct Counting<R>(R, usize);
+
+impl<R: std::io::Read> std::io::Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.0.read(buf)?;
+        self.1 += n;
+        Ok(n)
+    }
+}
+
+impl<R: std::io::Seek> std::io::Seek for Counting<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn read_in_chunks() {
+    /// Count the reads made of a reader.
+    struct Reads<'a>(&'a [u8], usize);
+
+    impl std::io::Read for Reads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1 += 1;
+            self.0.read(buf)
+        }
+    }
+
+    let records = [COMPRESSED_TIMESTAMPS; 64].concat();
+    let mut data = document(&records);
+    let len = data.len();
+    data.extend([0xAA; 4]);
+
+    let mut r = Reads(&data, 0);
+    let mut o = RecordSet::default();
+    derailleur::avec::decode_reader(&mut r, &mut o).unwrap();
+    assert_eq!(o.records.len(), 3 * 64);
+
+    // Records are read in few reads, without passing the end of the document.
+    assert!(r.1 < 16, "{} reads", r.1);
+    assert_eq!(r.0, &data[len..]);
+}
+
+#[test]
+fn seek_past_records() {
+    use std::io::Cursor;
+
+    let mut records = vec![
+        0x40, 0, 0, 0, 0, 1, 3, 4, 0x8C, // Definition (local 0).
+        0x00, 1, 0, 0, 0, // Serial number 1.
+        0x41, 0, 0, 20, 0, 1, 3, 200, 0x0D, // Definition (local 1).
+        0x01, // Record without a receiver.
+    ];
+    records.extend([0xFF; 200]);
+    records.extend([0x00, 2, 0, 0, 0]); // Serial number 2.
+    let data = document(&records);
+
+    // Records without a receiver are passed over without the CRC.
+    let options = DecodeOptions::default().verify_crc(false);
+    let mut r = Counting(Cursor::new(&data), 0);
+    let mut o = KeepLast::default();
+    derailleur::avec::reader::decode_seekable_with(&mut r, &mut o, &options).unwrap();
+    assert_eq!(o.file_id.unwrap().serial_number, Some(2));
+    assert_eq!(r.1, data.len() - 200);
+
+    let mut r = Counting(Cursor::new(&data), 0);
+    let mut o = KeepLast::default();
+    derailleur::avec::reader::decode_seekable(&mut r, &mut o).unwrap();
+    assert_eq!(o.file_id.unwrap().serial_number, Some(2));
+    assert_eq!(r.1, data.len());
+}
+
+#[test]
+fn skip_declined_records() {
+    use derailleur::avec::reader::Error;
+
+    let mut records = vec![
+        0x40, 0, 0, 0, 0, 1, 3, 4, 0x8C, // Definition (local 0).
+        0x41, 0, 0, 20, 0, 1, 3, 200, 0x0D, // Definition (local 1).
+        0x01, // Record without a receiver.
+    ];
+    records.extend([0xFF; 200]);
+    records.extend([0x00, 2, 0, 0, 0]); // Serial number 2.
+    let mut data = document(&records);
+
+    let mut o = KeepLast::default();
+    derailleur::avec::decode_slice(&data, &mut o).unwrap();
+    assert_eq!(o.file_id.unwrap().serial_number, Some(2));
+
+    let mut o = KeepLast::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut o).unwrap();
+    assert_eq!(o.file_id.unwrap().serial_number, Some(2));
+
+    // Bytes passed over are still checked against the CRC.
+    data[14 + 19 + 100] = 0;
+    let mut o = KeepLast::default();
+    let result = derailleur::avec::decode_reader(&mut data.as_slice(), &mut o);
+    assert!(matches!(result, Err(Error::CyclicRedundancyCheck { .. })));
+}
+
+#[test]
+fn compute_check_value() {
+    assert_eq!(compute_crc(0, b"123456789"), 0xBB3D);
+
+    // Accumulating in parts matches accumulating at once.
+    let data = document(COMPRESSED_TIMESTAMPS);
+    let (a, b) = data.split_at(7);
+    assert_eq!(compute_crc(compute_crc(0, a), b), compute_crc(0, &data));
+}
+
+#[test]
+fn drive_states() {
+    use std::io::Read;
+
+    use derailleur::sans::{
+        data::{AnyField, FieldSuccessor},
+        definition::{DataSuccessor, DefinitionSuccessor},
+        header::{DocumentHeader, DocumentHeaderSuccessor, RecordHeaderSuccessor},
+        step::{Step, drive},
+    };
+
+    let data = document(DUPLICATE_RECORDS);
+    let mut r = data.as_slice();
+    let mut take = |buf: &mut [u8]| r.read_exact(buf);
+
+    assert_eq!(<DocumentHeader as Step>::LEN, 12);
+    let (size, DocumentHeaderSuccessor::NextRecord(header)) =
+        drive(DocumentHeader, &mut take).unwrap().unwrap()
+    else {
+        panic!("expected a record header");
+    };
+    assert_eq!(size as usize, DUPLICATE_RECORDS.len());
+
+    let (0, RecordHeaderSuccessor::Definition(definition)) =
+        drive(header, &mut take).unwrap().unwrap()
+    else {
+        panic!("expected a definition header");
+    };
+    let DefinitionSuccessor::NextField(field) = drive(definition, &mut take).unwrap() else {
+        panic!("expected a definition field");
+    };
+    let DefinitionSuccessor::NextRecord(mut header, 4) = drive(field, &mut take).unwrap() else {
+        panic!("expected a record header");
+    };
+
+    // Replay the stored definition for each data record, as another source.
+    let mut serial_numbers = Vec::new();
+    for _ in 0..2 {
+        let mut d = &DUPLICATE_RECORDS[..9];
+        let mut replay = |buf: &mut [u8]| d.read_exact(buf);
+
+        let (0, RecordHeaderSuccessor::Data(None, state)) =
+            drive(header, &mut take).unwrap().unwrap()
+        else {
+            panic!("expected a data header");
+        };
+        let (0, DataSuccessor::NextField(field)) = drive(state, &mut replay).unwrap() else {
+            panic!("expected a definition field");
+        };
+        let (3, AnyField::U32Z(field)) = drive(field, &mut replay).unwrap() else {
+            panic!("expected a `uint32z` field");
+        };
+        let (value, FieldSuccessor::Next(DataSuccessor::NextRecord(next))) =
+            drive(field, &mut take).unwrap()
+        else {
+            panic!("expected a record header");
+        };
+        serial_numbers.extend(value);
+        header = next;
+    }
+
+    assert_eq!(serial_numbers, [1, 2]);
+    assert_eq!(r.len(), 2);
+}
+
+/// A source of bytes taken only in whole, failing once exhausted.
+struct Metered<'a>(&'a [u8]);
+
+impl derailleur::avec::ByteSource for Metered<'_> {
+    type Error = &'static str;
+
+    fn take(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        if self.0.len() < buf.len() {
+            return Err("meter ran out");
+        }
+
+        let (bytes, rest) = self.0.split_at(buf.len());
+        buf.copy_from_slice(bytes);
+        self.0 = rest;
+
+        Ok(buf.len())
+    }
+}
+
+#[test]
+fn decode_byte_source() {
+    use derailleur::avec::iter::{Error, decode_source};
+
+    let data = document(DEVELOPER_FIELDS);
+    let mut records = DeveloperRecords::default();
+    decode_source(data.as_slice(), &mut records).unwrap();
+    assert_eq!(records.0.len(), 2);
+
+    // Bytes are never requested past the end of the document.
+    let mut source = Metered(&data);
+    let mut records = DeveloperRecords::default();
+    decode_source(&mut source, &mut records).unwrap();
+    assert_eq!(records.0.len(), 2);
+    assert!(source.0.is_empty());
+
+    let mut records = DeveloperRecords::default();
+    let result = decode_source(Metered(&data[..30]), &mut records);
+    assert!(matches!(
+        result,
+        Err(Error::Source {
+            source: "meter ran out",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn decode_source_in_storage() {
+    use derailleur::avec::{
+        DecodeOptions,
+        iter::{Error, decode_source_in},
+    };
+
+    let data = document(DEVELOPER_FIELDS);
+    let options = DecodeOptions::default();
+
+    // The largest definition record takes 18 bytes.
+    let mut records = DeveloperRecords::default();
+    decode_source_in(data.as_slice(), &mut records, &options, &mut [0; 16 * 18]).unwrap();
+    assert_eq!(records.0.len(), 2);
+
+    let mut records = DeveloperRecords::default();
+    let result = decode_source_in(data.as_slice(), &mut records, &options, &mut [0; 16 * 17]);
+    let Err(Error::DefinitionStorage { at }) = result else {
+        panic!("expected a definition exceeding its storage");
+    };
+    assert_eq!(at.local, Some(1));
+}
+
+#[test]
+fn advance_array_slice() {
+    use derailleur::sans::{
+        data::{AnyField, FieldSuccessor},
+        definition::DataSuccessor,
+        header::{RecordHeader, RecordHeaderSuccessor},
+    };
+
+    let d = &ARRAY_FIELD[..12];
+    let r = &ARRAY_FIELD[12..23];
+
+    let (_, RecordHeaderSuccessor::Data(None, state)) =
+        RecordHeader::random_access().advance([r[0]]).unwrap()
+    else {
+        panic!("expected a data header");
+    };
+    let (0, DataSuccessor::NextField(field)) = state.advance(d[..6].try_into().unwrap()) else {
+        panic!("expected a definition field");
+    };
+    let (number, field) = field.advance(d[6..9].try_into().unwrap());
+    assert_eq!((number, field.value_size()), (9, 2));
+    let AnyField::U16(field) = field else {
+        panic!("expected a `uint16` field");
+    };
+
+    // Part of the array, with a trailing partial value.
+    let (values, FieldSuccessor::MoreBytes(field)) = field.advance_slice(&r[1..6]) else {
+        panic!("expected more values");
+    };
+    assert_eq!(values.collect::<Vec<_>>(), [Some(1), None]);
+    assert_eq!(field.bytes_remaining(), 2);
+
+    // The rest of the array, with bytes of the next field following.
+    let (values, FieldSuccessor::Next(DataSuccessor::NextField(_))) = field.advance_slice(&r[5..])
+    else {
+        panic!("expected the next field");
+    };
+    assert_eq!(values.collect::<Vec<_>>(), [Some(3)]);
+}
+
+#[test]
+fn carry_base_types() {
+    use derailleur::sans::{
+        data::{AnyField, BaseType, FieldSuccessor},
+        definition::DataSuccessor,
+        header::{RecordHeader, RecordHeaderSuccessor},
+    };
+
+    // Definition (local 0), of `enum`, `byte` and `uint8` fields.
+    let d = [0x40, 0, 0, 0, 0, 3, 0, 1, 0x00, 1, 1, 0x0D, 2, 1, 0x02];
+
+    let (_, RecordHeaderSuccessor::Data(None, state)) =
+        RecordHeader::random_access().advance([0x00]).unwrap()
+    else {
+        panic!("expected a data header");
+    };
+    let (_, DataSuccessor::NextField(mut field)) = state.advance(d[..6].try_into().unwrap()) else {
+        panic!("expected a definition field");
+    };
+
+    let mut base_types = Vec::new();
+    for entry in d[6..].chunks(3) {
+        let (_, AnyField::U8(state)) = field.advance(entry.try_into().unwrap()) else {
+            panic!("expected a `uint8` field");
+        };
+        base_types.push(BaseType::from_byte(state.base_type()));
+
+        field = match state.advance([0]) {
+            (_, FieldSuccessor::Next(DataSuccessor::NextField(next))) => next,
+            (_, FieldSuccessor::Next(DataSuccessor::NextRecord(_))) => break,
+            _ => panic!("expected the next field"),
+        };
+    }
+
+    assert_eq!(
+        base_types,
+        [
+            Some(BaseType::Enum),
+            Some(BaseType::Byte),
+            Some(BaseType::Uint8)
+        ]
+    );
+}
+
+#[test]
+fn value_sizes_match_steps() {
+    use derailleur::sans::{
+        data::AnyField,
+        definition::DataSuccessor,
+        header::{RecordHeader, RecordHeaderSuccessor},
+        step::Step,
+    };
+
+    fn len<S: Step>(_: &S) -> usize {
+        S::LEN
+    }
+
+    // Base type and size of a field, and the expected size of its values.
+    let fields = [
+        (0x00, 1, 1),
+        (0x01, 1, 1),
+        (0x83, 2, 2),
+        (0x84, 2, 2),
+        (0x85, 4, 4),
+        (0x86, 4, 4),
+        (0x07, 3, 1),
+        (0x88, 4, 4),
+        (0x89, 8, 8),
+        (0x0A, 1, 1),
+        (0x8B, 2, 2),
+        (0x8C, 4, 4),
+        (0x0D, 1, 1),
+        (0x8E, 8, 8),
+        (0x8F, 8, 8),
+        (0x90, 8, 8),
+        (0xFF, 2, 1), // Unknown.
+        (0x84, 3, 1), // Misaligned.
+        (0x84, 0, 0), // Empty.
+    ];
+
+    for (base_type, size, expected) in fields {
+        let (_, RecordHeaderSuccessor::Data(None, state)) =
+            RecordHeader::random_access().advance([0x00]).unwrap()
+        else {
+            panic!("expected a data header");
+        };
+        let (_, DataSuccessor::NextField(field)) = state.advance([0x40, 0, 0, 0, 0, 1]) else {
+            panic!("expected a definition field");
+        };
+        let (_, field) = field.advance([0, size, base_type]);
+
+        let step = match &field {
+            AnyField::U8(s) => len(s),
+            AnyField::U8Z(s) => len(s),
+            AnyField::U16(s) => len(s),
+            AnyField::U16Z(s) => len(s),
+            AnyField::U32(s) => len(s),
+            AnyField::U32Z(s) => len(s),
+            AnyField::U64(s) => len(s),
+            AnyField::U64Z(s) => len(s),
+            AnyField::I8(s) => len(s),
+            AnyField::I16(s) => len(s),
+            AnyField::I32(s) => len(s),
+            AnyField::I64(s) => len(s),
+            AnyField::F32(s) => len(s),
+            AnyField::F64(s) => len(s),
+            AnyField::String(s) => len(s),
+            AnyField::Unknown(s) | AnyField::Misaligned(s) => len(s),
+            AnyField::Empty(s) => len(s),
+        };
+
+        assert_eq!(field.value_size() as usize, step, "{field:?}");
+        assert_eq!(step, expected, "{field:?}");
+    }
+}
+
+#[test]
+fn base_type_metadata() {
+    use derailleur::sans::data::BaseType;
+
+    let base_type = BaseType::from_byte(0x84).unwrap();
+    assert_eq!(base_type, BaseType::Uint16);
+    assert_eq!(base_type.byte(), 0x84);
+    assert_eq!(base_type.size(), 2);
+    assert_eq!(base_type.invalid(), 0xFFFF);
+
+    assert_eq!(BaseType::Sint8.invalid(), 0x7F);
+    assert_eq!(BaseType::Uint32z.invalid(), 0);
+    assert_eq!(BaseType::try_from(0x07), Ok(BaseType::String));
+    assert_eq!(BaseType::try_from(0x91), Err(0x91));
+
+    // Every known byte maps back to itself.
+    for byte in 0..=u8::MAX {
+        if let Some(base_type) = BaseType::from_byte(byte) {
+            assert_eq!(u8::from(base_type), byte);
+        }
+    }
+}
+
+#[test]
+fn redefine_local_message() {
+    #[rustfmt::skip]
+    let data = document(&[
+        0x40, 0, 0, 20, 0, 2, 253, 4, 0x86, 3, 1, 0x02, // Definition (local 0).
+        0x00, 0xE8, 0x03, 0, 0, 100,
+        0x40, 0, 0, 20, 0, 1, 3, 1, 0x02, // Definition (local 0), replacing the first.
+        0x00, 101,
+    ]);
+
+    let mut records = RecordSet::default();
+    derailleur::avec::decode_reader(&mut data.as_slice(), &mut records).unwrap();
+
+    let records: Vec<_> = records
+        .records
+        .iter()
+        .map(|r| (r.timestamp, r.heart_rate))
+        .collect();
+    assert_eq!(records, [(Some(1000), Some(100)), (None, Some(101))]);
+
+    // Data records are sized by the latest definition.
+    let sizes: Vec<_> = derailleur::avec::slice::messages(&data)
+        .map(|m| m.unwrap().bytes().len())
+        .collect();
+    assert_eq!(sizes, [5, 1]);
+}
+
+#[test]
+fn parse_definition_view() {
+    use derailleur::avec::{DefinitionView, DeveloperFieldInfo};
+
+    // Definition (local 2), with developer fields.
+    let d = &DEVELOPER_FIELDS[41..57];
+    let view = DefinitionView::parse(d).unwrap();
+    assert_eq!(view.global(), 20);
+    assert_eq!(view.as_bytes(), d);
+    assert_eq!(view.entries().collect::<Vec<_>>(), [(3, 1, 0x02, true)]);
+    assert_eq!(
+        view.developer_fields(),
+        [
+            DeveloperFieldInfo {
+                number: 0,
+                size: 2,
+                developer: 0,
+            },
+            DeveloperFieldInfo {
+                number: 1,
+                size: 3,
+                developer: 0,
+            },
+        ]
+    );
+    assert_eq!(view.data_size(), 6);
+    assert_eq!(view.info().local(), 2);
+
+    // Truncated definitions, and data records, aren't parsed.
+    assert!(DefinitionView::parse(&d[..15]).is_none());
+    assert!(DefinitionView::parse(&DEVELOPER_FIELDS[57..64]).is_none());
+}
@@ -0,0 +1,14 @@
+use derailleur::avec::FromRecord;
+
+mod units {
+    #[allow(non_camel_case_types)]
+    pub struct u16;
+}
+
+#[derive(FromRecord)]
+struct Record {
+    #[field(7)]
+    power: Option<units::u16>,
+}
+
+fn main() {}
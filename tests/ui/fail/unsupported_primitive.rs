@@ -0,0 +1,9 @@
+use derailleur::avec::FromRecord;
+
+#[derive(FromRecord)]
+struct Record {
+    #[field(5)]
+    name: Option<String>,
+}
+
+fn main() {}
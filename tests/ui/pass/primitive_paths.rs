@@ -0,0 +1,21 @@
+use derailleur::avec::FromRecord;
+
+#[derive(Default, FromRecord)]
+struct Record {
+    #[field(253)]
+    timestamp: Option<core::primitive::u32>,
+    #[field(3)]
+    heart_rate: std::option::Option<::core::primitive::u8>,
+    #[field(4)]
+    cadence: Option<u8>,
+    #[field(7, |v, p: core::primitive::u16| *v = Some(p))]
+    power: Option<u16>,
+}
+
+fn main() {
+    let mut record = Record::default();
+    record.add_u8(3, 150);
+    record.add_u16(7, 250);
+    assert_eq!(record.heart_rate, Some(150));
+    assert_eq!(record.power, Some(250));
+}